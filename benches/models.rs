@@ -0,0 +1,109 @@
+use std::hint::black_box;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+
+use aicad::modelling::*;
+use aicad::mdd::*;
+use aicad::mdd::heuristics::*;
+use aicad::models::*;
+
+// Benchmarks of the compilation, the propagation and the counting passes on the instances of the
+// built-in models, with their sizes. The propagation is measured by the compilation of the MDD of
+// width 1, which propagates the constraints on the relaxation without refining it. Run with
+// `cargo bench`, or `cargo bench -- <filter>` for a subset of the benchmarks (e.g., `compile/queens`).
+// The exact instances with a known number of solutions are checked before their counting is
+// measured, hence `cargo test --benches` runs each benchmark once and checks the instances.
+
+/// Instance of the benchmarks, compiled with the given width
+struct Instance {
+    name: String,
+    problem: Problem,
+    width: usize,
+    /// Number of solutions of the instance, if it is known
+    solutions: Option<u128>,
+}
+
+/// Returns the graph of the grid of the given side, each cell being adjacent to its right and
+/// bottom neighbours
+fn grid_graph(side: usize) -> Graph {
+    let mut edges = vec![];
+    for i in 0..side {
+        for j in 0..side {
+            if j + 1 < side {
+                edges.push((i * side + j, i * side + j + 1));
+            }
+            if i + 1 < side {
+                edges.push((i * side + j, (i + 1) * side + j));
+            }
+        }
+    }
+    Graph::new(side * side, edges)
+}
+
+fn instances() -> Vec<Instance> {
+    let mut instances = vec![];
+    for (n, solutions) in [(6, 4), (8, 92)] {
+        instances.push(Instance { name: format!("queens-{}", n), problem: n_queens(n), width: usize::MAX, solutions: Some(solutions) });
+    }
+    instances.push(Instance { name: "sudoku-2".to_string(), problem: sudoku(2), width: usize::MAX, solutions: Some(288) });
+    instances.push(Instance { name: "sudoku-3".to_string(), problem: sudoku(3), width: 64, solutions: None });
+    for side in [4, 6] {
+        instances.push(Instance { name: format!("coloring-{}", side), problem: graph_coloring(&grid_graph(side), 3), width: 256, solutions: None });
+    }
+    for n in [20, 40] {
+        let weights = (0..n).map(|i| (i * 7 % 13 + 1) as isize).collect::<Vec<isize>>();
+        let profits = (0..n).map(|i| (i * 11 % 17 + 1) as isize).collect::<Vec<isize>>();
+        let capacity = weights.iter().sum::<isize>() / 2;
+        instances.push(Instance { name: format!("knapsack-{}", n), problem: knapsack(&weights, &profits, capacity), width: usize::MAX, solutions: None });
+    }
+    instances
+}
+
+fn compile(instance: &Instance, width: usize) -> Mdd {
+    let mut mdd = Mdd::new(instance.problem.clone(), width, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+    mdd.refine();
+    mdd
+}
+
+fn bench_compilation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+    group.sample_size(10);
+    for instance in instances() {
+        group.bench_with_input(BenchmarkId::from_parameter(&instance.name), &instance, |b, instance| {
+            b.iter(|| compile(instance, instance.width));
+        });
+    }
+    group.finish();
+}
+
+fn bench_propagation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("propagate");
+    group.sample_size(10);
+    for instance in instances() {
+        group.bench_with_input(BenchmarkId::from_parameter(&instance.name), &instance, |b, instance| {
+            b.iter_batched(|| instance.problem.clone(), |problem| Mdd::new(problem, 1, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed), BatchSize::SmallInput);
+        });
+    }
+    group.finish();
+}
+
+fn bench_counting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("count");
+    group.sample_size(10);
+    for instance in instances() {
+        let mdd = compile(&instance, instance.width);
+        if let Some(solutions) = instance.solutions {
+            assert_eq!(mdd.count_solutions(), solutions, "Wrong number of solutions for {}", instance.name);
+        }
+        group.bench_with_input(BenchmarkId::new("paths", &instance.name), &mdd, |b, mdd| {
+            b.iter(|| black_box(mdd.evaluate::<Counting>(|_, _| 1)));
+        });
+        group.bench_with_input(BenchmarkId::new("marginals", &instance.name), &mdd, |b, mdd| {
+            b.iter(|| black_box(mdd.marginals()));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_compilation, bench_propagation, bench_counting);
+criterion_main!(benches);
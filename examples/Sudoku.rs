@@ -7,8 +7,6 @@ use aicad::modelling::*;
 use aicad::mdd::*;
 use aicad::mdd::heuristics::*;
 
-use std::time::Duration;
-
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() != 3 && args.len() != 4 {
@@ -25,14 +23,12 @@ fn main() {
 
     let n = tokens.next().unwrap().parse::<usize>().unwrap();
     let block_size = (n as f64).sqrt() as usize;
-    let mut rows: Vec<Vec<VariableIndex>> = (0..n).map(|_| vec![]).collect();
-    let mut cols: Vec<Vec<VariableIndex>> = (0..n).map(|_| vec![]).collect();
-    let mut blocks: Vec<Vec<VariableIndex>> = (0..n).map(|_| vec![]).collect();
+    let mut variables: Vec<VariableIndex> = vec![];
 
     let mut problem = Problem::default();
 
-    for (row_id, row) in rows.iter_mut().enumerate() {
-        for (col_id, col) in cols.iter_mut().enumerate() {
+    for _ in 0..n {
+        for _ in 0..n {
             let domain_size = tokens.next().unwrap().parse::<usize>().unwrap();
             let mut domain: Vec<isize> = vec![];
             let mut probabilities: Vec<f64> = vec![];
@@ -43,23 +39,20 @@ fn main() {
                 probabilities.push(proba);
             }
 
-            let variable = problem.add_variable(domain, Some(probabilities));
-            row.push(variable);
-            col.push(variable);
-            let block_id = (row_id / block_size) * block_size + (col_id / block_size);
-            blocks[block_id].push(variable);
+            variables.push(problem.add_variable(domain, Some(probabilities)));
         }
     }
+    let grid = Matrix::new(n, n, variables);
 
-    for row in rows.into_iter() {
+    for row in grid.iter_rows() {
         all_different(&mut problem, row);
     }
 
-    for col in cols.into_iter() {
+    for col in grid.iter_cols() {
         all_different(&mut problem, col);
     }
 
-    for block in blocks.into_iter() {
+    for block in grid.blocks(block_size, block_size) {
         all_different(&mut problem, block);
     }
 
@@ -0,0 +1,142 @@
+use std::env;
+use std::process;
+use std::io::BufReader;
+use std::fs::File;
+use std::time::Instant;
+
+use aicad::modelling::*;
+use aicad::models::*;
+use aicad::mdd::*;
+use aicad::mdd::heuristics::*;
+
+const USAGE: &str = "Usage: aicad <solve|count|sample|compile|viz> <instance> [options]
+
+Instances are read from their extension:
+    .cnf     DIMACS CNF formula, with optional literal weights
+    .col     DIMACS graph, colored with the number of colors given by --colors
+    .json    problem description (requires the serde feature)
+
+Options:
+    --width <w>       maximum width of the MDD (unbounded by default)
+    --colors <k>      number of colors of the graph coloring instances
+    --samples <n>     number of solutions sampled by the sample command (1 by default)
+                      (the counts of a relaxed MDD, of bounded width, are upper bounds, and the
+                      sample command requires an exact MDD)
+    --output <file>   file written by the compile (binary MDD) and viz (graphviz, or TikZ for .tex files) commands";
+
+/// Options of the command line
+struct Options {
+    command: String,
+    instance: String,
+    width: usize,
+    colors: Option<usize>,
+    samples: usize,
+    output: Option<String>,
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("Error: {}\n\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn parse_options(args: &[String]) -> Options {
+    if args.len() < 3 {
+        exit_with_error("Wrong number of arguments");
+    }
+    let mut options = Options {
+        command: args[1].clone(),
+        instance: args[2].clone(),
+        width: usize::MAX,
+        colors: None,
+        samples: 1,
+        output: None,
+    };
+    let mut remaining = args[3..].iter();
+    while let Some(option) = remaining.next() {
+        let value = remaining.next().unwrap_or_else(|| exit_with_error(&format!("Missing value for option {}", option)));
+        let parse = |value: &str| value.parse::<usize>().unwrap_or_else(|_| exit_with_error(&format!("Invalid value {} for option {}", value, option)));
+        match option.as_str() {
+            "--width" => options.width = parse(value),
+            "--colors" => options.colors = Some(parse(value)),
+            "--samples" => options.samples = parse(value),
+            "--output" => options.output = Some(value.clone()),
+            _ => exit_with_error(&format!("Unknown option {}", option)),
+        }
+    }
+    options
+}
+
+/// Reads the problem of the instance, with the factor by which its probability mass must be
+/// multiplied to obtain its weighted count (for the weighted CNF formulas)
+fn read_problem(options: &Options) -> (Problem, Option<f64>) {
+    let file = File::open(&options.instance).unwrap_or_else(|_| exit_with_error(&format!("Failed to open {}", options.instance)));
+    let reader = BufReader::new(file);
+    match options.instance.rsplit('.').next() {
+        Some("cnf") => {
+            let dimacs = parse_dimacs(reader);
+            (dimacs.problem, Some(dimacs.normalization))
+        },
+        Some("col") => {
+            let colors = options.colors.unwrap_or_else(|| exit_with_error("The number of colors must be given with --colors"));
+            (graph_coloring(&Graph::from_dimacs(reader), colors), None)
+        },
+        #[cfg(feature = "serde")]
+        Some("json") => (Problem::from_json(&std::io::read_to_string(reader).unwrap()), None),
+        _ => exit_with_error(&format!("Unsupported instance format {}", options.instance)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = parse_options(&args);
+    if !["solve", "count", "sample", "compile", "viz"].contains(&options.command.as_str()) {
+        exit_with_error(&format!("Unknown command {}", options.command));
+    }
+    let (problem, normalization) = read_problem(&options);
+    let number_variables = problem.number_variables();
+    let number_constraints = problem.number_constraints();
+
+    let start = Instant::now();
+    let mut mdd = Mdd::new(problem, options.width, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+    mdd.refine();
+    let number_nodes = (0..mdd.number_layers()).map(|layer| mdd.number_nodes_in_layer(layer)).sum::<usize>();
+    eprintln!("c {} variables, {} constraints", number_variables, number_constraints);
+    eprintln!("c compiled in {:.3}s, {} nodes", start.elapsed().as_secs_f64(), number_nodes);
+
+    match options.command.as_str() {
+        "solve" => match mdd.get_solution().filter(|solution| mdd.is_solution(solution)) {
+            Some(solution) => println!("{}", solution.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(" ")),
+            None if mdd.is_unsat() => println!("UNSAT"),
+            None => println!("UNKNOWN"),
+        },
+        "count" => {
+            // The paths of a relaxed MDD are a superset of the solutions
+            let bound = if mdd.is_exact() { "" } else { "upper bound " };
+            println!("{}{}", bound, mdd.count_solutions());
+            if let Some(normalization) = normalization {
+                println!("weighted {}{}", bound, mdd.probability_mass() * normalization);
+            }
+        },
+        "sample" => {
+            if mdd.is_unsat() {
+                println!("UNSAT");
+                return;
+            }
+            if !mdd.is_exact() {
+                exit_with_error("The sample command requires an exact MDD, whose width is not bounded by --width");
+            }
+            for _ in 0..options.samples {
+                println!("{}", mdd.sample().iter().map(|value| value.to_string()).collect::<Vec<String>>().join(" "));
+            }
+        },
+        "compile" => {
+            let output = options.output.unwrap_or_else(|| exit_with_error("The compile command requires --output"));
+            mdd.save(&output);
+        },
+        _ => match options.output {
+            Some(output) if output.ends_with(".tex") => std::fs::write(&output, mdd.as_tikz(&TikzOptions::default())).expect("Failed to write the TikZ file"),
+            Some(output) => mdd.to_file(&output),
+            None => println!("{}", mdd.as_graphviz()),
+        },
+    }
+}
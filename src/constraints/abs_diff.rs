@@ -0,0 +1,227 @@
+use super::*;
+use crate::utils::SparseBitset;
+use crate::modelling::*;
+use crate::mdd::*;
+use std::hash::Hasher;
+
+// Structures for the absolute-difference constraints $|x - y| \bowtie c$ with
+// $\bowtie \in \{=, \neq, \leq, \geq\}$.
+//
+// As for the notEquals constraint, the property of a node is the set of values taken on the paths
+// to/from the node by the variable of the scope assigned above/below it. An assignment of the
+// lowest (resp. highest) variable of the scope is supported if a value of the top-down (resp.
+// bottom-up) set is at the right distance. The membership tests are thus shifted by c.
+
+/// Relation between $|x - y|$ and the constant of an absolute-difference constraint
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Relation {
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+}
+
+impl Relation {
+
+    /// Returns true if the distance is in relation with the constant
+    pub fn holds(&self, distance: isize, constant: isize) -> bool {
+        match self {
+            Relation::Equal => distance == constant,
+            Relation::NotEqual => distance != constant,
+            Relation::LessEqual => distance <= constant,
+            Relation::GreaterEqual => distance >= constant,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AbsDiff {
+    x: VariableIndex,
+    y: VariableIndex,
+    relation: Relation,
+    constant: isize,
+    /// Union of the domains of x and y, in increasing order
+    domains: Vec<isize>,
+    top_down_properties: Vec<Vec<SparseBitset<isize>>>,
+    bottom_up_properties: Vec<Vec<SparseBitset<isize>>>,
+    layer_x: usize,
+    layer_y: usize,
+}
+
+impl AbsDiff {
+
+    /// Creates the constraint |x - y| relation constant
+    pub fn new(x: VariableIndex, y: VariableIndex, relation: Relation, constant: isize) -> Self {
+        Self {
+            x,
+            y,
+            relation,
+            constant,
+            domains: vec![],
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            layer_x: 0,
+            layer_y: 0,
+        }
+    }
+
+    /// Returns true if a value of the set is in relation with the assignment
+    fn is_supported(&self, set: &SparseBitset<isize>, assignment: isize) -> bool {
+        match self.relation {
+            Relation::Equal => [assignment - self.constant, assignment + self.constant].into_iter()
+                .any(|value| self.domains.binary_search(&value).is_ok() && set.contains(value)),
+            _ => self.domains.iter().copied()
+                .any(|value| set.contains(value) && self.relation.holds((assignment - value).abs(), self.constant)),
+        }
+    }
+}
+
+impl Constraint for AbsDiff {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.domains = vars[*self.x].iter_domain().chain(vars[*self.y].iter_domain()).collect::<Vec<isize>>();
+        self.domains.sort_unstable();
+        self.domains.dedup();
+        self.top_down_properties = (0..vars.len() + 1).map(|_| {
+            vec![SparseBitset::new(self.domains.iter().copied())]
+        }).collect::<Vec<Vec<SparseBitset<isize>>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| {
+            vec![SparseBitset::new(self.domains.iter().copied())]
+        }).collect::<Vec<Vec<SparseBitset<isize>>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.layer_x = ordering[self.x.0];
+        self.layer_y = ordering[self.y.0];
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset(0);
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        if self.is_layer_in_scope(source_layer) {
+            self.top_down_properties[target_layer][target_index].insert(assignment);
+        }
+        let (td_properties_above, td_properties_below) = self.top_down_properties.split_at_mut(target_layer);
+        td_properties_below[0][target_index].union(&td_properties_above[source_layer][source_index]);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset(0);
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        if self.is_layer_in_scope(target_layer) {
+            self.bottom_up_properties[target_layer][target_index].insert(assignment);
+        }
+        let (bu_properties_above, bu_properties_below) = self.bottom_up_properties.split_at_mut(source_layer);
+        bu_properties_above[target_layer][target_index].union(&bu_properties_below[0][source_index]);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        layer == self.layer_x || layer == self.layer_y
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        if source_layer == self.layer_x.max(self.layer_y) {
+            !self.is_supported(&self.top_down_properties[source_layer][source_index], assignment)
+        } else {
+            !self.is_supported(&self.bottom_up_properties[target_layer][target_index], assignment)
+        }
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(SparseBitset::new(self.domains.iter().copied()));
+        self.bottom_up_properties[layer].push(SparseBitset::new(self.domains.iter().copied()));
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new([self.x, self.y].into_iter())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        self.relation.holds((assignment[*self.x] - assignment[*self.y]).abs(), self.constant)
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for word in self.top_down_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+        for word in self.bottom_up_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn negation(&self) -> Option<Box<dyn Constraint + Send + Sync>> {
+        let (relation, constant) = match self.relation {
+            Relation::Equal => (Relation::NotEqual, self.constant),
+            Relation::NotEqual => (Relation::Equal, self.constant),
+            Relation::LessEqual => (Relation::GreaterEqual, self.constant + 1),
+            Relation::GreaterEqual => (Relation::LessEqual, self.constant - 1),
+        };
+        Some(Box::new(AbsDiff::new(self.x, self.y, relation, constant)))
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::AbsDiff { x: self.x, y: self.y, relation: self.relation, constant: self.constant })
+    }
+}
+
+#[cfg(test)]
+mod test_abs_diff {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_relations() {
+        for (relation, expected) in [(Relation::Equal, 4), (Relation::LessEqual, 7), (Relation::GreaterEqual, 6)] {
+            let mut problem = Problem::default();
+            let x = problem.add_variable(vec![0, 1, 2], None);
+            let y = problem.add_variable(vec![0, 1, 2], None);
+            abs_diff(&mut problem, x, y, relation, 1);
+
+            let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![1, 0]), MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            let solutions = get_all_solutions(&mdd);
+            assert_eq!(solutions.len(), expected);
+            assert!(solutions.iter().all(|solution| relation.holds((solution[0] - solution[1]).abs(), 1)));
+        }
+    }
+
+    #[test]
+    pub fn test_golomb_ruler_differences() {
+        let mut problem = Problem::default();
+        let marks = problem.add_variables(3, vec![0, 1, 2, 3], None);
+        less_than(&mut problem, marks[0], marks[1]);
+        less_than(&mut problem, marks[1], marks[2]);
+        equal(&mut problem, marks[0], 0);
+        abs_diff(&mut problem, marks[1], marks[2], Relation::GreaterEqual, 2);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 1);
+        assert!(is_solution(vec![0, 1, 3], &solutions));
+    }
+}
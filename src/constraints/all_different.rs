@@ -1,9 +1,9 @@
 use super::*;
-use crate::modelling::{VariableIndex, Problem};
+use crate::modelling::{Domain, VariableIndex};
 use crate::mdd::*;
 use rustc_hash::{FxHashMap, FxHashSet};
-use crate::utils::SparseBitset;
-use std::hash::{Hash, Hasher};
+use crate::utils::{Bitset, SparseBitset};
+use std::hash::Hasher;
 
 // Structures for the allDifferent constraint.
 //
@@ -35,10 +35,10 @@ struct AllDifferentProperty {
 
 impl AllDifferentProperty {
 
-    /// Creates a new property with bitsiets of nb_words 64-bit unsigned integers
-    pub fn new(domain: &FxHashSet<isize>) -> Self {
-        let value_all_path = SparseBitset::new(domain.iter().copied());
-        let value_some_path = SparseBitset::new(domain.iter().copied());
+    /// Creates a new property with two copies of the given empty bitset
+    pub fn new(no_value: &SparseBitset<isize>) -> Self {
+        let value_all_path = no_value.clone();
+        let value_some_path = no_value.clone();
         Self {
             value_all_path,
             value_some_path,
@@ -47,11 +47,20 @@ impl AllDifferentProperty {
 
 }
 
+#[derive(Clone)]
 pub struct AllDifferent {
     /// Scope of the constraint
     variables: Vec<VariableIndex>,
-    /// Union of the domain of the variables in the scope
-    domain: FxHashSet<isize>,
+    /// Offset added to the value of each variable in the scope
+    offsets: Vec<isize>,
+    /// For each layer in the scope, the offset of its decision variable
+    offset_at_layer: Vec<isize>,
+    /// Empty set over the union of the (shifted) domain of the variables in the scope
+    no_value: SparseBitset<isize>,
+    /// For each variable in the scope, the bit in the sets of each value index of its domain
+    bits_of_variable: Vec<Vec<usize>>,
+    /// For each layer in the scope, the bits of its decision variable
+    bits_at_layer: Vec<Vec<usize>>,
     /// Top-down properties for each node in the MDD
     top_down_properties: Vec<Vec<AllDifferentProperty>>,
     /// Bottom-up properties for each node in the MDD
@@ -60,80 +69,50 @@ pub struct AllDifferent {
     /// MDD.
     map_hall_set: FxHashMap<VariableIndex, (usize, usize)>,
     /// Bitvector to indicate if a layer is in the scope of the constraint or not
-    layer_in_scope: Vec<u64>,
+    layer_in_scope: Bitset,
 }
 
 impl AllDifferent {
 
     /// Creates a new AllDifferent constraint over variables
     pub fn new(variables: Vec<VariableIndex>) -> Self {
+        let offsets = vec![0; variables.len()];
+        Self::with_offsets(variables, offsets)
+    }
+
+    /// Creates a new AllDifferent constraint enforcing that the values $x_i + c_i$ are pairwise
+    /// distinct, with $c_i$ the offset of the i-th variable.
+    pub fn with_offsets(variables: Vec<VariableIndex>, offsets: Vec<isize>) -> Self {
+        debug_assert!(variables.len() == offsets.len());
         Self {
             variables,
-            domain: FxHashSet::<isize>::default(),
+            offsets,
+            offset_at_layer: vec![],
+            no_value: SparseBitset::new(std::iter::empty()),
+            bits_of_variable: vec![],
+            bits_at_layer: vec![],
             top_down_properties: vec![],
             bottom_up_properties: vec![],
             map_hall_set: FxHashMap::<VariableIndex, (usize, usize)>::default(),
-            layer_in_scope: vec![],
-        }
-    }
-
-}
-
-impl Constraint for AllDifferent {
-
-    fn init(&mut self, vars: &[Variable]) {
-        for variable in self.variables.iter().copied() {
-            for value in vars[*variable].iter_domain() {
-                self.domain.insert(value);
-            }
-        }
-        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![AllDifferentProperty::new(&self.domain)]).collect::<Vec<Vec<AllDifferentProperty>>>();
-        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![AllDifferentProperty::new(&self.domain)]).collect::<Vec<Vec<AllDifferentProperty>>>();
-        self.layer_in_scope = (0..(vars.len() / 64 + 1)).map(|_| 0).collect::<Vec<u64>>();
-    }
-
-    fn update_variable_ordering(&mut self, ordering: &[usize]) {
-        // The layers in the scope of the variable are indicated using a bitvector of 64-bit words.
-        // For each layer l its word index is given by l / 64 and the bit index by l % 64
-        for variable in self.variables.iter() {
-            let layer = ordering[variable.0];
-            // Sets the bit of the layer to 1
-            self.layer_in_scope[layer / 64] |= 1 << (layer % 64);
-        }
-        // Compute the hall set sizes up and down the mdd. For a given layer l in the scope of the
-        // constraint its hall set size up (resp. down) is the number of layer k such that k < l (k
-        // > l) and k is in the constraint's scope
-
-        // We sort each variable in the constraint's scope by its position in the ordering
-        let mut scope_variable_order = self.variables.iter().copied().map(|v| (ordering[v.0], v)).collect::<Vec<(usize, VariableIndex)>>();
-        scope_variable_order.sort_unstable();
-        // The hall set sizes are stored as a tuple (size up, size down) and is given, for node i, by (i, n-i)
-        let n = self.variables.len();
-        for (pos, (_, variable)) in scope_variable_order.iter().copied().enumerate() {
-            self.map_hall_set.insert(variable, (pos, n - 1 - pos));
+            layer_in_scope: Bitset::default(),
         }
     }
 
-    fn reset_property_top_down(&mut self, node: NodeIndex) {
-        let NodeIndex(layer, index) = node;
-        self.top_down_properties[layer][index].value_some_path.reset(0);
-        self.top_down_properties[layer][index].value_all_path.reset(!0);
-    }
-
-    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+    /// Updates the top-down property along an edge whose value has the bit, None if the layer is not
+    /// in the scope
+    fn update_top_down(&mut self, source: NodeIndex, target: NodeIndex, bit: Option<usize>) {
         let NodeIndex(source_layer, source_index) = source;
         let NodeIndex(target_layer, target_index) = target;
-        let layer_in_scope = self.is_layer_in_scope(source_layer);
 
         // For the set A we need to do $A \cap (A^\prime \cup \{assignment\})$. Hence,
         // we can not directly integrate the assignment into A (as is done for the S
         // set, since this is a union of union.
         // Hence, we integrate the assignment into $S^\prime$ and then reverse it.
-        let is_in_set = self.top_down_properties[source_layer][source_index].value_all_path.contains(assignment);
         // Only integrate the edge if the layer is in the scope of the constraint.
-        if layer_in_scope {
-            self.top_down_properties[target_layer][target_index].value_some_path.insert(assignment);
-            self.top_down_properties[source_layer][source_index].value_all_path.insert(assignment);
+        let is_in_set = bit.is_some_and(|bit| self.top_down_properties[source_layer][source_index].value_all_path.contains_bit(bit));
+        if let Some(bit) = bit {
+            self.top_down_properties[target_layer][target_index].value_some_path.insert_bit(bit);
+            self.top_down_properties[source_layer][source_index].value_all_path.insert_bit(bit);
         }
 
         // Aggregate the source properties into the target properties.
@@ -147,31 +126,26 @@ impl Constraint for AllDifferent {
         td_properties_below[0][target_index].value_some_path.union(&td_properties_above[source_layer][source_index].value_some_path);
 
         // Reverse the integration of the edge into the $A^\prime$ set.
-        if layer_in_scope && !is_in_set{
-            self.top_down_properties[source_layer][source_index].value_all_path.remove(assignment);
+        if let Some(bit) = bit.filter(|_| !is_in_set) {
+            self.top_down_properties[source_layer][source_index].value_all_path.remove_bit(bit);
         }
     }
 
-    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
-        let NodeIndex(layer, index) = node;
-        self.bottom_up_properties[layer][index].value_some_path.reset(0);
-        self.bottom_up_properties[layer][index].value_all_path.reset(!0);
-    }
-
-    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+    /// Updates the bottom-up property along an edge whose value has the bit, None if the layer is not
+    /// in the scope
+    fn update_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, bit: Option<usize>) {
         let NodeIndex(source_layer, source_index) = source;
         let NodeIndex(target_layer, target_index) = target;
-        let layer_in_scope = self.is_layer_in_scope(target_layer);
 
         // For the set A we need to do $A \cap (A^\prime \cup \{assignment\})$. Hence,
         // we can not directly integrate the assignment into A (as is done for the S
         // set, since this is a union of union.
         // Hence, we integrate the assignment into $A^\prime$ and then reverse it.
-        let is_in_set = self.bottom_up_properties[source_layer][source_index].value_all_path.contains(assignment);
         // Only integrate the edge if the layer is in the scope of the constraint.
-        if layer_in_scope {
-            self.bottom_up_properties[target_layer][target_index].value_some_path.insert(assignment);
-            self.bottom_up_properties[source_layer][source_index].value_all_path.insert(assignment);
+        let is_in_set = bit.is_some_and(|bit| self.bottom_up_properties[source_layer][source_index].value_all_path.contains_bit(bit));
+        if let Some(bit) = bit {
+            self.bottom_up_properties[target_layer][target_index].value_some_path.insert_bit(bit);
+            self.bottom_up_properties[source_layer][source_index].value_all_path.insert_bit(bit);
         }
 
         // Aggregate the source properties into the target properties.
@@ -185,31 +159,26 @@ impl Constraint for AllDifferent {
         bu_properties_above[target_layer][target_index].value_some_path.union(&bu_properties_below[0][source_index].value_some_path);
 
         // Reverse the integration of the edge into the $A^\prime$ set.
-        if layer_in_scope && !is_in_set{
-            self.bottom_up_properties[source_layer][source_index].value_all_path.remove(assignment);
+        if let Some(bit) = bit.filter(|_| !is_in_set) {
+            self.bottom_up_properties[source_layer][source_index].value_all_path.remove_bit(bit);
         }
     }
 
-    /// Returns true if the layer is constrained by self
-    fn is_layer_in_scope(&self, layer: usize) -> bool {
-        self.layer_in_scope[layer / 64] & (1 << (layer % 64)) != 0
-    }
-
-    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, decision: VariableIndex, assignment: isize) -> bool {
+    /// Returns true if the edge whose value has the bit can be removed
+    fn is_bit_invalid(&self, source: NodeIndex, target: NodeIndex, decision: VariableIndex, bit: usize) -> bool {
         let NodeIndex(source_layer, source_index) = source;
         let NodeIndex(target_layer, target_index) = target;
 
-
         // If the value appears on all path from the source or to the sink, then it will be taken
         // by another variable and can not be assigned to this one.
-        if self.top_down_properties[source_layer][source_index].value_all_path.contains(assignment) ||
-           self.bottom_up_properties[target_layer][target_index].value_all_path.contains(assignment) {
+        if self.top_down_properties[source_layer][source_index].value_all_path.contains_bit(bit) ||
+           self.bottom_up_properties[target_layer][target_index].value_all_path.contains_bit(bit) {
                 return true;
         }
         // If not, we check for Hall-set conditions
         let (hall_set_size_up, hall_set_size_down) = *self.map_hall_set.get(&decision).unwrap();
-        let is_on_td_path = self.top_down_properties[source_layer][source_index].value_some_path.contains(assignment);
-        let is_on_bu_path = self.bottom_up_properties[target_layer][target_index].value_some_path.contains(assignment);
+        let is_on_td_path = self.top_down_properties[source_layer][source_index].value_some_path.contains_bit(bit);
+        let is_on_bu_path = self.bottom_up_properties[target_layer][target_index].value_some_path.contains_bit(bit);
         if is_on_td_path && hall_set_size_up == self.top_down_properties[source_layer][source_index].value_some_path.size() {
             // First, the variables above are a Hall set: they can take as much values as the union of
             // their domain and this union includes the current assignment.
@@ -226,9 +195,94 @@ impl Constraint for AllDifferent {
         false
     }
 
+}
+
+impl Constraint for AllDifferent {
+
+    fn init(&mut self, vars: &[Variable]) {
+        let domains = self.variables.iter().copied().zip(self.offsets.iter().copied()).map(|(variable, offset)| vars[*variable].domain().shift(offset)).collect::<Vec<Domain>>();
+        self.no_value = SparseBitset::from_domain(&Domain::union(domains.iter()));
+        self.bits_of_variable = domains.iter().map(|domain| domain.iter().map(|value| self.no_value.bit(value)).collect()).collect();
+        self.offset_at_layer = vec![0; vars.len()];
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![AllDifferentProperty::new(&self.no_value)]).collect::<Vec<Vec<AllDifferentProperty>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![AllDifferentProperty::new(&self.no_value)]).collect::<Vec<Vec<AllDifferentProperty>>>();
+        self.layer_in_scope = Bitset::with_capacity(vars.len() + 1);
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        // The layers in the scope of the variable are indicated using a bitset over the layers. It
+        // is cleared as the ordering may change.
+        self.layer_in_scope.reset(0);
+        self.layer_in_scope.grow(ordering.len() + 1);
+        self.bits_at_layer = vec![vec![]; ordering.len() + 1];
+        for (i, (variable, offset)) in self.variables.iter().zip(self.offsets.iter().copied()).enumerate() {
+            let layer = ordering[variable.0];
+            self.layer_in_scope.insert(layer);
+            self.offset_at_layer[layer] = offset;
+            self.bits_at_layer[layer] = self.bits_of_variable[i].clone();
+        }
+        // Compute the hall set sizes up and down the mdd. For a given layer l in the scope of the
+        // constraint its hall set size up (resp. down) is the number of layer k such that k < l (k
+        // > l) and k is in the constraint's scope
+
+        // We sort each variable in the constraint's scope by its position in the ordering
+        let mut scope_variable_order = self.variables.iter().copied().map(|v| (ordering[v.0], v)).collect::<Vec<(usize, VariableIndex)>>();
+        scope_variable_order.sort_unstable();
+        // The hall set sizes are stored as a tuple (size up, size down) and is given, for node i, by (i, n-i)
+        let n = self.variables.len();
+        for (pos, (_, variable)) in scope_variable_order.iter().copied().enumerate() {
+            self.map_hall_set.insert(variable, (pos, n - 1 - pos));
+        }
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].value_some_path.reset(0);
+        self.top_down_properties[layer][index].value_all_path.reset(!0);
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let bit = self.is_layer_in_scope(source.0).then(|| self.no_value.bit(assignment + self.offset_at_layer[source.0]));
+        self.update_top_down(source, target, bit);
+    }
+
+    fn update_property_top_down_at(&mut self, source: NodeIndex, target: NodeIndex, assignment: ValueIndex, _value: isize) {
+        let bit = self.is_layer_in_scope(source.0).then(|| self.bits_at_layer[source.0][assignment.0]);
+        self.update_top_down(source, target, bit);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].value_some_path.reset(0);
+        self.bottom_up_properties[layer][index].value_all_path.reset(!0);
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let bit = self.is_layer_in_scope(target.0).then(|| self.no_value.bit(assignment + self.offset_at_layer[target.0]));
+        self.update_bottom_up(source, target, bit);
+    }
+
+    fn update_property_bottom_up_at(&mut self, source: NodeIndex, target: NodeIndex, assignment: ValueIndex, _value: isize) {
+        let bit = self.is_layer_in_scope(target.0).then(|| self.bits_at_layer[target.0][assignment.0]);
+        self.update_bottom_up(source, target, bit);
+    }
+
+    /// Returns true if the layer is constrained by self
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.layer_in_scope.contains(layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, decision: VariableIndex, assignment: isize) -> bool {
+        self.is_bit_invalid(source, target, decision, self.no_value.bit(assignment + self.offset_at_layer[source.0]))
+    }
+
+    fn is_assignment_invalid_at(&self, source: NodeIndex, target: NodeIndex, decision: VariableIndex, assignment: ValueIndex, _value: isize) -> bool {
+        self.is_bit_invalid(source, target, decision, self.bits_at_layer[source.0][assignment.0])
+    }
+
     fn add_node_in_layer(&mut self, layer: usize) {
-        let top_down_property = AllDifferentProperty::new(&self.domain);
-        let bottom_up_property = AllDifferentProperty::new(&self.domain);
+        let top_down_property = AllDifferentProperty::new(&self.no_value);
+        let bottom_up_property = AllDifferentProperty::new(&self.no_value);
         self.top_down_properties[layer].push(top_down_property);
         self.bottom_up_properties[layer].push(bottom_up_property);
     }
@@ -239,8 +293,8 @@ impl Constraint for AllDifferent {
 
     fn is_satisfied(&self, assignment: &[isize]) -> bool {
         let mut set = FxHashSet::<isize>::default();
-        for variable in self.variables.iter().copied() {
-            let value = assignment[*variable];
+        for (variable, offset) in self.variables.iter().copied().zip(self.offsets.iter().copied()) {
+            let value = assignment[*variable] + offset;
             if set.contains(&value) {
                 return false;
             }
@@ -273,12 +327,23 @@ impl Constraint for AllDifferent {
         self.bottom_up_properties[layer][index].value_all_path == self.bottom_up_properties[olayer][oindex].value_all_path &&
         self.bottom_up_properties[layer][index].value_some_path == self.bottom_up_properties[olayer][oindex].value_some_path
     }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::AllDifferent { variables: self.variables.clone(), offsets: self.offsets.clone() })
+    }
+
+    fn node_state_label(&self, node: NodeIndex) -> Option<String> {
+        let NodeIndex(layer, index) = node;
+        let top_down = self.top_down_properties.get(layer)?.get(index)?;
+        let bottom_up = self.bottom_up_properties.get(layer)?.get(index)?;
+        Some(format!("td {} / bu {}", top_down, bottom_up))
+    }
 }
 
 impl std::fmt::Display for AllDifferentProperty {
 
     fn fmt(&self, f:&mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "A: {} - S: {}", self.value_all_path, self.value_some_path)
+        write!(f, "A={} S={}", format_values(&self.value_all_path.elements()), format_values(&self.value_some_path.elements()))
     }
 }
 
@@ -305,6 +370,26 @@ mod test_all_diff {
         assert!(is_solution(vec![0, 1], &solutions));
     }
 
+    #[test]
+    pub fn test_value_index_filtering() {
+        // The filtering through the value indexes matches the filtering through the values
+        let mut problem = Problem::default();
+        let xs = vec![problem.add_variable(vec![0, 2, 5], None), problem.add_variable(vec![1, 2, 3], None), problem.add_variable(vec![-1, 0, 4], None)];
+        all_different_offset(&mut problem, xs.clone(), vec![0, 1, -1]);
+        let mut mdd = Mdd::new(problem, 2, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let constraint = &mdd.problem()[ConstraintIndex(0)];
+        for layer in 0..mdd.number_layers() - 1 {
+            let variable = mdd.decision_at_layer(layer);
+            for edge in (0..mdd.number_nodes_in_layer(layer)).flat_map(|index| mdd[NodeIndex(layer, index)].iter_children()).map(|edge| mdd.edge(edge)) {
+                for assignment in (0..3).map(ValueIndex) {
+                    let value = mdd.problem()[variable].value(assignment);
+                    assert_eq!(constraint.is_assignment_invalid(edge.from(), edge.to(), variable, value), constraint.is_assignment_invalid_at(edge.from(), edge.to(), variable, assignment, value));
+                }
+            }
+        }
+    }
+
     #[test]
     pub fn test_no_propagation() {
         let mut problem = Problem::default();
@@ -381,6 +466,7 @@ mod test_all_diff {
 
         let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1]), MergeHeuristic::LessRelaxed);
         mdd.refine();
+        #[cfg(feature = "fs")]
         mdd.to_file("mdd.txt");
         let solutions = get_all_solutions(&mdd);
         assert_eq!(solutions.len(), 2);
@@ -388,6 +474,37 @@ mod test_all_diff {
         assert!(is_solution(vec![1, 0], &solutions));
     }
 
+    #[test]
+    pub fn test_more_than_64_layers_and_values() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable((0..70).collect(), None);
+        let others = problem.add_variables(64, vec![0, 1], None);
+        let y = problem.add_variable((0..70).collect(), None);
+        all_different(&mut problem, vec![x, y]);
+        let mut order = vec![x.0];
+        order.extend(others.iter().map(|variable| variable.0));
+        order.push(y.0);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(order), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.count_solutions(), 70 * 69 * (1u128 << 64));
+    }
+
+    #[test]
+    pub fn test_n_queens_offsets() {
+        for (n, expected) in [(4, 2), (5, 10), (6, 4)] {
+            let mut problem = Problem::default();
+            let queens = problem.add_variables(n, (0..n as isize).collect(), None);
+            all_different(&mut problem, queens.clone());
+            all_different_offset(&mut problem, queens.clone(), (0..n as isize).collect());
+            all_different_offset(&mut problem, queens.clone(), (0..n as isize).map(|i| -i).collect());
+
+            let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            let solutions = get_all_solutions(&mdd);
+            assert_eq!(solutions.len(), expected);
+        }
+    }
+
     #[test]
     pub fn test_value_all_path() {
         let mut problem = Problem::default();
@@ -0,0 +1,221 @@
+use super::*;
+use crate::modelling::{Domain, VariableIndex};
+use crate::mdd::*;
+use rustc_hash::FxHashSet;
+use crate::utils::{Bitset, SparseBitset};
+use std::hash::Hasher;
+
+// Structures for the allDifferentExcept constraint. The variables must take pairwise distinct
+// values, except for a sentinel value (usually 0) that can be taken by any number of variables.
+//
+// The property of a node is the set $A(n)$ of the values (other than the sentinel) appearing on all
+// path to/from $n$, as in the allDifferent constraint. The sentinel is never integrated in the
+// property. Since any number of variables can take the sentinel, the Hall-set reasoning of the
+// allDifferent constraint does not hold and is not used.
+
+#[derive(Clone)]
+pub struct AllDifferentExcept {
+    /// Scope of the constraint
+    variables: Vec<VariableIndex>,
+    /// Value that can be taken by more than one variable
+    sentinel: isize,
+    /// Empty set over the union of the domain of the variables in the scope
+    no_value: SparseBitset<isize>,
+    /// Top-down properties for each node in the MDD
+    top_down_properties: Vec<Vec<SparseBitset<isize>>>,
+    /// Bottom-up properties for each node in the MDD
+    bottom_up_properties: Vec<Vec<SparseBitset<isize>>>,
+    /// Bitvector to indicate if a layer is in the scope of the constraint or not
+    layer_in_scope: Bitset,
+}
+
+impl AllDifferentExcept {
+
+    /// Creates a new AllDifferentExcept constraint over variables, the sentinel value being allowed
+    /// to repeat.
+    pub fn new(variables: Vec<VariableIndex>, sentinel: isize) -> Self {
+        Self {
+            variables,
+            sentinel,
+            no_value: SparseBitset::new(std::iter::empty()),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            layer_in_scope: Bitset::default(),
+        }
+    }
+
+}
+
+impl Constraint for AllDifferentExcept {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.no_value = SparseBitset::from_domain(&Domain::union(self.variables.iter().map(|variable| vars[**variable].domain())));
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![self.no_value.clone()]).collect::<Vec<Vec<SparseBitset<isize>>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![self.no_value.clone()]).collect::<Vec<Vec<SparseBitset<isize>>>>();
+        self.layer_in_scope = Bitset::with_capacity(vars.len() + 1);
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.layer_in_scope.reset(0);
+        self.layer_in_scope.grow(ordering.len() + 1);
+        for variable in self.variables.iter() {
+            let layer = ordering[variable.0];
+            self.layer_in_scope.insert(layer);
+        }
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset(!0);
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        // The assignment is temporarily integrated into the source property, and removed after the
+        // aggregation if it was not already in the set.
+        let integrate = self.is_layer_in_scope(source_layer) && assignment != self.sentinel;
+        let is_in_set = integrate && self.top_down_properties[source_layer][source_index].contains(assignment);
+        if integrate {
+            self.top_down_properties[source_layer][source_index].insert(assignment);
+        }
+        let (td_properties_above, td_properties_below) = self.top_down_properties.split_at_mut(target_layer);
+        td_properties_below[0][target_index].interesect(&td_properties_above[source_layer][source_index]);
+        if integrate && !is_in_set {
+            self.top_down_properties[source_layer][source_index].remove(assignment);
+        }
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset(!0);
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let integrate = self.is_layer_in_scope(target_layer) && assignment != self.sentinel;
+        let is_in_set = integrate && self.bottom_up_properties[source_layer][source_index].contains(assignment);
+        if integrate {
+            self.bottom_up_properties[source_layer][source_index].insert(assignment);
+        }
+        let (bu_properties_above, bu_properties_below) = self.bottom_up_properties.split_at_mut(source_layer);
+        bu_properties_above[target_layer][target_index].interesect(&bu_properties_below[0][source_index]);
+        if integrate && !is_in_set {
+            self.bottom_up_properties[source_layer][source_index].remove(assignment);
+        }
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.layer_in_scope.contains(layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        if assignment == self.sentinel {
+            return false;
+        }
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        // If the value appears on all path from the source or to the sink, then it is taken by
+        // another variable.
+        self.top_down_properties[source_layer][source_index].contains(assignment) ||
+        self.bottom_up_properties[target_layer][target_index].contains(assignment)
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(self.no_value.clone());
+        self.bottom_up_properties[layer].push(self.no_value.clone());
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.iter().copied())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let mut set = FxHashSet::<isize>::default();
+        for variable in self.variables.iter().copied() {
+            let value = assignment[*variable];
+            if value != self.sentinel && !set.insert(value) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for word in self.top_down_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+        for word in self.bottom_up_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::AllDifferentExcept { variables: self.variables.clone(), sentinel: self.sentinel })
+    }
+}
+
+#[cfg(test)]
+mod test_all_diff_except {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_sentinel_repeats() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different_except(&mut problem, vars, 0);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        // 1 solution with three 0s, 3 * 2 with two 0s, 3 * 2 with one 0
+        assert_eq!(solutions.len(), 13);
+        assert!(is_solution(vec![0, 0, 0], &solutions));
+        assert!(is_solution(vec![0, 2, 0], &solutions));
+        assert!(is_solution(vec![1, 0, 2], &solutions));
+        assert!(!is_solution(vec![1, 0, 1], &solutions));
+    }
+
+    #[test]
+    pub fn test_fixed_value_removed() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![1], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        all_different_except(&mut problem, vec![x, y], 0);
+
+        let mdd = Mdd::new(problem, 1, OrderingHeuristic::Custom(vec![0, 1]), MergeHeuristic::LessRelaxed);
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 2);
+        assert!(is_solution(vec![1, 0], &solutions));
+        assert!(is_solution(vec![1, 2], &solutions));
+    }
+
+    #[test]
+    pub fn test_variable_out_of_scope() {
+        // The values of z are not in the domains of the scope, and its layer comes first
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1], None);
+        let y = problem.add_variable(vec![0, 1], None);
+        let z = problem.add_variable(vec![5, 6], None);
+        all_different_except(&mut problem, vec![x, y], 0);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![z.0, y.0, x.0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 6);
+        assert!(!is_solution(vec![1, 1, 5], &solutions));
+    }
+}
@@ -0,0 +1,255 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use std::hash::Hasher;
+
+// Structures for the ternary arithmetic constraints $z = x \circ y$ with
+// $\circ \in \{+, \times, \div, \bmod\}$. The division and the modulo follow the semantic of Rust
+// (truncated towards zero) and are undefined if $y = 0$.
+//
+// The property of a node is, for each variable of the scope, the interval (min, max) of its values
+// on the paths to/from the node. The interval of a variable that is not assigned on these paths
+// is the bounds of its domain, so that the intervals of the scope at a node are obtained by
+// intersecting its top-down and bottom-up properties. An assignment is valid if the constraint is
+// bound-consistent with these intervals. For the division and the modulo, the supports are
+// enumerated from the domains, unless there are too many of them.
+
+/// Maximum number of pairs (x, y) enumerated to find a support for the division and the modulo
+const MAX_ENUMERATION: usize = 4096;
+
+/// Arithmetic operation of a ternary constraint
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation {
+    Add,
+    Mul,
+    Div,
+    Mod,
+}
+
+impl Operation {
+
+    /// Returns the result of the operation, if defined
+    pub fn apply(&self, x: isize, y: isize) -> Option<isize> {
+        match self {
+            Operation::Add => x.checked_add(y),
+            Operation::Mul => x.checked_mul(y),
+            Operation::Div => x.checked_div(y),
+            Operation::Mod => x.checked_rem(y),
+        }
+    }
+}
+
+/// Intervals of the variables x, y and z
+type Intervals = [(isize, isize); 3];
+
+#[derive(Clone)]
+pub struct Arithmetic {
+    /// The variables x, y and z
+    variables: [VariableIndex; 3],
+    operation: Operation,
+    /// Domains of x, y and z, in increasing order
+    domains: [Vec<isize>; 3],
+    /// Layers of x, y and z
+    layers: [usize; 3],
+    top_down_properties: Vec<Vec<Intervals>>,
+    bottom_up_properties: Vec<Vec<Intervals>>,
+}
+
+impl Arithmetic {
+
+    /// Creates the constraint z = x operation y
+    pub fn new(x: VariableIndex, y: VariableIndex, z: VariableIndex, operation: Operation) -> Self {
+        Self {
+            variables: [x, y, z],
+            operation,
+            domains: [vec![], vec![], vec![]],
+            layers: [0; 3],
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+        }
+    }
+
+    /// Returns the intervals of the variables when none of them is assigned
+    fn domain_bounds(&self) -> Intervals {
+        let mut intervals = [(0, 0); 3];
+        for (interval, domain) in intervals.iter_mut().zip(self.domains.iter()) {
+            *interval = (domain.first().copied().unwrap_or(isize::MAX), domain.last().copied().unwrap_or(isize::MIN));
+        }
+        intervals
+    }
+
+    /// Returns the intervals obtained by assigning the value to the decision of the layer
+    fn assign(&self, mut intervals: Intervals, layer: usize, assignment: isize) -> Intervals {
+        for (interval, variable_layer) in intervals.iter_mut().zip(self.layers) {
+            if variable_layer == layer {
+                *interval = (assignment, assignment);
+            }
+        }
+        intervals
+    }
+
+    /// Returns true if there is a support for the constraint within the intervals
+    fn is_feasible(&self, intervals: &Intervals) -> bool {
+        if intervals.iter().any(|(min, max)| min > max) {
+            return false;
+        }
+        let [(x_min, x_max), (y_min, y_max), (z_min, z_max)] = *intervals;
+        match self.operation {
+            Operation::Add => x_min.saturating_add(y_min) <= z_max && x_max.saturating_add(y_max) >= z_min,
+            Operation::Mul => {
+                let corners = [x_min.saturating_mul(y_min), x_min.saturating_mul(y_max), x_max.saturating_mul(y_min), x_max.saturating_mul(y_max)];
+                *corners.iter().min().unwrap() <= z_max && *corners.iter().max().unwrap() >= z_min
+            },
+            Operation::Div | Operation::Mod => {
+                let xs = self.domains[0].iter().copied().filter(|x| x_min <= *x && *x <= x_max).collect::<Vec<isize>>();
+                let ys = self.domains[1].iter().copied().filter(|y| y_min <= *y && *y <= y_max).collect::<Vec<isize>>();
+                if xs.len() * ys.len() > MAX_ENUMERATION {
+                    return true;
+                }
+                xs.iter().any(|x| ys.iter().any(|y| {
+                    self.operation.apply(*x, *y).is_some_and(|z| z_min <= z && z <= z_max && self.domains[2].binary_search(&z).is_ok())
+                }))
+            },
+        }
+    }
+}
+
+impl Constraint for Arithmetic {
+
+    fn init(&mut self, vars: &[Variable]) {
+        for (domain, variable) in self.domains.iter_mut().zip(self.variables) {
+            *domain = vars[*variable].iter_domain().collect::<Vec<isize>>();
+            domain.sort_unstable();
+        }
+        let bounds = self.domain_bounds();
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![bounds]).collect::<Vec<Vec<Intervals>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![bounds]).collect::<Vec<Vec<Intervals>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        for (layer, variable) in self.layers.iter_mut().zip(self.variables) {
+            *layer = ordering[variable.0];
+        }
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index] = [(isize::MAX, isize::MIN); 3];
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let intervals = self.assign(self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        for (interval, other) in self.top_down_properties[target_layer][target_index].iter_mut().zip(intervals) {
+            *interval = (interval.0.min(other.0), interval.1.max(other.1));
+        }
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index] = [(isize::MAX, isize::MIN); 3];
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let intervals = self.assign(self.bottom_up_properties[source_layer][source_index], target_layer, assignment);
+        for (interval, other) in self.bottom_up_properties[target_layer][target_index].iter_mut().zip(intervals) {
+            *interval = (interval.0.min(other.0), interval.1.max(other.1));
+        }
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.layers.contains(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let mut intervals = self.top_down_properties[source_layer][source_index];
+        for (interval, other) in intervals.iter_mut().zip(self.bottom_up_properties[target_layer][target_index]) {
+            *interval = (interval.0.max(other.0), interval.1.min(other.1));
+        }
+        !self.is_feasible(&self.assign(intervals, source_layer, assignment))
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        let bounds = self.domain_bounds();
+        self.top_down_properties[layer].push(bounds);
+        self.bottom_up_properties[layer].push(bounds);
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.into_iter())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let [x, y, z] = self.variables;
+        self.operation.apply(assignment[*x], assignment[*y]) == Some(assignment[*z])
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for (min, max) in self.top_down_properties[layer][index].iter().chain(self.bottom_up_properties[layer][index].iter()).copied() {
+            state.write_isize(min);
+            state.write_isize(max);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        let [x, y, z] = self.variables;
+        Some(ConstraintDescription::Arithmetic { x, y, z, operation: self.operation })
+    }
+}
+
+#[cfg(test)]
+mod test_arithmetic {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_operations() {
+        for operation in [Operation::Add, Operation::Mul, Operation::Div, Operation::Mod] {
+            let mut problem = Problem::default();
+            let x = problem.add_variable(vec![-2, -1, 0, 1, 2, 3], None);
+            let y = problem.add_variable(vec![-1, 0, 1, 2], None);
+            let z = problem.add_variable(vec![-1, 0, 1, 2, 3], None);
+            arithmetic(&mut problem, x, y, z, operation);
+
+            let expected = [-2, -1, 0, 1, 2, 3].into_iter().flat_map(|x| [-1, 0, 1, 2].into_iter().map(move |y| (x, y)))
+                .filter(|(x, y)| operation.apply(*x, *y).is_some_and(|z| (-1..=3).contains(&z)))
+                .count();
+            let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![2, 0, 1]), MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            let solutions = get_all_solutions(&mdd);
+            assert_eq!(solutions.len(), expected);
+            assert!(solutions.iter().all(|solution| operation.apply(solution[0], solution[1]) == Some(solution[2])));
+        }
+    }
+
+    #[test]
+    pub fn test_sum_bounds() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1, 2, 3], None);
+        let y = problem.add_variable(vec![0, 1, 2, 3], None);
+        let z = problem.add_variable(vec![6], None);
+        plus(&mut problem, x, y, z);
+
+        let mdd = Mdd::new(problem, 1, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 1);
+        assert!(is_solution(vec![3, 3, 6], &solutions));
+    }
+}
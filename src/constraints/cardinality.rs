@@ -0,0 +1,196 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::hash::Hasher;
+
+// Structures for the boolean cardinality constraint: the number of true literals over 0/1
+// variables must be in [lower, upper]. A positive literal is true if its variable is 1, a negative
+// literal if its variable is 0. A clause is the special case lower = 1.
+//
+// The property of a node is the interval (min, max) of the number of true literals on the paths
+// to/from the node.
+
+#[derive(Clone)]
+pub struct Cardinality {
+    /// Literals of the constraint, as (variable, is positive)
+    literals: Vec<(VariableIndex, bool)>,
+    /// Minimum number of true literals
+    lower: usize,
+    /// Maximum number of true literals
+    upper: usize,
+    /// For each layer in the scope, the polarities of the literals of its decision
+    literals_at_layer: FxHashMap<usize, Vec<bool>>,
+    top_down_properties: Vec<Vec<(usize, usize)>>,
+    bottom_up_properties: Vec<Vec<(usize, usize)>>,
+}
+
+impl Cardinality {
+
+    /// Creates a new cardinality constraint. Between lower and upper literals must be true.
+    pub fn new(literals: Vec<(VariableIndex, bool)>, lower: usize, upper: usize) -> Self {
+        Self {
+            literals,
+            lower,
+            upper,
+            literals_at_layer: FxHashMap::<usize, Vec<bool>>::default(),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+        }
+    }
+
+    /// Returns the number of literals made true by assigning the value to the decision of the layer
+    fn true_literals(&self, layer: usize, assignment: isize) -> usize {
+        match self.literals_at_layer.get(&layer) {
+            Some(polarities) => polarities.iter().filter(|positive| (assignment == 1) == **positive).count(),
+            None => 0,
+        }
+    }
+
+    /// Returns the counts obtained by assigning the value to the decision of the layer
+    fn assign(&self, counts: (usize, usize), layer: usize, assignment: isize) -> (usize, usize) {
+        let count = self.true_literals(layer, assignment);
+        (counts.0.saturating_add(count), counts.1.saturating_add(count))
+    }
+}
+
+impl Constraint for Cardinality {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![(0, 0)]).collect::<Vec<Vec<(usize, usize)>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![(0, 0)]).collect::<Vec<Vec<(usize, usize)>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.literals_at_layer.clear();
+        for (variable, positive) in self.literals.iter().copied() {
+            self.literals_at_layer.entry(ordering[variable.0]).or_default().push(positive);
+        }
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index] = (usize::MAX, 0);
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let (min, max) = self.assign(self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        let property = &mut self.top_down_properties[target_layer][target_index];
+        *property = (property.0.min(min), property.1.max(max));
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index] = (usize::MAX, 0);
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let (min, max) = self.assign(self.bottom_up_properties[source_layer][source_index], target_layer, assignment);
+        let property = &mut self.bottom_up_properties[target_layer][target_index];
+        *property = (property.0.min(min), property.1.max(max));
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.literals_at_layer.contains_key(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let (min_above, max_above) = self.assign(self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        let (min_below, max_below) = self.bottom_up_properties[target_layer][target_index];
+        min_above.saturating_add(min_below) > self.upper || max_above + max_below < self.lower
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push((0, 0));
+        self.bottom_up_properties[layer].push((0, 0));
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.literals.iter().map(|(variable, _)| *variable))
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let count = self.literals.iter().filter(|(variable, positive)| (assignment[variable.0] == 1) == *positive).count();
+        self.lower <= count && count <= self.upper
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for (min, max) in [self.top_down_properties[layer][index], self.bottom_up_properties[layer][index]] {
+            state.write_usize(min);
+            state.write_usize(max);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn negation(&self) -> Option<Box<dyn Constraint + Send + Sync>> {
+        // The negation is a cardinality constraint only if one of the bounds is trivial
+        let n = self.literals.len();
+        if self.upper >= n {
+            Some(Box::new(Cardinality::new(self.literals.clone(), if self.lower == 0 { n + 1 } else { 0 }, self.lower.saturating_sub(1))))
+        } else if self.lower == 0 {
+            Some(Box::new(Cardinality::new(self.literals.clone(), self.upper + 1, n)))
+        } else {
+            None
+        }
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::Cardinality { literals: self.literals.clone(), lower: self.lower, upper: self.upper })
+    }
+}
+
+#[cfg(test)]
+mod test_cardinality {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_cnf() {
+        // (a or not b) and (b or c) and (not a or not c)
+        let mut problem = Problem::default();
+        let a = problem.add_variable(vec![0, 1], None);
+        let b = problem.add_variable(vec![0, 1], None);
+        let c = problem.add_variable(vec![0, 1], None);
+        clause(&mut problem, vec![a], vec![b]);
+        clause(&mut problem, vec![b, c], vec![]);
+        clause(&mut problem, vec![], vec![a, c]);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 2);
+        assert!(is_solution(vec![1, 1, 0], &solutions));
+        assert!(is_solution(vec![0, 0, 1], &solutions));
+    }
+
+    #[test]
+    pub fn test_exactly_k() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(5, vec![0, 1], None);
+        exactly_k(&mut problem, vars.clone(), 2);
+        at_most_k(&mut problem, vars[0..2].to_vec(), 1);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        // C(5, 2) minus the assignment with both of the two first variables true
+        assert_eq!(solutions.len(), 9);
+        assert!(solutions.iter().all(|solution| solution.iter().sum::<isize>() == 2));
+    }
+}
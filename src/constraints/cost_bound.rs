@@ -0,0 +1,204 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::hash::Hasher;
+
+// Structures for the bound on the total cost of an assignment. The cost of an assignment is the
+// sum of the costs of the values taken by the variables, and must be at most the bound. The costs
+// are given to the constraint (see `cost_at_most`, which reads them from `Variable::cost`), so
+// that it can check an assignment before the problem is compiled.
+//
+// The property of a node is the minimum cost of the paths to/from the node. An edge is removed if
+// the cheapest path through it exceeds the bound, which makes the compilation cost-aware.
+
+#[derive(Clone)]
+pub struct CostBound {
+    /// Variables whose costs are summed
+    variables: Vec<VariableIndex>,
+    /// Maximum total cost
+    bound: isize,
+    /// Cost of each value of the variables in the scope, by layer
+    costs_at_layer: FxHashMap<usize, FxHashMap<isize, isize>>,
+    /// Cost of each value of the variables in the scope, by variable
+    costs: FxHashMap<VariableIndex, FxHashMap<isize, isize>>,
+    /// Costs of the values of each variable in the scope, as (value, cost)
+    value_costs: Vec<Vec<(isize, isize)>>,
+    top_down_properties: Vec<Vec<isize>>,
+    bottom_up_properties: Vec<Vec<isize>>,
+}
+
+impl CostBound {
+
+    /// Creates the constraint total cost of the variables <= bound. The costs of the values of
+    /// variables[i] are given by costs[i], as (value, cost).
+    pub fn new(variables: Vec<VariableIndex>, costs: Vec<Vec<(isize, isize)>>, bound: isize) -> Self {
+        debug_assert!(variables.len() == costs.len());
+        Self {
+            costs: variables.iter().copied().zip(costs.iter()).map(|(variable, costs)| (variable, costs.iter().copied().collect::<FxHashMap<isize, isize>>())).collect(),
+            value_costs: costs,
+            variables,
+            bound,
+            costs_at_layer: FxHashMap::<usize, FxHashMap<isize, isize>>::default(),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+        }
+    }
+
+    /// Returns the cost obtained by assigning the value to the decision of the layer
+    fn assign(&self, cost: isize, layer: usize, assignment: isize) -> isize {
+        match self.costs_at_layer.get(&layer) {
+            Some(costs) => cost.saturating_add(costs[&assignment]),
+            None => cost,
+        }
+    }
+}
+
+impl Constraint for CostBound {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![0]).collect::<Vec<Vec<isize>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![0]).collect::<Vec<Vec<isize>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.costs_at_layer = self.costs.iter().map(|(variable, costs)| (ordering[variable.0], costs.clone())).collect::<FxHashMap<usize, FxHashMap<isize, isize>>>();
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index] = isize::MAX;
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let cost = self.assign(self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        let property = &mut self.top_down_properties[target_layer][target_index];
+        *property = (*property).min(cost);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index] = isize::MAX;
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let cost = self.assign(self.bottom_up_properties[source_layer][source_index], target_layer, assignment);
+        let property = &mut self.bottom_up_properties[target_layer][target_index];
+        *property = (*property).min(cost);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.costs_at_layer.contains_key(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let above = self.assign(self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        above.saturating_add(self.bottom_up_properties[target_layer][target_index]) > self.bound
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(0);
+        self.bottom_up_properties[layer].push(0);
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.iter().copied())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        self.variables.iter().map(|variable| self.costs[variable][&assignment[variable.0]]).sum::<isize>() <= self.bound
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        state.write_isize(self.top_down_properties[layer][index]);
+        state.write_isize(self.bottom_up_properties[layer][index]);
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::CostBound { variables: self.variables.clone(), costs: self.value_costs.clone(), bound: self.bound })
+    }
+}
+
+#[cfg(test)]
+mod test_cost_bound {
+
+    use crate::constraints::*;
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_minimum_violation() {
+        // The three constraints form a cycle and can not be all satisfied
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1, 2], None);
+        soft(&mut problem, LessEqual::new(vars[0], vars[1], 1), 3);
+        soft(&mut problem, LessEqual::new(vars[1], vars[2], 1), 2);
+        soft(&mut problem, LessEqual::new(vars[2], vars[0], 1), 4);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let (cost, solution) = mdd.minimum_cost_solution().unwrap();
+        assert_eq!(cost, 2);
+        assert!(solution[0] < solution[1] && solution[2] < solution[0]);
+    }
+
+    #[test]
+    pub fn test_cost_bound() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1], None);
+        for variable in vars.iter().copied() {
+            problem[variable].set_costs(&[0, 2]);
+        }
+        cost_at_most(&mut problem, vars, 3);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        // At most one variable can take the value 1
+        assert_eq!(solutions.len(), 4);
+        assert!(solutions.iter().all(|solution| solution.iter().sum::<isize>() <= 1));
+    }
+
+    #[test]
+    pub fn test_check_before_compilation() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1], None);
+        for variable in vars.iter().copied() {
+            problem[variable].set_costs(&[0, 2]);
+        }
+        cost_at_most(&mut problem, vars, 3);
+        // The costs set after the constraint is posted are not read
+        problem[VariableIndex(0)].set_costs(&[0, 5]);
+
+        assert!(problem.check(&[1, 0, 0]).is_ok());
+        assert_eq!(problem.check(&[1, 1, 0]), Err(Violation::Constraint(ConstraintIndex(0))));
+        assert_eq!(reference::count_solutions(&problem), 4);
+        let problem = Problem::from_description(&problem.description());
+        assert!(problem.check(&[0, 1, 0]).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "no negation")]
+    pub fn test_soft_without_negation() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1], None);
+        soft(&mut problem, AllDifferent::new(vars), 1);
+    }
+}
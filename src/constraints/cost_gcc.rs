@@ -0,0 +1,241 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::hash::Hasher;
+
+// Structures for the cost global cardinality constraint: each value must be taken by a number of
+// variables in its bounds, and each occurrence of a value has a cost. The total cost of the
+// occurrences must be at most the value of the cost variable z. The values without bounds can be
+// taken freely, at no cost.
+//
+// As for the cardinality constraint, the property of a node stores, for each value, the interval of
+// its number of occurrences on the paths to/from the node. It also stores the minimum cost of
+// these paths and the interval of z.
+
+/// Local property of a node for the cost global cardinality constraint
+#[derive(Clone, PartialEq, Eq)]
+struct CostGccProperty {
+    /// Interval of the number of occurrences of each value
+    counts: Vec<(usize, usize)>,
+    /// Minimum cost of the occurrences
+    cost: isize,
+    /// Interval of z
+    z: (isize, isize),
+}
+
+impl CostGccProperty {
+
+    pub fn reset(&mut self) {
+        self.counts.fill((usize::MAX, 0));
+        self.cost = isize::MAX;
+        self.z = (isize::MAX, isize::MIN);
+    }
+
+    pub fn aggregate(&mut self, other: &Self) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter().copied()) {
+            *count = (count.0.min(other_count.0), count.1.max(other_count.1));
+        }
+        self.cost = self.cost.min(other.cost);
+        self.z = (self.z.0.min(other.z.0), self.z.1.max(other.z.1));
+    }
+}
+
+#[derive(Clone)]
+pub struct CostGcc {
+    variables: Vec<VariableIndex>,
+    /// Values with bounds on their number of occurrences
+    values: Vec<isize>,
+    /// For each value, its index in the values
+    index_of_value: FxHashMap<isize, usize>,
+    lower: Vec<usize>,
+    upper: Vec<usize>,
+    /// Cost of each occurrence of the values
+    costs: Vec<isize>,
+    z: VariableIndex,
+    /// Property of the root and the sink
+    neutral: CostGccProperty,
+    top_down_properties: Vec<Vec<CostGccProperty>>,
+    bottom_up_properties: Vec<Vec<CostGccProperty>>,
+    layers_x: FxHashSet<usize>,
+    layer_z: usize,
+}
+
+impl CostGcc {
+
+    /// Creates a new cost global cardinality constraint. The value values[i] must be taken by
+    /// between lower[i] and upper[i] variables, and each of its occurrences costs costs[i].
+    pub fn new(variables: Vec<VariableIndex>, values: Vec<isize>, lower: Vec<usize>, upper: Vec<usize>, costs: Vec<isize>, z: VariableIndex) -> Self {
+        let index_of_value = values.iter().copied().enumerate().map(|(index, value)| (value, index)).collect::<FxHashMap<isize, usize>>();
+        let neutral = CostGccProperty { counts: vec![(0, 0); values.len()], cost: 0, z: (isize::MIN, isize::MAX) };
+        Self {
+            variables,
+            values,
+            index_of_value,
+            lower,
+            upper,
+            costs,
+            z,
+            neutral,
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            layers_x: FxHashSet::<usize>::default(),
+            layer_z: 0,
+        }
+    }
+
+    /// Returns the property obtained by assigning the value to the decision of the layer
+    fn assign(&self, property: &CostGccProperty, layer: usize, assignment: isize) -> CostGccProperty {
+        let mut assigned = property.clone();
+        if layer == self.layer_z {
+            assigned.z = (assignment, assignment);
+        } else if self.layers_x.contains(&layer) && let Some(index) = self.index_of_value.get(&assignment).copied() {
+            let (min, max) = assigned.counts[index];
+            assigned.counts[index] = (min.saturating_add(1), max + 1);
+            assigned.cost = assigned.cost.saturating_add(self.costs[index]);
+        }
+        assigned
+    }
+}
+
+impl Constraint for CostGcc {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.neutral.z = (vars[*self.z].iter_domain().min().unwrap_or(isize::MAX), vars[*self.z].iter_domain().max().unwrap_or(isize::MIN));
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![self.neutral.clone()]).collect::<Vec<Vec<CostGccProperty>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![self.neutral.clone()]).collect::<Vec<Vec<CostGccProperty>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.layers_x = self.variables.iter().map(|x| ordering[x.0]).collect::<FxHashSet<usize>>();
+        self.layer_z = ordering[self.z.0];
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset();
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let property = self.assign(&self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        self.top_down_properties[target_layer][target_index].aggregate(&property);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset();
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let property = self.assign(&self.bottom_up_properties[source_layer][source_index], target_layer, assignment);
+        self.bottom_up_properties[target_layer][target_index].aggregate(&property);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        layer == self.layer_z || self.layers_x.contains(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let above = self.assign(&self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        let below = &self.bottom_up_properties[target_layer][target_index];
+        let z_max = above.z.1.min(below.z.1);
+        if above.cost.saturating_add(below.cost) > z_max {
+            return true;
+        }
+        (0..self.values.len()).any(|index| {
+            let (min_above, max_above) = above.counts[index];
+            let (min_below, max_below) = below.counts[index];
+            min_above.saturating_add(min_below) > self.upper[index] || max_above + max_below < self.lower[index]
+        })
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(self.neutral.clone());
+        self.bottom_up_properties[layer].push(self.neutral.clone());
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.iter().copied().chain(std::iter::once(self.z)))
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let mut counts = vec![0; self.values.len()];
+        for variable in self.variables.iter().copied() {
+            if let Some(index) = self.index_of_value.get(&assignment[variable.0]).copied() {
+                counts[index] += 1;
+            }
+        }
+        let cost = counts.iter().zip(self.costs.iter()).map(|(count, cost)| *count as isize * cost).sum::<isize>();
+        (0..self.values.len()).all(|index| self.lower[index] <= counts[index] && counts[index] <= self.upper[index]) && cost <= assignment[self.z.0]
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for property in [&self.top_down_properties[layer][index], &self.bottom_up_properties[layer][index]] {
+            for (min, max) in property.counts.iter().copied() {
+                state.write_usize(min);
+                state.write_usize(max);
+            }
+            state.write_isize(property.cost);
+            state.write_isize(property.z.0);
+            state.write_isize(property.z.1);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::CostGcc { variables: self.variables.clone(), values: self.values.clone(), lower: self.lower.clone(), upper: self.upper.clone(), costs: self.costs.clone(), z: self.z })
+    }
+}
+
+#[cfg(test)]
+mod test_cost_gcc {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_cost_gcc() {
+        // Value 1 must appear at least once and value 2 at most once, with costs 1 and 3
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let z = problem.add_variable(vec![3], None);
+        cost_global_cardinality(&mut problem, xs, vec![1, 2], vec![1, 0], vec![3, 1], vec![1, 3], z);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2, 3]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        // A 1 and a 2 cost 4, so the solutions have between one and three 1s and no 2
+        assert_eq!(solutions.len(), 7);
+        assert!(solutions.iter().all(|solution| solution[0..3].contains(&1) && !solution[0..3].contains(&2)));
+    }
+
+    #[test]
+    pub fn test_cost_gcc_cost_variable_first() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1], None);
+        let z = problem.add_variable(vec![0, 1, 2, 3], None);
+        cost_global_cardinality(&mut problem, xs, vec![1], vec![2], vec![3], vec![1], z);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![1, 2, 3, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        // Three assignments with two 1s for z in {2, 3}, one with three 1s for z = 3
+        assert_eq!(solutions.len(), 7);
+        assert!(solutions.iter().all(|solution| solution[0..3].iter().sum::<isize>() <= solution[3]));
+    }
+}
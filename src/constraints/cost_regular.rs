@@ -0,0 +1,408 @@
+use super::*;
+use super::sequence::{SequenceLayers, PendingValues};
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::hash::Hasher;
+
+// Structures for the cost-regular constraint: the sequence of values taken by the variables must
+// be accepted by a deterministic automaton, and the total cost of the transitions taken must be at
+// most the value of the cost variable z.
+//
+// The top-down property of a node is, for each state of the automaton, the minimum cost of the
+// runs on the paths from the root reaching it (isize::MAX if no run reaches it). Similarly, the
+// bottom-up property is, for each state, the minimum cost to reach a final state on the paths to
+// the sink. If the sequence is assigned in reverse order, the roles are swapped and the automaton
+// is read backward from the root. As for the stretch constraint, the runs can only be followed
+// while the variables are assigned in the order of the sequence (see `SequenceLayers`). Past that
+// point, every state is considered reachable at no cost from the sink. From the root, the costs
+// are kept unchanged over the pending positions, whose values are recorded and read by the runs at
+// the last position of the sequence (see `PendingValues`). Until then, the costs do not match the
+// states of the bottom-up property and only the latter is used. Both properties also store the
+// interval of z.
+
+/// Local property of a node for the cost-regular constraint
+#[derive(Clone, PartialEq, Eq)]
+struct CostRegularProperty {
+    /// Minimum cost of the runs reaching each state
+    costs: Vec<isize>,
+    /// Interval of z
+    z: (isize, isize),
+}
+
+impl CostRegularProperty {
+
+    pub fn reset(&mut self) {
+        self.costs.fill(isize::MAX);
+        self.z = (isize::MAX, isize::MIN);
+    }
+
+    pub fn aggregate(&mut self, other: &Self) {
+        for (cost, other_cost) in self.costs.iter_mut().zip(other.costs.iter().copied()) {
+            *cost = (*cost).min(other_cost);
+        }
+        self.z = (self.z.0.min(other.z.0), self.z.1.max(other.z.1));
+    }
+
+    /// Returns the minimum cost of a run going through a state of both properties
+    pub fn meet(&self, other: &Self) -> isize {
+        self.costs.iter().zip(other.costs.iter()).map(|(cost, other_cost)| cost.saturating_add(*other_cost)).min().unwrap_or(isize::MAX)
+    }
+}
+
+#[derive(Clone)]
+pub struct CostRegular {
+    variables: Vec<VariableIndex>,
+    /// Transitions of the automaton, as (from, value) -> [(to, cost)]
+    transitions: FxHashMap<(usize, isize), Vec<(usize, isize)>>,
+    /// Reversed transitions of the automaton, as (to, value) -> [(from, cost)]
+    reversed: FxHashMap<(usize, isize), Vec<(usize, isize)>>,
+    initial: usize,
+    finals: Vec<usize>,
+    z: VariableIndex,
+    /// Property of a node before its update
+    neutral: CostRegularProperty,
+    top_down_properties: Vec<Vec<CostRegularProperty>>,
+    bottom_up_properties: Vec<Vec<CostRegularProperty>>,
+    sequence: SequenceLayers,
+    layer_z: usize,
+    /// Values of the pending positions on the paths from the root, for each node in the MDD
+    pending: Vec<Vec<PendingValues>>,
+}
+
+impl CostRegular {
+
+    /// Creates a new cost-regular constraint. The transitions of the automaton are given as (from,
+    /// value, to, cost) and must be deterministic. The states are numbered from 0.
+    pub fn new(variables: Vec<VariableIndex>, transitions: Vec<(usize, isize, usize, isize)>, initial: usize, finals: Vec<usize>, z: VariableIndex) -> Self {
+        let number_states = transitions.iter().map(|(from, _, to, _)| from.max(to) + 1)
+            .chain(finals.iter().map(|state| state + 1))
+            .chain(std::iter::once(initial + 1))
+            .max().unwrap();
+        let mut forward = FxHashMap::<(usize, isize), Vec<(usize, isize)>>::default();
+        let mut reversed = FxHashMap::<(usize, isize), Vec<(usize, isize)>>::default();
+        for (from, value, to, cost) in transitions.iter().copied() {
+            forward.entry((from, value)).or_default().push((to, cost));
+            reversed.entry((to, value)).or_default().push((from, cost));
+        }
+        Self {
+            variables,
+            transitions: forward,
+            reversed,
+            initial,
+            finals,
+            z,
+            neutral: CostRegularProperty { costs: vec![isize::MAX; number_states], z: (isize::MIN, isize::MAX) },
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            sequence: SequenceLayers::default(),
+            layer_z: 0,
+            pending: vec![],
+        }
+    }
+
+    /// Returns the states at which the runs start, when the automaton is read forward (from the
+    /// initial state) or backward (from the final states)
+    fn start(&self, forward: bool) -> CostRegularProperty {
+        let mut property = self.neutral.clone();
+        if forward {
+            property.costs[self.initial] = 0;
+        } else {
+            for state in self.finals.iter().copied() {
+                property.costs[state] = 0;
+            }
+        }
+        property
+    }
+
+    /// Returns the states reached from the state by reading the value, forward or backward
+    fn step(&self, forward: bool, state: usize, value: isize) -> &[(usize, isize)] {
+        let transitions = if forward { &self.transitions } else { &self.reversed };
+        transitions.get(&(state, value)).map(|next| next.as_slice()).unwrap_or(&[])
+    }
+
+    /// Returns the minimum cost of the runs reaching each state after reading the value, forward or
+    /// backward
+    fn read(&self, costs: &[isize], value: isize, forward: bool) -> Vec<isize> {
+        let mut next_costs = vec![isize::MAX; costs.len()];
+        for (state, cost) in costs.iter().copied().enumerate().filter(|(_, cost)| *cost != isize::MAX) {
+            for (next, transition_cost) in self.step(forward, state, value).iter().copied() {
+                next_costs[next] = next_costs[next].min(cost.saturating_add(transition_cost));
+            }
+        }
+        next_costs
+    }
+
+    /// Returns the property obtained by assigning the value to the decision of the layer. The
+    /// automaton is read forward or backward, and the runs are followed only if the position of
+    /// the layer is tracked.
+    fn assign(&self, property: &CostRegularProperty, layer: usize, assignment: isize, forward: bool, tracked: bool) -> CostRegularProperty {
+        let mut assigned = property.clone();
+        if layer == self.layer_z {
+            assigned.z = (assignment, assignment);
+        } else if self.sequence.is_layer_in_scope(layer) {
+            if tracked {
+                assigned.costs = self.read(&property.costs, assignment, forward);
+            } else {
+                assigned.costs.fill(0);
+            }
+        }
+        assigned
+    }
+
+    /// Returns the property obtained by reading the values of the pending positions, followed by
+    /// the value of the last one, from the top-down property. If the values are not known, every
+    /// state is reachable at no cost.
+    fn read_pending(&self, property: &CostRegularProperty, pending: &PendingValues, assignment: isize) -> CostRegularProperty {
+        let mut read = property.clone();
+        match pending.sequences() {
+            Some(sequences) => {
+                read.costs.fill(isize::MAX);
+                let forward = self.sequence.is_forward_top_down();
+                for values in sequences.iter() {
+                    let costs = self.sequence.pending_in_order(values, assignment).into_iter().fold(property.costs.clone(), |costs, value| self.read(&costs, value, forward));
+                    for (cost, other_cost) in read.costs.iter_mut().zip(costs) {
+                        *cost = (*cost).min(other_cost);
+                    }
+                }
+            },
+            None => read.costs.fill(0),
+        }
+        read
+    }
+
+    /// Returns the minimum cost of a run through the top-down property of a node of the layer and
+    /// the bottom-up property. If the top-down costs are pending, they are not used.
+    fn meet(&self, layer: usize, above: &CostRegularProperty, below: &CostRegularProperty) -> isize {
+        if self.sequence.is_pending_at_layer(layer) {
+            below.costs.iter().copied().min().unwrap_or(isize::MAX)
+        } else {
+            above.meet(below)
+        }
+    }
+
+    fn assign_top_down(&self, property: &CostRegularProperty, layer: usize, assignment: isize) -> CostRegularProperty {
+        let tracked = self.sequence.position(layer).is_some_and(|position| self.sequence.is_tracked_top_down(position));
+        self.assign(property, layer, assignment, self.sequence.is_forward_top_down(), tracked)
+    }
+
+    fn assign_bottom_up(&self, property: &CostRegularProperty, layer: usize, assignment: isize) -> CostRegularProperty {
+        let tracked = self.sequence.position(layer).is_some_and(|position| self.sequence.is_tracked_bottom_up(position));
+        self.assign(property, layer, assignment, !self.sequence.is_forward_top_down(), tracked)
+    }
+}
+
+impl Constraint for CostRegular {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.neutral.z = (vars[*self.z].iter_domain().min().unwrap_or(isize::MAX), vars[*self.z].iter_domain().max().unwrap_or(isize::MIN));
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![self.neutral.clone()]).collect::<Vec<Vec<CostRegularProperty>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![self.neutral.clone()]).collect::<Vec<Vec<CostRegularProperty>>>();
+        self.pending = (0..vars.len() + 1).map(|_| vec![PendingValues::new()]).collect::<Vec<Vec<PendingValues>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.sequence = SequenceLayers::new(&self.variables, ordering);
+        self.layer_z = ordering[self.z.0];
+        // The runs start at the root and the sink, from the end of the sequence on their side
+        let forward = self.sequence.is_forward_top_down();
+        self.top_down_properties[0][0] = self.start(forward);
+        let sink_layer = self.bottom_up_properties.len() - 1;
+        self.bottom_up_properties[sink_layer][0] = self.start(!forward);
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset();
+        self.pending[layer][index].reset();
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let source_property = &self.top_down_properties[source_layer][source_index];
+        let position = self.sequence.position(source_layer);
+        let is_pending = position.is_some_and(|position| !self.sequence.is_tracked_top_down(position));
+        let is_last_pending = position.is_some_and(|position| self.sequence.is_last_pending(position));
+        let property = if is_last_pending {
+            self.read_pending(source_property, &self.pending[source_layer][source_index], assignment)
+        } else if is_pending {
+            source_property.clone()
+        } else {
+            self.assign_top_down(source_property, source_layer, assignment)
+        };
+        self.top_down_properties[target_layer][target_index].aggregate(&property);
+        let (pending_above, pending_below) = self.pending.split_at_mut(target_layer);
+        if is_last_pending {
+            pending_below[0][target_index].aggregate(&PendingValues::new(), None);
+        } else {
+            pending_below[0][target_index].aggregate(&pending_above[source_layer][source_index], Some(assignment).filter(|_| is_pending));
+        }
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset();
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let property = self.assign_bottom_up(&self.bottom_up_properties[source_layer][source_index], target_layer, assignment);
+        self.bottom_up_properties[target_layer][target_index].aggregate(&property);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        layer == self.layer_z || self.sequence.is_layer_in_scope(layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let above = &self.top_down_properties[source_layer][source_index];
+        let below = &self.bottom_up_properties[target_layer][target_index];
+        if source_layer == self.layer_z {
+            return self.meet(source_layer, above, below) > assignment;
+        }
+        let position = self.sequence.position(source_layer).unwrap();
+        let z_max = above.z.1.min(below.z.1);
+        if self.sequence.is_last_pending(position) {
+            // The runs read the whole sequence
+            return self.read_pending(above, &self.pending[source_layer][source_index], assignment).meet(below) > z_max;
+        }
+        if !self.sequence.is_tracked_top_down(position) || !self.sequence.is_tracked_bottom_up(position) {
+            // The runs can not be followed through the edge, only the non-emptiness of the
+            // properties is checked
+            return self.meet(source_layer, above, below) > z_max;
+        }
+        let forward = self.sequence.is_forward_top_down();
+        above.costs.iter().copied().enumerate().filter(|(_, cost)| *cost != isize::MAX).all(|(state, cost)| {
+            self.step(forward, state, assignment).iter().all(|(next, transition_cost)| cost.saturating_add(*transition_cost).saturating_add(below.costs[*next]) > z_max)
+        })
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(self.neutral.clone());
+        self.bottom_up_properties[layer].push(self.neutral.clone());
+        self.pending[layer].push(PendingValues::new());
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.iter().copied().chain(std::iter::once(self.z)))
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let mut state = self.initial;
+        let mut total = 0;
+        for variable in self.variables.iter().copied() {
+            match self.step(true, state, assignment[variable.0]).first() {
+                Some((to, cost)) => {
+                    state = *to;
+                    total += cost;
+                },
+                None => return false,
+            }
+        }
+        self.finals.contains(&state) && total <= assignment[self.z.0]
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for property in [&self.top_down_properties[layer][index], &self.bottom_up_properties[layer][index]] {
+            for cost in property.costs.iter().copied() {
+                state.write_isize(cost);
+            }
+            state.write_isize(property.z.0);
+            state.write_isize(property.z.1);
+        }
+        self.pending[layer][index].hash(state);
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex] &&
+        self.pending[layer][index] == self.pending[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        let mut transitions = self.transitions.iter().flat_map(|((from, value), targets)| targets.iter().map(move |(to, cost)| (*from, *value, *to, *cost))).collect::<Vec<(usize, isize, usize, isize)>>();
+        transitions.sort_unstable();
+        Some(ConstraintDescription::CostRegular { variables: self.variables.clone(), transitions, initial: self.initial, finals: self.finals.clone(), z: self.z })
+    }
+}
+
+#[cfg(test)]
+mod test_cost_regular {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+    use crate::reference;
+
+    /// Automaton for the shifts: a night shift (2) can not be followed by a day shift (1), and a
+    /// rest (0) after a night shift costs 1 less
+    fn shifts() -> Vec<(usize, isize, usize, isize)> {
+        vec![
+            (0, 0, 0, 1), (0, 1, 0, 2), (0, 2, 1, 3),
+            (1, 0, 0, 0), (1, 2, 1, 3),
+        ]
+    }
+
+    #[test]
+    pub fn test_cost_regular() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let z = problem.add_variable(vec![4], None);
+        cost_regular(&mut problem, xs, shifts(), 0, vec![0, 1], z);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2, 3]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 6);
+        assert!(is_solution(vec![0, 0, 0, 4], &solutions));
+        assert!(is_solution(vec![0, 2, 0, 4], &solutions));
+        assert!(is_solution(vec![0, 0, 1, 4], &solutions));
+        assert!(is_solution(vec![0, 1, 0, 4], &solutions));
+        assert!(is_solution(vec![1, 0, 0, 4], &solutions));
+        assert!(is_solution(vec![2, 0, 0, 4], &solutions));
+    }
+
+    #[test]
+    pub fn test_cost_regular_cost_variable_first() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let z = problem.add_variable((0..10).collect(), None);
+        cost_regular(&mut problem, xs, shifts(), 0, vec![0, 1], z);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![3, 2, 1, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert!(!solutions.is_empty());
+        for solution in solutions.iter() {
+            assert!(solution[3] >= 3);
+            assert!(solution[0..2].iter().zip(solution[1..3].iter()).all(|(x, y)| *x != 2 || *y != 1));
+        }
+        assert!((3..10).all(|z| solutions.iter().any(|solution| solution[3] == z)));
+    }
+
+
+    #[test]
+    pub fn test_any_order() {
+        // Two consecutive 1s cost 3 instead of 2
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1], None);
+        let z = problem.add_variable((0..6).collect(), None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        cost_regular(&mut problem, xs.clone(), vec![(0, 0, 0, 1), (0, 1, 1, 2), (1, 0, 0, 0), (1, 1, 1, 3)], 0, vec![0, 1], z);
+        not_equals(&mut problem, xs[1], y);
+        let expected = reference::count_solutions(&problem);
+        for order in [OrderingHeuristic::MinDomMaxLinked, OrderingHeuristic::Custom(vec![1, 4, 3, 2, 0]), OrderingHeuristic::Custom(vec![2, 0, 3, 1, 4])] {
+            let mut mdd = Mdd::new(problem.clone(), usize::MAX, order, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            assert_eq!(mdd.count_solutions(), expected);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+// Structures for constraints defined by user closures, to prototype new constraints without
+// implementing the `Constraint` trait. The constraint is given as a dynamic program over a state:
+// the state of the root, a transition giving the state after assigning a variable of the scope (or
+// None if the assignment is infeasible), a merge of two states into a state relaxing both, and a
+// feasibility test on the state after the last variable of the scope.
+//
+// The property of a node is the state on the paths from the root (None if no path reaches it). It
+// is only propagated top-down: an edge is removed if its transition is infeasible, or if it assigns
+// the last variable of the scope and the resulting state is not feasible.
+
+/// Closure giving the state after assigning a value to a variable of the scope
+pub type Transition<S> = Arc<dyn Fn(&S, VariableIndex, isize) -> Option<S> + Send + Sync>;
+/// Closure merging two states into a state relaxing both
+pub type Merge<S> = Arc<dyn Fn(&S, &S) -> S + Send + Sync>;
+/// Closure testing if the state after the last variable of the scope is feasible
+pub type Feasibility<S> = Arc<dyn Fn(&S) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+pub struct FunctionalConstraint<S> {
+    scope: Vec<VariableIndex>,
+    initial: S,
+    transition: Transition<S>,
+    merge: Merge<S>,
+    is_feasible: Feasibility<S>,
+    top_down_properties: Vec<Vec<Option<S>>>,
+    /// For each layer in the scope, its decision
+    decisions: FxHashMap<usize, VariableIndex>,
+    /// Last layer of the scope
+    last_layer: usize,
+    /// Scope sorted by layer, i.e., in the order of the transitions
+    ordered_scope: Vec<VariableIndex>,
+}
+
+impl<S: Clone> FunctionalConstraint<S> {
+
+    /// Creates a new constraint from its initial state and closures. The merge of two states must
+    /// relax both of them: a transition or feasibility test failing on the merged state must fail
+    /// on the two states.
+    pub fn new(scope: Vec<VariableIndex>, initial: S, transition: Transition<S>, merge: Merge<S>, is_feasible: Feasibility<S>) -> Self {
+        let ordered_scope = scope.clone();
+        Self {
+            scope,
+            initial,
+            transition,
+            merge,
+            is_feasible,
+            top_down_properties: vec![],
+            decisions: FxHashMap::<usize, VariableIndex>::default(),
+            last_layer: 0,
+            ordered_scope,
+        }
+    }
+
+    /// Returns the state obtained by assigning the value to the decision of the layer
+    fn assign(&self, state: &Option<S>, layer: usize, assignment: isize) -> Option<S> {
+        let state = state.as_ref()?;
+        match self.decisions.get(&layer) {
+            Some(decision) => (self.transition)(state, *decision, assignment),
+            None => Some(state.clone()),
+        }
+    }
+}
+
+impl<S: Clone + Eq + Hash + Send + Sync + 'static> Constraint for FunctionalConstraint<S> {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![Some(self.initial.clone())]).collect::<Vec<Vec<Option<S>>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.decisions = self.scope.iter().map(|variable| (ordering[variable.0], *variable)).collect::<FxHashMap<usize, VariableIndex>>();
+        self.last_layer = self.decisions.keys().copied().max().unwrap_or(0);
+        self.ordered_scope.sort_by_key(|variable| ordering[variable.0]);
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index] = None;
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        if let Some(state) = self.assign(&self.top_down_properties[source_layer][source_index], source_layer, assignment) {
+            let property = &mut self.top_down_properties[target_layer][target_index];
+            *property = match property.take() {
+                Some(current) => Some((self.merge)(&current, &state)),
+                None => Some(state),
+            };
+        }
+    }
+
+    fn reset_property_bottom_up(&mut self, _node: NodeIndex) {}
+
+    fn update_property_bottom_up(&mut self, _source: NodeIndex, _target: NodeIndex, _assignment: isize) {}
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.decisions.contains_key(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, _target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        match self.assign(&self.top_down_properties[source_layer][source_index], source_layer, assignment) {
+            Some(state) => source_layer == self.last_layer && !(self.is_feasible)(&state),
+            None => true,
+        }
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(Some(self.initial.clone()));
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.scope.iter().copied())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let mut state = self.initial.clone();
+        for variable in self.ordered_scope.iter().copied() {
+            match (self.transition)(&state, variable, assignment[variable.0]) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        (self.is_feasible)(&state)
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, mut state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].hash(&mut state);
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex]
+    }
+}
+
+#[cfg(test)]
+mod test_functional {
+
+    use crate::constraints::*;
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+    use std::sync::Arc;
+
+    #[test]
+    pub fn test_bounded_sum() {
+        // sum(xs) <= 3, with the minimum partial sum as relaxation
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        problem.add_constraint(FunctionalConstraint::new(
+            xs,
+            0,
+            Arc::new(|sum: &isize, _, value| Some(sum + value).filter(|sum| *sum <= 3)),
+            Arc::new(|a: &isize, b: &isize| *a.min(b)),
+            Arc::new(|sum: &isize| *sum <= 3),
+        ));
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 17);
+        assert!(solutions.iter().all(|solution| solution.iter().sum::<isize>() <= 3));
+    }
+
+    /// All different constraint, with the set of used values as state. The merge keeps the
+    /// values used on both paths.
+    fn all_different(xs: Vec<VariableIndex>) -> FunctionalConstraint<u64> {
+        FunctionalConstraint::new(
+            xs,
+            0,
+            Arc::new(|used: &u64, _, value| if used & (1 << value) == 0 { Some(used | (1 << value)) } else { None }),
+            Arc::new(|a: &u64, b: &u64| a & b),
+            Arc::new(|_: &u64| true),
+        )
+    }
+
+    #[test]
+    pub fn test_all_different_prototype() {
+        for width in [2, usize::MAX] {
+            let mut problem = Problem::default();
+            let xs = problem.add_variables(3, vec![0, 1, 2], None);
+            problem.add_constraint(all_different(xs));
+
+            let mut mdd = Mdd::new(problem, width, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            let solutions = get_all_solutions(&mdd);
+            // The relaxed MDD contains at least the solutions
+            assert!(solutions.len() >= 6);
+            if width == usize::MAX {
+                assert_eq!(solutions.len(), 6);
+                assert!(is_solution(vec![2, 0, 1], &solutions));
+            }
+        }
+    }
+}
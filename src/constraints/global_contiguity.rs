@@ -0,0 +1,265 @@
+use super::*;
+use super::sequence::{SequenceLayers, PendingValues};
+use crate::modelling::VariableIndex;
+use crate::mdd::*;
+use std::hash::Hasher;
+
+// Structures for the global contiguity constraint. All the variables of the sequence taking the
+// value 1 must be consecutive (any other value is treated as 0).
+//
+// The constraint is the automaton with three states: no 1 has been seen yet (BEFORE), the last
+// variable is part of the block of 1s (INSIDE), and the block has been closed by a 0 (AFTER). A 1
+// after the block is rejected. Since the constraint is symmetric, the same automaton is used in
+// both directions. The property of a node is the set of states reached on the paths to/from the
+// node, as a bitmask. As for the stretch constraint, the state is only tracked for the positions
+// whose neighbours on the root (resp. sink) side are assigned above (resp. below) them, see
+// `SequenceLayers`; otherwise every state is possible. Top-down, the states are kept over the
+// pending positions and read their values at the last position of the sequence (see
+// `PendingValues`), which keeps the propagation exact at unbounded width for any ordering.
+
+const BEFORE: u8 = 1;
+const INSIDE: u8 = 1 << 1;
+const AFTER: u8 = 1 << 2;
+const ANY: u8 = BEFORE | INSIDE | AFTER;
+
+/// Returns the states reached from the set of states when reading the value
+fn step(states: u8, value: isize) -> u8 {
+    if value == 1 {
+        if states & (BEFORE | INSIDE) != 0 { INSIDE } else { 0 }
+    } else {
+        let mut next = states & BEFORE;
+        if states & (INSIDE | AFTER) != 0 {
+            next |= AFTER;
+        }
+        next
+    }
+}
+
+/// Returns true if the two parts of a sequence, each read from one end up to the same position,
+/// can be joined without creating two blocks of 1s, for some of their (non-empty) sets of states.
+fn is_compatible(left: u8, right: u8) -> bool {
+    left & BEFORE != 0 || right & BEFORE != 0 || (left & INSIDE != 0 && right & INSIDE != 0)
+}
+
+#[derive(Clone)]
+pub struct GlobalContiguity {
+    /// Variables of the sequence, in order
+    variables: Vec<VariableIndex>,
+    /// Layers of the sequence in the MDD
+    sequence: SequenceLayers,
+    /// Top-down properties for each node in the MDD
+    top_down_properties: Vec<Vec<u8>>,
+    /// Bottom-up properties for each node in the MDD
+    bottom_up_properties: Vec<Vec<u8>>,
+    /// Values of the pending positions on the paths from the root, for each node in the MDD
+    pending: Vec<Vec<PendingValues>>,
+}
+
+impl GlobalContiguity {
+
+    /// Creates a new global contiguity constraint on the sequence of variables
+    pub fn new(variables: Vec<VariableIndex>) -> Self {
+        Self {
+            variables,
+            sequence: SequenceLayers::default(),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            pending: vec![],
+        }
+    }
+
+    /// Returns the states reached by reading the values of the pending positions, followed by the
+    /// value of the last one, from the states. Returns ANY if the values are not known.
+    fn read_pending(&self, states: u8, pending: &PendingValues, assignment: isize) -> u8 {
+        match pending.sequences() {
+            Some(sequences) => sequences.iter().fold(0, |reached, values| {
+                reached | self.sequence.pending_in_order(values, assignment).into_iter().fold(states, step)
+            }),
+            None => ANY,
+        }
+    }
+}
+
+impl Constraint for GlobalContiguity {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![BEFORE]).collect::<Vec<Vec<u8>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![BEFORE]).collect::<Vec<Vec<u8>>>();
+        self.pending = (0..vars.len() + 1).map(|_| vec![PendingValues::new()]).collect::<Vec<Vec<PendingValues>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.sequence = SequenceLayers::new(&self.variables, ordering);
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index] = 0;
+        self.pending[layer][index].reset();
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let states = self.top_down_properties[source_layer][source_index];
+        let position = self.sequence.position(source_layer);
+        let is_pending = position.is_some_and(|position| !self.sequence.is_tracked_top_down(position));
+        let is_last_pending = position.is_some_and(|position| self.sequence.is_last_pending(position));
+        let next = match position {
+            Some(_) if is_last_pending => self.read_pending(states, &self.pending[source_layer][source_index], assignment),
+            Some(_) if !is_pending => step(states, assignment),
+            _ => states,
+        };
+        self.top_down_properties[target_layer][target_index] |= next;
+        let (pending_above, pending_below) = self.pending.split_at_mut(target_layer);
+        if is_last_pending {
+            pending_below[0][target_index].aggregate(&PendingValues::new(), None);
+        } else {
+            pending_below[0][target_index].aggregate(&pending_above[source_layer][source_index], Some(assignment).filter(|_| is_pending));
+        }
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index] = 0;
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let states = self.bottom_up_properties[source_layer][source_index];
+        let next = match self.sequence.position(target_layer) {
+            None => states,
+            Some(position) if self.sequence.is_tracked_bottom_up(position) => step(states, assignment),
+            Some(_) => ANY,
+        };
+        self.bottom_up_properties[target_layer][target_index] |= next;
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.sequence.is_layer_in_scope(layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let position = self.sequence.position(source_layer).unwrap();
+        if self.sequence.is_last_pending(position) {
+            // The states are read up to the end of the sequence, in which every state is accepting
+            return self.read_pending(self.top_down_properties[source_layer][source_index], &self.pending[source_layer][source_index], assignment) == 0;
+        }
+        let before = if self.sequence.is_first_top_down(position) {
+            BEFORE
+        } else if self.sequence.is_tracked_top_down(position) {
+            self.top_down_properties[source_layer][source_index]
+        } else {
+            ANY
+        };
+        let after = if self.sequence.is_first_bottom_up(position) {
+            BEFORE
+        } else if self.sequence.is_tracked_bottom_up(position) {
+            self.bottom_up_properties[target_layer][target_index]
+        } else {
+            ANY
+        };
+        let left = step(before, assignment);
+        left == 0 || after == 0 || !is_compatible(left, after)
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(BEFORE);
+        self.bottom_up_properties[layer].push(BEFORE);
+        self.pending[layer].push(PendingValues::new());
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.iter().copied())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let states = self.variables.iter().fold(BEFORE, |states, variable| step(states, assignment[variable.0]));
+        states != 0
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        state.write_u8(self.top_down_properties[layer][index]);
+        state.write_u8(self.bottom_up_properties[layer][index]);
+        self.pending[layer][index].hash(state);
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex] &&
+        self.pending[layer][index] == self.pending[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::GlobalContiguity { variables: self.variables.clone() })
+    }
+}
+
+#[cfg(test)]
+mod test_global_contiguity {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_contiguous_blocks() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(4, vec![0, 1], None);
+        global_contiguity(&mut problem, vars);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2, 3]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        // The empty block and the 4 * 5 / 2 non-empty blocks
+        assert_eq!(solutions.len(), 11);
+        assert!(is_solution(vec![0, 0, 0, 0], &solutions));
+        assert!(is_solution(vec![0, 1, 1, 0], &solutions));
+        assert!(is_solution(vec![1, 1, 1, 1], &solutions));
+        assert!(!is_solution(vec![1, 0, 0, 1], &solutions));
+    }
+
+    #[test]
+    pub fn test_reverse_order() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(4, vec![0, 1], None);
+        global_contiguity(&mut problem, vars.clone());
+        equal(&mut problem, vars[0], 1);
+        equal(&mut problem, vars[2], 0);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![3, 2, 1, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 2);
+        assert!(is_solution(vec![1, 0, 0, 0], &solutions));
+        assert!(is_solution(vec![1, 1, 0, 0], &solutions));
+    }
+
+    #[test]
+    pub fn test_any_order() {
+        for n in 4..7 {
+            let mut problem = Problem::default();
+            let vars = problem.add_variables(n, vec![0, 1], None);
+            global_contiguity(&mut problem, vars.clone());
+            not_equals(&mut problem, vars[0], vars[n - 1]);
+            let expected = reference::count_solutions(&problem);
+            assert_eq!(expected, 2 * (n as u128 - 1));
+            let mut shuffled = (0..n).collect::<Vec<usize>>();
+            shuffled.swap(0, 2);
+            shuffled.swap(1, n - 1);
+            for order in [OrderingHeuristic::MinDomMaxLinked, OrderingHeuristic::Custom(shuffled)] {
+                let mut mdd = Mdd::new(problem.clone(), usize::MAX, order, MergeHeuristic::LessRelaxed);
+                mdd.refine();
+                assert_eq!(mdd.count_solutions(), expected);
+            }
+        }
+    }
+}
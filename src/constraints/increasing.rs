@@ -0,0 +1,268 @@
+use super::*;
+use crate::modelling::VariableIndex;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::hash::Hasher;
+
+// Structures for the increasing constraint.
+//
+// The constraint enforces x_0 <= x_1 <= ... <= x_n (or x_0 < x_1 < ... < x_n for the strict
+// variant). The strict variant is reduced to the non-strict one by shifting the values: for
+// integers, x_i < x_{i+1} is equivalent to x_i - i <= x_{i+1} - (i + 1).
+
+/// Local property of a node for the increasing constraint. Since the variables of the sequence can
+/// appear in any order in the MDD, the property stores, for each position in the sequence, the
+/// bounds imposed on its (shifted) value by the positions assigned on the paths to/from the node.
+///     - lower[i] is the minimum, over all paths, of the largest value assigned before position i
+///     - upper[i] is the maximum, over all paths, of the smallest value assigned after position i
+#[derive(Clone, PartialEq, Eq)]
+struct IncreasingProperty {
+    lower: Vec<isize>,
+    upper: Vec<isize>,
+}
+
+impl IncreasingProperty {
+
+    /// Creates a new property for a sequence of n variables. Such property does not constrain any
+    /// position.
+    pub fn new(n: usize) -> Self {
+        Self {
+            lower: vec![isize::MIN; n],
+            upper: vec![isize::MAX; n],
+        }
+    }
+
+    /// Resets the property to the neutral element of the aggregation
+    pub fn reset(&mut self) {
+        self.lower.fill(isize::MAX);
+        self.upper.fill(isize::MIN);
+    }
+
+    /// Aggregates the property of a path, extended with the (shifted) value at the given position
+    /// (if any), into self.
+    pub fn aggregate(&mut self, other: &IncreasingProperty, assignment: Option<(usize, isize)>) {
+        for position in 0..self.lower.len() {
+            let (mut lower, mut upper) = (other.lower[position], other.upper[position]);
+            if let Some((assigned_position, value)) = assignment {
+                if position > assigned_position {
+                    lower = lower.max(value);
+                } else if position < assigned_position {
+                    upper = upper.min(value);
+                }
+            }
+            self.lower[position] = self.lower[position].min(lower);
+            self.upper[position] = self.upper[position].max(upper);
+        }
+    }
+
+    /// Returns true if the (shifted) value can not be assigned at the given position
+    pub fn is_out_of_bounds(&self, position: usize, value: isize) -> bool {
+        value < self.lower[position] || value > self.upper[position]
+    }
+}
+
+#[derive(Clone)]
+pub struct Increasing {
+    /// Variables of the sequence, in order
+    variables: Vec<VariableIndex>,
+    /// Is the sequence strictly increasing
+    strict: bool,
+    /// For each layer in the scope of the constraint, gives the position of its decision in the
+    /// sequence
+    position_at_layer: FxHashMap<usize, usize>,
+    /// Top-down properties for each node in the MDD
+    top_down_properties: Vec<Vec<IncreasingProperty>>,
+    /// Bottom-up properties for each node in the MDD
+    bottom_up_properties: Vec<Vec<IncreasingProperty>>,
+}
+
+impl Increasing {
+
+    /// Creates a new constraint enforcing that the variables are increasing (strictly if `strict`
+    /// is true) in the given order.
+    pub fn new(variables: Vec<VariableIndex>, strict: bool) -> Self {
+        Self {
+            variables,
+            strict,
+            position_at_layer: FxHashMap::<usize, usize>::default(),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+        }
+    }
+
+    /// Returns the value shifted so that the strict variant can be checked with <=
+    fn shift(&self, position: usize, value: isize) -> isize {
+        if self.strict {
+            value - position as isize
+        } else {
+            value
+        }
+    }
+
+    /// Returns the position, and shifted value, of an assignment made at the given layer.
+    fn assignment_at_layer(&self, layer: usize, value: isize) -> Option<(usize, isize)> {
+        self.position_at_layer.get(&layer).map(|&position| (position, self.shift(position, value)))
+    }
+}
+
+impl Constraint for Increasing {
+
+    fn init(&mut self, vars: &[Variable]) {
+        let n = self.variables.len();
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![IncreasingProperty::new(n)]).collect::<Vec<Vec<IncreasingProperty>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![IncreasingProperty::new(n)]).collect::<Vec<Vec<IncreasingProperty>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.position_at_layer.clear();
+        for (position, variable) in self.variables.iter().copied().enumerate() {
+            self.position_at_layer.insert(ordering[variable.0], position);
+        }
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset();
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let assignment = self.assignment_at_layer(source_layer, assignment);
+        let (td_properties_above, td_properties_below) = self.top_down_properties.split_at_mut(target_layer);
+        td_properties_below[0][target_index].aggregate(&td_properties_above[source_layer][source_index], assignment);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset();
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let assignment = self.assignment_at_layer(target_layer, assignment);
+        let (bu_properties_above, bu_properties_below) = self.bottom_up_properties.split_at_mut(source_layer);
+        bu_properties_above[target_layer][target_index].aggregate(&bu_properties_below[0][source_index], assignment);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.position_at_layer.contains_key(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let (position, value) = self.assignment_at_layer(source_layer, assignment).unwrap();
+        self.top_down_properties[source_layer][source_index].is_out_of_bounds(position, value) ||
+        self.bottom_up_properties[target_layer][target_index].is_out_of_bounds(position, value)
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        let n = self.variables.len();
+        self.top_down_properties[layer].push(IncreasingProperty::new(n));
+        self.bottom_up_properties[layer].push(IncreasingProperty::new(n));
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.iter().copied())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        self.variables.windows(2).all(|pair| {
+            let (x, y) = (assignment[*pair[0]], assignment[*pair[1]]);
+            if self.strict { x < y } else { x <= y }
+        })
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for property in [&self.top_down_properties[layer][index], &self.bottom_up_properties[layer][index]] {
+            for value in property.lower.iter().chain(property.upper.iter()).copied() {
+                state.write_isize(value);
+            }
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::Increasing { variables: self.variables.clone(), strict: self.strict })
+    }
+}
+
+#[cfg(test)]
+mod test_increasing {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_increasing() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1, 2], None);
+        increasing(&mut problem, vars);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 10);
+        assert!(is_solution(vec![0, 0, 0], &solutions));
+        assert!(is_solution(vec![0, 1, 1], &solutions));
+        assert!(is_solution(vec![1, 1, 2], &solutions));
+        assert!(!is_solution(vec![1, 0, 2], &solutions));
+    }
+
+    #[test]
+    pub fn test_strictly_increasing_unordered_layers() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1, 2, 3], None);
+        strictly_increasing(&mut problem, vars);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![2, 0, 1]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 4);
+        assert!(is_solution(vec![0, 1, 2], &solutions));
+        assert!(is_solution(vec![0, 1, 3], &solutions));
+        assert!(is_solution(vec![0, 2, 3], &solutions));
+        assert!(is_solution(vec![1, 2, 3], &solutions));
+    }
+
+    #[test]
+    pub fn test_decreasing() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1, 2], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        strictly_decreasing(&mut problem, vec![x, y]);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![1, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 3);
+        assert!(is_solution(vec![1, 0], &solutions));
+        assert!(is_solution(vec![2, 0], &solutions));
+        assert!(is_solution(vec![2, 1], &solutions));
+    }
+
+    #[test]
+    pub fn test_width_one_bounds() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![2, 3], None);
+        let y = problem.add_variable(vec![0, 1, 2, 3], None);
+        let z = problem.add_variable(vec![0, 1, 2, 3, 4], None);
+        strictly_increasing(&mut problem, vec![x, y, z]);
+
+        let mdd = Mdd::new(problem, 1, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 1);
+        assert!(is_solution(vec![2, 3, 4], &solutions));
+    }
+}
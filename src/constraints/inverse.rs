@@ -0,0 +1,238 @@
+use super::*;
+use crate::modelling::VariableIndex;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use crate::utils::Bitset;
+use std::hash::Hasher;
+
+// Structures for the inverse (channeling) constraint between two arrays of variables xs and ys of
+// size n: $xs_i = j \Leftrightarrow ys_j = i$.
+//
+// Both an assignment $xs_i = j$ and an assignment $ys_j = i$ are seen as the pair $(i, j)$ of the
+// underlying permutation. The constraint is satisfied if and only if the pairs of an assignment do
+// not conflict, i.e., there are no two pairs $(i, j)$ and $(i, j^\prime)$ (or $(i, j)$ and
+// $(i^\prime, j)$) with $j \neq j^\prime$ (resp. $i \neq i^\prime$).
+//
+// The property of a node is the set of pairs appearing on all paths to/from the node, represented
+// as a bitset of $n^2$ bits (the bit $i \times n + j$ represents the pair $(i, j)$). The aggregation
+// of two properties is their intersection.
+
+/// Side of the constraint a variable belongs to, with its index in the array
+#[derive(Clone, Copy)]
+enum Side {
+    X(usize),
+    Y(usize),
+}
+
+#[derive(Clone)]
+pub struct Inverse {
+    /// First array of variables
+    xs: Vec<VariableIndex>,
+    /// Second array of variables
+    ys: Vec<VariableIndex>,
+    /// For each layer in the scope, the side of its decision variable
+    side_at_layer: FxHashMap<usize, Side>,
+    /// Top-down properties for each node in the MDD
+    top_down_properties: Vec<Vec<Bitset>>,
+    /// Bottom-up properties for each node in the MDD
+    bottom_up_properties: Vec<Vec<Bitset>>,
+}
+
+impl Inverse {
+
+    /// Creates a new inverse constraint between xs and ys
+    pub fn new(xs: Vec<VariableIndex>, ys: Vec<VariableIndex>) -> Self {
+        assert!(xs.len() == ys.len(), "The two arrays of an inverse constraint must have the same size");
+        Self {
+            xs,
+            ys,
+            side_at_layer: FxHashMap::<usize, Side>::default(),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+        }
+    }
+
+    fn n(&self) -> usize {
+        self.xs.len()
+    }
+
+    /// Returns the pair (i, j) corresponding to assigning the value to the decision variable of
+    /// the layer, if the value is a valid index
+    fn pair_at_layer(&self, layer: usize, assignment: isize) -> Option<(usize, usize)> {
+        if assignment < 0 || assignment as usize >= self.n() {
+            return None;
+        }
+        match self.side_at_layer.get(&layer) {
+            Some(Side::X(i)) => Some((*i, assignment as usize)),
+            Some(Side::Y(j)) => Some((assignment as usize, *j)),
+            None => None,
+        }
+    }
+
+    /// Returns true if the pair conflicts with a pair in the set
+    fn is_conflicting(&self, set: &Bitset, pair: (usize, usize)) -> bool {
+        let n = self.n();
+        let (i, j) = pair;
+        (0..n).any(|k| (k != j && set.contains(i * n + k)) || (k != i && set.contains(k * n + j)))
+    }
+}
+
+impl Constraint for Inverse {
+
+    fn init(&mut self, vars: &[Variable]) {
+        let bits = self.n() * self.n();
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![Bitset::new(bits)]).collect::<Vec<Vec<Bitset>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![Bitset::new(bits)]).collect::<Vec<Vec<Bitset>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.side_at_layer.clear();
+        for (i, x) in self.xs.iter().copied().enumerate() {
+            self.side_at_layer.insert(ordering[x.0], Side::X(i));
+        }
+        for (j, y) in self.ys.iter().copied().enumerate() {
+            self.side_at_layer.insert(ordering[y.0], Side::Y(j));
+        }
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset(!0);
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let n = self.n();
+        // As for the allDifferent constraint, the pair is temporarily integrated in the source
+        // property and removed after the aggregation.
+        let pair = self.pair_at_layer(source_layer, assignment).map(|(i, j)| i * n + j);
+        let is_in_set = pair.is_some_and(|bit| self.top_down_properties[source_layer][source_index].contains(bit));
+        if let Some(bit) = pair {
+            self.top_down_properties[source_layer][source_index].insert(bit);
+        }
+        let (td_properties_above, td_properties_below) = self.top_down_properties.split_at_mut(target_layer);
+        td_properties_below[0][target_index].intersect(&td_properties_above[source_layer][source_index]);
+        if let Some(bit) = pair && !is_in_set {
+            self.top_down_properties[source_layer][source_index].remove(bit);
+        }
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset(!0);
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let n = self.n();
+        let pair = self.pair_at_layer(target_layer, assignment).map(|(i, j)| i * n + j);
+        let is_in_set = pair.is_some_and(|bit| self.bottom_up_properties[source_layer][source_index].contains(bit));
+        if let Some(bit) = pair {
+            self.bottom_up_properties[source_layer][source_index].insert(bit);
+        }
+        let (bu_properties_above, bu_properties_below) = self.bottom_up_properties.split_at_mut(source_layer);
+        bu_properties_above[target_layer][target_index].intersect(&bu_properties_below[0][source_index]);
+        if let Some(bit) = pair && !is_in_set {
+            self.bottom_up_properties[source_layer][source_index].remove(bit);
+        }
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.side_at_layer.contains_key(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        match self.pair_at_layer(source_layer, assignment) {
+            // The value is not a valid index in the other array
+            None => true,
+            Some(pair) => self.is_conflicting(&self.top_down_properties[source_layer][source_index], pair) ||
+                self.is_conflicting(&self.bottom_up_properties[target_layer][target_index], pair),
+        }
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        let bits = self.n() * self.n();
+        self.top_down_properties[layer].push(Bitset::new(bits));
+        self.bottom_up_properties[layer].push(Bitset::new(bits));
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.xs.iter().chain(self.ys.iter()).copied())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let n = self.n() as isize;
+        let channels = |from: &[VariableIndex], to: &[VariableIndex]| {
+            from.iter().enumerate().all(|(i, variable)| {
+                let j = assignment[variable.0];
+                (0..n).contains(&j) && assignment[to[j as usize].0] == i as isize
+            })
+        };
+        channels(&self.xs, &self.ys) && channels(&self.ys, &self.xs)
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for word in self.top_down_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+        for word in self.bottom_up_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::Inverse { xs: self.xs.clone(), ys: self.ys.clone() })
+    }
+}
+
+#[cfg(test)]
+mod test_inverse {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_permutations() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let ys = problem.add_variables(3, vec![0, 1, 2], None);
+        inverse(&mut problem, xs, ys);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 6);
+        assert!(is_solution(vec![0, 1, 2, 0, 1, 2], &solutions));
+        assert!(is_solution(vec![1, 2, 0, 2, 0, 1], &solutions));
+        assert!(!is_solution(vec![1, 2, 0, 1, 2, 0], &solutions));
+    }
+
+    #[test]
+    pub fn test_channeling_fixed_value() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2, 3], None);
+        let ys = problem.add_variables(3, vec![0, 1, 2], None);
+        inverse(&mut problem, xs.clone(), ys.clone());
+        equal(&mut problem, ys[2], 0);
+
+        let mdd = Mdd::new(problem, 1, OrderingHeuristic::Custom(vec![5, 0, 1, 2, 3, 4]), MergeHeuristic::LessRelaxed);
+        let solutions = get_all_solutions(&mdd);
+        // y2 = 0 forces x0 = 2, and the value 3 is not a valid index
+        assert!(solutions.iter().all(|solution| solution[0] == 2));
+        assert!(solutions.iter().all(|solution| solution[1] != 3 && solution[2] != 3));
+    }
+}
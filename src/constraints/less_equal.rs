@@ -0,0 +1,212 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use std::hash::Hasher;
+
+// Structures for the binary comparison $x + c \leq y$.
+//
+// The property of a node is the minimum value of x and the maximum value of y on the paths to/from
+// the node. A variable that is not assigned on these paths does not restrict the other one, which
+// is represented by the bounds isize::MIN and isize::MAX.
+
+/// Local property of a node for the less-equal constraint
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct ComparisonProperty {
+    /// Minimum value of x on the paths
+    min_x: isize,
+    /// Maximum value of y on the paths
+    max_y: isize,
+}
+
+impl ComparisonProperty {
+
+    /// Creates the property of a root/sink node, with no restriction
+    pub fn new() -> Self {
+        Self {
+            min_x: isize::MIN,
+            max_y: isize::MAX,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.min_x = isize::MAX;
+        self.max_y = isize::MIN;
+    }
+
+    pub fn aggregate(&mut self, other: &Self) {
+        self.min_x = self.min_x.min(other.min_x);
+        self.max_y = self.max_y.max(other.max_y);
+    }
+}
+
+#[derive(Clone)]
+pub struct LessEqual {
+    x: VariableIndex,
+    y: VariableIndex,
+    /// Offset of the comparison
+    offset: isize,
+    top_down_properties: Vec<Vec<ComparisonProperty>>,
+    bottom_up_properties: Vec<Vec<ComparisonProperty>>,
+    layer_x: usize,
+    layer_y: usize,
+}
+
+impl LessEqual {
+
+    /// Creates the constraint x + offset <= y
+    pub fn new(x: VariableIndex, y: VariableIndex, offset: isize) -> Self {
+        Self {
+            x,
+            y,
+            offset,
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            layer_x: 0,
+            layer_y: 0,
+        }
+    }
+
+    /// Returns the property obtained by assigning the value to the decision of the layer
+    fn assign(&self, property: &ComparisonProperty, layer: usize, assignment: isize) -> ComparisonProperty {
+        let mut property = *property;
+        if layer == self.layer_x {
+            property.min_x = assignment;
+        } else if layer == self.layer_y {
+            property.max_y = assignment;
+        }
+        property
+    }
+
+    /// Returns true if the assignment of the decision of the layer conflicts with the property
+    fn is_conflicting(&self, property: &ComparisonProperty, layer: usize, assignment: isize) -> bool {
+        if layer == self.layer_x {
+            assignment.saturating_add(self.offset) > property.max_y
+        } else {
+            property.min_x.saturating_add(self.offset) > assignment
+        }
+    }
+}
+
+impl Constraint for LessEqual {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![ComparisonProperty::new()]).collect::<Vec<Vec<ComparisonProperty>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![ComparisonProperty::new()]).collect::<Vec<Vec<ComparisonProperty>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.layer_x = ordering[self.x.0];
+        self.layer_y = ordering[self.y.0];
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset();
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let property = self.assign(&self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        self.top_down_properties[target_layer][target_index].aggregate(&property);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset();
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let property = self.assign(&self.bottom_up_properties[source_layer][source_index], target_layer, assignment);
+        self.bottom_up_properties[target_layer][target_index].aggregate(&property);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        layer == self.layer_x || layer == self.layer_y
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        self.is_conflicting(&self.top_down_properties[source_layer][source_index], source_layer, assignment) ||
+        self.is_conflicting(&self.bottom_up_properties[target_layer][target_index], source_layer, assignment)
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(ComparisonProperty::new());
+        self.bottom_up_properties[layer].push(ComparisonProperty::new());
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new([self.x, self.y].into_iter())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        assignment[*self.x] + self.offset <= assignment[*self.y]
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for property in [&self.top_down_properties[layer][index], &self.bottom_up_properties[layer][index]] {
+            state.write_isize(property.min_x);
+            state.write_isize(property.max_y);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn negation(&self) -> Option<Box<dyn Constraint + Send + Sync>> {
+        // x + c > y is y + 1 - c <= x
+        Some(Box::new(LessEqual::new(self.y, self.x, 1 - self.offset)))
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::LessEqual { x: self.x, y: self.y, offset: self.offset })
+    }
+}
+
+#[cfg(test)]
+mod test_less_equal {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_less_than() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1, 2], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        less_than(&mut problem, x, y);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![1, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 3);
+        assert!(is_solution(vec![0, 1], &solutions));
+        assert!(is_solution(vec![0, 2], &solutions));
+        assert!(is_solution(vec![1, 2], &solutions));
+    }
+
+    #[test]
+    pub fn test_precedence_chain() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1, 2, 3, 4, 5], None);
+        less_equal(&mut problem, vars[0], vars[1], 2);
+        less_equal(&mut problem, vars[1], vars[2], 3);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 1);
+        assert!(is_solution(vec![0, 2, 5], &solutions));
+    }
+}
@@ -0,0 +1,333 @@
+use super::*;
+use super::sequence::{SequenceLayers, PendingValues};
+use crate::utils::Bitset;
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::hash::Hasher;
+
+// Structures for the extensional constraint given by a decision diagram: the values of the
+// variables must be the labels of a path from the root to the terminal of the diagram. The diagram
+// decides the variables in a fixed order, and can be a previously compiled MDD (see
+// `MddConstraint::from_mdd`), allowing to reuse the compilation of a sub-problem in a larger one.
+//
+// The diagram is read as an automaton whose states are its nodes. As for the cost-regular
+// constraint, the property of a node is the set of states reached on the paths from the root
+// (top-down) or the sink (bottom-up). The states can only be followed while the variables are
+// assigned in the order of the diagram (see `SequenceLayers`). Past that point, every state is
+// considered reachable from the sink. From the root, the states are kept unchanged over the
+// pending positions, whose values are recorded and read at the last variable of the diagram (see
+// `PendingValues`). Hence, the diagram is enforced exactly at unbounded width whatever the
+// variable ordering of the MDD in which it is posted.
+
+#[derive(Clone)]
+pub struct MddConstraint {
+    /// Variables in the order in which they are decided by the diagram
+    variables: Vec<VariableIndex>,
+    /// Arcs of the diagram, as (from, value) -> [to]
+    arcs: FxHashMap<(usize, isize), Vec<usize>>,
+    /// Reversed arcs of the diagram, as (to, value) -> [from]
+    reversed: FxHashMap<(usize, isize), Vec<usize>>,
+    root: usize,
+    terminal: usize,
+    number_states: usize,
+    /// Property of a node before its update
+    no_state: Bitset,
+    top_down_properties: Vec<Vec<Bitset>>,
+    bottom_up_properties: Vec<Vec<Bitset>>,
+    sequence: SequenceLayers,
+    /// Values of the pending positions on the paths from the root, for each node in the MDD
+    pending: Vec<Vec<PendingValues>>,
+}
+
+impl MddConstraint {
+
+    /// Creates a new constraint from the arcs (from, value, to) of a layered diagram deciding the
+    /// variables in the given order. The nodes of the diagram are numbered from 0.
+    pub fn new(variables: Vec<VariableIndex>, arcs: Vec<(usize, isize, usize)>, root: usize, terminal: usize) -> Self {
+        let number_states = arcs.iter().map(|(from, _, to)| from.max(to) + 1).max().unwrap_or(0).max(root + 1).max(terminal + 1);
+        let mut forward = FxHashMap::<(usize, isize), Vec<usize>>::default();
+        let mut reversed = FxHashMap::<(usize, isize), Vec<usize>>::default();
+        for (from, value, to) in arcs.iter().copied() {
+            forward.entry((from, value)).or_default().push(to);
+            reversed.entry((to, value)).or_default().push(from);
+        }
+        Self {
+            variables,
+            arcs: forward,
+            reversed,
+            root,
+            terminal,
+            number_states,
+            no_state: Bitset::new(number_states),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            sequence: SequenceLayers::default(),
+            pending: vec![],
+        }
+    }
+
+    /// Creates a new constraint from a compiled MDD. The variable i of the problem of the MDD is
+    /// the variable variables[i] of the problem in which the constraint is posted.
+    pub fn from_mdd(mdd: &Mdd, variables: &[VariableIndex]) -> Self {
+        let number_decisions = mdd.number_layers() - 1;
+        let ordered = (0..number_decisions).map(|layer| variables[mdd.decision_at_layer(layer).0]).collect::<Vec<VariableIndex>>();
+        if mdd.is_unsat() {
+            return Self::new(ordered, vec![], 0, 1);
+        }
+        let arcs = mdd.topological_order().into_iter().map(|(from, to, _, value)| (from, value, to)).collect::<Vec<(usize, isize, usize)>>();
+        let terminal = arcs.iter().map(|(_, _, to)| *to).max().unwrap_or(0);
+        Self::new(ordered, arcs, 0, terminal)
+    }
+
+    /// Returns the set containing only the given state
+    fn single(&self, state: usize) -> Bitset {
+        let mut states = self.no_state.clone();
+        states.insert(state);
+        states
+    }
+
+    /// Returns the set of all the states
+    fn all_states(&self) -> Bitset {
+        let mut states = self.no_state.clone();
+        for state in 0..self.number_states {
+            states.insert(state);
+        }
+        states
+    }
+
+    /// Returns the states reached from the states by reading the value, forward or backward
+    fn step(&self, states: &Bitset, value: isize, forward: bool) -> Bitset {
+        let arcs = if forward { &self.arcs } else { &self.reversed };
+        let mut next = self.no_state.clone();
+        for state in (0..self.number_states).filter(|state| states.contains(*state)) {
+            for to in arcs.get(&(state, value)).into_iter().flatten().copied() {
+                next.insert(to);
+            }
+        }
+        next
+    }
+
+    /// Returns the states obtained by assigning the value to the decision of the layer, from the
+    /// root side (top_down = true) or the sink side
+    fn assign(&self, states: &Bitset, layer: usize, assignment: isize, top_down: bool) -> Bitset {
+        match self.sequence.position(layer) {
+            Some(position) => {
+                let tracked = if top_down { self.sequence.is_tracked_top_down(position) } else { self.sequence.is_tracked_bottom_up(position) };
+                if tracked {
+                    self.step(states, assignment, top_down == self.sequence.is_forward_top_down())
+                } else {
+                    self.all_states()
+                }
+            },
+            None => states.clone(),
+        }
+    }
+
+    /// Returns the states reached by reading the values of the pending positions, followed by the
+    /// value of the last one, from the top-down states. Returns all the states if the values are
+    /// not known.
+    fn read_pending(&self, states: &Bitset, pending: &PendingValues, assignment: isize) -> Bitset {
+        match pending.sequences() {
+            Some(sequences) => {
+                let mut reached = self.no_state.clone();
+                let forward = self.sequence.is_forward_top_down();
+                for values in sequences.iter() {
+                    reached.union(&self.sequence.pending_in_order(values, assignment).into_iter().fold(states.clone(), |states, value| self.step(&states, value, forward)));
+                }
+                reached
+            },
+            None => self.all_states(),
+        }
+    }
+}
+
+impl Constraint for MddConstraint {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![self.no_state.clone()]).collect::<Vec<Vec<Bitset>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![self.no_state.clone()]).collect::<Vec<Vec<Bitset>>>();
+        self.pending = (0..vars.len() + 1).map(|_| vec![PendingValues::new()]).collect::<Vec<Vec<PendingValues>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.sequence = SequenceLayers::new(&self.variables, ordering);
+        // The paths start at the root and the sink, from the end of the diagram on their side
+        let (top, bottom) = if self.sequence.is_forward_top_down() { (self.root, self.terminal) } else { (self.terminal, self.root) };
+        self.top_down_properties[0][0] = self.single(top);
+        let sink_layer = self.bottom_up_properties.len() - 1;
+        self.bottom_up_properties[sink_layer][0] = self.single(bottom);
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset(0);
+        self.pending[layer][index].reset();
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let source_states = &self.top_down_properties[source_layer][source_index];
+        let position = self.sequence.position(source_layer);
+        let is_pending = position.is_some_and(|position| !self.sequence.is_tracked_top_down(position));
+        let is_last_pending = position.is_some_and(|position| self.sequence.is_last_pending(position));
+        let states = if is_last_pending {
+            self.read_pending(source_states, &self.pending[source_layer][source_index], assignment)
+        } else if is_pending {
+            source_states.clone()
+        } else {
+            self.assign(source_states, source_layer, assignment, true)
+        };
+        self.top_down_properties[target_layer][target_index].union(&states);
+        let (pending_above, pending_below) = self.pending.split_at_mut(target_layer);
+        if is_last_pending {
+            pending_below[0][target_index].aggregate(&PendingValues::new(), None);
+        } else {
+            pending_below[0][target_index].aggregate(&pending_above[source_layer][source_index], Some(assignment).filter(|_| is_pending));
+        }
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset(0);
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let states = self.assign(&self.bottom_up_properties[source_layer][source_index], target_layer, assignment, false);
+        self.bottom_up_properties[target_layer][target_index].union(&states);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.sequence.is_layer_in_scope(layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let states = &self.top_down_properties[source_layer][source_index];
+        let mut reached = if self.sequence.position(source_layer).is_some_and(|position| self.sequence.is_last_pending(position)) {
+            self.read_pending(states, &self.pending[source_layer][source_index], assignment)
+        } else {
+            self.assign(states, source_layer, assignment, true)
+        };
+        reached.intersect(&self.bottom_up_properties[target_layer][target_index]);
+        reached.size() == 0
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(self.no_state.clone());
+        self.bottom_up_properties[layer].push(self.no_state.clone());
+        self.pending[layer].push(PendingValues::new());
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.iter().copied())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let mut states = self.single(self.root);
+        for variable in self.variables.iter().copied() {
+            states = self.step(&states, assignment[variable.0], true);
+        }
+        states.contains(self.terminal)
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for word in self.top_down_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+        for word in self.bottom_up_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+        self.pending[layer][index].hash(state);
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex] &&
+        self.pending[layer][index] == self.pending[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        let mut arcs = self.arcs.iter().flat_map(|((from, value), targets)| targets.iter().map(move |to| (*from, *value, *to))).collect::<Vec<(usize, isize, usize)>>();
+        arcs.sort_unstable();
+        Some(ConstraintDescription::MddConstraint { variables: self.variables.clone(), arcs, root: self.root, terminal: self.terminal })
+    }
+}
+
+#[cfg(test)]
+mod test_mdd_constraint {
+
+    use crate::constraints::*;
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_compiled_mdd() {
+        // Compiles x < y, then reuses it on two pairs of variables of a larger problem
+        let mut sub_problem = Problem::default();
+        let x = sub_problem.add_variable(vec![0, 1, 2], None);
+        let y = sub_problem.add_variable(vec![0, 1, 2], None);
+        less_than(&mut sub_problem, x, y);
+        let mut sub_mdd = Mdd::new(sub_problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1]), MergeHeuristic::LessRelaxed);
+        sub_mdd.refine();
+
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1, 2], None);
+        mdd_constraint(&mut problem, &sub_mdd, &[vars[0], vars[1]]);
+        mdd_constraint(&mut problem, &sub_mdd, &[vars[1], vars[2]]);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![2, 1, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 1);
+        assert!(is_solution(vec![0, 1, 2], &solutions));
+    }
+
+    #[test]
+    pub fn test_diagram_arcs() {
+        // Diagram accepting (0, 1) and (1, 0), with the variables interleaved in the problem
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1], None);
+        problem.add_constraint(MddConstraint::new(vec![vars[0], vars[2]], vec![(0, 0, 1), (0, 1, 2), (1, 1, 3), (2, 0, 3)], 0, 3));
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 4);
+        assert!(solutions.iter().all(|solution| solution[0] != solution[2]));
+    }
+
+    #[test]
+    pub fn test_any_order() {
+        // Compiles allDifferent(x, y, z) with x < z, then posts it on (v3, v0, v2) among five
+        // variables ordered differently
+        let mut sub_problem = Problem::default();
+        let xs = sub_problem.add_variables(3, vec![0, 1, 2, 3], None);
+        all_different(&mut sub_problem, xs.clone());
+        less_than(&mut sub_problem, xs[0], xs[2]);
+        let mut sub_mdd = Mdd::new(sub_problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        sub_mdd.refine();
+
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(5, vec![0, 1, 2, 3], None);
+        mdd_constraint(&mut problem, &sub_mdd, &[vars[3], vars[0], vars[2]]);
+        not_equals(&mut problem, vars[1], vars[3]);
+        less_than(&mut problem, vars[4], vars[0]);
+        let expected = reference::count_solutions(&problem);
+        for order in [OrderingHeuristic::MinDomMaxLinked, OrderingHeuristic::Custom(vec![2, 4, 0, 3, 1]), OrderingHeuristic::Custom(vec![0, 1, 2, 3, 4])] {
+            let mut mdd = Mdd::new(problem.clone(), usize::MAX, order, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            assert_eq!(mdd.count_solutions(), expected);
+        }
+    }
+}
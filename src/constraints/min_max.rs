@@ -0,0 +1,218 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::FxHashSet;
+use std::hash::Hasher;
+
+// Structures for the aggregation constraints $y = \max(xs)$ and $y = \min(xs)$. The minimum is
+// handled as a maximum over the opposite of the values.
+//
+// The property of a node is the interval of the running maximum of the xs assigned on the paths
+// to/from the node (isize::MIN if none is assigned), and the interval of the values of y (the
+// bounds of its domain if it is not assigned). The maximum over all the xs is the maximum of the
+// top-down and bottom-up running maximums.
+
+/// Local property of a node for the min/max constraint
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct MinMaxProperty {
+    /// Interval of the running maximum
+    maximum: (isize, isize),
+    /// Interval of y
+    y: (isize, isize),
+}
+
+impl MinMaxProperty {
+
+    pub fn reset(&mut self) {
+        self.maximum = (isize::MAX, isize::MIN);
+        self.y = (isize::MAX, isize::MIN);
+    }
+
+    pub fn aggregate(&mut self, other: &Self) {
+        self.maximum = (self.maximum.0.min(other.maximum.0), self.maximum.1.max(other.maximum.1));
+        self.y = (self.y.0.min(other.y.0), self.y.1.max(other.y.1));
+    }
+}
+
+#[derive(Clone)]
+pub struct MinMax {
+    xs: Vec<VariableIndex>,
+    y: VariableIndex,
+    /// 1 for the maximum, -1 for the minimum
+    sign: isize,
+    /// Property of the root and the sink
+    neutral: MinMaxProperty,
+    top_down_properties: Vec<Vec<MinMaxProperty>>,
+    bottom_up_properties: Vec<Vec<MinMaxProperty>>,
+    layers_x: FxHashSet<usize>,
+    layer_y: usize,
+}
+
+impl MinMax {
+
+    fn new(xs: Vec<VariableIndex>, y: VariableIndex, sign: isize) -> Self {
+        Self {
+            xs,
+            y,
+            sign,
+            neutral: MinMaxProperty { maximum: (isize::MIN, isize::MIN), y: (isize::MIN, isize::MAX) },
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            layers_x: FxHashSet::<usize>::default(),
+            layer_y: 0,
+        }
+    }
+
+    /// Creates the constraint y = max(xs)
+    pub fn max(xs: Vec<VariableIndex>, y: VariableIndex) -> Self {
+        Self::new(xs, y, 1)
+    }
+
+    /// Creates the constraint y = min(xs)
+    pub fn min(xs: Vec<VariableIndex>, y: VariableIndex) -> Self {
+        Self::new(xs, y, -1)
+    }
+
+    /// Returns the property obtained by assigning the value to the decision of the layer
+    fn assign(&self, mut property: MinMaxProperty, layer: usize, assignment: isize) -> MinMaxProperty {
+        let value = self.sign * assignment;
+        if self.layers_x.contains(&layer) {
+            property.maximum = (property.maximum.0.max(value), property.maximum.1.max(value));
+        }
+        if layer == self.layer_y {
+            property.y = (value, value);
+        }
+        property
+    }
+}
+
+impl Constraint for MinMax {
+
+    fn init(&mut self, vars: &[Variable]) {
+        let values = vars[*self.y].iter_domain().map(|value| self.sign * value).collect::<Vec<isize>>();
+        self.neutral.y = (values.iter().copied().min().unwrap_or(isize::MAX), values.iter().copied().max().unwrap_or(isize::MIN));
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![self.neutral]).collect::<Vec<Vec<MinMaxProperty>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![self.neutral]).collect::<Vec<Vec<MinMaxProperty>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.layers_x = self.xs.iter().map(|x| ordering[x.0]).collect::<FxHashSet<usize>>();
+        self.layer_y = ordering[self.y.0];
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset();
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let property = self.assign(self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        self.top_down_properties[target_layer][target_index].aggregate(&property);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset();
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let property = self.assign(self.bottom_up_properties[source_layer][source_index], target_layer, assignment);
+        self.bottom_up_properties[target_layer][target_index].aggregate(&property);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        layer == self.layer_y || self.layers_x.contains(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let above = self.assign(self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        let below = &self.bottom_up_properties[target_layer][target_index];
+        let maximum = (above.maximum.0.max(below.maximum.0), above.maximum.1.max(below.maximum.1));
+        let y = (above.y.0.max(below.y.0), above.y.1.min(below.y.1));
+        maximum.0 > y.1 || maximum.1 < y.0 || y.0 > y.1
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(self.neutral);
+        self.bottom_up_properties[layer].push(self.neutral);
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.xs.iter().copied().chain(std::iter::once(self.y)))
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        self.xs.iter().map(|x| self.sign * assignment[x.0]).max() == Some(self.sign * assignment[self.y.0])
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for property in [&self.top_down_properties[layer][index], &self.bottom_up_properties[layer][index]] {
+            state.write_isize(property.maximum.0);
+            state.write_isize(property.maximum.1);
+            state.write_isize(property.y.0);
+            state.write_isize(property.y.1);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        if self.sign == 1 {
+            Some(ConstraintDescription::Maximum { xs: self.xs.clone(), y: self.y })
+        } else {
+            Some(ConstraintDescription::Minimum { xs: self.xs.clone(), y: self.y })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_min_max {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_maximum() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        maximum(&mut problem, xs, y);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![1, 3, 0, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 27);
+        assert!(solutions.iter().all(|solution| solution[3] == *solution[0..3].iter().max().unwrap()));
+    }
+
+    #[test]
+    pub fn test_minimum_makespan() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(2, vec![1, 2, 3], None);
+        let y = problem.add_variable(vec![2, 3], None);
+        minimum(&mut problem, xs, y);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 4);
+        assert!(is_solution(vec![2, 2, 2], &solutions));
+        assert!(is_solution(vec![3, 2, 2], &solutions));
+        assert!(is_solution(vec![2, 3, 2], &solutions));
+        assert!(is_solution(vec![3, 3, 3], &solutions));
+    }
+}
@@ -1,5 +1,25 @@
+pub mod abs_diff;
 pub mod all_different;
+pub mod all_different_except;
+pub mod arithmetic;
+pub mod cardinality;
+pub mod cost_bound;
+pub mod cost_gcc;
+pub mod cost_regular;
+pub mod functional;
+pub mod global_contiguity;
+pub mod increasing;
+pub mod inverse;
+pub mod less_equal;
+pub mod mdd_constraint;
+pub mod min_max;
 pub mod not_equals;
+pub mod pseudo_boolean;
+pub mod reified;
+pub mod stretch;
+pub mod table;
+pub mod tautology;
+mod sequence;
 
 use std::hash::Hasher;
 
@@ -7,10 +27,57 @@ use crate::mdd::*;
 use crate::modelling::*;
 use crate::modelling::variable::Variable;
 
+pub use abs_diff::{AbsDiff, Relation};
 pub use all_different::AllDifferent;
+pub use all_different_except::AllDifferentExcept;
+pub use arithmetic::{Arithmetic, Operation};
+pub use cardinality::Cardinality;
+pub use cost_bound::CostBound;
+pub use cost_gcc::CostGcc;
+pub use cost_regular::CostRegular;
+pub use functional::FunctionalConstraint;
+pub use global_contiguity::GlobalContiguity;
+pub use increasing::Increasing;
+pub use inverse::Inverse;
+pub use less_equal::LessEqual;
+pub use mdd_constraint::MddConstraint;
+pub use min_max::MinMax;
 pub use not_equals::NotEquals;
+pub use pseudo_boolean::PseudoBoolean;
+pub use reified::Reified;
+pub use stretch::Stretch;
+pub use table::{Table, compress_tuples};
+pub use tautology::Tautology;
 
-pub trait Constraint {
+/// Class of the cost of the propagation of a constraint. The constraints of a cheaper class are
+/// propagated to their fixpoint before the ones of a more expensive class are scheduled (see
+/// `Mdd::propagate_constraints`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Unary,
+    Binary,
+    Global,
+}
+
+/// Clones a constraint behind a box. It is implemented for every constraint that is `Clone`, and
+/// allows to clone the problems (see `Problem`).
+pub trait CloneConstraint {
+    fn clone_boxed(&self) -> Box<dyn Constraint + Send + Sync>;
+}
+
+impl<C: Constraint + Clone + Send + Sync + 'static> CloneConstraint for C {
+    fn clone_boxed(&self) -> Box<dyn Constraint + Send + Sync> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Constraint + Send + Sync> {
+    fn clone(&self) -> Self {
+        self.clone_boxed()
+    }
+}
+
+pub trait Constraint: CloneConstraint {
     /// Initialise the data structures for constraint propagation (e.g., properties)
     fn init(&mut self, vars: &[Variable]);
     /// Update the variable ordering. Update the (optional) information for the constraint's
@@ -34,4 +101,68 @@ pub trait Constraint {
     fn is_satisfied(&self, assignment: &[isize]) -> bool;
     fn hash_node_state(&self, node: NodeIndex, hasher: &mut dyn Hasher);
     fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool;
+    /// Returns a new constraint, on the same scope, satisfied by exactly the assignments violating
+    /// this one. It is used to propagate the reification of the constraint when it is false. By
+    /// default, the constraint has no negation and can not be reified.
+    fn negation(&self) -> Option<Box<dyn Constraint + Send + Sync>> {
+        None
+    }
+    /// Updates the top-down property along an edge given the index of its value in the domain of
+    /// its variable, for the constraints indexing dense per-value arrays. By default, the property
+    /// is updated with the value (see `update_property_top_down`).
+    fn update_property_top_down_at(&mut self, source: NodeIndex, target: NodeIndex, _assignment: ValueIndex, value: isize) {
+        self.update_property_top_down(source, target, value);
+    }
+    /// Updates the bottom-up property along an edge given the index of its value (see
+    /// `update_property_top_down_at`)
+    fn update_property_bottom_up_at(&mut self, source: NodeIndex, target: NodeIndex, _assignment: ValueIndex, value: isize) {
+        self.update_property_bottom_up(source, target, value);
+    }
+    /// Returns true if the edge can be removed, given the index of its value (see
+    /// `update_property_top_down_at`)
+    fn is_assignment_invalid_at(&self, source: NodeIndex, target: NodeIndex, decision: VariableIndex, _assignment: ValueIndex, value: isize) -> bool {
+        self.is_assignment_invalid(source, target, decision, value)
+    }
+    /// Notifies the constraint that the domain of a variable of its scope has been narrowed (see
+    /// `Problem::restrict_domain`). The properties are only built by `init`, hence constraints
+    /// caching information on the domains before it can update them here. Does nothing by default.
+    fn on_domain_change(&mut self, _variable: VariableIndex, _vars: &[Variable]) {}
+    /// Returns the declarative description of the constraint, from which it can be built again
+    /// (see `ConstraintDescription`). It is used to serialize the problems, and is None for the
+    /// constraints that can not be described (e.g., defined by closures).
+    fn description(&self) -> Option<ConstraintDescription> {
+        None
+    }
+    /// Returns a short summary of the top-down and bottom-up properties of the node, used to label
+    /// the nodes of the graphviz export (see `GraphvizOptions`). None by default.
+    fn node_state_label(&self, _node: NodeIndex) -> Option<String> {
+        None
+    }
+    /// Returns true if the constraint is satisfied by every path of the MDD, hence can not remove
+    /// any edge. By default, the constraint is entailed if every variable of its scope takes a
+    /// single value in the MDD (see `Mdd::single_value`) and these values satisfy it.
+    fn is_entailed(&self, mdd: &Mdd) -> bool {
+        let mut assignment = vec![0; mdd.number_layers() - 1];
+        for variable in self.iter_scope() {
+            let Some(value) = mdd.single_value(variable) else {
+                return false;
+            };
+            assignment[variable.0] = value;
+        }
+        self.is_satisfied(&assignment)
+    }
+    /// Returns the class of the cost of the propagation of the constraint. By default, the class
+    /// is given by the size of the scope.
+    fn priority(&self) -> Priority {
+        match self.iter_scope().count() {
+            0 | 1 => Priority::Unary,
+            2 => Priority::Binary,
+            _ => Priority::Global,
+        }
+    }
+}
+
+/// Formats the values of a set property, e.g. {1,2,3}
+pub(crate) fn format_values(values: &[isize]) -> String {
+    format!("{{{}}}", values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(","))
 }
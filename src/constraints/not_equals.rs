@@ -2,13 +2,14 @@ use super::*;
 use crate::utils::SparseBitset;
 use crate::modelling::*;
 use crate::mdd::*;
-use std::hash::{Hash, Hasher};
-use rustc_hash::FxHashSet;
+use std::hash::Hasher;
 
+#[derive(Clone)]
 pub struct NotEquals {
     x: VariableIndex,
     y: VariableIndex,
-    domains: FxHashSet<isize>,
+    /// Empty set over the union of the domains of x and y
+    no_value: SparseBitset<isize>,
     top_down_properties: Vec<Vec<SparseBitset<isize>>>,
     bottom_up_properties: Vec<Vec<SparseBitset<isize>>>,
     layer_x: usize,
@@ -21,7 +22,7 @@ impl NotEquals {
         Self {
             x,
             y,
-            domains: FxHashSet::<isize>::default(),
+            no_value: SparseBitset::new(std::iter::empty()),
             top_down_properties: vec![],
             bottom_up_properties: vec![],
             layer_x: 0,
@@ -34,17 +35,12 @@ impl NotEquals {
 impl Constraint for NotEquals {
 
     fn init(&mut self, vars: &[Variable]) {
-        for value in vars[*self.x].iter_domain() {
-            self.domains.insert(value);
-        }
-        for value in vars[*self.y].iter_domain() {
-            self.domains.insert(value);
-        }
+        self.no_value = SparseBitset::from_domain(&Domain::union([vars[*self.x].domain(), vars[*self.y].domain()].into_iter()));
         self.top_down_properties = (0..vars.len() + 1).map(|_| {
-            vec![SparseBitset::new(self.domains.iter().copied())]
+            vec![self.no_value.clone()]
         }).collect::<Vec<Vec<SparseBitset<isize>>>>();
         self.bottom_up_properties = (0..vars.len() + 1).map(|_| {
-            vec![SparseBitset::new(self.domains.iter().copied())]
+            vec![self.no_value.clone()]
         }).collect::<Vec<Vec<SparseBitset<isize>>>>();
     }
 
@@ -76,8 +72,8 @@ impl Constraint for NotEquals {
     fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
         let NodeIndex(source_layer, source_index) = source;
         let NodeIndex(target_layer, target_index) = target;
-        if self.is_layer_in_scope(source_layer) {
-            self.bottom_up_properties[source_layer][source_index].insert(assignment);
+        if self.is_layer_in_scope(target_layer) {
+            self.bottom_up_properties[target_layer][target_index].insert(assignment);
         }
         let (bu_properties_above, bu_properties_below) = self.bottom_up_properties.split_at_mut(source_layer);
         bu_properties_above[target_layer][target_index].union(&bu_properties_below[0][source_index]);
@@ -87,25 +83,27 @@ impl Constraint for NotEquals {
         layer == self.layer_x || layer == self.layer_y
     }
 
-    fn is_assignment_invalid(&self, source: NodeIndex, _target: NodeIndex, decision: VariableIndex, assignment: isize) -> bool {
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, decision: VariableIndex, assignment: isize) -> bool {
         let NodeIndex(source_layer, source_index) = source;
+        // The values of the other variable below the edge are the ones assigned from its target
+        let NodeIndex(target_layer, target_index) = target;
 
         if decision == self.x {
             if self.layer_x < self.layer_y {
-                self.bottom_up_properties[source_layer][source_index].contains(assignment) && self.bottom_up_properties[source_layer][source_index].size() == 1
+                self.bottom_up_properties[target_layer][target_index].contains(assignment) && self.bottom_up_properties[target_layer][target_index].size() == 1
             } else {
                 self.top_down_properties[source_layer][source_index].contains(assignment) && self.top_down_properties[source_layer][source_index].size() == 1
             }
         } else if self.layer_x > self.layer_y {
-            self.bottom_up_properties[source_layer][source_index].contains(assignment) && self.bottom_up_properties[source_layer][source_index].size() == 1
+            self.bottom_up_properties[target_layer][target_index].contains(assignment) && self.bottom_up_properties[target_layer][target_index].size() == 1
         } else {
             self.top_down_properties[source_layer][source_index].contains(assignment) && self.top_down_properties[source_layer][source_index].size() == 1
         }
     }
 
     fn add_node_in_layer(&mut self, layer: usize) {
-        let top_down_property = SparseBitset::new(self.domains.iter().copied());
-        let bottom_up_property = SparseBitset::new(self.domains.iter().copied());
+        let top_down_property = self.no_value.clone();
+        let bottom_up_property = self.no_value.clone();
         self.top_down_properties[layer].push(top_down_property);
         self.bottom_up_properties[layer].push(bottom_up_property);
     }
@@ -134,4 +132,64 @@ impl Constraint for NotEquals {
         self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
         self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
     }
+
+    fn negation(&self) -> Option<Box<dyn Constraint + Send + Sync>> {
+        Some(Box::new(AbsDiff::new(self.x, self.y, Relation::Equal, 0)))
+    }
+
+    fn is_entailed(&self, mdd: &Mdd) -> bool {
+        let values = mdd.feasible_values(self.y);
+        mdd.feasible_values(self.x).iter().all(|value| !values.contains(value))
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::NotEquals { x: self.x, y: self.y })
+    }
+
+    fn node_state_label(&self, node: NodeIndex) -> Option<String> {
+        let NodeIndex(layer, index) = node;
+        let top_down = self.top_down_properties.get(layer)?.get(index)?;
+        let bottom_up = self.bottom_up_properties.get(layer)?.get(index)?;
+        Some(format!("td {} / bu {}", format_values(&top_down.elements()), format_values(&bottom_up.elements())))
+    }
+}
+
+#[cfg(test)]
+mod test_not_equals {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_not_equals_chain() {
+        // x0 != x1 != x2 != x3, with the chain decided in both directions
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2], None);
+        for i in 0..3 {
+            not_equals(&mut problem, xs[i], xs[i + 1]);
+        }
+        problem.restrict_domain(xs[3], [0, 1]);
+        let count = reference::count_solutions(&problem);
+        for order in [vec![0, 1, 2, 3], vec![3, 2, 1, 0], vec![1, 3, 0, 2]] {
+            let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::Custom(order), MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            assert_eq!(mdd.count_solutions(), count);
+        }
+    }
+
+    #[test]
+    pub fn test_not_equals_below_fixed() {
+        // x0 is decided below x1, hence the values of x0 below an edge of x1 are the ones assigned
+        // from the target of the edge, not from its source
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1], None);
+        not_equals(&mut problem, xs[1], xs[0]);
+        problem.fix(xs[1], 0);
+        let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::Custom(vec![3, 2, 1, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.count_solutions(), reference::count_solutions(&problem));
+    }
 }
+
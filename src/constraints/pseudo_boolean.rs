@@ -0,0 +1,171 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::hash::Hasher;
+
+// Structures for the pseudo-Boolean constraint $\sum_i w_i b_i \geq k$ over 0/1 variables.
+//
+// Since the variables are binary, the largest weighted sum on the paths to/from a node is obtained
+// by setting to 1 exactly the variables with a positive weight on the best path. The property of a
+// node is thus the maximum partial sum on the paths to/from the node, and an assignment is valid if
+// the best completion above and below reaches k. Constraints with $\leq$ are obtained by negating
+// the weights and the bound.
+
+#[derive(Clone)]
+pub struct PseudoBoolean {
+    /// Terms of the sum, as (variable, weight)
+    terms: Vec<(VariableIndex, isize)>,
+    /// Lower bound on the sum
+    bound: isize,
+    /// For each layer in the scope, the total weight of its decision
+    weight_at_layer: FxHashMap<usize, isize>,
+    top_down_properties: Vec<Vec<isize>>,
+    bottom_up_properties: Vec<Vec<isize>>,
+}
+
+impl PseudoBoolean {
+
+    /// Creates the constraint sum(weight * variable) >= bound
+    pub fn new(terms: Vec<(VariableIndex, isize)>, bound: isize) -> Self {
+        Self {
+            terms,
+            bound,
+            weight_at_layer: FxHashMap::<usize, isize>::default(),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+        }
+    }
+
+    /// Returns the partial sum obtained by assigning the value to the decision of the layer
+    fn assign(&self, sum: isize, layer: usize, assignment: isize) -> isize {
+        match self.weight_at_layer.get(&layer) {
+            Some(weight) if assignment == 1 => sum.saturating_add(*weight),
+            _ => sum,
+        }
+    }
+}
+
+impl Constraint for PseudoBoolean {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![0]).collect::<Vec<Vec<isize>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![0]).collect::<Vec<Vec<isize>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.weight_at_layer.clear();
+        for (variable, weight) in self.terms.iter().copied() {
+            *self.weight_at_layer.entry(ordering[variable.0]).or_default() += weight;
+        }
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index] = isize::MIN;
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let sum = self.assign(self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        let property = &mut self.top_down_properties[target_layer][target_index];
+        *property = (*property).max(sum);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index] = isize::MIN;
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let sum = self.assign(self.bottom_up_properties[source_layer][source_index], target_layer, assignment);
+        let property = &mut self.bottom_up_properties[target_layer][target_index];
+        *property = (*property).max(sum);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.weight_at_layer.contains_key(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let above = self.assign(self.top_down_properties[source_layer][source_index], source_layer, assignment);
+        above.saturating_add(self.bottom_up_properties[target_layer][target_index]) < self.bound
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(0);
+        self.bottom_up_properties[layer].push(0);
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.terms.iter().map(|(variable, _)| *variable))
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        self.terms.iter().filter(|(variable, _)| assignment[variable.0] == 1).map(|(_, weight)| *weight).sum::<isize>() >= self.bound
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        state.write_isize(self.top_down_properties[layer][index]);
+        state.write_isize(self.bottom_up_properties[layer][index]);
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn negation(&self) -> Option<Box<dyn Constraint + Send + Sync>> {
+        // sum < k is -sum >= 1 - k
+        let terms = self.terms.iter().map(|(variable, weight)| (*variable, -weight)).collect::<Vec<(VariableIndex, isize)>>();
+        Some(Box::new(PseudoBoolean::new(terms, 1 - self.bound)))
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::PseudoBoolean { terms: self.terms.clone(), bound: self.bound })
+    }
+}
+
+#[cfg(test)]
+mod test_pseudo_boolean {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_knapsack() {
+        // 3a + 2b + 2c <= 4 and a + b + c >= 2
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1], None);
+        pseudo_boolean_less_equal(&mut problem, vars.clone(), vec![3, 2, 2], 4);
+        pseudo_boolean_greater_equal(&mut problem, vars, vec![1, 1, 1], 2);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 1);
+        assert!(is_solution(vec![0, 1, 1], &solutions));
+    }
+
+    #[test]
+    pub fn test_negative_weights() {
+        // 2a - b + c >= 2
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1], None);
+        pseudo_boolean_greater_equal(&mut problem, vars, vec![2, -1, 1], 2);
+
+        let mdd = Mdd::new(problem, 1, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        let solutions = get_all_solutions(&mdd);
+        assert!(solutions.iter().all(|solution| solution[0] == 1));
+    }
+}
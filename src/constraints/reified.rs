@@ -0,0 +1,330 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::hash::Hasher;
+
+// Structures for the reification of a constraint $C$ by a 0/1 variable $b$: $b = 1 \Leftrightarrow C$.
+//
+// The reified constraint owns $C$ and its negation $\neg C$ (see `Constraint::negation`), and
+// forwards them every update of the properties. The propagation goes in both directions:
+//  - If $b$ is 1 (resp. 0) on all the paths through an edge of the scope of $C$, the edge is
+//    filtered by $C$ (resp. $\neg C$).
+//  - If $C$ (resp. $\neg C$) is violated on all the paths through an edge assigning $b$, the edge
+//    can not assign 1 (resp. 0) to $b$.
+//
+// For the second direction, the violation of $C$ is known for the paths that assigned all the
+// variables of its scope. Hence, the property of a node contains the values of $b$ on its paths,
+// and the status of $C$ and $\neg C$ on its paths: violated, or not known to be violated. The
+// status is set on the edges of the last (first) layer of the scope in the top-down (bottom-up)
+// direction. In the top-down pass, the bottom-up properties of the nodes are not up to date, so
+// the edges are filtered against the sink, whose properties are neutral. If $b$ is in the middle
+// of the scope, the first direction is enough since the nodes below $b$ know its value.
+
+/// Bit of the value 0 of b
+const FALSE: u8 = 1;
+/// Bit of the value 1 of b
+const TRUE: u8 = 1 << 1;
+
+/// Bit of the status of a constraint violated on a path
+const VIOLATED: u8 = 1;
+/// Bit of the status of a constraint not known to be violated on a path
+const POSSIBLE: u8 = 1 << 1;
+
+/// Local property of a node for the reified constraint
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+struct ReifiedProperty {
+    /// Values taken by b on the paths
+    values: u8,
+    /// Status of the constraint on the paths, empty if its scope is not assigned
+    status: u8,
+    /// Status of the negation of the constraint on the paths
+    negation_status: u8,
+}
+
+impl ReifiedProperty {
+
+    pub fn aggregate(&mut self, other: &Self) {
+        self.values |= other.values;
+        self.status |= other.status;
+        self.negation_status |= other.negation_status;
+    }
+}
+
+#[derive(Clone)]
+pub struct Reified {
+    /// Reified constraint
+    constraint: Box<dyn Constraint + Send + Sync>,
+    /// Negation of the reified constraint
+    negation: Box<dyn Constraint + Send + Sync>,
+    /// Variable equal to the truth value of the constraint
+    b: VariableIndex,
+    layer_b: usize,
+    /// First and last layers of the scope of the constraint
+    scope_layers: (usize, usize),
+    /// Decision of each layer in the scope of the constraint
+    decision_at_layer: FxHashMap<usize, VariableIndex>,
+    /// Sink of the MDD
+    sink: NodeIndex,
+    top_down_properties: Vec<Vec<ReifiedProperty>>,
+    bottom_up_properties: Vec<Vec<ReifiedProperty>>,
+}
+
+impl Reified {
+
+    /// Creates the constraint b = 1 <=> constraint. Panics if the constraint has no negation.
+    pub fn new(constraint: Box<dyn Constraint + Send + Sync>, b: VariableIndex) -> Self {
+        let negation = constraint.negation().expect("The constraint can not be reified as it has no negation");
+        Self {
+            constraint,
+            negation,
+            b,
+            layer_b: 0,
+            scope_layers: (0, 0),
+            decision_at_layer: FxHashMap::<usize, VariableIndex>::default(),
+            sink: NodeIndex(0, 0),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+        }
+    }
+
+    /// Returns the status of the constraint for an edge of the layer, filtered against the given
+    /// nodes
+    fn status(constraint: &(dyn Constraint + Send + Sync), source: NodeIndex, target: NodeIndex, decision: VariableIndex, assignment: isize) -> u8 {
+        if constraint.is_assignment_invalid(source, target, decision, assignment) { VIOLATED } else { POSSIBLE }
+    }
+
+    /// Returns the values of b known on the paths through an edge from source to target
+    fn values(&self, source: NodeIndex, target: NodeIndex) -> u8 {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        if source_layer > self.layer_b {
+            self.top_down_properties[source_layer][source_index].values
+        } else {
+            self.bottom_up_properties[target_layer][target_index].values
+        }
+    }
+}
+
+impl Constraint for Reified {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.constraint.init(vars);
+        self.negation.init(vars);
+        self.sink = NodeIndex(vars.len(), 0);
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![ReifiedProperty::default()]).collect::<Vec<Vec<ReifiedProperty>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![ReifiedProperty::default()]).collect::<Vec<Vec<ReifiedProperty>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.constraint.update_variable_ordering(ordering);
+        self.negation.update_variable_ordering(ordering);
+        self.layer_b = ordering[self.b.0];
+        self.decision_at_layer = self.constraint.iter_scope().map(|variable| (ordering[variable.0], variable)).collect::<FxHashMap<usize, VariableIndex>>();
+        let first = self.decision_at_layer.keys().copied().min().unwrap_or(0);
+        let last = self.decision_at_layer.keys().copied().max().unwrap_or(0);
+        self.scope_layers = (first, last);
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        self.constraint.reset_property_top_down(node);
+        self.negation.reset_property_top_down(node);
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index] = ReifiedProperty::default();
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        self.constraint.update_property_top_down(source, target, assignment);
+        self.negation.update_property_top_down(source, target, assignment);
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let mut property = self.top_down_properties[source_layer][source_index];
+        if source_layer == self.layer_b {
+            property.values = if assignment == 1 { TRUE } else { FALSE };
+        }
+        if source_layer == self.scope_layers.1 {
+            let decision = self.decision_at_layer[&source_layer];
+            property.status = Self::status(self.constraint.as_ref(), source, self.sink, decision, assignment);
+            property.negation_status = Self::status(self.negation.as_ref(), source, self.sink, decision, assignment);
+        }
+        self.top_down_properties[target_layer][target_index].aggregate(&property);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        self.constraint.reset_property_bottom_up(node);
+        self.negation.reset_property_bottom_up(node);
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index] = ReifiedProperty::default();
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        self.constraint.update_property_bottom_up(source, target, assignment);
+        self.negation.update_property_bottom_up(source, target, assignment);
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let mut property = self.bottom_up_properties[source_layer][source_index];
+        if target_layer == self.layer_b {
+            property.values = if assignment == 1 { TRUE } else { FALSE };
+        }
+        if target_layer == self.scope_layers.0 {
+            let decision = self.decision_at_layer[&target_layer];
+            property.status = Self::status(self.constraint.as_ref(), target, source, decision, assignment);
+            property.negation_status = Self::status(self.negation.as_ref(), target, source, decision, assignment);
+        }
+        self.bottom_up_properties[target_layer][target_index].aggregate(&property);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        layer == self.layer_b || self.decision_at_layer.contains_key(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        if source_layer == self.layer_b {
+            // The constraint (or its negation) is violated on all the paths through the edge
+            let property = if self.scope_layers.1 < self.layer_b {
+                &self.top_down_properties[source_layer][source_index]
+            } else {
+                &self.bottom_up_properties[target_layer][target_index]
+            };
+            let status = if assignment == 1 { property.status } else { property.negation_status };
+            if status == VIOLATED {
+                return true;
+            }
+        }
+        if self.decision_at_layer.contains_key(&source_layer) {
+            match self.values(source, target) {
+                TRUE => return self.constraint.is_assignment_invalid(source, target, decision, assignment),
+                FALSE => return self.negation.is_assignment_invalid(source, target, decision, assignment),
+                _ => (),
+            };
+        }
+        false
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.constraint.add_node_in_layer(layer);
+        self.negation.add_node_in_layer(layer);
+        self.top_down_properties[layer].push(ReifiedProperty::default());
+        self.bottom_up_properties[layer].push(ReifiedProperty::default());
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.constraint.iter_scope().chain(std::iter::once(self.b)))
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        (assignment[self.b.0] == 1) == self.constraint.is_satisfied(assignment)
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        self.constraint.hash_node_state(node, state);
+        self.negation.hash_node_state(node, state);
+        let NodeIndex(layer, index) = node;
+        for property in [&self.top_down_properties[layer][index], &self.bottom_up_properties[layer][index]] {
+            state.write_u8(property.values);
+            state.write_u8(property.status);
+            state.write_u8(property.negation_status);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.constraint.eq_node_state(node, other) &&
+        self.negation.eq_node_state(node, other) &&
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn negation(&self) -> Option<Box<dyn Constraint + Send + Sync>> {
+        // b = 1 <=> not C
+        Some(Box::new(Reified::new(self.constraint.negation()?, self.b)))
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::Reified { constraint: Box::new(self.constraint.description()?), b: self.b })
+    }
+}
+
+#[cfg(test)]
+mod test_reified {
+
+    use crate::constraints::*;
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_reified_less_than() {
+        // b is placed before, in the middle of, and after the scope of the constraint
+        for order in [vec![1, 2, 0], vec![0, 2, 1], vec![0, 1, 2]] {
+            let mut problem = Problem::default();
+            let x = problem.add_variable(vec![0, 1, 2], None);
+            let y = problem.add_variable(vec![0, 1, 2], None);
+            let b = problem.add_variable(vec![0, 1], None);
+            reify(&mut problem, LessEqual::new(x, y, 1), b);
+
+            let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(order), MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            let solutions = get_all_solutions(&mdd);
+            assert_eq!(solutions.len(), 9);
+            assert!(solutions.iter().all(|solution| (solution[2] == 1) == (solution[0] < solution[1])));
+        }
+    }
+
+    #[test]
+    pub fn test_reified_count() {
+        // b <=> at most one of the variables is 1, with at least one b
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1], None);
+        let bs = problem.add_variables(2, vec![0, 1], None);
+        reify(&mut problem, Cardinality::new(vars[0..2].iter().map(|v| (*v, true)).collect(), 0, 1), bs[0]);
+        reify(&mut problem, Cardinality::new(vars[1..3].iter().map(|v| (*v, true)).collect(), 0, 1), bs[1]);
+        equal(&mut problem, bs[0], 0);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 2);
+        assert!(is_solution(vec![1, 1, 0, 0, 1], &solutions));
+        assert!(is_solution(vec![1, 1, 1, 0, 0], &solutions));
+    }
+
+    #[test]
+    pub fn test_implies() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1, 2], None);
+        implies(&mut problem, LessEqual::new(vars[0], vars[1], 1), LessEqual::new(vars[1], vars[2], 1));
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        // 27 assignments, minus the 8 with x < y and y >= z
+        assert_eq!(solutions.len(), 19);
+        assert!(solutions.iter().all(|solution| solution[0] >= solution[1] || solution[1] < solution[2]));
+    }
+
+    #[test]
+    pub fn test_if_then_else() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1, 2], None);
+        if_then_else(
+            &mut problem,
+            AbsDiff::new(vars[0], vars[1], Relation::Equal, 0),
+            LessEqual::new(vars[2], vars[0], 0),
+            AbsDiff::new(vars[1], vars[2], Relation::GreaterEqual, 2),
+        );
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        let expected = (0..27isize).map(|i| (i / 9, (i / 3) % 3, i % 3))
+            .filter(|(x, y, z)| if x == y { z <= x } else { (y - z).abs() >= 2 })
+            .count();
+        assert_eq!(solutions.len(), expected);
+        assert!(solutions.iter().all(|solution| if solution[0] == solution[1] { solution[2] <= solution[0] } else { (solution[1] - solution[2]).abs() >= 2 }));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::modelling::VariableIndex;
+use rustc_hash::FxHashMap;
+use std::collections::BTreeSet;
+use std::hash::Hasher;
+
+/// Maximum number of sequences of values recorded for a node (see `PendingValues`)
+const MAX_PENDING_SEQUENCES: usize = 64;
+
+/// Position of the variables of a sequence (e.g., for the stretch or regular constraints) in the
+/// MDD. The propagators of such constraints follow the sequence from one end and can only track
+/// their state exactly if the variables are assigned in order, which depends on the variable
+/// ordering of the MDD.
+///
+/// Seen from the root, the sequence is oriented forward if its first variable is above its last
+/// one, and backward otherwise. A position is tracked top-down if the positions assigned above it
+/// are exactly the positions preceding it in that orientation (and similarly for the bottom-up
+/// direction, seen from the sink). If the variables of the sequence are ordered in the MDD as in
+/// the sequence (or in the reverse order), every position is tracked in both directions. The
+/// positions that are not tracked top-down are pending: their values are recorded on the paths
+/// from the root (see `PendingValues`) until the last of them is assigned.
+#[derive(Default, Clone)]
+pub(crate) struct SequenceLayers {
+    /// For each layer in the scope, the position of its decision in the sequence
+    position_at_layer: FxHashMap<usize, usize>,
+    /// Is the sequence oriented forward when seen from the root
+    forward_top_down: bool,
+    /// Is the sequence oriented forward when seen from the sink
+    forward_bottom_up: bool,
+    /// Number of positions tracked top-down
+    tracked_top_down: usize,
+    /// Number of positions tracked bottom-up
+    tracked_bottom_up: usize,
+    /// Layers and positions that are not tracked top-down, in the order of the layers
+    pending: Vec<(usize, usize)>,
+    /// Length of the sequence
+    length: usize,
+}
+
+impl SequenceLayers {
+
+    /// Computes the layers of the sequence given the ordering (for each variable, its layer)
+    pub fn new(variables: &[VariableIndex], ordering: &[usize]) -> Self {
+        let length = variables.len();
+        let mut position_at_layer = FxHashMap::<usize, usize>::default();
+        let mut by_layer = variables.iter().copied().enumerate().map(|(position, variable)| (ordering[variable.0], position)).collect::<Vec<(usize, usize)>>();
+        for (layer, position) in by_layer.iter().copied() {
+            position_at_layer.insert(layer, position);
+        }
+        by_layer.sort_unstable();
+        let forward_top_down = length == 0 || ordering[variables[0].0] <= ordering[variables[length - 1].0];
+        let forward_bottom_up = !forward_top_down;
+        let mut sequence = Self {
+            position_at_layer,
+            forward_top_down,
+            forward_bottom_up,
+            tracked_top_down: 0,
+            tracked_bottom_up: 0,
+            pending: vec![],
+            length,
+        };
+        sequence.tracked_top_down = by_layer.iter().enumerate().take_while(|(rank, (_, position))| sequence.rank_top_down(*position) == *rank).count();
+        sequence.tracked_bottom_up = by_layer.iter().rev().enumerate().take_while(|(rank, (_, position))| sequence.rank_bottom_up(*position) == *rank).count();
+        sequence.pending = by_layer[sequence.tracked_top_down..].to_vec();
+        sequence
+    }
+
+    /// Returns the rank of the position in the sequence oriented from the root
+    fn rank_top_down(&self, position: usize) -> usize {
+        if self.forward_top_down { position } else { self.length - 1 - position }
+    }
+
+    /// Returns the rank of the position in the sequence oriented from the sink
+    fn rank_bottom_up(&self, position: usize) -> usize {
+        if self.forward_bottom_up { position } else { self.length - 1 - position }
+    }
+
+    /// Returns true if the sequence is oriented forward when seen from the root
+    pub fn is_forward_top_down(&self) -> bool {
+        self.forward_top_down
+    }
+
+    /// Returns the position in the sequence of the decision of the layer, if it is in the scope
+    pub fn position(&self, layer: usize) -> Option<usize> {
+        self.position_at_layer.get(&layer).copied()
+    }
+
+    pub fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.position_at_layer.contains_key(&layer)
+    }
+
+    /// Returns true if the positions assigned above the position are exactly the ones preceding it
+    /// in the sequence oriented from the root.
+    pub fn is_tracked_top_down(&self, position: usize) -> bool {
+        self.rank_top_down(position) < self.tracked_top_down
+    }
+
+    /// Returns true if the positions assigned below the position are exactly the ones preceding it
+    /// in the sequence oriented from the sink.
+    pub fn is_tracked_bottom_up(&self, position: usize) -> bool {
+        self.rank_bottom_up(position) < self.tracked_bottom_up
+    }
+
+    /// Returns true if the position is the first of the sequence oriented from the root (i.e., it
+    /// has no neighbour on the root side).
+    pub fn is_first_top_down(&self, position: usize) -> bool {
+        self.rank_top_down(position) == 0
+    }
+
+    /// Returns true if the position is the first of the sequence oriented from the sink (i.e., it
+    /// has no neighbour on the sink side).
+    pub fn is_first_bottom_up(&self, position: usize) -> bool {
+        self.rank_bottom_up(position) == 0
+    }
+
+    /// Returns true if the position is the last pending one, i.e., the last position of the
+    /// sequence in the order of the layers if some position is not tracked top-down
+    pub fn is_last_pending(&self, position: usize) -> bool {
+        self.pending.last().is_some_and(|(_, last)| *last == position)
+    }
+
+    /// Returns true if the top-down state of the nodes of the layer is pending, i.e., if some
+    /// pending positions are assigned above the layer but not all of them
+    pub fn is_pending_at_layer(&self, layer: usize) -> bool {
+        match (self.pending.first(), self.pending.last()) {
+            (Some((first, _)), Some((last, _))) => *first < layer && layer <= *last,
+            _ => false,
+        }
+    }
+
+    /// Returns the values of the pending positions, given in the order of the layers followed by
+    /// the value of the last one, in the order of the sequence oriented from the root
+    pub fn pending_in_order(&self, values: &[isize], last: isize) -> Vec<isize> {
+        let mut ordered = vec![0; self.pending.len()];
+        for ((_, position), value) in self.pending.iter().zip(values.iter().copied().chain(std::iter::once(last))) {
+            ordered[self.rank_top_down(*position) - self.tracked_top_down] = value;
+        }
+        ordered
+    }
+}
+
+/// Values assigned to the pending positions of a sequence (see `SequenceLayers`) on the paths from
+/// the root to a node, one sequence of values per path, in the order of the layers. The
+/// propagators keep the state of the tracked positions unchanged over the pending ones and record
+/// their values, until the last pending position completes the state by reading the values in the
+/// order of the sequence. A node of an exact MDD is reached by a single sequence of values, hence
+/// the propagation stays exact whatever the variable ordering. The values are forgotten (None) if
+/// more than MAX_PENDING_SEQUENCES sequences reach the node.
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct PendingValues(Option<BTreeSet<Vec<isize>>>);
+
+impl PendingValues {
+
+    /// Creates the values of the root, reached by the empty sequence
+    pub fn new() -> Self {
+        Self(Some(BTreeSet::from([vec![]])))
+    }
+
+    /// Removes all the sequences, before the aggregation of the parents of a node
+    pub fn reset(&mut self) {
+        self.0 = Some(BTreeSet::new());
+    }
+
+    /// Adds the sequences of the source, extended with the value if it is assigned to a pending
+    /// position
+    pub fn aggregate(&mut self, source: &Self, value: Option<isize>) {
+        if let (Some(sequences), Some(source_sequences)) = (self.0.as_mut(), source.0.as_ref()) {
+            for sequence in source_sequences.iter() {
+                let mut sequence = sequence.clone();
+                sequence.extend(value);
+                sequences.insert(sequence);
+            }
+            if sequences.len() > MAX_PENDING_SEQUENCES {
+                self.0 = None;
+            }
+        } else {
+            self.0 = None;
+        }
+    }
+
+    /// Returns the sequences of values, or None if they are not known
+    pub fn sequences(&self) -> Option<&BTreeSet<Vec<isize>>> {
+        self.0.as_ref()
+    }
+
+    pub fn hash(&self, state: &mut dyn Hasher) {
+        match self.0.as_ref() {
+            Some(sequences) => for sequence in sequences.iter() {
+                state.write_usize(sequence.len());
+                for value in sequence.iter().copied() {
+                    state.write_isize(value);
+                }
+            },
+            None => state.write_u8(0),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use super::*;
+use super::sequence::{SequenceLayers, PendingValues};
+use crate::modelling::VariableIndex;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::collections::BTreeSet;
+use std::hash::Hasher;
+
+// Structures for the stretch constraint. A stretch is a maximal run of consecutive variables of the
+// sequence taking the same value. For each constrained value v, every stretch of v must have a
+// length in [min(v), max(v)]. Values without bounds can form stretches of any length.
+//
+// The state of a path is the run ending at the last assigned position (top-down) or starting at
+// the first assigned position (bottom-up). It is only tracked for the positions whose neighbours
+// on the root (resp. sink) side are assigned above (resp. below) them, see `SequenceLayers`. Past
+// these positions, the top-down runs are kept unchanged and the values of the pending positions
+// are recorded (see `PendingValues`), the runs being read through them when the last position of
+// the sequence is assigned. Hence, the propagation is exact at unbounded width whatever the
+// variable ordering, but only the orderings following the sequence (or its reverse) filter the
+// edges before its last position.
+
+/// A run of a value in the sequence, as (value, length). A length of 0 represents the absence of
+/// run (no position assigned yet).
+type Run = (isize, usize);
+
+/// Local property of a node for the stretch constraint.
+#[derive(Clone, PartialEq, Eq)]
+struct StretchProperty {
+    /// Runs ending (top-down) or starting (bottom-up) at the node on some path
+    runs: BTreeSet<Run>,
+    /// True if on some path an untracked position has been assigned, in which case the runs are
+    /// not known (bottom-up, or top-down if the pending values have been forgotten)
+    unknown: bool,
+}
+
+impl StretchProperty {
+
+    /// Creates the property of a root/sink node, with no run
+    pub fn new() -> Self {
+        Self {
+            runs: BTreeSet::from([(0, 0)]),
+            unknown: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.runs.clear();
+        self.unknown = false;
+    }
+}
+
+#[derive(Clone)]
+pub struct Stretch {
+    /// Variables of the sequence, in order
+    variables: Vec<VariableIndex>,
+    /// For each constrained value, the minimum and maximum length of its stretches
+    lengths: FxHashMap<isize, (usize, usize)>,
+    /// Layers of the sequence in the MDD
+    sequence: SequenceLayers,
+    /// Top-down properties for each node in the MDD
+    top_down_properties: Vec<Vec<StretchProperty>>,
+    /// Bottom-up properties for each node in the MDD
+    bottom_up_properties: Vec<Vec<StretchProperty>>,
+    /// Values of the pending positions on the paths from the root, for each node in the MDD
+    pending: Vec<Vec<PendingValues>>,
+}
+
+impl Stretch {
+
+    /// Creates a new stretch constraint on the sequence of variables. The stretches of values[i]
+    /// must have a length between min_lengths[i] and max_lengths[i].
+    pub fn new(variables: Vec<VariableIndex>, values: Vec<isize>, min_lengths: Vec<usize>, max_lengths: Vec<usize>) -> Self {
+        debug_assert!(values.len() == min_lengths.len() && values.len() == max_lengths.len());
+        let lengths = values.into_iter().zip(min_lengths.into_iter().zip(max_lengths)).collect::<FxHashMap<isize, (usize, usize)>>();
+        Self {
+            variables,
+            lengths,
+            sequence: SequenceLayers::default(),
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            pending: vec![],
+        }
+    }
+
+    /// Returns the minimum and maximum length of the stretches of the value
+    fn bounds(&self, value: isize) -> (usize, usize) {
+        self.lengths.get(&value).copied().unwrap_or((1, usize::MAX))
+    }
+
+    /// Extends the run with the value. If the stretches of the value are not bounded above, the
+    /// length saturates at the minimum length so that the number of distinct runs stays small.
+    fn extend(&self, run: Run, value: isize) -> Run {
+        let (value_run, length) = run;
+        let length = if length > 0 && value_run == value { length + 1 } else { 1 };
+        let (min, max) = self.bounds(value);
+        if max == usize::MAX {
+            (value, length.min(min.max(1)))
+        } else {
+            (value, length)
+        }
+    }
+
+    /// Returns true if the value can be assigned between the run before it and the run after it.
+    /// An unknown run is represented by None.
+    fn is_feasible(&self, before: Option<Run>, value: isize, after: Option<Run>) -> bool {
+        let mut length = 1;
+        for (value_run, length_run) in [before, after].into_iter().flatten() {
+            if length_run > 0 {
+                if value_run == value {
+                    length += length_run;
+                } else if length_run < self.bounds(value_run).0 {
+                    // The value closes the stretch, which is too short
+                    return false;
+                }
+            }
+        }
+        let (min, max) = self.bounds(value);
+        length <= max && (before.is_none() || after.is_none() || length >= min)
+    }
+
+    /// Returns the run obtained by reading the values after the run, or None if a stretch is too
+    /// short or too long
+    fn read(&self, run: Run, values: &[isize]) -> Option<Run> {
+        values.iter().copied().try_fold(run, |(value_run, length), value| {
+            if length > 0 && value_run != value && length < self.bounds(value_run).0 {
+                return None;
+            }
+            Some(self.extend((value_run, length), value)).filter(|(_, length)| *length <= self.bounds(value).1)
+        })
+    }
+
+    /// Returns the runs obtained by reading the values of the pending positions, followed by the
+    /// value of the last one, after the runs of the property. Returns None if the values are not
+    /// known.
+    fn read_pending(&self, property: &StretchProperty, pending: &PendingValues, assignment: isize) -> Option<Vec<Run>> {
+        let sequences = pending.sequences()?;
+        let mut runs = vec![];
+        for values in sequences.iter() {
+            let values = self.sequence.pending_in_order(values, assignment);
+            runs.extend(property.runs.iter().filter_map(|run| self.read(*run, &values)));
+        }
+        Some(runs)
+    }
+
+    /// Returns the runs before (or after) a position, or None if they are not known
+    fn known_runs(property: &StretchProperty, is_end: bool, is_tracked: bool) -> Option<Vec<Run>> {
+        if is_end {
+            Some(vec![(0, 0)])
+        } else if is_tracked && !property.unknown {
+            Some(property.runs.iter().copied().collect())
+        } else {
+            None
+        }
+    }
+}
+
+impl Constraint for Stretch {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![StretchProperty::new()]).collect::<Vec<Vec<StretchProperty>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![StretchProperty::new()]).collect::<Vec<Vec<StretchProperty>>>();
+        self.pending = (0..vars.len() + 1).map(|_| vec![PendingValues::new()]).collect::<Vec<Vec<PendingValues>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.sequence = SequenceLayers::new(&self.variables, ordering);
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset();
+        self.pending[layer][index].reset();
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let source_property = &self.top_down_properties[source_layer][source_index];
+        let mut unknown = source_property.unknown;
+        let position = self.sequence.position(source_layer);
+        let is_pending = position.is_some_and(|position| !self.sequence.is_tracked_top_down(position));
+        let is_last_pending = position.is_some_and(|position| self.sequence.is_last_pending(position));
+        let runs = match position {
+            Some(_) if is_last_pending => {
+                let runs = self.read_pending(source_property, &self.pending[source_layer][source_index], assignment);
+                unknown |= runs.is_none();
+                runs.unwrap_or_default()
+            },
+            Some(_) if !is_pending => source_property.runs.iter().map(|run| self.extend(*run, assignment)).collect::<Vec<Run>>(),
+            _ => source_property.runs.iter().copied().collect::<Vec<Run>>(),
+        };
+        let target_property = &mut self.top_down_properties[target_layer][target_index];
+        target_property.runs.extend(runs);
+        target_property.unknown |= unknown;
+        let (pending_above, pending_below) = self.pending.split_at_mut(target_layer);
+        if is_last_pending {
+            // The runs have read all the pending values
+            pending_below[0][target_index].aggregate(&PendingValues::new(), None);
+        } else {
+            pending_below[0][target_index].aggregate(&pending_above[source_layer][source_index], Some(assignment).filter(|_| is_pending));
+        }
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset();
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let source_property = &self.bottom_up_properties[source_layer][source_index];
+        let unknown = source_property.unknown;
+        let runs = match self.sequence.position(target_layer) {
+            None => source_property.runs.iter().copied().collect::<Vec<Run>>(),
+            Some(position) if self.sequence.is_tracked_bottom_up(position) => source_property.runs.iter().map(|run| self.extend(*run, assignment)).collect::<Vec<Run>>(),
+            Some(_) => {
+                self.bottom_up_properties[target_layer][target_index].unknown = true;
+                return;
+            },
+        };
+        let target_property = &mut self.bottom_up_properties[target_layer][target_index];
+        target_property.runs.extend(runs);
+        target_property.unknown |= unknown;
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.sequence.is_layer_in_scope(layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let position = self.sequence.position(source_layer).unwrap();
+        if self.sequence.is_last_pending(position) && let Some(runs) = self.read_pending(&self.top_down_properties[source_layer][source_index], &self.pending[source_layer][source_index], assignment) {
+            // The runs end at the end of the sequence, hence the last stretch is closed
+            return runs.iter().all(|(value, length)| *length < self.bounds(*value).0);
+        }
+        let before = Self::known_runs(&self.top_down_properties[source_layer][source_index], self.sequence.is_first_top_down(position), self.sequence.is_tracked_top_down(position));
+        let after = Self::known_runs(&self.bottom_up_properties[target_layer][target_index], self.sequence.is_first_bottom_up(position), self.sequence.is_tracked_bottom_up(position));
+        let before = before.map(|runs| runs.into_iter().map(Some).collect::<Vec<Option<Run>>>()).unwrap_or(vec![None]);
+        let after = after.map(|runs| runs.into_iter().map(Some).collect::<Vec<Option<Run>>>()).unwrap_or(vec![None]);
+        !before.iter().any(|run_before| after.iter().any(|run_after| self.is_feasible(*run_before, assignment, *run_after)))
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(StretchProperty::new());
+        self.bottom_up_properties[layer].push(StretchProperty::new());
+        self.pending[layer].push(PendingValues::new());
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.iter().copied())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        let values = self.variables.iter().map(|variable| assignment[variable.0]).collect::<Vec<isize>>();
+        values.chunk_by(|a, b| a == b).all(|stretch| {
+            let (min, max) = self.bounds(stretch[0]);
+            min <= stretch.len() && stretch.len() <= max
+        })
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for property in [&self.top_down_properties[layer][index], &self.bottom_up_properties[layer][index]] {
+            state.write_u8(property.unknown as u8);
+            for (value, length) in property.runs.iter().copied() {
+                state.write_isize(value);
+                state.write_usize(length);
+            }
+        }
+        self.pending[layer][index].hash(state);
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex] &&
+        self.pending[layer][index] == self.pending[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        let mut lengths = self.lengths.iter().map(|(value, (min, max))| (*value, *min, *max)).collect::<Vec<(isize, usize, usize)>>();
+        lengths.sort_unstable();
+        Some(ConstraintDescription::Stretch {
+            variables: self.variables.clone(),
+            values: lengths.iter().map(|(value, _, _)| *value).collect(),
+            min_lengths: lengths.iter().map(|(_, min, _)| *min).collect(),
+            max_lengths: lengths.iter().map(|(_, _, max)| *max).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_stretch {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_exact_length() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(4, vec![0, 1], None);
+        stretch(&mut problem, vars, vec![1], vec![2], vec![2]);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2, 3]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 4);
+        assert!(is_solution(vec![0, 0, 0, 0], &solutions));
+        assert!(is_solution(vec![1, 1, 0, 0], &solutions));
+        assert!(is_solution(vec![0, 1, 1, 0], &solutions));
+        assert!(is_solution(vec![0, 0, 1, 1], &solutions));
+    }
+
+    #[test]
+    pub fn test_reverse_order() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(5, vec![0, 1], None);
+        stretch(&mut problem, vars, vec![0, 1], vec![1, 2], vec![1, 5]);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![4, 3, 2, 1, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 5);
+        assert!(is_solution(vec![1, 1, 1, 1, 1], &solutions));
+        assert!(is_solution(vec![0, 1, 1, 1, 1], &solutions));
+        assert!(is_solution(vec![1, 1, 1, 1, 0], &solutions));
+        assert!(is_solution(vec![1, 1, 0, 1, 1], &solutions));
+        assert!(is_solution(vec![0, 1, 1, 1, 0], &solutions));
+    }
+
+    #[test]
+    pub fn test_any_order() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(4, vec![0, 1], None);
+        stretch(&mut problem, vars, vec![1], vec![2], vec![3]);
+        let expected = reference::count_solutions(&problem);
+        assert_eq!(expected, 6);
+        for order in [OrderingHeuristic::MinDomMaxLinked, OrderingHeuristic::Custom(vec![1, 0, 2, 3]), OrderingHeuristic::Custom(vec![2, 0, 3, 1])] {
+            let mut mdd = Mdd::new(problem.clone(), usize::MAX, order, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            assert_eq!(mdd.count_solutions(), expected);
+        }
+    }
+}
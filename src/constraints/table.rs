@@ -0,0 +1,245 @@
+use super::*;
+use crate::utils::Bitset;
+use crate::modelling::*;
+use crate::mdd::*;
+use rustc_hash::FxHashMap;
+use std::hash::Hasher;
+
+// Structures for the (short) table constraint: the values of the variables must match one of the
+// tuples of the table. An entry of a tuple is either a value or a wildcard (None), matching any
+// value. Large tables can be compressed into fewer tuples with wildcards (see `compress_tuples`).
+//
+// The property of a node is the set of tuples matching the assignments on at least one path
+// to/from the node. An edge is supported if a tuple matches its assignment, and is in both the
+// top-down property of its source and the bottom-up property of its target. The matches are
+// precomputed, for each variable and value, as a bitset of tuples.
+
+#[derive(Clone)]
+pub struct Table {
+    variables: Vec<VariableIndex>,
+    tuples: Vec<Vec<Option<isize>>>,
+    /// For each position of the scope and value, the tuples matching the value at that position
+    supports: Vec<FxHashMap<isize, Bitset>>,
+    /// Tuples matching no value
+    empty: Bitset,
+    /// Property of the root and the sink
+    all_tuples: Bitset,
+    top_down_properties: Vec<Vec<Bitset>>,
+    bottom_up_properties: Vec<Vec<Bitset>>,
+    /// For each layer in the scope, the position of its decision in the scope
+    position_at_layer: FxHashMap<usize, usize>,
+}
+
+impl Table {
+
+    /// Creates a new table constraint. Each tuple gives an entry for each variable, None being
+    /// a wildcard.
+    pub fn new(variables: Vec<VariableIndex>, tuples: Vec<Vec<Option<isize>>>) -> Self {
+        debug_assert!(tuples.iter().all(|tuple| tuple.len() == variables.len()));
+        let mut all_tuples = Bitset::new(tuples.len());
+        for tuple in 0..tuples.len() {
+            all_tuples.insert(tuple);
+        }
+        Self {
+            variables,
+            supports: vec![],
+            empty: Bitset::new(tuples.len()),
+            tuples,
+            all_tuples,
+            top_down_properties: vec![],
+            bottom_up_properties: vec![],
+            position_at_layer: FxHashMap::<usize, usize>::default(),
+        }
+    }
+
+    /// Returns the tuples matching the assignment of the decision of the layer
+    fn matching(&self, layer: usize, assignment: isize) -> &Bitset {
+        let position = self.position_at_layer[&layer];
+        self.supports[position].get(&assignment).unwrap_or(&self.empty)
+    }
+}
+
+/// Compresses the tuples of a table by replacing the entries of a position by a wildcard whenever
+/// the tuples agreeing on all the other positions cover the domain of the variable at that
+/// position. The domains are given by position.
+pub fn compress_tuples(tuples: Vec<Vec<Option<isize>>>, domains: &[Vec<isize>]) -> Vec<Vec<Option<isize>>> {
+    let mut tuples = tuples;
+    tuples.sort_unstable();
+    tuples.dedup();
+    for (position, domain) in domains.iter().enumerate() {
+        // Groups the tuples by their entries at the other positions
+        let mut groups = FxHashMap::<Vec<Option<isize>>, Vec<Option<isize>>>::default();
+        for tuple in tuples.iter() {
+            let mut key = tuple.clone();
+            let entry = key[position].take();
+            groups.entry(key).or_default().push(entry);
+        }
+        let mut compressed = Vec::with_capacity(groups.len());
+        for (key, entries) in groups {
+            if entries.contains(&None) || domain.iter().all(|value| entries.contains(&Some(*value))) {
+                compressed.push(key);
+            } else {
+                for entry in entries {
+                    let mut tuple = key.clone();
+                    tuple[position] = entry;
+                    compressed.push(tuple);
+                }
+            }
+        }
+        compressed.sort_unstable();
+        tuples = compressed;
+    }
+    tuples
+}
+
+impl Constraint for Table {
+
+    fn init(&mut self, vars: &[Variable]) {
+        self.supports = self.variables.iter().copied().enumerate().map(|(position, variable)| {
+            vars[*variable].iter_domain().map(|value| {
+                let mut matching = Bitset::new(self.tuples.len());
+                for (index, tuple) in self.tuples.iter().enumerate() {
+                    if tuple[position].is_none_or(|entry| entry == value) {
+                        matching.insert(index);
+                    }
+                }
+                (value, matching)
+            }).collect::<FxHashMap<isize, Bitset>>()
+        }).collect::<Vec<FxHashMap<isize, Bitset>>>();
+        self.top_down_properties = (0..vars.len() + 1).map(|_| vec![self.all_tuples.clone()]).collect::<Vec<Vec<Bitset>>>();
+        self.bottom_up_properties = (0..vars.len() + 1).map(|_| vec![self.all_tuples.clone()]).collect::<Vec<Vec<Bitset>>>();
+    }
+
+    fn update_variable_ordering(&mut self, ordering: &[usize]) {
+        self.position_at_layer = self.variables.iter().copied().enumerate().map(|(position, variable)| (ordering[variable.0], position)).collect::<FxHashMap<usize, usize>>();
+    }
+
+    fn reset_property_top_down(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.top_down_properties[layer][index].reset(0);
+    }
+
+    fn update_property_top_down(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let mut tuples = self.top_down_properties[source_layer][source_index].clone();
+        if self.is_layer_in_scope(source_layer) {
+            tuples.intersect(self.matching(source_layer, assignment));
+        }
+        self.top_down_properties[target_layer][target_index].union(&tuples);
+    }
+
+    fn reset_property_bottom_up(&mut self, node: NodeIndex) {
+        let NodeIndex(layer, index) = node;
+        self.bottom_up_properties[layer][index].reset(0);
+    }
+
+    fn update_property_bottom_up(&mut self, source: NodeIndex, target: NodeIndex, assignment: isize) {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let mut tuples = self.bottom_up_properties[source_layer][source_index].clone();
+        if self.is_layer_in_scope(target_layer) {
+            tuples.intersect(self.matching(target_layer, assignment));
+        }
+        self.bottom_up_properties[target_layer][target_index].union(&tuples);
+    }
+
+    fn is_layer_in_scope(&self, layer: usize) -> bool {
+        self.position_at_layer.contains_key(&layer)
+    }
+
+    fn is_assignment_invalid(&self, source: NodeIndex, target: NodeIndex, _decision: VariableIndex, assignment: isize) -> bool {
+        let NodeIndex(source_layer, source_index) = source;
+        let NodeIndex(target_layer, target_index) = target;
+        let above = self.top_down_properties[source_layer][source_index].words();
+        let below = self.bottom_up_properties[target_layer][target_index].words();
+        let matching = self.matching(source_layer, assignment).words();
+        (0..matching.len()).all(|word| above[word] & matching[word] & below[word] == 0)
+    }
+
+    fn add_node_in_layer(&mut self, layer: usize) {
+        self.top_down_properties[layer].push(self.all_tuples.clone());
+        self.bottom_up_properties[layer].push(self.all_tuples.clone());
+    }
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(self.variables.iter().copied())
+    }
+
+    fn is_satisfied(&self, assignment: &[isize]) -> bool {
+        self.tuples.iter().any(|tuple| {
+            tuple.iter().zip(self.variables.iter()).all(|(entry, variable)| entry.is_none_or(|value| value == assignment[variable.0]))
+        })
+    }
+
+    fn hash_node_state(&self, node: NodeIndex, state: &mut dyn Hasher) {
+        let NodeIndex(layer, index) = node;
+        for word in self.top_down_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+        for word in self.bottom_up_properties[layer][index].words().iter().copied() {
+            state.write_u64(word);
+        }
+    }
+
+    fn eq_node_state(&self, node: NodeIndex, other: NodeIndex) -> bool {
+        let NodeIndex(layer, index) = node;
+        let NodeIndex(olayer, oindex) = other;
+        self.top_down_properties[layer][index] == self.top_down_properties[olayer][oindex] &&
+        self.bottom_up_properties[layer][index] == self.bottom_up_properties[olayer][oindex]
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::Table { variables: self.variables.clone(), tuples: self.tuples.clone() })
+    }
+}
+
+#[cfg(test)]
+mod test_table {
+
+    use crate::constraints::table::compress_tuples;
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_short_table() {
+        let mut problem = Problem::default();
+        let vars = problem.add_variables(3, vec![0, 1, 2], None);
+        short_table(&mut problem, vars, vec![
+            vec![Some(0), None, Some(1)],
+            vec![Some(2), Some(2), None],
+        ]);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![1, 0, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 6);
+        assert!(is_solution(vec![0, 2, 1], &solutions));
+        assert!(is_solution(vec![2, 2, 0], &solutions));
+        assert!(!is_solution(vec![0, 2, 0], &solutions));
+    }
+
+    #[test]
+    pub fn test_compression() {
+        let domains = vec![vec![0, 1], vec![0, 1, 2]];
+        let tuples = vec![
+            vec![Some(0), Some(0)], vec![Some(0), Some(1)], vec![Some(0), Some(2)],
+            vec![Some(1), Some(1)], vec![Some(0), Some(1)],
+        ];
+        let compressed = compress_tuples(tuples, &domains);
+        assert_eq!(compressed, vec![vec![None, Some(1)], vec![Some(0), Some(0)], vec![Some(0), Some(2)]]);
+
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        table(&mut problem, vec![x, y], vec![vec![0, 0], vec![0, 1], vec![0, 2], vec![1, 1]]);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![1, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 4);
+        assert!(is_solution(vec![1, 1], &solutions));
+    }
+}
@@ -0,0 +1,54 @@
+use super::*;
+use crate::modelling::*;
+use crate::mdd::*;
+use std::hash::Hasher;
+
+// Constraint without scope, satisfied by every assignment. It takes the place of the retracted
+// constraints in a problem (see `Problem::retract_constraint`), so that the indexes of the other
+// constraints do not change.
+
+#[derive(Clone, Default)]
+pub struct Tautology;
+
+impl Constraint for Tautology {
+
+    fn init(&mut self, _vars: &[Variable]) {}
+
+    fn update_variable_ordering(&mut self, _ordering: &[usize]) {}
+
+    fn reset_property_top_down(&mut self, _node: NodeIndex) {}
+
+    fn update_property_top_down(&mut self, _source: NodeIndex, _target: NodeIndex, _assignment: isize) {}
+
+    fn reset_property_bottom_up(&mut self, _node: NodeIndex) {}
+
+    fn update_property_bottom_up(&mut self, _source: NodeIndex, _target: NodeIndex, _assignment: isize) {}
+
+    fn is_layer_in_scope(&self, _layer: usize) -> bool {
+        false
+    }
+
+    fn is_assignment_invalid(&self, _source: NodeIndex, _target: NodeIndex, _decision: VariableIndex, _assignment: isize) -> bool {
+        false
+    }
+
+    fn add_node_in_layer(&mut self, _layer: usize) {}
+
+    fn iter_scope(&self) -> Box<dyn Iterator<Item = VariableIndex> + '_> {
+        Box::new(std::iter::empty())
+    }
+
+    fn is_satisfied(&self, _assignment: &[isize]) -> bool {
+        true
+    }
+
+    fn hash_node_state(&self, _node: NodeIndex, _state: &mut dyn Hasher) {}
+
+    fn eq_node_state(&self, _node: NodeIndex, _other: NodeIndex) -> bool {
+        true
+    }
+
+    fn description(&self) -> Option<ConstraintDescription> {
+        Some(ConstraintDescription::Tautology)
+    }
+}
@@ -0,0 +1,324 @@
+use crate::modelling::*;
+use crate::mdd::*;
+use crate::mdd::heuristics::*;
+use crate::reference;
+use rand::prelude::*;
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256Plus;
+
+// Differential fuzzing of the MDDs against the brute-force reference solver (see `reference`). A
+// seed generates a small random problem, whose MDDs are compiled with several widths and compared
+// with the solutions enumerated by the reference. A failing seed can be replayed with `check_seed`.
+
+/// Bounds of the random problems
+#[derive(Clone, Debug)]
+pub struct FuzzConfig {
+    pub min_variables: usize,
+    pub max_variables: usize,
+    /// Maximum size of the domains, the values being drawn in `min_value..=max_value`
+    pub max_domain_size: usize,
+    pub min_value: isize,
+    pub max_value: isize,
+    pub max_constraints: usize,
+    /// Maximum number of tuples of the table constraints
+    pub max_tuples: usize,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        Self {
+            min_variables: 2,
+            max_variables: 6,
+            max_domain_size: 4,
+            min_value: -1,
+            max_value: 4,
+            max_constraints: 4,
+            max_tuples: 8,
+        }
+    }
+}
+
+/// Returns distinct variables of the problem, at least two, in a random order
+fn random_scope(rng: &mut Xoshiro256Plus, number_variables: usize, max_size: usize) -> Vec<VariableIndex> {
+    let mut variables = (0..number_variables).map(VariableIndex).collect::<Vec<VariableIndex>>();
+    variables.shuffle(rng);
+    variables.truncate(rng.random_range(2..=max_size.clamp(2, number_variables)));
+    variables
+}
+
+/// Returns distinct 0/1 variables of the problem, at least two, in a random order, or None if the
+/// problem has less than two 0/1 variables
+fn random_boolean_scope(rng: &mut Xoshiro256Plus, problem: &Problem, max_size: usize) -> Option<Vec<VariableIndex>> {
+    let mut variables = problem.iter_variables().filter(|variable| problem[*variable].iter_domain().all(|value| value == 0 || value == 1)).collect::<Vec<VariableIndex>>();
+    if variables.len() < 2 {
+        return None;
+    }
+    variables.shuffle(rng);
+    variables.truncate(rng.random_range(2..=max_size.clamp(2, variables.len())));
+    Some(variables)
+}
+
+/// Transition (from, value, to, cost) of an automaton
+type Transition = (usize, isize, usize, isize);
+
+/// Returns a random deterministic automaton over the values of the domains, as the transitions
+/// (from, value, to, cost) and the final states, the initial state being 0
+fn random_automaton(rng: &mut Xoshiro256Plus, values: &[isize]) -> (Vec<Transition>, Vec<usize>) {
+    let number_states = rng.random_range(2..=3);
+    let mut transitions = vec![];
+    for from in 0..number_states {
+        for value in values.iter().copied() {
+            if rng.random_range(0..4) != 0 {
+                transitions.push((from, value, rng.random_range(0..number_states), rng.random_range(0..=2i64) as isize));
+            }
+        }
+    }
+    let finals = (0..number_states).filter(|state| *state == 0 || rng.random_bool(0.5)).collect::<Vec<usize>>();
+    (transitions, finals)
+}
+
+/// Generates the random problem of the seed. About a third of the variables are 0/1 variables, and
+/// the constraints are drawn among the global, arithmetic, 0/1, cost and diagram constraints.
+pub fn random_problem(seed: u64, config: &FuzzConfig) -> Problem {
+    assert!(config.min_variables >= 2, "The random problems must have at least two variables");
+    let mut rng = Xoshiro256Plus::seed_from_u64(seed);
+    let mut problem = Problem::default();
+    let number_variables = rng.random_range(config.min_variables..=config.max_variables);
+    for _ in 0..number_variables {
+        if rng.random_range(0..3) == 0 {
+            problem.add_variable(vec![0, 1], None);
+            continue;
+        }
+        let mut values = (config.min_value..=config.max_value).collect::<Vec<isize>>();
+        values.shuffle(&mut rng);
+        values.truncate(rng.random_range(1..=config.max_domain_size));
+        values.sort();
+        problem.add_variable(values, None);
+    }
+    let values = (config.min_value..=config.max_value).collect::<Vec<isize>>();
+    for _ in 0..rng.random_range(1..=config.max_constraints) {
+        match rng.random_range(0..18) {
+            0 => {
+                let scope = random_scope(&mut rng, number_variables, 4);
+                problem.add_constraint(crate::constraints::AllDifferent::new(scope));
+            },
+            1 => {
+                let scope = random_scope(&mut rng, number_variables, 2);
+                not_equals(&mut problem, scope[0], scope[1]);
+            },
+            2 => {
+                let scope = random_scope(&mut rng, number_variables, 4);
+                all_different_except(&mut problem, scope, *values.choose(&mut rng).unwrap());
+            },
+            3 => {
+                let scope = random_scope(&mut rng, number_variables, 2);
+                less_equal(&mut problem, scope[0], scope[1], rng.random_range(-1..=1i64) as isize);
+            },
+            4 => {
+                let scope = random_scope(&mut rng, number_variables, 2);
+                let relation = *[Relation::Equal, Relation::NotEqual, Relation::LessEqual, Relation::GreaterEqual].choose(&mut rng).unwrap();
+                abs_diff(&mut problem, scope[0], scope[1], relation, rng.random_range(0..=3i64) as isize);
+            },
+            5 => {
+                let scope = random_scope(&mut rng, number_variables, 3);
+                if scope.len() == 3 {
+                    let operation = *[Operation::Add, Operation::Mul, Operation::Div, Operation::Mod].choose(&mut rng).unwrap();
+                    arithmetic(&mut problem, scope[0], scope[1], scope[2], operation);
+                }
+            },
+            6 => {
+                let mut scope = random_scope(&mut rng, number_variables, 4);
+                let y = scope.pop().unwrap();
+                if rng.random_bool(0.5) {
+                    maximum(&mut problem, scope, y);
+                } else {
+                    minimum(&mut problem, scope, y);
+                }
+            },
+            7 => {
+                let scope = random_scope(&mut rng, number_variables, 3);
+                problem.add_constraint(crate::constraints::Increasing::new(scope, rng.random_bool(0.5)));
+            },
+            8 => {
+                let scope = random_scope(&mut rng, number_variables, 5);
+                let mut stretched = values.clone();
+                stretched.shuffle(&mut rng);
+                stretched.truncate(rng.random_range(1..=2));
+                let min_lengths = stretched.iter().map(|_| rng.random_range(1..=2)).collect::<Vec<usize>>();
+                let max_lengths = min_lengths.iter().map(|min| rng.random_range(*min..=3)).collect::<Vec<usize>>();
+                stretch(&mut problem, scope, stretched, min_lengths, max_lengths);
+            },
+            9 => {
+                let scope = random_scope(&mut rng, number_variables, 3);
+                let xs = scope.iter().copied().take(scope.len() / 2 + 1).collect::<Vec<VariableIndex>>();
+                let ys = scope.iter().copied().skip(scope.len() - xs.len()).collect::<Vec<VariableIndex>>();
+                if xs.iter().all(|x| !ys.contains(x)) {
+                    inverse(&mut problem, xs, ys);
+                }
+            },
+            10 => {
+                if let Some(scope) = random_boolean_scope(&mut rng, &problem, 5) {
+                    global_contiguity(&mut problem, scope);
+                }
+            },
+            11 => {
+                if let Some(scope) = random_boolean_scope(&mut rng, &problem, 4) {
+                    let lower = rng.random_range(0..=scope.len());
+                    let upper = rng.random_range(lower..=scope.len());
+                    let literals = scope.into_iter().map(|variable| (variable, rng.random_bool(0.5))).collect::<Vec<(VariableIndex, bool)>>();
+                    cardinality(&mut problem, literals, lower, upper);
+                }
+            },
+            12 => {
+                if let Some(scope) = random_boolean_scope(&mut rng, &problem, 4) {
+                    let weights = scope.iter().map(|_| rng.random_range(-2..=3i64) as isize).collect::<Vec<isize>>();
+                    pseudo_boolean_greater_equal(&mut problem, scope, weights, rng.random_range(-1..=3i64) as isize);
+                }
+            },
+            13 => {
+                let scope = random_scope(&mut rng, number_variables, 3);
+                let Some(b) = problem.iter_variables().filter(|variable| !scope.contains(variable) && problem[*variable].iter_domain().all(|value| value == 0 || value == 1)).choose(&mut rng) else { continue };
+                match rng.random_range(0..3) {
+                    0 => reify(&mut problem, crate::constraints::LessEqual::new(scope[0], scope[1], rng.random_range(-1..=1i64) as isize), b),
+                    1 => reify(&mut problem, crate::constraints::NotEquals::new(scope[0], scope[1]), b),
+                    _ => reify(&mut problem, crate::constraints::AbsDiff::new(scope[0], scope[1], Relation::LessEqual, rng.random_range(0..=2i64) as isize), b),
+                }
+            },
+            14 => {
+                let mut scope = random_scope(&mut rng, number_variables, 4);
+                if scope.len() >= 3 {
+                    let z = scope.pop().unwrap();
+                    let (transitions, finals) = random_automaton(&mut rng, &values);
+                    cost_regular(&mut problem, scope, transitions, 0, finals, z);
+                }
+            },
+            15 => {
+                let mut scope = random_scope(&mut rng, number_variables, 4);
+                if scope.len() >= 3 {
+                    let z = scope.pop().unwrap();
+                    let mut counted = values.clone();
+                    counted.shuffle(&mut rng);
+                    counted.truncate(rng.random_range(1..=2));
+                    let lower = counted.iter().map(|_| rng.random_range(0..=1)).collect::<Vec<usize>>();
+                    let upper = lower.iter().map(|lower| rng.random_range(*lower..=2)).collect::<Vec<usize>>();
+                    let costs = counted.iter().map(|_| rng.random_range(0..=2i64) as isize).collect::<Vec<isize>>();
+                    cost_global_cardinality(&mut problem, scope, counted, lower, upper, costs, z);
+                }
+            },
+            16 => {
+                let scope = random_scope(&mut rng, number_variables, 4);
+                for variable in scope.iter().copied() {
+                    let costs = (0..problem[variable].domain_size()).map(|_| rng.random_range(0..=2i64) as isize).collect::<Vec<isize>>();
+                    problem[variable].set_costs(&costs);
+                }
+                cost_at_most(&mut problem, scope, rng.random_range(0..=4i64) as isize);
+            },
+            _ => {
+                // The diagram is compiled from a problem over copies of the variables of its scope
+                let scope = random_scope(&mut rng, number_variables, 3);
+                let mut diagram = Problem::default();
+                let copies = scope.iter().map(|variable| diagram.add_variable(problem[*variable].iter_domain().collect(), None)).collect::<Vec<VariableIndex>>();
+                let domains = scope.iter().map(|variable| problem[*variable].iter_domain().collect::<Vec<isize>>()).collect::<Vec<Vec<isize>>>();
+                let tuples = (0..rng.random_range(1..=config.max_tuples))
+                    .map(|_| domains.iter().map(|domain| domain[rng.random_range(0..domain.len())]).collect::<Vec<isize>>())
+                    .collect::<Vec<Vec<isize>>>();
+                table(&mut diagram, copies, tuples);
+                let mut mdd = Mdd::new(diagram, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+                mdd.refine();
+                mdd_constraint(&mut problem, &mdd, &scope);
+            },
+        }
+    }
+    problem
+}
+
+/// Returns the random order of the variables of the seed, to compile its problem with a custom
+/// ordering
+pub fn random_order(seed: u64, number_variables: usize) -> Vec<usize> {
+    let mut rng = Xoshiro256Plus::seed_from_u64(!seed);
+    let mut order = (0..number_variables).collect::<Vec<usize>>();
+    order.shuffle(&mut rng);
+    order
+}
+
+/// Compiles the MDDs of the problem of the seed with the given width, with the default ordering
+/// and the random ordering of the seed (see `random_order`), and panics if one disagrees with the
+/// reference solver. An exact MDD must have the same number of solutions and the same feasible
+/// values, and a relaxed MDD must over-approximate them.
+pub fn check_seed(seed: u64, config: &FuzzConfig, max_width: usize) {
+    let problem = random_problem(seed, config);
+    let solutions = reference::solutions(&problem);
+    let expected = solutions.len() as u128;
+    let mut expected_values = vec![vec![]; problem.number_variables()];
+    for solution in solutions.iter() {
+        for (variable, value) in solution.iter().copied().enumerate() {
+            if !expected_values[variable].contains(&value) {
+                expected_values[variable].push(value);
+            }
+        }
+    }
+    for values in expected_values.iter_mut() {
+        values.sort();
+    }
+    let order = random_order(seed, problem.number_variables());
+    for (name, ordering) in [("default", OrderingHeuristic::MinDomMaxLinked), ("random", OrderingHeuristic::Custom(order))] {
+        let mut mdd = Mdd::new(problem.clone(), max_width, ordering, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let exact = max_width == usize::MAX;
+        let count = if mdd.is_unsat() { 0 } else { mdd.count_solutions() };
+        if exact {
+            assert_eq!(count, expected, "Seed {}: the MDD with the {} ordering has {} solutions, the reference {}", seed, name, count, expected);
+        } else {
+            assert!(count >= expected, "Seed {}: the relaxed MDD of width {} with the {} ordering has {} solutions, the reference {}", seed, max_width, name, count, expected);
+        }
+        if expected == 0 {
+            continue;
+        }
+        for (variable, values) in expected_values.iter().enumerate() {
+            let feasible = mdd.feasible_values(VariableIndex(variable));
+            if exact {
+                assert_eq!(&feasible, values, "Seed {}: wrong feasible values for the variable {} with the {} ordering", seed, variable, name);
+            } else {
+                assert!(values.iter().all(|value| feasible.contains(value)), "Seed {}: the relaxed MDD of width {} with the {} ordering removes a feasible value of the variable {}", seed, max_width, name, variable);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_fuzz {
+
+    use crate::fuzz::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_random_problem() {
+        let config = FuzzConfig::default();
+        let problem = random_problem(42, &config);
+        assert_eq!(random_problem(42, &config).description(), problem.description());
+        assert!((config.min_variables..=config.max_variables).contains(&problem.number_variables()));
+        assert!(problem.number_constraints() >= 1);
+    }
+
+    #[test]
+    pub fn test_fuzz_against_reference() {
+        let config = FuzzConfig::default();
+        for seed in 0..200 {
+            for max_width in [usize::MAX, 3, 1] {
+                check_seed(seed, &config, max_width);
+            }
+            let problem = random_problem(seed, &config);
+            let mut expected = reference::solutions(&problem);
+            let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            if mdd.is_unsat() {
+                assert!(expected.is_empty(), "Seed {}: the MDD is unsat but the problem has solutions", seed);
+                continue;
+            }
+            let mut solutions = get_all_solutions(&mdd);
+            expected.sort();
+            solutions.sort();
+            assert_eq!(solutions, expected, "Seed {}: wrong solutions", seed);
+        }
+    }
+}
@@ -1,5 +1,11 @@
 pub mod modelling;
 pub mod constraints;
 pub mod mdd;
-mod utils;
+pub mod models;
+pub mod fuzz;
+pub mod reference;
+pub mod utils;
+#[cfg(feature = "python")]
 pub mod pyaicad;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,300 @@
+use super::*;
+use crate::modelling::*;
+use rustc_hash::FxHashSet;
+
+// Analyses of a compiled MDD, e.g., to write integration tests. They only follow the active nodes
+// and edges, hence they can be used at any time of the compilation. They enumerate the paths of
+// the MDD, so they are meant for small MDDs.
+//
+// The constraint graph of a problem is analysed before its compilation, e.g., by the orderings of
+// `OrderingHeuristic::ConstraintGraph`. The vertices of the primal graph are the variables, linked
+// if a constraint has both in its scope, and the vertices of the dual graph are the constraints,
+// linked if their scopes intersect. The treewidth of the primal graph bounds the width of the exact
+// MDD for a good ordering, hence it tells whether the compilation is viable. It is NP-hard to
+// compute, and is estimated by the degeneracy of the graph, a lower bound, and by the width of the
+// min-fill elimination ordering, an upper bound.
+
+/// Returns the assignments of the paths from the root to the sink, i.e., the solutions if the MDD
+/// is exact. The values are indexed by variable, not by layer.
+pub fn get_all_solutions(mdd: &Mdd) -> Vec<Vec<isize>> {
+    let mut solutions: Vec<Vec<isize>> = vec![];
+    if mdd.is_unsat() {
+        return solutions;
+    }
+    let mut current_solution: Vec<isize> = vec![0; mdd.number_layers() - 1];
+    _get_all_solutions(mdd, NodeIndex(0, 0), &mut solutions, &mut current_solution);
+    solutions
+}
+
+fn _get_all_solutions(mdd: &Mdd, node: NodeIndex, solutions: &mut Vec<Vec<isize>>, current_solution: &mut Vec<isize>) {
+    let NodeIndex(layer, _) = node;
+    if layer == mdd.number_layers() - 1 {
+        solutions.push(current_solution.clone());
+        return;
+    }
+    let variable = mdd.decision_at_layer(layer);
+    for edge in mdd[node].iter_children().map(|edge| mdd.edge(edge)).filter(|edge| edge.is_active() && mdd[edge.to()].is_active()) {
+        current_solution[*variable] = mdd.problem()[variable].value(edge.assignment());
+        _get_all_solutions(mdd, edge.to(), solutions, current_solution);
+    }
+}
+
+/// Returns true if the solution is one of the given solutions
+pub fn is_solution(solution: Vec<isize>, all_solutions: &[Vec<isize>]) -> bool {
+    all_solutions.contains(&solution)
+}
+
+/// Returns the number of paths from the root to the sink, counted along the active nodes and edges
+/// only. Unlike `Mdd::count_solutions`, it does not rely on the propagation having removed the
+/// nodes without parent or child.
+pub fn count_number_solution(mdd: &Mdd) -> u128 {
+    if mdd.is_unsat() {
+        return 0;
+    }
+    let mut counts = vec![1u128];
+    for layer in 0..mdd.number_layers() - 1 {
+        let mut next = vec![0u128; mdd.number_nodes_in_layer(layer + 1)];
+        for (index, count) in counts.iter().copied().enumerate().filter(|(index, count)| *count > 0 && mdd[NodeIndex(layer, *index)].is_active()) {
+            for edge in mdd[NodeIndex(layer, index)].iter_children().map(|edge| mdd.edge(edge)).filter(|edge| edge.is_active()) {
+                next[edge.to().1] = next[edge.to().1].saturating_add(count);
+            }
+        }
+        counts = next;
+    }
+    counts[0]
+}
+
+/// Returns the values of the decision of the node's layer assigned by its active child edges, in
+/// the order of the domain
+pub fn node_possible_values(mdd: &Mdd, node: NodeIndex) -> Vec<isize> {
+    if !mdd[node].is_active() || node.0 == mdd.number_layers() - 1 {
+        return vec![];
+    }
+    let variable = mdd.decision_at_layer(node.0);
+    let mut assignments = mdd[node].iter_children().map(|edge| mdd.edge(edge)).filter(|edge| edge.is_active() && mdd[edge.to()].is_active()).map(|edge| edge.assignment()).collect::<Vec<ValueIndex>>();
+    assignments.sort();
+    assignments.dedup();
+    assignments.into_iter().map(|assignment| mdd.problem()[variable].value(assignment)).collect()
+}
+
+/// Minimum, maximum and mean degree of the vertices of a graph
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct DegreeStatistics {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+impl DegreeStatistics {
+
+    fn from_degrees(degrees: impl Iterator<Item = usize>) -> Self {
+        let mut statistics = Self { min: usize::MAX, ..Default::default() };
+        let mut number_vertices = 0;
+        for degree in degrees {
+            statistics.min = statistics.min.min(degree);
+            statistics.max = statistics.max.max(degree);
+            statistics.mean += degree as f64;
+            number_vertices += 1;
+        }
+        if number_vertices == 0 {
+            return Self::default();
+        }
+        statistics.mean /= number_vertices as f64;
+        statistics
+    }
+}
+
+/// Primal and dual constraint graphs of a problem (see `constraint_graph`)
+pub struct ConstraintGraph {
+    /// Neighbours of each variable in the primal graph, in increasing order
+    primal: Vec<Vec<VariableIndex>>,
+    /// Neighbours of each constraint in the dual graph, in increasing order
+    dual: Vec<Vec<ConstraintIndex>>,
+}
+
+/// Returns the primal and dual constraint graphs of the problem
+pub fn constraint_graph(problem: &Problem) -> ConstraintGraph {
+    let mut primal = vec![FxHashSet::<VariableIndex>::default(); problem.number_variables()];
+    let mut dual = vec![FxHashSet::<ConstraintIndex>::default(); problem.number_constraints()];
+    for constraint in problem.iter_constraints() {
+        let scope = problem[constraint].iter_scope().collect::<Vec<VariableIndex>>();
+        for x in scope.iter().copied() {
+            primal[x.0].extend(scope.iter().copied().filter(|y| *y != x));
+            dual[constraint.0].extend(problem[x].iter_constraints().filter(|other| *other != constraint));
+        }
+    }
+    ConstraintGraph {
+        primal: primal.into_iter().map(sorted).collect(),
+        dual: dual.into_iter().map(sorted).collect(),
+    }
+}
+
+fn sorted<T: Ord>(set: FxHashSet<T>) -> Vec<T> {
+    let mut elements = set.into_iter().collect::<Vec<T>>();
+    elements.sort();
+    elements
+}
+
+impl ConstraintGraph {
+
+    pub fn primal_neighbours(&self, variable: VariableIndex) -> &[VariableIndex] {
+        &self.primal[variable.0]
+    }
+
+    pub fn dual_neighbours(&self, constraint: ConstraintIndex) -> &[ConstraintIndex] {
+        &self.dual[constraint.0]
+    }
+
+    pub fn number_primal_edges(&self) -> usize {
+        self.primal.iter().map(|neighbours| neighbours.len()).sum::<usize>() / 2
+    }
+
+    pub fn number_dual_edges(&self) -> usize {
+        self.dual.iter().map(|neighbours| neighbours.len()).sum::<usize>() / 2
+    }
+
+    pub fn primal_degrees(&self) -> DegreeStatistics {
+        DegreeStatistics::from_degrees(self.primal.iter().map(|neighbours| neighbours.len()))
+    }
+
+    pub fn dual_degrees(&self) -> DegreeStatistics {
+        DegreeStatistics::from_degrees(self.dual.iter().map(|neighbours| neighbours.len()))
+    }
+
+    /// Returns the connected components of the primal graph, each in increasing order, sorted by
+    /// their least variable. A variable in the scope of no constraint is a component on its own.
+    pub fn connected_components(&self) -> Vec<Vec<VariableIndex>> {
+        let mut visited = vec![false; self.primal.len()];
+        let mut components = vec![];
+        for start in 0..self.primal.len() {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut component = vec![VariableIndex(start)];
+            let mut next = 0;
+            while next < component.len() {
+                for neighbour in self.primal[component[next].0].iter().copied() {
+                    if !visited[neighbour.0] {
+                        visited[neighbour.0] = true;
+                        component.push(neighbour);
+                    }
+                }
+                next += 1;
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    /// Returns the variables in the order they are eliminated by the min-fill heuristic (the
+    /// variable whose elimination adds the fewest edges between its neighbours, ties being broken
+    /// by the least degree) or the min-degree heuristic, and the width of the elimination, i.e., the
+    /// largest number of neighbours of a variable when it is eliminated. Ties are broken by the
+    /// least index.
+    pub fn elimination_order(&self, min_fill: bool) -> (Vec<VariableIndex>, usize) {
+        let mut neighbours = self.primal.iter().map(|adjacent| adjacent.iter().map(|variable| variable.0).collect::<FxHashSet<usize>>()).collect::<Vec<FxHashSet<usize>>>();
+        let fill = |neighbours: &[FxHashSet<usize>], x: usize| {
+            let adjacent = neighbours[x].iter().copied().collect::<Vec<usize>>();
+            (0..adjacent.len()).map(|i| adjacent[i + 1..].iter().filter(|y| !neighbours[adjacent[i]].contains(y)).count()).sum::<usize>()
+        };
+        let mut remaining = (0..neighbours.len()).collect::<Vec<usize>>();
+        let mut order = vec![];
+        let mut width = 0;
+        while !remaining.is_empty() {
+            let index = (0..remaining.len()).min_by_key(|index| {
+                let x = remaining[*index];
+                let fill = if min_fill { fill(&neighbours, x) } else { 0 };
+                (fill, neighbours[x].len(), x)
+            }).unwrap();
+            let selected = remaining.remove(index);
+            let adjacent = std::mem::take(&mut neighbours[selected]);
+            width = width.max(adjacent.len());
+            for x in adjacent.iter().copied() {
+                neighbours[x].remove(&selected);
+                neighbours[x].extend(adjacent.iter().copied().filter(|y| *y != x));
+            }
+            order.push(VariableIndex(selected));
+        }
+        (order, width)
+    }
+
+    /// Returns the degeneracy of the primal graph, i.e., the largest minimum degree of its
+    /// subgraphs, which is a lower bound on its treewidth. The vertices of least degree are removed
+    /// one by one, without connecting their neighbours.
+    pub fn degeneracy(&self) -> usize {
+        let mut degrees = self.primal.iter().map(|neighbours| neighbours.len()).collect::<Vec<usize>>();
+        let mut removed = vec![false; degrees.len()];
+        let mut degeneracy = 0;
+        for _ in 0..degrees.len() {
+            let selected = (0..degrees.len()).filter(|x| !removed[*x]).min_by_key(|x| degrees[*x]).unwrap();
+            degeneracy = degeneracy.max(degrees[selected]);
+            removed[selected] = true;
+            for neighbour in self.primal[selected].iter() {
+                degrees[neighbour.0] -= 1;
+            }
+        }
+        degeneracy
+    }
+
+    /// Returns a lower and an upper bound on the treewidth of the primal graph, i.e., its
+    /// degeneracy and the width of the min-fill elimination ordering
+    pub fn treewidth_bounds(&self) -> (usize, usize) {
+        (self.degeneracy(), self.elimination_order(true).1)
+    }
+}
+
+#[cfg(test)]
+mod test_analysis {
+
+    use crate::modelling::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::analysis::*;
+
+    #[test]
+    pub fn test_analysis() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[1]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 3);
+        assert!(is_solution(vec![0, 2, 1], &solutions));
+        assert!(!is_solution(vec![1, 0, 2], &solutions));
+        assert_eq!(count_number_solution(&mdd), 3);
+        assert_eq!(count_number_solution(&mdd), mdd.count_solutions());
+        let root_values = node_possible_values(&mdd, NodeIndex(0, 0));
+        let root_variable = mdd.decision_at_layer(0);
+        assert_eq!(root_values, mdd.feasible_values(root_variable));
+        assert!(node_possible_values(&mdd, NodeIndex(mdd.number_layers() - 1, 0)).is_empty());
+    }
+
+    #[test]
+    pub fn test_constraint_graph() {
+        // A cycle of four variables with a chord, and an isolated variable
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2], None);
+        for i in 0..4 {
+            not_equals(&mut problem, xs[i], xs[(i + 1) % 4]);
+        }
+        all_different(&mut problem, vec![xs[0], xs[2]]);
+        let graph = constraint_graph(&problem);
+        assert_eq!(graph.primal_neighbours(xs[0]), &[xs[1], xs[2], xs[3]]);
+        assert_eq!(graph.number_primal_edges(), 5);
+        assert_eq!(graph.primal_degrees(), DegreeStatistics { min: 0, max: 3, mean: 2.0 });
+        // The chord shares a variable with every constraint of the cycle
+        assert_eq!(graph.dual_neighbours(ConstraintIndex(4)), &[ConstraintIndex(0), ConstraintIndex(1), ConstraintIndex(2), ConstraintIndex(3)]);
+        assert_eq!(graph.number_dual_edges(), 8);
+        assert_eq!(graph.connected_components(), vec![xs[0..4].to_vec(), vec![xs[4]]]);
+        assert_eq!(graph.treewidth_bounds(), (2, 2));
+
+        // The treewidth of a clique of n vertices is n - 1
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3, 4], None);
+        all_different(&mut problem, xs);
+        assert_eq!(constraint_graph(&problem).treewidth_bounds(), (4, 4));
+    }
+}
@@ -0,0 +1,84 @@
+use super::*;
+use super::heuristics::*;
+use crate::modelling::*;
+
+// Anytime counting of the solutions of a problem. A relaxed MDD contains every solution, hence its
+// number of paths is an upper bound on the number of solutions, and a restricted MDD (see
+// `Mdd::set_restricted`) only contains solutions, hence its number of paths is a lower bound. Both
+// are compiled in rounds of doubling widths, from 1 to the maximum width, and the bounds are
+// reported after each round. The upper (resp. lower) bound reported is the best one of the rounds so
+// far, hence the bounds are monotone, and the count is exact once they meet.
+
+/// Bounds on the number of solutions after a round of `count_anytime`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CountBounds {
+    /// Width of the MDDs of the round
+    pub width: usize,
+    pub lower: u128,
+    pub upper: u128,
+}
+
+impl CountBounds {
+
+    pub fn is_exact(&self) -> bool {
+        self.lower == self.upper
+    }
+}
+
+/// Bounds the number of solutions of the problem with relaxed and restricted MDDs of doubling
+/// widths, up to the maximum width. The function is called with the bounds after each round, and
+/// the counting stops once the bounds meet or if it returns false. Returns the last bounds.
+pub fn count_anytime(problem: &Problem, order: &OrderingHeuristic, merge_heuristic: &MergeHeuristic, max_width: usize, mut report: impl FnMut(CountBounds) -> bool) -> CountBounds {
+    assert!(max_width > 0, "The width of the MDDs must be positive");
+    let mut bounds = CountBounds { width: 0, lower: 0, upper: u128::MAX };
+    let mut width = 1;
+    loop {
+        let mut relaxed = Mdd::new(problem.clone(), width, order.clone(), merge_heuristic.clone());
+        relaxed.refine();
+        let mut restricted = Mdd::new(problem.clone(), width, order.clone(), merge_heuristic.clone());
+        restricted.set_restricted(true);
+        restricted.refine();
+        bounds = CountBounds {
+            width,
+            lower: bounds.lower.max(restricted.count_solutions()),
+            upper: bounds.upper.min(relaxed.count_solutions()),
+        };
+        if !report(bounds) || bounds.is_exact() || width == max_width {
+            return bounds;
+        }
+        width = width.saturating_mul(2).min(max_width);
+    }
+}
+
+#[cfg(test)]
+mod test_anytime {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_count_anytime() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3, 4], None);
+        all_different(&mut problem, xs[0..4].to_vec());
+        less_than(&mut problem, xs[4], xs[1]);
+        let count = reference::count_solutions(&problem);
+        let mut rounds = vec![];
+        let bounds = count_anytime(&problem, &OrderingHeuristic::MinDomMaxLinked, &MergeHeuristic::LessRelaxed, usize::MAX, |bounds| {
+            rounds.push(bounds);
+            true
+        });
+        assert!(bounds.is_exact());
+        assert_eq!(bounds.lower, count);
+        assert!(rounds.len() > 1);
+        assert!(rounds.iter().all(|bounds| bounds.lower <= count && count <= bounds.upper));
+        assert!(rounds.windows(2).all(|pair| pair[0].lower <= pair[1].lower && pair[1].upper <= pair[0].upper && pair[0].width < pair[1].width));
+
+        // The counting stops at the first round
+        let bounds = count_anytime(&problem, &OrderingHeuristic::MinDomMaxLinked, &MergeHeuristic::LessRelaxed, usize::MAX, |_| false);
+        assert_eq!(bounds.width, 1);
+        assert!(bounds.lower < count && count < bounds.upper);
+    }
+}
@@ -0,0 +1,112 @@
+use super::*;
+use crate::modelling::*;
+use rand::prelude::*;
+
+// Approximate weighted counting on a relaxed MDD. The paths of the MDD are drawn with a probability
+// proportional to their weight (the product of the weights of their edges), hence a drawn path is a
+// solution with probability p = W / R, where W is the weighted count of the solutions and R the
+// weighted count of the MDD. The estimate of W is R times the estimate of p, given by the stopping
+// rule of Dagum, Karp, Luby and Ross ("An optimal algorithm for Monte Carlo estimation", 1995):
+// paths are drawn until Y = 1 + (1 + e) 4 (e - 2) ln(2 / d) / e^2 of them are solutions, and p is
+// estimated as Y over the number of drawn paths. The estimate is within a factor 1 +- e of W with
+// probability at least 1 - d. The expected number of drawn paths is inversely proportional to p,
+// hence the tighter the relaxation, the fewer paths are drawn.
+
+/// Estimate of the weighted count of the solutions (see `Mdd::approximate_count`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ApproximateCount {
+    pub estimate: f64,
+    /// Weighted count of the paths of the MDD, an upper bound on the weighted count of the
+    /// solutions
+    pub relaxed_count: f64,
+    pub number_samples: usize,
+}
+
+impl Mdd {
+
+    /// Returns an estimate of the weighted count of the solutions (see `Mdd::weighted_count`),
+    /// within a factor 1 +- epsilon of the count with probability at least 1 - delta. Returns None
+    /// if the stopping rule is not met within the maximum number of samples, e.g., if the MDD has
+    /// no solution on its paths.
+    pub fn approximate_count(&self, epsilon: f64, delta: f64, max_samples: usize, rng: &mut impl Rng) -> Option<ApproximateCount> {
+        assert!(epsilon > 0.0 && epsilon < 1.0, "The relative error must be in (0, 1)");
+        assert!(delta > 0.0 && delta < 1.0, "The failure probability must be in (0, 1)");
+        if self.is_unsat() {
+            return Some(ApproximateCount { estimate: 0.0, relaxed_count: 0.0, number_samples: 0 });
+        }
+        let weight = |variable: VariableIndex, assignment: ValueIndex| self.assignment_weight(variable, assignment);
+        let backward = self.backward_values(1.0, 0.0, |value, variable, assignment| value * weight(variable, assignment), |a, b| a + b);
+        let relaxed_count = backward[0][0];
+        if relaxed_count == 0.0 {
+            return Some(ApproximateCount { estimate: 0.0, relaxed_count, number_samples: 0 });
+        }
+        let threshold = 1.0 + (1.0 + epsilon) * 4.0 * (std::f64::consts::E - 2.0) * (2.0 / delta).ln() / (epsilon * epsilon);
+        let mut number_solutions = 0.0;
+        for number_samples in 1..=max_samples {
+            let path = self.weighted_path(&backward, rng);
+            if self.is_solution(&path) {
+                number_solutions += 1.0;
+                if number_solutions >= threshold {
+                    let estimate = relaxed_count * threshold / number_samples as f64;
+                    return Some(ApproximateCount { estimate, relaxed_count, number_samples });
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the assignment of a path drawn with a probability proportional to its weight, given
+    /// the weight of the paths from each node to the sink
+    fn weighted_path(&self, backward: &[Vec<f64>], rng: &mut impl Rng) -> Vec<isize> {
+        let mut assignment = vec![0; self.number_layers() - 1];
+        let mut node = NodeIndex(0, 0);
+        for layer in 0..self.number_layers() - 1 {
+            let variable = self.decision_at_layer(layer);
+            let edges = self[node].iter_children().map(|edge| self.edge(edge)).filter(|edge| edge.is_active()).collect::<Vec<Edge>>();
+            let masses = edges.iter().map(|edge| self.assignment_weight(variable, edge.assignment()) * backward[layer + 1][edge.to().1]).collect::<Vec<f64>>();
+            let mut target = rng.random_range(0.0..masses.iter().sum::<f64>());
+            let index = masses.iter().position(|mass| {
+                target -= mass;
+                target < 0.0
+            }).unwrap_or(edges.len() - 1);
+            assignment[variable.0] = self.problem()[variable].value(edges[index].assignment());
+            node = edges[index].to();
+        }
+        assignment
+    }
+}
+
+#[cfg(test)]
+mod test_approximate {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    #[test]
+    pub fn test_approximate_count() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3, 4], None);
+        all_different(&mut problem, xs[0..4].to_vec());
+        less_than(&mut problem, xs[4], xs[1]);
+        let solutions = reference::solutions(&problem);
+        let count = solutions.len() as f64;
+        let mut mdd = Mdd::new(problem, 4, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert!(mdd.count_solutions() as f64 > count);
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let approximate = mdd.approximate_count(0.1, 0.01, 1_000_000, &mut rng).unwrap();
+        assert_eq!(approximate.relaxed_count, mdd.count_solutions() as f64);
+        assert!((approximate.estimate - count).abs() <= 0.1 * count);
+
+        // The weighted count of the solutions with x0 = 0 weighted 3 times more
+        mdd.set_edge_weights(|variable, value| if variable == xs[0] && value == 0 { 3.0 } else { 1.0 });
+        let weighted = count + 2.0 * solutions.iter().filter(|solution| solution[0] == 0).count() as f64;
+        let approximate = mdd.approximate_count(0.1, 0.01, 1_000_000, &mut rng).unwrap();
+        assert!((approximate.estimate - weighted).abs() <= 0.1 * weighted);
+        assert!(mdd.approximate_count(0.1, 0.01, 1, &mut rng).is_none());
+    }
+}
@@ -0,0 +1,157 @@
+use super::*;
+use crate::modelling::*;
+use rustc_hash::FxHashMap;
+
+// Reduced ordered binary decision diagrams, converted from the MDDs of problems whose variables
+// are 0/1. The MDD keeps a node in each layer on its paths, whereas the BDD also removes the nodes
+// whose two edges reach the same node, the variable being free on the paths through them. Each node
+// of the BDD is unique given its variable and children, and the edges without node in the MDD reach
+// the false terminal.
+
+/// Node of a BDD, whose low (resp. high) child is reached when the variable takes the value 0
+/// (resp. 1)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BddNode {
+    pub variable: VariableIndex,
+    pub low: usize,
+    pub high: usize,
+}
+
+/// Reduced ordered BDD, whose nodes are indexed from the terminals `Bdd::FALSE` and `Bdd::TRUE`,
+/// each node being after its children
+#[derive(Clone, Debug)]
+pub struct Bdd {
+    order: Vec<VariableIndex>,
+    /// Nodes of the BDD, the terminals having no node
+    nodes: Vec<BddNode>,
+    /// Layer of the variable of each node, the terminals being after the last layer
+    layers: Vec<usize>,
+    root: usize,
+}
+
+impl Bdd {
+
+    pub const FALSE: usize = 0;
+    pub const TRUE: usize = 1;
+
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    pub fn order(&self) -> &[VariableIndex] {
+        &self.order
+    }
+
+    /// Returns the number of nodes, including the terminals
+    pub fn number_nodes(&self) -> usize {
+        self.nodes.len() + 2
+    }
+
+    /// Returns the node at the index, which must not be a terminal
+    pub fn node(&self, index: usize) -> BddNode {
+        assert!(index > Self::TRUE, "The terminals have no variable");
+        self.nodes[index - 2]
+    }
+
+    /// Returns true if the assignment, indexed by the variables, is accepted by the BDD
+    pub fn contains(&self, assignment: &[bool]) -> bool {
+        let mut index = self.root;
+        while index > Self::TRUE {
+            let node = self.node(index);
+            index = if assignment[node.variable.0] { node.high } else { node.low };
+        }
+        index == Self::TRUE
+    }
+
+    /// Returns the number of assignments of the variables accepted by the BDD, saturating at
+    /// `u128::MAX`
+    pub fn count_solutions(&self) -> u128 {
+        let layer = |index: usize| if index > Self::TRUE { self.layers[index - 2] } else { self.order.len() };
+        let free = |from: usize, to: usize| 2u128.saturating_pow((layer(to) - from) as u32);
+        let mut counts = vec![0u128, 1u128];
+        for (node, node_layer) in self.nodes.iter().zip(self.layers.iter().copied()) {
+            let count = counts[node.low].saturating_mul(free(node_layer + 1, node.low)).saturating_add(counts[node.high].saturating_mul(free(node_layer + 1, node.high)));
+            counts.push(count);
+        }
+        counts[self.root].saturating_mul(free(0, self.root))
+    }
+}
+
+impl Mdd {
+
+    /// Returns the reduced ordered BDD of the paths of the MDD, with the same variable ordering.
+    /// Panics if a variable has a value other than 0 and 1.
+    pub fn to_bdd(&self) -> Bdd {
+        assert!(self.problem().iter_variables().all(|variable| self.problem()[variable].iter_domain().all(|value| value == 0 || value == 1)), "The variables of the BDD must be 0/1");
+        let order = (0..self.number_layers() - 1).map(|layer| self.decision_at_layer(layer)).collect::<Vec<VariableIndex>>();
+        let mut bdd = Bdd { order, nodes: vec![], layers: vec![], root: Bdd::FALSE };
+        if self.is_unsat() {
+            return bdd;
+        }
+        let children = self.path_children();
+        let mut unique = FxHashMap::<BddNode, usize>::default();
+        let mut ids = vec![Bdd::TRUE];
+        for layer in (0..self.number_layers() - 1).rev() {
+            let variable = self.decision_at_layer(layer);
+            ids = children[layer].iter().map(|node_children| {
+                let mut node = BddNode { variable, low: Bdd::FALSE, high: Bdd::FALSE };
+                for (to, assignment) in node_children.iter().copied() {
+                    if self.problem()[variable].value(assignment) == 0 {
+                        node.low = ids[to];
+                    } else {
+                        node.high = ids[to];
+                    }
+                }
+                if node.low == node.high {
+                    return node.low;
+                }
+                *unique.entry(node).or_insert_with(|| {
+                    bdd.nodes.push(node);
+                    bdd.layers.push(layer);
+                    bdd.nodes.len() + 1
+                })
+            }).collect();
+        }
+        bdd.root = ids[0];
+        bdd
+    }
+}
+
+#[cfg(test)]
+mod test_bdd {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_to_bdd() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1], None);
+        clause(&mut problem, vec![xs[0], xs[1]], vec![]);
+        at_most_k(&mut problem, xs[1..4].to_vec(), 1);
+        let solutions = reference::solutions(&problem);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let bdd = mdd.to_bdd();
+        assert_eq!(bdd.count_solutions(), solutions.len() as u128);
+        for bits in 0..(1 << 5) {
+            let assignment = (0..5).map(|variable| (bits >> variable) & 1 == 1).collect::<Vec<bool>>();
+            let values = assignment.iter().map(|value| *value as isize).collect::<Vec<isize>>();
+            assert_eq!(bdd.contains(&assignment), solutions.contains(&values));
+        }
+        // The last variable is free, hence it has no node
+        assert!((2..bdd.number_nodes()).all(|index| bdd.node(index).variable != xs[4]));
+    }
+
+    #[test]
+    #[should_panic(expected = "The variables of the BDD must be 0/1")]
+    pub fn test_to_bdd_non_binary() {
+        let mut problem = Problem::default();
+        problem.add_variables(2, vec![0, 1, 2], None);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        mdd.to_bdd();
+    }
+}
@@ -0,0 +1,242 @@
+use super::*;
+use super::heuristics::*;
+use crate::modelling::*;
+use crate::utils::map_items;
+
+// Compilation of a problem by components. Two variables are connected if they are in the scope of a
+// common constraint, and the connected components of the variables partition the constraints as
+// well. The components are independent, hence each of them is compiled into its own MDD, on the
+// sub-problem of its variables and constraints (see `Problem::sub_problem`), in parallel with the
+// `parallel` feature. The sub-problem is built from the descriptions of the constraints, hence a
+// component with a constraint that can not be described (e.g., defined by closures) is compiled on
+// the whole problem instead, with the constraints of the other components retracted and their
+// variables fixed to a value of cost 0, which leaves a single edge in their layers. The variables
+// in the scope of no constraint are compiled together in a last component. The solutions of the
+// problem are the cross-product of the solutions of the components: their counts multiply and their
+// minimum costs add up. The sum of the sizes of the MDDs of the components can be exponentially
+// smaller than the size of the MDD of the problem, whose layers of different components are not
+// independent once the width is bounded.
+
+/// MDD of a component of the problem, on the sub-problem of its variables
+pub struct Component {
+    /// Variables of the problem in the component
+    pub variables: Vec<VariableIndex>,
+    /// Variable of the MDD of each variable of the component
+    pub mdd_variables: Vec<VariableIndex>,
+    pub mdd: Mdd,
+}
+
+impl Component {
+
+    /// Compiles the MDD of the component, on its sub-problem if its constraints can be described,
+    /// and on the whole problem otherwise
+    fn compile(problem: &Problem, variables: &[VariableIndex], constraints: &[ConstraintIndex], max_width: usize, order: &OrderingHeuristic, merge_heuristic: &MergeHeuristic) -> Self {
+        let (component_problem, mdd_variables) = match problem.sub_problem(variables, constraints) {
+            Some(sub_problem) => (sub_problem, (0..variables.len()).map(VariableIndex).collect()),
+            None => {
+                let mut whole = problem.clone();
+                for constraint in problem.iter_constraints().filter(|constraint| constraints.binary_search(constraint).is_err()) {
+                    whole.retract_constraint(constraint);
+                }
+                for variable in problem.iter_variables().filter(|variable| variables.binary_search(variable).is_err()) {
+                    if let Some(value) = problem[variable].iter_domain().next() {
+                        whole.fix(variable, value);
+                        whole[variable].set_costs(&[0]);
+                    }
+                }
+                (whole, variables.to_vec())
+            },
+        };
+        let mut mdd = Mdd::new(component_problem, max_width, order.clone(), merge_heuristic.clone());
+        mdd.refine();
+        Self { variables: variables.to_vec(), mdd_variables, mdd }
+    }
+
+    /// Writes the values of the variables of the component, from the assignment of the variables
+    /// of its MDD, into the solution of the problem
+    fn write_assignment(&self, assignment: &[isize], solution: &mut [isize]) {
+        for (variable, mdd_variable) in self.variables.iter().zip(self.mdd_variables.iter()) {
+            solution[variable.0] = assignment[mdd_variable.0];
+        }
+    }
+}
+
+/// Problem compiled by connected components (see `ComposedMdd::compile`)
+pub struct ComposedMdd {
+    components: Vec<Component>,
+    number_variables: usize,
+}
+
+/// Returns the connected components of the variables of the problem in the scope of at least one
+/// constraint, two variables being connected if a constraint has both in its scope, and the
+/// constraints of each component
+pub(crate) fn connected_components(problem: &Problem) -> Vec<(Vec<VariableIndex>, Vec<ConstraintIndex>)> {
+    let mut visited_variables = vec![false; problem.number_variables()];
+    let mut visited_constraints = vec![false; problem.number_constraints()];
+    let mut components = vec![];
+    for start in problem.iter_variables().filter(|variable| problem[*variable].number_constraints() > 0) {
+        if visited_variables[start.0] {
+            continue;
+        }
+        visited_variables[start.0] = true;
+        let mut variables = vec![start];
+        let mut constraints = vec![];
+        let mut next = 0;
+        while next < variables.len() {
+            let variable = variables[next];
+            next += 1;
+            for constraint in problem[variable].iter_constraints() {
+                if !visited_constraints[constraint.0] {
+                    visited_constraints[constraint.0] = true;
+                    constraints.push(constraint);
+                    for other in problem[constraint].iter_scope() {
+                        if !visited_variables[other.0] {
+                            visited_variables[other.0] = true;
+                            variables.push(other);
+                        }
+                    }
+                }
+            }
+        }
+        variables.sort();
+        constraints.sort();
+        components.push((variables, constraints));
+    }
+    components
+}
+
+impl ComposedMdd {
+
+    /// Compiles the MDD of each connected component of the problem with the given parameters, and
+    /// refines it
+    pub fn compile(problem: &Problem, max_width: usize, order: OrderingHeuristic, merge_heuristic: MergeHeuristic) -> Self {
+        let mut partition = connected_components(problem);
+        let unconstrained = problem.iter_variables().filter(|variable| problem[*variable].number_constraints() == 0).collect::<Vec<VariableIndex>>();
+        if !unconstrained.is_empty() {
+            partition.push((unconstrained, vec![]));
+        }
+        let components = map_items(&partition, |_, (variables, constraints)| Component::compile(problem, variables, constraints, max_width, &order, &merge_heuristic));
+        Self { components, number_variables: problem.number_variables() }
+    }
+
+    pub fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    pub fn is_unsat(&self) -> bool {
+        self.components.iter().any(|component| component.mdd.is_unsat())
+    }
+
+    /// Returns the number of solutions of the problem, i.e., the product of the numbers of paths of
+    /// the MDDs of the components. It is exact if the MDDs are exact and an upper bound otherwise,
+    /// and saturates at `u128::MAX`.
+    pub fn count_solutions(&self) -> u128 {
+        if self.is_unsat() {
+            return 0;
+        }
+        self.components.iter().fold(1, |count, component| count.saturating_mul(component.mdd.count_solutions()))
+    }
+
+    /// Returns the total number of nodes of the MDDs of the components
+    pub fn number_nodes(&self) -> usize {
+        self.components.iter().map(|component| component.mdd.number_nodes()).sum()
+    }
+
+    /// Returns the solutions of the problem, as the cross-product of the paths of the MDDs of the
+    /// components (see `get_all_solutions`). The values are indexed by variable of the problem.
+    pub fn get_all_solutions(&self) -> Vec<Vec<isize>> {
+        let mut solutions = vec![vec![0; self.number_variables]];
+        for component in self.components.iter() {
+            let paths = analysis::get_all_solutions(&component.mdd);
+            solutions = solutions.iter().flat_map(|solution| paths.iter().map(|path| {
+                let mut solution = solution.clone();
+                component.write_assignment(path, &mut solution);
+                solution
+            })).collect();
+        }
+        solutions
+    }
+
+    /// Returns the minimum total cost of a solution, and the solution, from the minimum cost
+    /// solution of each component (see `Mdd::minimum_cost_solution`)
+    pub fn minimum_cost_solution(&self) -> Option<(isize, Vec<isize>)> {
+        let mut cost = 0;
+        let mut solution = vec![0; self.number_variables];
+        for component in self.components.iter() {
+            let (component_cost, assignment) = component.mdd.minimum_cost_solution()?;
+            cost += component_cost;
+            component.write_assignment(&assignment, &mut solution);
+        }
+        Some((cost, solution))
+    }
+}
+
+#[cfg(test)]
+mod test_components {
+
+    use crate::constraints::FunctionalConstraint;
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+    use std::sync::Arc;
+
+    #[test]
+    pub fn test_composed_mdd() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(7, vec![0, 1, 2], None);
+        all_different(&mut problem, xs[0..3].to_vec());
+        less_than(&mut problem, xs[3], xs[4]);
+        not_equals(&mut problem, xs[4], xs[5]);
+        problem[xs[5]].set_costs(&[3, 1, 2]);
+        let composed = ComposedMdd::compile(&problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        // {x0, x1, x2}, {x3, x4, x5} and the unconstrained x6
+        assert_eq!(composed.components().len(), 3);
+        assert_eq!(composed.components()[1].variables, xs[3..6].to_vec());
+        let mut solutions = reference::solutions(&problem);
+        assert_eq!(composed.count_solutions(), solutions.len() as u128);
+        let mut composed_solutions = composed.get_all_solutions();
+        composed_solutions.sort();
+        solutions.sort();
+        assert_eq!(composed_solutions, solutions);
+        let (cost, solution) = composed.minimum_cost_solution().unwrap();
+        assert_eq!(cost, 1);
+        assert!(problem.check(&solution).is_ok());
+
+        not_equals(&mut problem, xs[3], xs[6]);
+        less_than(&mut problem, xs[6], xs[3]);
+        less_than(&mut problem, xs[5], xs[6]);
+        less_than(&mut problem, xs[5], xs[3]);
+        let composed = ComposedMdd::compile(&problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        assert_eq!(composed.components().len(), 2);
+        assert!(composed.is_unsat());
+        assert_eq!(composed.count_solutions(), 0);
+        assert!(composed.get_all_solutions().is_empty());
+    }
+
+    #[test]
+    pub fn test_composed_mdd_closure_constraint() {
+        // The component {x0, x1, x2} has a constraint defined by closures, sum(x0, x1, x2) <= 2
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2], None);
+        problem.add_constraint(FunctionalConstraint::new(
+            xs[0..3].to_vec(),
+            0,
+            Arc::new(|sum: &isize, _, value| Some(sum + value).filter(|sum| *sum <= 2)),
+            Arc::new(|a: &isize, b: &isize| *a.min(b)),
+            Arc::new(|sum: &isize| *sum <= 2),
+        ));
+        not_equals(&mut problem, xs[3], xs[4]);
+        problem[xs[4]].set_costs(&[2, 1, 3]);
+        let composed = ComposedMdd::compile(&problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        assert_eq!(composed.components().len(), 2);
+        assert_eq!(composed.components()[0].mdd_variables, xs[0..3].to_vec());
+        let mut solutions = reference::solutions(&problem);
+        assert_eq!(composed.count_solutions(), solutions.len() as u128);
+        let mut composed_solutions = composed.get_all_solutions();
+        composed_solutions.sort();
+        solutions.sort();
+        assert_eq!(composed_solutions, solutions);
+        assert_eq!(composed.minimum_cost_solution().unwrap().0, 1);
+    }
+}
@@ -0,0 +1,447 @@
+use super::*;
+use crate::modelling::*;
+use rand::prelude::*;
+use rand_xoshiro::Xoshiro256Plus;
+
+// Depth-first search over a compiled MDD, used as the constraint store of a constraint programming
+// solver. Each node of the search branches on a variable with more than one value left in the MDD
+// (by default, the first one in the order of the layers): each of its values is tried in turn by saving the state of
+// the MDD (see `Mdd::push_state`), conditioning it on the value, which propagates the constraints
+// on the MDD, and restoring the state once the subtree is explored. A branch fails as soon as the
+// MDD is unsatisfiable. The MDD is not refined during the search, hence it can be a relaxation of
+// any width: the search is complete since every variable is eventually fixed, and the only path
+// left is then checked against the constraints.
+//
+// When minimizing the cost of the solutions, the cost of the shortest path of the relaxed MDD is a
+// lower bound on the cost of the solutions of the subtree, which is pruned when the bound is not
+// better than the best solution found. If the shortest path is a solution, it is the best solution
+// of the subtree and the subtree is not explored further.
+//
+// The search for one solution, or for a solution of minimum cost, can be restarted: each run of the
+// search is stopped once its number of failures reaches a limit given by the restart strategy, and
+// the next run starts again from the root with a larger limit, hence the search stays complete.
+// The best solution found is kept across the runs to bound the next ones. The restarts are useful
+// with heuristics that learn from the previous runs or are randomized. The values of the branching
+// variable are tried in the order of its domain, or in a random order given by a seed. The
+// variable is either the first one in the order of the layers, or the one with the largest
+// activity per value left, the activity of a variable being the sum of the activities of its
+// constraints. The activity of a constraint is incremented for each edge it removes after a
+// decision, and every activity decays after each failure, so that the constraints pruning the
+// MDD recently dominate the choice.
+
+/// Statistics of a depth-first search over an MDD
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStatistics {
+    /// Number of nodes of the search tree
+    pub nodes: usize,
+    /// Number of nodes whose MDD is unsatisfiable, or pruned by the bound
+    pub failures: usize,
+    /// Number of solutions found
+    pub solutions: usize,
+    /// Number of restarts of the search
+    pub restarts: usize,
+}
+
+/// Limits on the number of failures of the successive runs of a search
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestartStrategy {
+    /// The search is never restarted
+    Never,
+    /// The limit of the i-th run is the scale times the i-th term of the Luby sequence
+    /// (1, 1, 2, 1, 1, 2, 4, ...)
+    Luby { scale: usize },
+    /// The limit of the i-th run is the initial limit times the factor to the power i. The factor
+    /// must be larger than 1.
+    Geometric { initial: usize, factor: f64 },
+}
+
+impl RestartStrategy {
+
+    /// Returns the limit on the number of failures of the given run, starting at 0
+    fn limit(&self, run: usize) -> Option<usize> {
+        match *self {
+            RestartStrategy::Never => None,
+            RestartStrategy::Luby { scale } => Some(scale.max(1) * luby(run + 1)),
+            RestartStrategy::Geometric { initial, factor } => {
+                assert!(factor > 1.0, "The factor of the geometric restarts must be larger than 1");
+                Some(((initial.max(1) as f64) * factor.powi(run as i32)) as usize)
+            },
+        }
+    }
+}
+
+/// Returns the i-th term of the Luby sequence, starting at 1
+fn luby(mut i: usize) -> usize {
+    loop {
+        let mut k = 1;
+        while (1 << k) - 1 < i {
+            k += 1;
+        }
+        if (1 << k) - 1 == i {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+/// Selection of the variable to branch on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VariableSelection {
+    /// The first variable, in the order of the layers, with more than one value left
+    FirstLayer,
+    /// The variable with the largest activity per value left, the activities being multiplied by
+    /// the decay, in ]0, 1], after each failure
+    Activity { decay: f64 },
+}
+
+/// Order of the values of the branching variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueSelection {
+    /// The order of the domain of the variable
+    Domain,
+    /// A random order
+    Random,
+}
+
+/// Parameters of a depth-first search over an MDD
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchOptions {
+    pub variable_selection: VariableSelection,
+    pub value_selection: ValueSelection,
+    pub restarts: RestartStrategy,
+    /// Seed of the random choices of the search
+    pub seed: u64,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            variable_selection: VariableSelection::FirstLayer,
+            value_selection: ValueSelection::Domain,
+            restarts: RestartStrategy::Never,
+            seed: 0,
+        }
+    }
+}
+
+/// State of a search, kept across its runs
+struct Search {
+    options: SearchOptions,
+    rng: Xoshiro256Plus,
+    /// Activity of each constraint of the problem, the constraints added by the decisions excluded
+    activity: Vec<f64>,
+    run: usize,
+    limit: Option<usize>,
+    run_failures: usize,
+    interrupted: bool,
+    statistics: SearchStatistics,
+}
+
+impl Search {
+
+    fn new(mdd: &Mdd, options: &SearchOptions) -> Self {
+        if let VariableSelection::Activity { decay } = options.variable_selection {
+            assert!(decay > 0.0 && decay <= 1.0, "The decay of the activities must be in ]0, 1]");
+        }
+        Self {
+            options: *options,
+            rng: Xoshiro256Plus::seed_from_u64(options.seed),
+            activity: vec![0.0; mdd.problem().number_constraints()],
+            run: 0,
+            limit: None,
+            run_failures: 0,
+            interrupted: false,
+            statistics: SearchStatistics::default(),
+        }
+    }
+
+    /// Starts the next run of the search
+    fn start_run(&mut self) {
+        if self.run > 0 {
+            self.statistics.restarts += 1;
+        }
+        self.limit = self.options.restarts.limit(self.run);
+        self.run += 1;
+        self.run_failures = 0;
+        self.interrupted = false;
+    }
+
+    /// Records a failure. Returns false if the run must be interrupted.
+    fn fail(&mut self) -> bool {
+        self.statistics.failures += 1;
+        self.run_failures += 1;
+        if let VariableSelection::Activity { decay } = self.options.variable_selection {
+            for activity in self.activity.iter_mut() {
+                *activity *= decay;
+            }
+        }
+        if self.limit.is_some_and(|limit| self.run_failures >= limit) {
+            self.interrupted = true;
+        }
+        !self.interrupted
+    }
+
+    /// Increments the activity of each constraint by the number of edges it removed since the
+    /// given numbers of removed edges
+    fn bump(&mut self, removed: &[usize], statistics: &[PropagationStatistics]) {
+        for (constraint, activity) in self.activity.iter_mut().enumerate() {
+            if let Some(statistics) = statistics.get(constraint) {
+                *activity += (statistics.removed_edges - removed.get(constraint).copied().unwrap_or(0)) as f64;
+            }
+        }
+    }
+
+    /// Orders the values of the branching variable
+    fn order_values(&mut self, values: &mut [isize]) {
+        if self.options.value_selection == ValueSelection::Random {
+            values.shuffle(&mut self.rng);
+        }
+    }
+}
+
+impl Mdd {
+
+    /// Enumerates the solutions of the MDD by a depth-first search, calling the given function on
+    /// each of them until it returns false. The MDD is restored once the search ends.
+    pub fn search_solutions(&mut self, mut on_solution: impl FnMut(&[isize]) -> bool) -> SearchStatistics {
+        let mut search = Search::new(self, &SearchOptions::default());
+        search.start_run();
+        self.search_solutions_from(&mut search, &mut on_solution);
+        search.statistics
+    }
+
+    /// Returns a solution of the MDD found by a depth-first search with the given options, or None
+    /// if there is no solution. The MDD is restored once the search ends.
+    pub fn search_solution(&mut self, options: &SearchOptions) -> (Option<Vec<isize>>, SearchStatistics) {
+        let mut search = Search::new(self, options);
+        let mut solution = None;
+        loop {
+            search.start_run();
+            self.search_solutions_from(&mut search, &mut |assignment| {
+                solution = Some(assignment.to_vec());
+                false
+            });
+            if solution.is_some() || !search.interrupted {
+                return (solution, search.statistics);
+            }
+        }
+    }
+
+    /// Returns a solution of minimum cost (see `Variable::cost`) with its cost, found by a
+    /// depth-first search bounded by the relaxed MDD, or None if there is no solution. The MDD is
+    /// restored once the search ends.
+    pub fn search_minimum_cost(&mut self) -> (Option<(isize, Vec<isize>)>, SearchStatistics) {
+        self.search_minimum_cost_with(&SearchOptions::default())
+    }
+
+    /// Same as `Mdd::search_minimum_cost`, with the given options of the search
+    pub fn search_minimum_cost_with(&mut self, options: &SearchOptions) -> (Option<(isize, Vec<isize>)>, SearchStatistics) {
+        let mut search = Search::new(self, options);
+        let mut best = None;
+        loop {
+            search.start_run();
+            self.search_minimum_from(&mut search, &mut best);
+            if !search.interrupted {
+                return (best, search.statistics);
+            }
+        }
+    }
+
+    /// Explores the subtree of the current state of the MDD. Returns false once the search must stop.
+    fn search_solutions_from(&mut self, search: &mut Search, on_solution: &mut impl FnMut(&[isize]) -> bool) -> bool {
+        search.statistics.nodes += 1;
+        if self.is_unsat() {
+            return search.fail();
+        }
+        match self.branching_variable(search) {
+            None => {
+                let assignment = self.problem().iter_variables().map(|variable| self.feasible_values(variable)[0]).collect::<Vec<isize>>();
+                if !self.is_solution(&assignment) {
+                    return search.fail();
+                }
+                search.statistics.solutions += 1;
+                on_solution(&assignment)
+            },
+            Some((variable, values)) => {
+                for value in values {
+                    self.push_state();
+                    self.decide(search, variable, value);
+                    let proceed = self.search_solutions_from(search, on_solution);
+                    self.pop_state();
+                    if !proceed {
+                        return false;
+                    }
+                }
+                true
+            },
+        }
+    }
+
+    /// Explores the subtree of the current state of the MDD, updating the best solution found.
+    /// Returns false once the run is interrupted.
+    fn search_minimum_from(&mut self, search: &mut Search, best: &mut Option<(isize, Vec<isize>)>) -> bool {
+        search.statistics.nodes += 1;
+        let Some((bound, path)) = self.minimum_cost_solution() else {
+            return search.fail();
+        };
+        if best.as_ref().is_some_and(|(cost, _)| bound >= *cost) {
+            return search.fail();
+        }
+        if self.is_solution(&path) {
+            search.statistics.solutions += 1;
+            *best = Some((bound, path));
+            return true;
+        }
+        let Some((variable, values)) = self.branching_variable(search) else {
+            // The only path left is not a solution
+            return search.fail();
+        };
+        for value in values {
+            self.push_state();
+            self.decide(search, variable, value);
+            let proceed = self.search_minimum_from(search, best);
+            self.pop_state();
+            if !proceed {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Conditions the MDD on the variable taking the value, and updates the activities of the
+    /// constraints removing edges
+    fn decide(&mut self, search: &mut Search, variable: VariableIndex, value: isize) {
+        let removed = self.propagation_statistics().iter().map(|statistics| statistics.removed_edges).collect::<Vec<usize>>();
+        self.condition(variable, value);
+        search.bump(&removed, self.propagation_statistics());
+    }
+
+    /// Returns the variable to branch on, among the ones with more than one value left in the MDD,
+    /// and its values in the order they must be tried
+    fn branching_variable(&self, search: &mut Search) -> Option<(VariableIndex, Vec<isize>)> {
+        let candidates = (0..self.number_layers() - 1).map(|layer| self.decision_at_layer(layer)).map(|variable| (variable, self.feasible_values(variable))).filter(|(_, values)| values.len() > 1);
+        let (variable, mut values) = match search.options.variable_selection {
+            VariableSelection::FirstLayer => candidates.into_iter().next()?,
+            VariableSelection::Activity { .. } => {
+                let score = |(variable, values): &(VariableIndex, Vec<isize>)| {
+                    let activity = self.problem()[*variable].iter_constraints().filter(|constraint| constraint.0 < search.activity.len()).map(|constraint| search.activity[constraint.0]).sum::<f64>();
+                    activity / values.len() as f64
+                };
+                // The first variable of the largest score is kept on ties
+                candidates.fold(None, |best: Option<((VariableIndex, Vec<isize>), f64)>, candidate| {
+                    let candidate_score = score(&candidate);
+                    match best {
+                        Some((_, best_score)) if best_score >= candidate_score => best,
+                        _ => Some((candidate, candidate_score)),
+                    }
+                })?.0
+            },
+        };
+        search.order_values(&mut values);
+        Some((variable, values))
+    }
+}
+
+#[cfg(test)]
+mod test_dfs {
+
+    use crate::fuzz::*;
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::dfs::luby;
+    use crate::models::n_queens;
+    use crate::reference;
+
+    #[test]
+    pub fn test_search_solutions() {
+        let config = FuzzConfig { min_variables: 4, max_constraints: 5, ..FuzzConfig::default() };
+        for seed in 0..30 {
+            let problem = random_problem(seed, &config);
+            let mut expected = reference::solutions(&problem);
+            expected.sort();
+            for width in [1, 2] {
+                let mut mdd = Mdd::new(problem.clone(), width, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+                mdd.refine();
+                let mut found = vec![];
+                let statistics = mdd.search_solutions(|solution| {
+                    found.push(solution.to_vec());
+                    true
+                });
+                found.sort();
+                assert_eq!(found, expected, "Seed {} with width {}", seed, width);
+                assert_eq!(statistics.solutions, expected.len());
+                assert_eq!(mdd.number_saved_states(), 0);
+
+                // The search stops with the first solution
+                let statistics = mdd.search_solutions(|_| false);
+                assert_eq!(statistics.solutions, expected.len().min(1));
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_luby() {
+        assert_eq!((1..16).map(luby).collect::<Vec<usize>>(), vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    /// Returns the options of the search used by the tests, each one with restarts
+    fn restarted_options(seed: u64) -> Vec<SearchOptions> {
+        vec![
+            SearchOptions { restarts: RestartStrategy::Luby { scale: 1 }, value_selection: ValueSelection::Random, seed, ..SearchOptions::default() },
+            SearchOptions { restarts: RestartStrategy::Geometric { initial: 2, factor: 1.5 }, variable_selection: VariableSelection::Activity { decay: 0.9 }, seed, ..SearchOptions::default() },
+            SearchOptions { restarts: RestartStrategy::Luby { scale: 2 }, variable_selection: VariableSelection::Activity { decay: 1.0 }, value_selection: ValueSelection::Random, seed },
+        ]
+    }
+
+    #[test]
+    pub fn test_search_solution() {
+        let config = FuzzConfig { min_variables: 4, max_constraints: 5, ..FuzzConfig::default() };
+        for seed in 0..30 {
+            let problem = random_problem(seed, &config);
+            let satisfiable = !reference::solutions(&problem).is_empty();
+            let mut mdd = Mdd::new(problem.clone(), 1, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            for options in restarted_options(seed) {
+                let (solution, _) = mdd.search_solution(&options);
+                assert_eq!(solution.is_some(), satisfiable, "Seed {} with {:?}", seed, options);
+                if let Some(solution) = solution {
+                    assert_eq!(problem.check(&solution), Ok(()));
+                }
+                assert_eq!(mdd.number_saved_states(), 0);
+            }
+        }
+
+        // The random choices depend only on the seed
+        let mut mdd = Mdd::new(n_queens(8), 1, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        let options = restarted_options(7)[2];
+        let (solution, statistics) = mdd.search_solution(&options);
+        assert_eq!(n_queens(8).check(solution.as_ref().unwrap()), Ok(()));
+        assert!(statistics.restarts > 0);
+        assert_eq!(mdd.search_solution(&options), (solution, statistics));
+    }
+
+    #[test]
+    pub fn test_search_minimum_cost() {
+        let config = FuzzConfig { min_variables: 4, max_constraints: 5, ..FuzzConfig::default() };
+        for seed in 0..30 {
+            let mut problem = random_problem(seed, &config);
+            for variable in problem.iter_variables().collect::<Vec<VariableIndex>>() {
+                let costs = (0..problem[variable].domain_size()).map(|index| ((index * 5 + variable.0 * 3 + seed as usize) % 7) as isize).collect::<Vec<isize>>();
+                problem[variable].set_costs(&costs);
+            }
+            let expected = minimize(&problem, usize::MAX, SearchStrategy::BranchAndBound).map(|(cost, _)| cost);
+            let mut mdd = Mdd::new(problem.clone(), 2, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            let (result, _) = mdd.search_minimum_cost();
+            assert_eq!(result.as_ref().map(|(cost, _)| *cost), expected, "Seed {}", seed);
+            if let Some((_, solution)) = result {
+                assert_eq!(problem.check(&solution), Ok(()));
+            }
+            for options in restarted_options(seed) {
+                let (result, _) = mdd.search_minimum_cost_with(&options);
+                assert_eq!(result.map(|(cost, _)| cost), expected, "Seed {} with {:?}", seed, options);
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use super::*;
 use crate::modelling::*;
+use crate::utils::{Bitset, SparseSet};
 use rustc_hash::FxHashMap;
 
-#[derive(Clone)]
+/// Edge of the MDD, as read from the edges of its layer (see `EdgeLayer`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Edge {
     from: NodeIndex,
     to: NodeIndex,
@@ -11,46 +13,278 @@ pub struct Edge {
 }
 
 impl Edge {
-    
-    pub fn new(from: NodeIndex, to: NodeIndex, assignment: ValueIndex) -> Self {
-        Self {
-            from,
-            to,
-            assignment,
-            active: true,
-        }
-    }
 
     pub fn from(&self) -> NodeIndex {
         self.from
     }
 
-    pub fn set_from(&mut self, from: NodeIndex) {
-        self.from = from;
-    }
-
     pub fn to(&self) -> NodeIndex {
         self.to
     }
 
-    pub fn set_to(&mut self, to: NodeIndex) {
-        self.to = to;
-    }
-
     pub fn assignment(&self) -> ValueIndex {
         self.assignment
     }
 
-    pub fn deactivate(&mut self) {
-        self.active = false;
-    }
-
     pub fn is_active(&self) -> bool {
         self.active
     }
+}
+
+/// Edges of a layer of the MDD, stored as parallel arrays with a packed bitmap of the active
+/// edges. The passes over a layer (propagation, counting) read the arrays contiguously instead of
+/// following the edges of each node. The slots of the deactivated edges are reused by the next
+/// added edges, hence an edge must be removed from the edges of its nodes before being
+/// deactivated. The number of active edges assigning each value is maintained with the edges, and
+/// the values assigned by at least one of them are kept in a sparse set.
+#[derive(Clone, Default)]
+pub struct EdgeLayer {
+    from: Vec<NodeIndex>,
+    to: Vec<NodeIndex>,
+    assignment: Vec<ValueIndex>,
+    active: Bitset,
+    /// Slots of the deactivated edges
+    free: Vec<usize>,
+    /// Have the edges changed since the path counts of the nodes were updated
+    changed: bool,
+    /// Number of active edges assigning each value
+    supports: Vec<usize>,
+    /// Values assigned by at least one active edge
+    supported: SparseSet,
+}
+
+impl EdgeLayer {
+
+    /// Adds an active edge to the layer, in the slot of a deactivated edge if any, and returns its
+    /// index
+    pub fn push(&mut self, from: NodeIndex, to: NodeIndex, assignment: ValueIndex) -> usize {
+        self.changed = true;
+        self.support(assignment);
+        if let Some(index) = self.free.pop() {
+            self.from[index] = from;
+            self.to[index] = to;
+            self.assignment[index] = assignment;
+            self.active.insert(index);
+            return index;
+        }
+        let index = self.from.len();
+        self.from.push(from);
+        self.to.push(to);
+        self.assignment.push(assignment);
+        self.active.grow(index + 1);
+        self.active.insert(index);
+        index
+    }
+
+    /// Returns the number of edges in the layer, active or not
+    pub fn len(&self) -> usize {
+        self.from.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.from.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Edge {
+        Edge {
+            from: self.from[index],
+            to: self.to[index],
+            assignment: self.assignment[index],
+            active: self.is_active(index),
+        }
+    }
+
+    pub fn from(&self, index: usize) -> NodeIndex {
+        self.from[index]
+    }
+
+    pub fn set_from(&mut self, index: usize, from: NodeIndex) {
+        self.changed = true;
+        self.from[index] = from;
+    }
+
+    pub fn to(&self, index: usize) -> NodeIndex {
+        self.to[index]
+    }
+
+    pub fn set_to(&mut self, index: usize, to: NodeIndex) {
+        self.changed = true;
+        self.to[index] = to;
+    }
+
+    pub fn assignment(&self, index: usize) -> ValueIndex {
+        self.assignment[index]
+    }
+
+    pub fn is_active(&self, index: usize) -> bool {
+        self.active.contains(index)
+    }
+
+    /// Returns the number of active edges of the layer
+    pub fn number_active(&self) -> usize {
+        self.active.size()
+    }
+
+    pub fn deactivate(&mut self, index: usize) {
+        if self.active.contains(index) {
+            self.active.remove(index);
+            self.free.push(index);
+            self.changed = true;
+            self.unsupport(self.assignment[index]);
+        }
+    }
+
+    /// Activates again a deactivated edge whose slot has not been reused
+    pub fn restore(&mut self, index: usize) {
+        if !self.active.contains(index) {
+            self.active.insert(index);
+            if let Some(position) = self.free.iter().rposition(|free| *free == index) {
+                self.free.swap_remove(position);
+            }
+            self.changed = true;
+            self.support(self.assignment[index]);
+        }
+    }
+
+    /// Returns the number of active edges assigning the value
+    pub fn number_supports(&self, assignment: ValueIndex) -> usize {
+        self.supports.get(assignment.0).copied().unwrap_or(0)
+    }
+
+    /// Returns the values assigned by at least one active edge, in no particular order
+    pub fn supported_values(&self) -> &SparseSet {
+        &self.supported
+    }
+
+    fn support(&mut self, assignment: ValueIndex) {
+        if self.supports.len() <= assignment.0 {
+            self.supports.resize(assignment.0 + 1, 0);
+            self.supported.grow(assignment.0 + 1);
+        }
+        self.supports[assignment.0] += 1;
+        self.supported.insert(assignment.0);
+    }
+
+    fn unsupport(&mut self, assignment: ValueIndex) {
+        self.supports[assignment.0] -= 1;
+        if self.supports[assignment.0] == 0 {
+            self.supported.remove(assignment.0);
+        }
+    }
+
+    /// Returns true if edges have been added, removed or moved since the last call to
+    /// `EdgeLayer::clear_changed`
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    pub fn clear_changed(&mut self) {
+        self.changed = false;
+    }
+
+    /// Returns the number of bytes allocated for the edges of the layer, the activity of each
+    /// slot taking a bit
+    pub fn memory(&self) -> usize {
+        (self.from.capacity() + self.to.capacity()) * std::mem::size_of::<NodeIndex>()
+            + self.assignment.capacity() * std::mem::size_of::<ValueIndex>()
+            + (self.free.capacity() + 3 * self.supports.capacity()) * std::mem::size_of::<usize>()
+            + self.from.capacity().div_ceil(8)
+    }
+
+    /// Releases the unused capacity of the edges of the layer
+    pub fn shrink_to_fit(&mut self) {
+        self.from.shrink_to_fit();
+        self.to.shrink_to_fit();
+        self.assignment.shrink_to_fit();
+        self.free.shrink_to_fit();
+        self.supports.shrink_to_fit();
+    }
+
+    /// Returns the indexes of the active edges, in increasing order
+    pub fn iter_active(&self) -> impl Iterator<Item = usize> + '_ {
+        self.active.iter_ones()
+    }
+
+    /// Returns the active edges of the layer
+    pub fn iter(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.iter_active().map(|index| self.get(index))
+    }
+
+    /// Keeps the active edges satisfying the predicate, compacted at the start of the arrays in
+    /// the same order, and returns the new index of each kept edge
+    pub fn retain(&mut self, keep: impl Fn(NodeIndex, NodeIndex) -> bool) -> Vec<Option<usize>> {
+        let mut new_indices = vec![None; self.len()];
+        let mut new_index = 0;
+        for (index, mapped) in new_indices.iter_mut().enumerate() {
+            if self.active.contains(index) && keep(self.from[index], self.to[index]) {
+                self.from[new_index] = self.from[index];
+                self.to[new_index] = self.to[index];
+                self.assignment[new_index] = self.assignment[index];
+                *mapped = Some(new_index);
+                new_index += 1;
+            }
+        }
+        self.changed |= new_index < self.active.size();
+        self.from.truncate(new_index);
+        self.to.truncate(new_index);
+        self.assignment.truncate(new_index);
+        self.free.clear();
+        self.active = Bitset::with_capacity(new_index);
+        for index in 0..new_index {
+            self.active.insert(index);
+        }
+        self.supports.fill(0);
+        self.supported.clear();
+        for index in 0..new_index {
+            self.support(self.assignment[index]);
+        }
+        new_indices
+    }
 
     pub fn update_node_indices(&mut self, map: &FxHashMap::<NodeIndex, NodeIndex>) {
-        self.from = map[&self.from];
-        self.to = map[&self.to];
+        for index in 0..self.len() {
+            self.from[index] = map[&self.from[index]];
+            self.to[index] = map[&self.to[index]];
+        }
+    }
+}
+
+/// Edge removed by the propagation of a constraint, either because the constraint invalidated it
+/// or because one of its nodes was removed as a consequence. It is kept to repair the MDD if the
+/// constraint is retracted (see `Mdd::retract_constraint`). An endpoint is None once the node has
+/// been removed from the MDD.
+#[derive(Clone, Debug)]
+pub struct RemovedEdge {
+    pub layer: usize,
+    pub from: Option<NodeIndex>,
+    pub to: Option<NodeIndex>,
+    pub assignment: ValueIndex,
+    pub constraint: ConstraintIndex,
+}
+
+#[cfg(test)]
+mod test_edge {
+
+    use crate::mdd::*;
+    use crate::modelling::ValueIndex;
+
+    #[test]
+    pub fn test_edge_layer() {
+        let mut edges = EdgeLayer::default();
+        for i in 0..70 {
+            edges.push(NodeIndex(0, 0), NodeIndex(1, i % 3), ValueIndex(i));
+        }
+        edges.deactivate(1);
+        edges.deactivate(65);
+        assert_eq!(edges.iter_active().count(), 68);
+        assert_eq!(edges.push(NodeIndex(0, 0), NodeIndex(1, 0), ValueIndex(70)), 65);
+        edges.deactivate(65);
+        let new_indices = edges.retain(|_, to| to != NodeIndex(1, 2));
+        assert_eq!(new_indices[0], Some(0));
+        assert_eq!(new_indices[1], None);
+        assert_eq!(new_indices[2], None);
+        assert_eq!(new_indices[3], Some(1));
+        assert_eq!(edges.len(), 46);
+        assert!(edges.iter().all(|edge| edge.is_active() && edge.to() != NodeIndex(1, 2) && edge.assignment().0 != 65));
     }
 }
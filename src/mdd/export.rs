@@ -0,0 +1,277 @@
+use super::*;
+use crate::modelling::*;
+use rustc_hash::FxHashMap;
+
+// Exports of compiled MDDs to the formats of knowledge compilation tools. These formats are over
+// Boolean variables, hence each pair of a variable and a value of its domain is encoded by an
+// indicator variable [x = v] (see `Mdd::indicator`), a path assigning each variable a single value.
+//
+// The MDD is translated into a d-DNNF: each node is the disjunction, over its edges, of the
+// conjunction of the indicator of the value of the edge, of the negation of the other indicators of
+// the variable, and of the translation of the child. The conjunctions are decomposable since the
+// children only decide the next variables, and the disjunctions are deterministic since their
+// conjunctions assign different values to the variable. The formula is also smooth, hence its
+// models are counted by the tools as the paths of the MDD.
+//
+// The CNF encoding has an auxiliary variable per node, true if the node is on the path of the
+// assignment. Each variable takes exactly one value, the root is on the path, and the path follows
+// the edges: a node on the path with the value of an edge implies its child, the values without
+// edge are forbidden, and a node on the path implies, for the value of the previous variable, one of
+// its parents with an edge of this value. The last clauses keep the nodes off the path false, such
+// that each model of the CNF is a path of the MDD.
+
+/// Formula in conjunctive normal form, whose variables are numbered from 1 and whose literals are
+/// negative for the negated variables
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cnf {
+    pub number_variables: usize,
+    pub clauses: Vec<Vec<isize>>,
+}
+
+impl Cnf {
+
+    /// Returns the formula in the DIMACS format
+    pub fn as_dimacs(&self) -> String {
+        let mut out = format!("p cnf {} {}\n", self.number_variables, self.clauses.len());
+        for clause in self.clauses.iter() {
+            for literal in clause.iter() {
+                out.push_str(&format!("{} ", literal));
+            }
+            out.push_str("0\n");
+        }
+        out
+    }
+}
+
+impl Mdd {
+
+    /// Returns the Boolean variable, numbered from 1, indicating that the variable takes the value
+    /// at the given index of its domain. The indicators are numbered by variable, then by value.
+    pub fn indicator(&self, variable: VariableIndex, assignment: ValueIndex) -> usize {
+        1 + (0..variable.0).map(|v| self.problem()[VariableIndex(v)].domain_size()).sum::<usize>() + assignment.0
+    }
+
+    /// Returns the number of indicator variables (see `Mdd::indicator`)
+    pub fn number_indicators(&self) -> usize {
+        self.problem().iter_variables().map(|variable| self.problem()[variable].domain_size()).sum()
+    }
+
+    /// Returns the paths of the MDD as a d-DNNF in the NNF format of the c2d compiler, over the
+    /// indicator variables (see `Mdd::indicator`). The last node of the file is the root.
+    pub fn as_nnf(&self) -> String {
+        let mut lines: Vec<String> = vec![];
+        let mut number_edges = 0;
+        let mut add_node = |line: String, children: usize| {
+            lines.push(line);
+            number_edges += children;
+            lines.len() - 1
+        };
+        if self.is_unsat() {
+            add_node(String::from("O 0 0"), 0);
+        } else {
+            let children = self.path_children();
+            let mut literals = FxHashMap::<isize, usize>::default();
+            let sink = add_node(String::from("A 0"), 0);
+            let mut ids = vec![sink];
+            for layer in (0..self.number_layers() - 1).rev() {
+                let variable = self.decision_at_layer(layer);
+                let domain_size = self.problem()[variable].domain_size();
+                let mut layer_ids = vec![0; children[layer].len()];
+                for (index, node_children) in children[layer].iter().enumerate().filter(|(_, node_children)| !node_children.is_empty()) {
+                    let conjunctions = node_children.iter().copied().map(|(to, assignment)| {
+                        let mut conjunction = (0..domain_size).map(|other| {
+                            let literal = self.indicator(variable, ValueIndex(other)) as isize;
+                            let literal = if other == assignment.0 { literal } else { -literal };
+                            *literals.entry(literal).or_insert_with(|| add_node(format!("L {}", literal), 0))
+                        }).collect::<Vec<usize>>();
+                        conjunction.push(ids[to]);
+                        add_node(format!("A {} {}", conjunction.len(), join(&conjunction)), conjunction.len())
+                    }).collect::<Vec<usize>>();
+                    layer_ids[index] = if conjunctions.len() == 1 {
+                        conjunctions[0]
+                    } else {
+                        add_node(format!("O 0 {} {}", conjunctions.len(), join(&conjunctions)), conjunctions.len())
+                    };
+                }
+                ids = layer_ids;
+            }
+        }
+        let mut out = format!("nnf {} {} {}\n", lines.len(), number_edges, self.number_indicators());
+        for line in lines {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Returns the CNF encoding of the paths of the MDD over the indicator variables (see
+    /// `Mdd::indicator`) and an auxiliary variable per node on a path, numbered after the
+    /// indicators. Each assignment of the indicators satisfying the formula is a path of the MDD,
+    /// and it has a single extension to the auxiliary variables.
+    pub fn to_cnf(&self) -> Cnf {
+        let mut clauses = vec![];
+        for variable in self.problem().iter_variables() {
+            let indicators = (0..self.problem()[variable].domain_size()).map(|index| self.indicator(variable, ValueIndex(index)) as isize).collect::<Vec<isize>>();
+            for (i, first) in indicators.iter().enumerate() {
+                for second in indicators[i + 1..].iter() {
+                    clauses.push(vec![-first, -second]);
+                }
+            }
+            clauses.push(indicators);
+        }
+        let mut number_variables = self.number_indicators();
+        if self.is_unsat() {
+            clauses.push(vec![]);
+            return Cnf { number_variables, clauses };
+        }
+        let children = self.path_children();
+        let mut auxiliaries = vec![vec![0isize; 1]];
+        for layer_children in children.iter() {
+            let mut next = vec![0isize; layer_children.iter().flatten().map(|(to, _)| to + 1).max().unwrap_or(0)];
+            for (to, _) in layer_children.iter().flatten() {
+                next[*to] = 1;
+            }
+            auxiliaries.push(next);
+        }
+        auxiliaries[0][0] = 1;
+        for auxiliary in auxiliaries.iter_mut().flatten().filter(|auxiliary| **auxiliary != 0) {
+            number_variables += 1;
+            *auxiliary = number_variables as isize;
+        }
+        clauses.push(vec![auxiliaries[0][0]]);
+        for (layer, layer_children) in children.iter().enumerate() {
+            let variable = self.decision_at_layer(layer);
+            let indicator = |assignment: ValueIndex| self.indicator(variable, assignment) as isize;
+            // Parents of each node of the next layer, by value
+            let mut parents = FxHashMap::<(usize, ValueIndex), Vec<isize>>::default();
+            for (index, node_children) in layer_children.iter().enumerate().filter(|(_, node_children)| !node_children.is_empty()) {
+                let node = auxiliaries[layer][index];
+                for assignment in (0..self.problem()[variable].domain_size()).map(ValueIndex) {
+                    match node_children.iter().find(|(_, a)| *a == assignment) {
+                        Some((to, _)) => {
+                            clauses.push(vec![-node, -indicator(assignment), auxiliaries[layer + 1][*to]]);
+                            parents.entry((*to, assignment)).or_default().push(node);
+                        },
+                        None => clauses.push(vec![-node, -indicator(assignment)]),
+                    }
+                }
+            }
+            for (to, child) in auxiliaries[layer + 1].iter().copied().enumerate().filter(|(_, child)| *child != 0) {
+                for assignment in (0..self.problem()[variable].domain_size()).map(ValueIndex) {
+                    let mut clause = vec![-child, -indicator(assignment)];
+                    clause.extend(parents.get(&(to, assignment)).into_iter().flatten().copied());
+                    clauses.push(clause);
+                }
+            }
+        }
+        Cnf { number_variables, clauses }
+    }
+}
+
+fn join(ids: &[usize]) -> String {
+    ids.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+#[cfg(test)]
+mod test_export {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    /// Returns the number of models of the smooth d-DNNF and whether the assignment of the
+    /// indicators, by their number, satisfies it
+    fn evaluate_nnf(nnf: &str, assignment: &[bool]) -> (u128, bool) {
+        let mut lines = nnf.lines();
+        let header = lines.next().unwrap().split_whitespace().collect::<Vec<&str>>();
+        assert_eq!(header[0], "nnf");
+        let mut values: Vec<(u128, bool)> = vec![];
+        let mut number_edges = 0;
+        for line in lines {
+            let tokens = line.split_whitespace().collect::<Vec<&str>>();
+            let value = match tokens[0] {
+                "L" => {
+                    let literal = tokens[1].parse::<isize>().unwrap();
+                    (1, assignment[literal.unsigned_abs()] == (literal > 0))
+                },
+                "A" => {
+                    let children = tokens[2..].iter().map(|id| values[id.parse::<usize>().unwrap()]).collect::<Vec<(u128, bool)>>();
+                    number_edges += children.len();
+                    (children.iter().map(|(count, _)| count).product(), children.iter().all(|(_, satisfied)| *satisfied))
+                },
+                _ => {
+                    let children = tokens[3..].iter().map(|id| values[id.parse::<usize>().unwrap()]).collect::<Vec<(u128, bool)>>();
+                    number_edges += children.len();
+                    (children.iter().map(|(count, _)| count).sum(), children.iter().any(|(_, satisfied)| *satisfied))
+                },
+            };
+            values.push(value);
+        }
+        assert_eq!(header[1].parse::<usize>().unwrap(), values.len());
+        assert_eq!(header[2].parse::<usize>().unwrap(), number_edges);
+        *values.last().unwrap()
+    }
+
+    #[test]
+    pub fn test_to_cnf() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs[0..2].to_vec());
+        less_than(&mut problem, xs[2], xs[0]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let cnf = mdd.to_cnf();
+        assert!(cnf.number_variables > mdd.number_indicators() && cnf.number_variables <= 20);
+        // Models of the CNF, by brute force, projected on the indicators
+        let mut models = vec![];
+        for bits in 0..(1u32 << cnf.number_variables) {
+            let value = |literal: isize| ((bits >> (literal.unsigned_abs() - 1)) & 1 == 1) == (literal > 0);
+            if cnf.clauses.iter().all(|clause| clause.iter().any(|literal| value(*literal))) {
+                models.push(mdd.problem().iter_variables().map(|variable| {
+                    (0..3).find(|index| value(mdd.indicator(variable, ValueIndex(*index)) as isize)).unwrap() as isize
+                }).collect::<Vec<isize>>());
+            }
+        }
+        models.sort();
+        let mut solutions = get_all_solutions(&mdd);
+        solutions.sort();
+        assert_eq!(models, solutions);
+        let dimacs = cnf.as_dimacs();
+        assert!(dimacs.starts_with(&format!("p cnf {} {}\n", cnf.number_variables, cnf.clauses.len())));
+        assert_eq!(dimacs.lines().count(), cnf.clauses.len() + 1);
+    }
+
+    #[test]
+    pub fn test_as_nnf() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2], None);
+        all_different(&mut problem, xs[0..3].to_vec());
+        less_than(&mut problem, xs[3], xs[0]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let nnf = mdd.as_nnf();
+        assert!(nnf.starts_with("nnf "));
+        assert!(nnf.lines().next().unwrap().ends_with(" 12"));
+        let indicators = |solution: &[isize]| {
+            let mut assignment = vec![false; mdd.number_indicators() + 1];
+            for (variable, value) in solution.iter().enumerate() {
+                assignment[mdd.indicator(VariableIndex(variable), ValueIndex(*value as usize))] = true;
+            }
+            assignment
+        };
+        let solutions = get_all_solutions(&mdd);
+        for solution in solutions.iter() {
+            assert_eq!(evaluate_nnf(&nnf, &indicators(solution)), (solutions.len() as u128, true));
+        }
+        assert!(!evaluate_nnf(&nnf, &indicators(&[0, 1, 2, 1])).1);
+
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(2, vec![0, 1], None);
+        less_than(&mut problem, xs[0], xs[1]);
+        less_than(&mut problem, xs[1], xs[0]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.as_nnf(), "nnf 1 0 4\nO 0 0\n");
+    }
+}
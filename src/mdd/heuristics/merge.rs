@@ -1,28 +1,32 @@
 use crate::mdd::*;
 
+#[derive(Clone)]
 pub enum MergeHeuristic {
     LessRelaxed,
     MostLikely,
 }
 
 impl MergeHeuristic {
+    /// Returns the scores of the nodes of the layer with their index, by increasing score, the ties
+    /// being broken by `Mdd::tie_keys`. The nodes ranked first are kept by the merges.
     pub fn rank_nodes(&self, mdd: &Mdd, layer: usize) -> Vec<(f64, usize)> {
         let n = mdd.number_nodes_in_layer(layer);
         let mut scores = vec![(0.0, 0); n];
         match self {
             Self::LessRelaxed => {
-                for i in 0..n {
+                for (i, score) in scores.iter_mut().enumerate() {
                     let node = NodeIndex(layer, i);
                     let number_parents = mdd[node].number_parents() as f64;
-                    let number_parents_relaxed = mdd[node].iter_parents().map(|edge| mdd[edge].from()).filter(|parent| !mdd[*parent].is_relaxed()).count() as f64;
-                    scores[i] = (number_parents_relaxed / number_parents, i);
+                    let number_parents_relaxed = mdd[node].iter_parents().map(|edge| mdd.edge(edge).from()).filter(|parent| !mdd[*parent].is_relaxed()).count() as f64;
+                    *score = (number_parents_relaxed / number_parents, i);
                 }
             },
             Self::MostLikely => {
                 panic!("Merge heuristic: most likely not implemented");
             },
         }
-        scores.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        let ties = mdd.tie_keys(layer);
+        scores.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(ties[a.1].cmp(&ties[b.1])));
         scores
     }
 }
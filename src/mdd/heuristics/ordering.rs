@@ -1,15 +1,36 @@
 use crate::modelling::{Problem, VariableIndex};
+use crate::mdd::analysis::constraint_graph;
 
+#[derive(Clone)]
 pub enum OrderingHeuristic {
+    /// Decides first the fixed variables, then the variable linked to the most decided variables
+    /// by the constraints, ties being broken by the most constraints, then by the first candidate
+    /// scanned (the selected candidates being swap-removed from the variables in index order)
     MinDomMaxLinked,
     Custom(Vec<usize>),
+    /// Ordering computed on the primal constraint graph (see `from_constraint_graph`)
+    ConstraintGraph(Strategy),
+}
+
+/// Strategies ordering the vertices of the primal constraint graph of a problem, whose vertices are
+/// the variables and whose edges link the variables sharing a constraint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Eliminates first the variable whose elimination adds the fewest edges between its
+    /// neighbours, ties being broken by the least degree
+    MinFill,
+    /// Eliminates first the variable with the fewest neighbours
+    MinDegree,
+    /// Visits first the variable with the most visited neighbours
+    MaximumCardinality,
 }
 
 impl OrderingHeuristic {
 
     pub fn get_order(&self, problem: &Problem) -> Vec<VariableIndex> {
         match self {
-            Self::Custom(order) => return order.iter().copied().map(VariableIndex).collect::<Vec<VariableIndex>>(),
+            Self::Custom(order) => order.iter().copied().map(VariableIndex).collect::<Vec<VariableIndex>>(),
+            Self::ConstraintGraph(strategy) => from_constraint_graph(problem, *strategy),
             Self::MinDomMaxLinked => {
                 let n = problem.number_variables();
                 let mut scores = vec![0; n];
@@ -55,3 +76,68 @@ impl OrderingHeuristic {
     }
 
 }
+
+/// Returns an ordering of the variables computed on the primal constraint graph of the problem.
+/// The eliminations of min-fill and min-degree connect the neighbours of the eliminated variable,
+/// and the layers decide the variables in the reverse elimination order, such that the variables
+/// eliminated last, which are the most connected, are decided first. Maximum cardinality search
+/// decides the variables in the order they are visited. Ties are broken by the least index.
+pub fn from_constraint_graph(problem: &Problem, strategy: Strategy) -> Vec<VariableIndex> {
+    let graph = constraint_graph(problem);
+    match strategy {
+        Strategy::MaximumCardinality => {
+            let n = problem.number_variables();
+            let mut remaining = (0..n).collect::<Vec<usize>>();
+            let mut order = vec![];
+            let mut visited_neighbours = vec![0; n];
+            while !remaining.is_empty() {
+                let index = (0..remaining.len()).min_by_key(|index| (std::cmp::Reverse(visited_neighbours[remaining[*index]]), remaining[*index])).unwrap();
+                let selected = remaining.remove(index);
+                for neighbour in graph.primal_neighbours(VariableIndex(selected)) {
+                    visited_neighbours[neighbour.0] += 1;
+                }
+                order.push(VariableIndex(selected));
+            }
+            order
+        },
+        Strategy::MinFill | Strategy::MinDegree => {
+            let (mut order, _) = graph.elimination_order(strategy == Strategy::MinFill);
+            order.reverse();
+            order
+        },
+    }
+}
+
+#[cfg(test)]
+mod test_ordering {
+
+    use crate::modelling::*;
+    use crate::mdd::heuristics::ordering::*;
+
+    #[test]
+    pub fn test_from_constraint_graph() {
+        // The pairs (x_i, y_i) are linked, hence they are adjacent in the orderings
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let ys = problem.add_variables(3, vec![0, 1, 2], None);
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            not_equals(&mut problem, *x, *y);
+        }
+        for strategy in [Strategy::MinFill, Strategy::MinDegree, Strategy::MaximumCardinality] {
+            let order = OrderingHeuristic::ConstraintGraph(strategy).get_order(&problem);
+            let mut sorted = order.clone();
+            sorted.sort();
+            assert_eq!(sorted, problem.iter_variables().collect::<Vec<VariableIndex>>());
+            let position = |variable: VariableIndex| order.iter().position(|v| *v == variable).unwrap();
+            assert!(xs.iter().zip(ys.iter()).all(|(x, y)| position(*x).abs_diff(position(*y)) == 1), "{:?}: {:?}", strategy, order);
+        }
+        // The center of a star is eliminated last, hence decided first
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1], None);
+        for x in xs[..3].iter() {
+            not_equals(&mut problem, *x, xs[3]);
+        }
+        assert_eq!(from_constraint_graph(&problem, Strategy::MinFill)[0], xs[3]);
+        assert_eq!(from_constraint_graph(&problem, Strategy::MinDegree)[0], xs[3]);
+    }
+}
@@ -0,0 +1,119 @@
+use super::*;
+use rustc_hash::FxHashMap;
+
+// Information measures of the distribution induced by an MDD over its paths, each path x having the
+// probability P(x) = w(x) / Z, with w(x) the product of the probabilities of its assignments and Z
+// the probability mass of the MDD. The entropy is ln Z - E[ln w(x)] (in nats), the sum of the w(x)
+// ln w(x) being computed in the expectation semiring.
+//
+// The KL-divergence between the distributions of two MDDs over the same variables, with the same
+// ordering, is computed on their product: each path of the first MDD is followed in the second one
+// by the values of its edges, the domains of the MDDs possibly differing (e.g., if a variable is
+// fixed by an evidence). The divergence is infinite if a path of positive probability of the first
+// MDD is not a path of the second one.
+
+impl Mdd {
+
+    /// Returns the entropy, in nats, of the distribution of the paths of the MDD drawn with the
+    /// probabilities of their assignments. Returns 0 if the MDD is unsat.
+    pub fn entropy(&self) -> f64 {
+        let (mass, weighted_log) = self.evaluate::<Expectation>(|variable, assignment| {
+            let probability = self.problem()[variable].probability(assignment);
+            (probability, if probability > 0.0 { probability * probability.ln() } else { 0.0 })
+        });
+        if mass == 0.0 {
+            return 0.0;
+        }
+        mass.ln() - weighted_log / mass
+    }
+
+    /// Returns the KL-divergence, in nats, from the distribution of the paths of the other MDD to
+    /// the distribution of the paths of this MDD (see `Mdd::entropy`), i.e., the expectation over
+    /// the paths x of this MDD of ln P(x) / Q(x). Both MDDs must decide the same variables in the
+    /// same order, and this one must have a path.
+    pub fn kl_divergence(&self, other: &Mdd) -> f64 {
+        assert!(self.number_layers() == other.number_layers() && (0..self.number_layers() - 1).all(|layer| self.decision_at_layer(layer) == other.decision_at_layer(layer)), "The MDDs must decide the same variables in the same order");
+        assert!(!self.is_unsat(), "The divergence is not defined for an MDD without path");
+        if other.is_unsat() {
+            return f64::INFINITY;
+        }
+        let (children, other_children) = (self.path_children(), other.path_children());
+        // Value of the paths reaching each pair of nodes, with their weight p and p ln(p / q)
+        let mut states = FxHashMap::<(usize, usize), (f64, f64)>::default();
+        states.insert((0, 0), Expectation::one());
+        for layer in 0..self.number_layers() - 1 {
+            let variable = self.decision_at_layer(layer);
+            let mut next_states = FxHashMap::<(usize, usize), (f64, f64)>::default();
+            for ((node, other_node), value) in states {
+                for (to, assignment) in children[layer][node].iter().copied() {
+                    let p = self.problem()[variable].probability(assignment);
+                    if p == 0.0 {
+                        continue;
+                    }
+                    let edge_value = self.problem()[variable].value(assignment);
+                    let Some((other_to, other_assignment)) = other_children[layer][other_node].iter().find(|(_, a)| other.problem()[variable].value(*a) == edge_value) else { return f64::INFINITY };
+                    let q = other.problem()[variable].probability(*other_assignment);
+                    let next = next_states.entry((to, *other_to)).or_insert(Expectation::zero());
+                    *next = Expectation::add(*next, Expectation::mul(value, (p, p * (p / q).ln())));
+                }
+            }
+            states = next_states;
+        }
+        let (mass, weighted_log_ratio) = states.into_values().fold(Expectation::zero(), Expectation::add);
+        weighted_log_ratio / mass - mass.ln() + other.probability_mass().ln()
+    }
+}
+
+#[cfg(test)]
+mod test_information {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_entropy() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        problem.set_probabilities(xs[0], vec![0.5, 0.25, 0.25]);
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let weights = get_all_solutions(&mdd).iter().map(|solution| {
+            xs.iter().map(|x| problem[*x].probability(ValueIndex(solution[x.0] as usize))).product::<f64>()
+        }).collect::<Vec<f64>>();
+        let mass = weights.iter().sum::<f64>();
+        let expected = -weights.iter().map(|weight| weight / mass * (weight / mass).ln()).sum::<f64>();
+        assert!((mdd.entropy() - expected).abs() < 1e-9);
+
+        // The uniform distribution over the 6 permutations
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert!((mdd.entropy() - 6.0f64.ln()).abs() < 1e-9);
+    }
+
+    #[test]
+    pub fn test_kl_divergence() {
+        let mut prior = Problem::default();
+        let xs = prior.add_variables(3, vec![0, 1], None);
+        prior.set_probabilities(xs[0], vec![0.3, 0.7]);
+        clause(&mut prior, xs.clone(), vec![]);
+        let order = OrderingHeuristic::Custom(vec![0, 1, 2]);
+        let mut prior_mdd = Mdd::new(prior.clone(), usize::MAX, order.clone(), MergeHeuristic::LessRelaxed);
+        prior_mdd.refine();
+        // The evidence x0 = 1 restricts the distribution to the paths with x0 = 1, whose prior
+        // probability sums to 0.7 / mass
+        let mut posterior = prior.clone();
+        posterior.fix(xs[0], 1);
+        let mut posterior_mdd = Mdd::new(posterior, usize::MAX, order, MergeHeuristic::LessRelaxed);
+        posterior_mdd.refine();
+        let mass = prior_mdd.probability_mass();
+        assert!((posterior_mdd.kl_divergence(&prior_mdd) - (mass / 0.7).ln()).abs() < 1e-9);
+        assert_eq!(prior_mdd.kl_divergence(&posterior_mdd), f64::INFINITY);
+        assert!(prior_mdd.kl_divergence(&prior_mdd).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,128 @@
+use super::*;
+use crate::modelling::*;
+
+// Lagrangian relaxation of linear inequalities over a relaxed MDD. The minimum cost of a path of a
+// relaxed MDD is a lower bound on the optimal cost of the problem, which is weak when the MDD keeps
+// many infeasible paths. Each inequality is dualized: its violation, weighted by a non-negative
+// multiplier, is added to the cost of the edges, and the multipliers are updated by subgradient
+// steps to maximize the resulting bound.
+
+/// Linear inequality sum_i c_i [x_i = v_i] <= bound, each term adding its coefficient c_i to the
+/// left-hand side if the variable x_i takes the value v_i
+#[derive(Clone, Debug)]
+pub struct Penalty {
+    terms: Vec<(VariableIndex, isize, f64)>,
+    bound: f64,
+}
+
+impl Penalty {
+
+    pub fn new(terms: Vec<(VariableIndex, isize, f64)>, bound: f64) -> Self {
+        Self { terms, bound }
+    }
+
+    /// Enforces that at most `count` of the variables take the value
+    pub fn at_most(variables: &[VariableIndex], value: isize, count: usize) -> Self {
+        Self::new(variables.iter().map(|variable| (*variable, value, 1.0)).collect(), count as f64)
+    }
+
+    /// Returns the violation of the inequality by the assignment, negative if it is satisfied
+    /// with slack
+    pub fn violation(&self, assignment: &[isize]) -> f64 {
+        self.terms.iter().filter(|(variable, value, _)| assignment[variable.0] == *value).map(|(_, _, coefficient)| coefficient).sum::<f64>() - self.bound
+    }
+}
+
+/// Result of the Lagrangian relaxation (see `Mdd::lagrangian_bound`)
+#[derive(Clone, Debug)]
+pub struct LagrangianBound {
+    /// Best lower bound found on the minimum cost of a solution
+    pub bound: f64,
+    /// Multipliers of the penalties giving the bound
+    pub multipliers: Vec<f64>,
+    /// Shortest path of the MDD with the penalized costs giving the bound
+    pub solution: Vec<isize>,
+    /// Is the bound proven optimal, i.e., the solution satisfies the problem and the penalties,
+    /// and the penalties with a positive multiplier are tight
+    pub optimal: bool,
+}
+
+impl Mdd {
+
+    /// Returns a lower bound on the minimum cost of a solution satisfying the penalties, the cost
+    /// of a solution being the sum of the costs of its values. The bound is improved by at most
+    /// `iterations` subgradient steps, whose length decreases as step / (i + 1). Returns None if
+    /// the MDD is unsat.
+    pub fn lagrangian_bound(&self, cost: impl Fn(VariableIndex, isize) -> f64, penalties: &[Penalty], iterations: usize, step: f64) -> Option<LagrangianBound> {
+        assert!(step > 0.0, "The subgradient step must be positive");
+        if self.is_unsat() {
+            return None;
+        }
+        let costs = self.problem().iter_variables().map(|variable| {
+            self.problem()[variable].iter_domain().map(|value| cost(variable, value)).collect::<Vec<f64>>()
+        }).collect::<Vec<Vec<f64>>>();
+        // Index of the value of each term in the domain of its variable, the terms on values outside
+        // the domain being dropped
+        let terms = penalties.iter().map(|penalty| {
+            penalty.terms.iter().filter_map(|(variable, value, coefficient)| {
+                self.problem()[*variable].iter_domain().position(|v| v == *value).map(|index| (*variable, index, *coefficient))
+            }).collect::<Vec<(VariableIndex, usize, f64)>>()
+        }).collect::<Vec<Vec<(VariableIndex, usize, f64)>>>();
+        let mut multipliers = vec![0.0; penalties.len()];
+        let mut best: Option<LagrangianBound> = None;
+        for iteration in 0..iterations.max(1) {
+            let mut weights = costs.clone();
+            for (penalty, multiplier) in multipliers.iter().copied().enumerate() {
+                for (variable, index, coefficient) in terms[penalty].iter().copied() {
+                    weights[variable.0][index] += multiplier * coefficient;
+                }
+            }
+            let (weight, solution) = self.best_path::<MinPlus>(|variable, assignment| weights[variable.0][assignment.0])?;
+            let violations = penalties.iter().map(|penalty| penalty.violation(&solution)).collect::<Vec<f64>>();
+            let bound = weight - multipliers.iter().zip(penalties.iter()).map(|(multiplier, penalty)| multiplier * penalty.bound).sum::<f64>();
+            let optimal = violations.iter().all(|violation| *violation <= 0.0)
+                && violations.iter().zip(multipliers.iter()).all(|(violation, multiplier)| *multiplier == 0.0 || *violation == 0.0)
+                && self.is_solution(&solution);
+            if best.as_ref().is_none_or(|best| bound > best.bound) || optimal {
+                best = Some(LagrangianBound { bound, multipliers: multipliers.clone(), solution, optimal });
+            }
+            if optimal {
+                break;
+            }
+            let length = step / (iteration + 1) as f64;
+            for (multiplier, violation) in multipliers.iter_mut().zip(violations.iter()) {
+                *multiplier = (*multiplier + length * violation).max(0.0);
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod test_lagrangian {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+
+    #[test]
+    pub fn test_lagrangian_bound() {
+        // The relaxed MDD of width 1 keeps every assignment, whose cheapest one assigns 2 to each
+        // variable. The penalties enforce that each value is taken at most once.
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, 1, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let cost = |_: VariableIndex, value: isize| -value as f64;
+        let penalties = (0..3).map(|value| Penalty::at_most(&xs, value, 1)).collect::<Vec<Penalty>>();
+        let relaxed = mdd.lagrangian_bound(cost, &penalties, 1, 1.0).unwrap();
+        assert_eq!(relaxed.bound, -6.0);
+        assert_eq!(relaxed.solution, vec![2, 2, 2]);
+        assert!(!relaxed.optimal);
+
+        let lagrangian = mdd.lagrangian_bound(cost, &penalties, 100, 1.0).unwrap();
+        assert!(lagrangian.bound > -6.0 && lagrangian.bound <= -3.0 + 1e-9);
+        assert!(lagrangian.multipliers.iter().all(|multiplier| *multiplier >= 0.0));
+    }
+}
@@ -0,0 +1,126 @@
+use super::*;
+use crate::modelling::*;
+
+// Learning of the probabilities of the variables from observed assignments, possibly partial. The
+// MDD is a circuit over its solutions, each solution x having the probability P(x) = w(x) / Z, with
+// w(x) the product of the probabilities of its values and Z the probability mass of the MDD. Since
+// a path assigns exactly one value to each variable, this is a log-linear model whose features are
+// the indicators of the values, and whose likelihood is maximized when the marginals of the model
+// match the expected frequencies of the values in the data.
+//
+// The probabilities are fitted by expectation-maximization. The E-step computes, for each
+// observation, the marginals of the solutions consistent with it (one forward and one backward pass
+// over the MDD with the inconsistent values weighted by 0), whose mean gives the expected frequency
+// of each value. The M-step is a step of generalized iterative scaling: the probability of each value
+// is multiplied by (frequency / marginal)^(1 / n), with n the number of variables (i.e., the number
+// of features of each path), then the probabilities of the variable are normalized. Each iteration
+// does not decrease the likelihood of the data.
+
+impl Mdd {
+
+    /// Returns the log-likelihood of the observations, each one giving the observed value of
+    /// each variable, if any, i.e., the sum over the observations of the logarithm of the
+    /// probability of the solutions consistent with them. The MDD must be exact.
+    pub fn log_likelihood(&self, data: &[Vec<Option<isize>>]) -> f64 {
+        let total = self.probability_mass();
+        data.iter().map(|observation| {
+            (self.evaluate::<SumProduct>(|variable, assignment| self.observed_probability(observation, variable, assignment)) / total).ln()
+        }).sum()
+    }
+
+    /// Fits the probabilities of the variables to the observations (see `Mdd::log_likelihood`)
+    /// with the given number of iterations of expectation-maximization, and returns the
+    /// log-likelihood of the observations with the learned probabilities. The observations without
+    /// any consistent solution are ignored by the updates. The MDD must be exact.
+    pub fn learn_probabilities(&mut self, data: &[Vec<Option<isize>>], iterations: usize) -> f64 {
+        assert!(data.iter().all(|observation| observation.len() == self.number_layers() - 1), "The observations must give a value, or None, for each variable");
+        let number_variables = self.number_layers() - 1;
+        for _ in 0..iterations {
+            let mut frequencies = self.problem().iter_variables().map(|variable| vec![0.0; self.problem()[variable].domain_size()]).collect::<Vec<Vec<f64>>>();
+            let mut number_consistent = 0;
+            for observation in data.iter() {
+                let marginals = self.marginals_with::<SumProduct>(|variable, assignment| self.observed_probability(observation, variable, assignment));
+                // The marginals are 0 if no solution is consistent with the observation
+                if number_variables == 0 || marginals[0].iter().sum::<f64>() == 0.0 {
+                    continue;
+                }
+                number_consistent += 1;
+                for (frequency, marginal) in frequencies.iter_mut().flatten().zip(marginals.iter().flatten()) {
+                    *frequency += marginal;
+                }
+            }
+            if number_consistent == 0 {
+                break;
+            }
+            let model = self.marginals();
+            let probabilities = self.problem().iter_variables().map(|variable| {
+                let scaled = (0..self.problem()[variable].domain_size()).map(|index| {
+                    let probability = self.problem()[variable].probability(ValueIndex(index));
+                    let marginal = model[variable.0][index];
+                    if marginal == 0.0 {
+                        return probability;
+                    }
+                    let frequency = frequencies[variable.0][index] / number_consistent as f64;
+                    probability * (frequency / marginal).powf(1.0 / number_variables as f64)
+                }).collect::<Vec<f64>>();
+                let total = scaled.iter().sum::<f64>();
+                scaled.into_iter().map(|probability| probability / total).collect()
+            }).collect::<Vec<Vec<f64>>>();
+            self.set_probabilities(&probabilities);
+        }
+        self.log_likelihood(data)
+    }
+
+    /// Returns the probability of the value of the variable if it is consistent with the
+    /// observation, and 0 otherwise
+    fn observed_probability(&self, observation: &[Option<isize>], variable: VariableIndex, assignment: ValueIndex) -> f64 {
+        match observation[variable.0] {
+            Some(value) if value != self.problem()[variable].value(assignment) => 0.0,
+            _ => self.problem()[variable].probability(assignment),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_learning {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+
+    #[test]
+    pub fn test_learn_probabilities() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs[0..2].to_vec());
+        less_than(&mut problem, xs[2], xs[1]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let data = vec![
+            vec![Some(0), Some(2), Some(1)],
+            vec![Some(0), Some(2), Some(0)],
+            vec![Some(2), Some(1), Some(0)],
+            vec![Some(0), Some(1), Some(0)],
+        ];
+        let mut likelihood = mdd.log_likelihood(&data);
+        for _ in 0..200 {
+            let next = mdd.learn_probabilities(&data, 1);
+            assert!(next >= likelihood - 1e-9);
+            likelihood = next;
+        }
+        // The marginals of the model match the frequencies of the values in the data
+        let marginals = mdd.marginals();
+        for (variable, marginals) in marginals.iter().enumerate() {
+            for (value, marginal) in marginals.iter().enumerate() {
+                let frequency = data.iter().filter(|observation| observation[variable] == Some(value as isize)).count() as f64 / data.len() as f64;
+                assert!((marginal - frequency).abs() < 1e-2);
+            }
+        }
+
+        // A partial observation, and one without consistent solution
+        let data = vec![vec![Some(0), None, None], vec![Some(1), Some(1), None]];
+        let likelihood = mdd.log_likelihood(&data[0..1]);
+        assert!(mdd.learn_probabilities(&data, 10) == f64::NEG_INFINITY);
+        assert!(mdd.log_likelihood(&data[0..1]) > likelihood);
+    }
+}
@@ -8,8 +8,11 @@ use rand::prelude::*;
 use rand_xoshiro::Xoshiro256Plus;
 use rand::SeedableRng;
 
+#[cfg(feature = "fs")]
 use std::fs;
 use rustc_hash::{FxHashSet, FxHashMap};
+use crate::utils::{map_items, SparseSet};
+use crate::constraints::Table;
 
 thread_local! {
     static RNG: RefCell<Xoshiro256Plus> = RefCell::new(Xoshiro256Plus::from_rng(&mut rand::rng()));
@@ -18,12 +21,19 @@ thread_local! {
 /// Structure for the MDD. The MDD is organised in layers (one layer per variable in the problem)
 /// and each layer contains the necessary information to propagate the constraint and generate
 /// solutions.
+#[derive(Clone)]
 pub struct Mdd {
     problem: Problem,
     /// Nodes of the MDD.
     nodes: Vec<Vec<Node>>,
     /// Edges of the MDD.
-    edges: Vec<Vec<Edge>>,
+    edges: Vec<EdgeLayer>,
+    /// Slots of the deactivated nodes of each layer, reused by the next added nodes until the MDD
+    /// is cleaned
+    free_nodes: Vec<Vec<usize>>,
+    /// Number of nodes of each layer for which the constraints have a state. The constraints do
+    /// not compact their states with the nodes, hence the states of the removed nodes are reused.
+    constraint_slots: Vec<usize>,
     /// Branching order
     order: Vec<VariableIndex>,
     /// Max width allows during compilation
@@ -36,17 +46,87 @@ pub struct Mdd {
     root: NodeIndex,
     /// Sink of the mdd
     sink: NodeIndex,
+    /// Edges removed by the propagation of the constraints, to repair the MDD when a constraint is
+    /// retracted, if they are recorded (see `Mdd::enable_repair`)
+    removed_edges: Vec<RemovedEdge>,
+    /// Positions in `removed_edges` of the edges removed from or to each node, to update them when
+    /// the node is split or its slot reused without scanning all the removed edges
+    removed_by_node: FxHashMap<NodeIndex, Vec<usize>>,
+    /// Are the edges removed by the propagation recorded. It is undecided until the MDD is refined,
+    /// and the edges are recorded until then, their number being bounded by the edges of the MDD
+    /// of width 1 (see `Mdd::enable_repair`).
+    repair_enabled: Option<bool>,
+    /// Trace of the compilation, if enabled
+    trace: Option<Trace>,
+    /// Maximum number of rounds of a propagation
+    max_propagation_rounds: usize,
+    /// Weight of the edges, for each variable and each value of its domain, if set (see
+    /// `Mdd::set_edge_weights`)
+    weights: Option<Vec<Vec<f64>>>,
+    /// Are the nodes exceeding the width removed instead of merged (see `Mdd::set_restricted`)
+    restricted: bool,
+    /// Seed breaking the ties between the nodes ranked by the merge heuristic, if set (see
+    /// `Mdd::set_seed`)
+    seed: Option<u64>,
+    /// Maximum estimated memory of the MDD, in bytes, with the action taken when it is exceeded
+    /// (see `Mdd::set_memory_budget`)
+    memory_budget: Option<(usize, BudgetAction)>,
+    /// Has the refinement been stopped by the memory budget
+    budget_exceeded: bool,
+    /// Nodes and edges deactivated since the oldest saved state, in order (see `Mdd::push_state`)
+    trail: Vec<TrailEntry>,
+    /// States saved by `Mdd::push_state`, from the oldest
+    saved_states: Vec<SavedState>,
+    /// Statistics of the propagation of each constraint
+    propagation_statistics: Vec<PropagationStatistics>,
+    /// Is the time spent propagating each constraint measured (see `Mdd::set_propagation_timing`)
+    propagation_timing: bool,
 }
 
+/// Deactivation of a node or an edge, recorded on the trail to be undone
+#[derive(Clone, Copy, Debug)]
+enum TrailEntry {
+    Node(NodeIndex),
+    Edge(EdgeIndex),
+}
+
+/// State of the MDD saved by `Mdd::push_state`
+#[derive(Clone, Copy, Debug)]
+struct SavedState {
+    /// Length of the trail
+    trail: usize,
+    /// Number of edges removed by the propagation
+    removed_edges: usize,
+    number_constraints: usize,
+    unsat: bool,
+}
+
+/// Action taken by the refinement when the estimated memory of the MDD exceeds its budget
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetAction {
+    /// Stops the refinement
+    Stop,
+    /// Releases the unused capacity of the storage of the MDD, and stops the refinement if it is
+    /// still over the budget
+    Compact,
+}
+
+/// Estimated number of bytes of the state of a constraint on a node, the constraints not reporting
+/// the size of their states
+const PROPERTY_BYTES: usize = 4 * std::mem::size_of::<usize>();
+
 impl Mdd {
 
     /// Creates a new MDD for the given problem and variable ordering. The ordering array gives,
     /// for each variable, the layer at which it is branched on.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "compile", skip_all, fields(variables = problem.number_variables(), constraints = problem.number_constraints(), max_width)))]
     pub fn new(problem: Problem, max_width: usize, order: OrderingHeuristic, merge_heuristic: MergeHeuristic) -> Self {
         let number_layers = problem.number_variables() + 1;
         let mut mdd = Self {
             nodes: vec![vec![]; problem.number_variables() + 1],
-            edges: vec![vec![]; problem.number_variables()],
+            edges: vec![EdgeLayer::default(); problem.number_variables()],
+            free_nodes: vec![vec![]; number_layers],
+            constraint_slots: vec![0; number_layers],
             order: vec![],
             max_width,
             merge_heuristic,
@@ -54,6 +134,20 @@ impl Mdd {
             unsat: false,
             root: NodeIndex(0, 0),
             sink: NodeIndex(number_layers - 1, 0),
+            removed_edges: vec![],
+            removed_by_node: FxHashMap::default(),
+            repair_enabled: None,
+            trace: None,
+            max_propagation_rounds: usize::MAX,
+            weights: None,
+            restricted: false,
+            seed: None,
+            memory_budget: None,
+            budget_exceeded: false,
+            trail: vec![],
+            saved_states: vec![],
+            propagation_statistics: vec![],
+            propagation_timing: false,
         };
         mdd.problem.init_constraints();
 
@@ -106,49 +200,554 @@ impl Mdd {
         mdd
     }
 
+    /// Returns a minimal subset of the constraints of the problem that is infeasible on its own, or
+    /// None if the compiled MDD of the problem is not unsat. The core is shrunk by deletion: each
+    /// constraint is retracted in turn, and is kept in the core only if the problem without it is
+    /// feasible (i.e., its refined MDD is not unsat). If the width is bounded, the unsat MDD is a
+    /// relaxation and the core is minimal for this relaxation.
+    pub fn unsat_core(problem: &Problem, max_width: usize, order: &OrderingHeuristic, merge_heuristic: &MergeHeuristic) -> Option<Vec<ConstraintIndex>> {
+        let is_unsat = |problem: &Problem| {
+            let mut mdd = Mdd::new(problem.clone(), max_width, order.clone(), merge_heuristic.clone());
+            mdd.refine();
+            mdd.is_unsat()
+        };
+        if !is_unsat(problem) {
+            return None;
+        }
+        let mut problem = problem.clone();
+        let mut core = vec![];
+        for constraint in problem.iter_constraints().collect::<Vec<ConstraintIndex>>() {
+            let mut shrunk = problem.clone();
+            shrunk.retract_constraint(constraint);
+            if is_unsat(&shrunk) {
+                problem = shrunk;
+            } else {
+                core.push(constraint);
+            }
+        }
+        Some(core)
+    }
+
+    /// Adds a constraint to the problem of the already compiled MDD. The properties of the
+    /// constraint are allocated for the existing nodes, and the MDD is pruned by propagating the
+    /// constraint (with the others). The MDD is not refined further.
+    pub fn add_constraint(&mut self, constraint: impl Constraint + 'static + Send + Sync) -> ConstraintIndex {
+        let index = self.problem.add_constraint(constraint);
+        self.setup_constraint(index);
+        if self.unsat {
+            return index;
+        }
+        self.propagate_constraints();
+        if !self[self.root].is_active() || !self[self.sink].is_active() {
+            self.unsat = true;
+            return index;
+        }
+        self.clean();
+        index
+    }
+
+    /// Conditions the MDD on the variable taking the value, by adding the constraint fixing it (see
+    /// `Mdd::add_constraint`)
+    pub fn condition(&mut self, variable: VariableIndex, value: isize) -> ConstraintIndex {
+        self.add_constraint(Table::new(vec![variable], vec![vec![Some(value)]]))
+    }
+
+    /// Saves the state of the MDD, to be restored by `Mdd::pop_state`. While a state is saved, the
+    /// MDD can be conditioned and propagated (e.g., by `Mdd::condition`), the deactivated nodes
+    /// and edges being recorded on a trail and keeping their slots, but it cannot be refined or
+    /// repaired. The states are nested.
+    pub fn push_state(&mut self) {
+        self.saved_states.push(SavedState {
+            trail: self.trail.len(),
+            removed_edges: self.removed_edges.len(),
+            number_constraints: self.problem.number_constraints(),
+            unsat: self.unsat,
+        });
+    }
+
+    /// Restores the last saved state of the MDD (see `Mdd::push_state`): the nodes and edges
+    /// deactivated since are activated again, and the constraints added since are removed
+    pub fn pop_state(&mut self) {
+        let state = self.saved_states.pop().expect("No state of the MDD is saved");
+        while self.trail.len() > state.trail {
+            match self.trail.pop().unwrap() {
+                TrailEntry::Edge(edge) => {
+                    self.edges[edge.0].restore(edge.1);
+                    let (from, to) = (self.edge(edge).from(), self.edge(edge).to());
+                    // A deactivated node keeps the edges it had when it was deactivated
+                    if !self[from].iter_children().any(|child| child == edge) {
+                        self[from].add_child_edge(edge);
+                    }
+                    if !self[to].iter_parents().any(|parent| parent == edge) {
+                        self[to].add_parent_edge(edge);
+                    }
+                },
+                TrailEntry::Node(node) => {
+                    self[node].restore();
+                    if let Some(position) = self.free_nodes[node.0].iter().rposition(|index| *index == node.1) {
+                        self.free_nodes[node.0].swap_remove(position);
+                    }
+                },
+            }
+        }
+        for edge in self.removed_edges.drain(state.removed_edges..) {
+            for node in [edge.from, edge.to].into_iter().flatten() {
+                if let Some(positions) = self.removed_by_node.get_mut(&node) {
+                    positions.retain(|position| *position < state.removed_edges);
+                }
+            }
+        }
+        self.problem.truncate_constraints(state.number_constraints);
+        self.propagation_statistics.truncate(state.number_constraints);
+        self.unsat = state.unsat;
+        if !self.unsat {
+            self.update_properties();
+            self.update_path_counts();
+        }
+    }
+
+    /// Returns, for each constraint, the statistics of its propagation since the MDD was created or
+    /// the statistics were reset
+    pub fn propagation_statistics(&self) -> &[PropagationStatistics] {
+        &self.propagation_statistics
+    }
+
+    pub fn reset_propagation_statistics(&mut self) {
+        self.propagation_statistics.fill(PropagationStatistics::default());
+    }
+
+    /// Measures the time spent propagating each constraint in the next propagations (see
+    /// `PropagationStatistics::time`). The passes over the MDD are then run separately for each
+    /// constraint, which slows down the propagation. The propagation is not timed by default.
+    pub fn set_propagation_timing(&mut self, timing: bool) {
+        self.propagation_timing = timing;
+    }
+
+    /// Returns the number of states saved by `Mdd::push_state`
+    pub fn number_saved_states(&self) -> usize {
+        self.saved_states.len()
+    }
+
+    /// Deactivates the edge, recording it on the trail if a state is saved
+    fn deactivate_edge(&mut self, edge: EdgeIndex) {
+        if !self.saved_states.is_empty() && self.edges[edge.0].is_active(edge.1) {
+            self.trail.push(TrailEntry::Edge(edge));
+        }
+        self.edges[edge.0].deactivate(edge.1);
+    }
+
+    /// Initialises a constraint added to the problem after the creation of the MDD, allocating its
+    /// properties for the existing nodes
+    fn setup_constraint(&mut self, index: ConstraintIndex) {
+        self.problem.init_constraint(index);
+        // The properties created by init cover the first node of each layer
+        for layer in 0..self.number_layers() {
+            for _ in 1..self.constraint_slots[layer] {
+                self.problem[index].add_node_in_layer(layer);
+            }
+        }
+        let mut var_order_inv = vec![0; self.order.len()];
+        for (layer, variable) in self.order.iter().copied().enumerate() {
+            var_order_inv[variable.0] = layer;
+        }
+        self.problem[index].update_variable_ordering(&var_order_inv);
+    }
+
+    /// Records the edges removed by the propagation of the constraints, with the constraint removing
+    /// them, so that the MDD can be repaired when a constraint is retracted or relaxed (see
+    /// `Mdd::retract_constraint`) and that the removed values can be explained (see `Mdd::explain`).
+    /// The record grows with every propagation, hence it is dropped by default when the MDD is
+    /// refined. The repair must thus be enabled before the MDD is refined, and it can not be
+    /// enabled on an MDD built from its layers (e.g., loaded or reordered), whose removed edges are
+    /// unknown.
+    pub fn enable_repair(&mut self) {
+        assert!(self.repair_enabled != Some(false), "The repair must be enabled before the MDD is refined");
+        self.repair_enabled = Some(true);
+    }
+
+    /// Retracts the constraint from the problem (see `Problem::retract_constraint`) and repairs the
+    /// MDD by restoring the edges removed by its propagation (see `Mdd::enable_repair`). The edges
+    /// whose nodes have been removed since are restored on a new relaxed node of their layer, so the
+    /// MDD remains a relaxation of the new problem. The edges are then filtered again by the other
+    /// constraints.
+    pub fn retract_constraint(&mut self, index: ConstraintIndex) -> Box<dyn Constraint + Send + Sync> {
+        let retracted = self.problem.retract_constraint(index);
+        self.repair(index);
+        retracted
+    }
+
+    /// Replaces the constraint by a weaker one (i.e., satisfied by at least the same assignments)
+    /// and repairs the MDD as for `Mdd::retract_constraint`. Returns the replaced constraint.
+    pub fn relax_constraint(&mut self, index: ConstraintIndex, constraint: impl Constraint + 'static + Send + Sync) -> Box<dyn Constraint + Send + Sync> {
+        let replaced = self.problem.replace_constraint(index, constraint);
+        self.setup_constraint(index);
+        self.repair(index);
+        replaced
+    }
+
+    /// Restores the edges removed by the constraint, then propagates the constraints. Restoring an
+    /// edge changes the top-down state of its target and the bottom-up state of its source, hence
+    /// the edges removed (by any constraint) below the target or above the source are restored as
+    /// well, and filtered again by the propagation if they are still invalid.
+    fn repair(&mut self, constraint: ConstraintIndex) {
+        assert!(self.repair_enabled != Some(false), "The MDD cannot be repaired without recording the removed edges (see Mdd::enable_repair)");
+        assert!(self.saved_states.is_empty(), "The MDD cannot be repaired while a state is saved");
+        let (mut pending, kept): (Vec<RemovedEdge>, Vec<RemovedEdge>) = std::mem::take(&mut self.removed_edges).into_iter().partition(|edge| edge.constraint == constraint);
+        self.removed_edges = kept;
+        self.index_removed_edges();
+        if self.unsat {
+            self.unsat = false;
+            let (root, sink) = (self.root, self.sink);
+            self[root].reactivate();
+            self[sink].reactivate();
+        }
+        let mut existing = FxHashSet::<(NodeIndex, NodeIndex, ValueIndex)>::default();
+        for layer in 0..self.edges.len() {
+            for edge in self.edges[layer].iter() {
+                existing.insert((edge.from(), edge.to(), edge.assignment()));
+            }
+        }
+        // For each layer, the node on which the edges of the removed nodes are restored
+        let mut replacements: Vec<Option<NodeIndex>> = vec![None; self.number_layers()];
+        // Nodes whose top-down (resp. bottom-up) state has changed
+        let mut changed_top_down = FxHashSet::<NodeIndex>::default();
+        let mut changed_bottom_up = FxHashSet::<NodeIndex>::default();
+        while !pending.is_empty() {
+            let mut descendants = vec![];
+            let mut ancestors = vec![];
+            for edge in pending {
+                let from = self.restored_endpoint(edge.from, edge.layer, &mut replacements);
+                let to = self.restored_endpoint(edge.to, edge.layer + 1, &mut replacements);
+                if existing.insert((from, to, edge.assignment)) {
+                    self.add_edge(edge.layer, from, to, edge.assignment);
+                    self[to].set_relaxed(true);
+                    ancestors.push(from);
+                    descendants.push(to);
+                }
+            }
+            // The states of the descendants (resp. ancestors) of the nodes change as well
+            while let Some(node) = descendants.pop() {
+                if changed_top_down.insert(node) {
+                    descendants.extend(self[node].iter_children().filter(|edge| self.edge(*edge).is_active()).map(|edge| self.edge(edge).to()));
+                }
+            }
+            while let Some(node) = ancestors.pop() {
+                if changed_bottom_up.insert(node) {
+                    ancestors.extend(self[node].iter_parents().filter(|edge| self.edge(*edge).is_active()).map(|edge| self.edge(edge).from()));
+                }
+            }
+            let removed = std::mem::take(&mut self.removed_edges);
+            (pending, self.removed_edges) = removed.into_iter().partition(|edge| {
+                self.current_endpoint(edge.from, edge.layer, &replacements).is_some_and(|from| changed_top_down.contains(&from)) ||
+                self.current_endpoint(edge.to, edge.layer + 1, &replacements).is_some_and(|to| changed_bottom_up.contains(&to))
+            });
+            self.index_removed_edges();
+        }
+        for node in replacements.into_iter().flatten() {
+            if self[node].number_parents() == 0 || self[node].number_children() == 0 {
+                self.remove_node(node, None);
+            }
+        }
+        self.propagate_constraints();
+        if !self[self.root].is_active() || !self[self.sink].is_active() {
+            self.unsat = true;
+            return;
+        }
+        self.clean();
+    }
+
+    /// Returns the node currently holding an endpoint of a removed edge, if any
+    fn current_endpoint(&self, node: Option<NodeIndex>, layer: usize, replacements: &[Option<NodeIndex>]) -> Option<NodeIndex> {
+        if layer == 0 {
+            return Some(self.root);
+        }
+        if layer == self.number_layers() - 1 {
+            return Some(self.sink);
+        }
+        match node {
+            Some(node) if self[node].is_active() => Some(node),
+            _ => replacements[layer],
+        }
+    }
+
+    /// Returns the node on which an endpoint of a restored edge must be attached. If the node has
+    /// been removed, the edge is attached to the (new) replacement node of the layer.
+    fn restored_endpoint(&mut self, node: Option<NodeIndex>, layer: usize, replacements: &mut [Option<NodeIndex>]) -> NodeIndex {
+        match self.current_endpoint(node, layer, replacements) {
+            Some(node) => node,
+            None => {
+                let replacement = self.add_node(layer, true);
+                replacements[layer] = Some(replacement);
+                replacement
+            },
+        }
+    }
+
+    /// Adds a node to the layer, in the slot of a deactivated node if any, and returns its index
     fn add_node(&mut self, layer: usize, relaxed: bool) -> NodeIndex {
+        if let Some(index_in_layer) = self.free_nodes[layer].pop() {
+            let index = NodeIndex(layer, index_in_layer);
+            self.nodes[layer][index_in_layer] = Node::new(layer, index_in_layer, relaxed);
+            // The edges removed from the previous node of the slot are not restored on this one
+            for position in self.removed_by_node.remove(&index).unwrap_or_default() {
+                let edge = &mut self.removed_edges[position];
+                if edge.from == Some(index) {
+                    edge.from = None;
+                }
+                if edge.to == Some(index) {
+                    edge.to = None;
+                }
+            }
+            return index;
+        }
         let index_in_layer = self.nodes[layer].len();
         let node = Node::new(layer, index_in_layer, relaxed);
         let index = NodeIndex(layer, index_in_layer);
         self.nodes[layer].push(node);
-        for constraint in (0..self.problem.number_constraints()).map(ConstraintIndex) {
-            self.problem[constraint].add_node_in_layer(layer);
+        if index_in_layer == self.constraint_slots[layer] {
+            for constraint in (0..self.problem.number_constraints()).map(ConstraintIndex) {
+                self.problem[constraint].add_node_in_layer(layer);
+            }
+            self.constraint_slots[layer] += 1;
         }
         index
     }
 
+    /// Deactivates the node and makes its slot available to the next added node of its layer. The
+    /// slots of the root and the sink are never reused.
+    fn free_node(&mut self, node: NodeIndex) {
+        if self[node].is_active() && node != self.root && node != self.sink {
+            self.free_nodes[node.0].push(node.1);
+        }
+        if self[node].is_active() && !self.saved_states.is_empty() {
+            self.trail.push(TrailEntry::Node(node));
+        }
+        self[node].deactivate();
+    }
+
     fn add_edge(&mut self, layer: usize, from: NodeIndex, to: NodeIndex, assignment: ValueIndex) {
-        let edge_index = EdgeIndex(layer, self.edges[layer].len());
+        let edge_index = EdgeIndex(layer, self.edges[layer].push(from, to, assignment));
         self[from].add_child_edge(edge_index);
         self[to].add_parent_edge(edge_index);
-        let edge = Edge::new(from, to, assignment);
-        self.edges[layer].push(edge);
+    }
+
+    pub fn edge(&self, index: EdgeIndex) -> Edge {
+        self.edges[index.0].get(index.1)
+    }
+
+    /// Limits the number of rounds of the next propagations, the edges being possibly not all
+    /// filtered if the fixpoint is not reached within the limit. The propagation is not limited by
+    /// default.
+    pub fn set_max_propagation_rounds(&mut self, rounds: usize) {
+        assert!(rounds > 0, "The propagation needs at least one round");
+        self.max_propagation_rounds = rounds;
+    }
+
+    /// Sets the weight of the edges assigning each value to each variable. The weights are used by
+    /// the shortest and longest paths and the weighted count.
+    pub fn set_edge_weights(&mut self, weight: impl Fn(VariableIndex, isize) -> f64) {
+        self.weights = Some(self.problem.iter_variables().map(|variable| {
+            self.problem[variable].iter_domain().map(|value| weight(variable, value)).collect()
+        }).collect());
+    }
+
+    /// Sets the weight of the edges to the probability of their assignment, such that the weighted
+    /// count is the probability mass of the MDD
+    pub fn set_edge_weights_from_probabilities(&mut self) {
+        self.weights = Some(self.problem.iter_variables().map(|variable| {
+            (0..self.problem[variable].domain_size()).map(|index| self.problem[variable].probability(ValueIndex(index))).collect()
+        }).collect());
+    }
+
+    /// Removes the nodes exceeding the width during the next refinements instead of merging them.
+    /// The paths through the removed nodes are lost, hence the refined MDD is exact on a subset of
+    /// the solutions, and its number of paths is a lower bound on the number of solutions.
+    pub fn set_restricted(&mut self, restricted: bool) {
+        self.restricted = restricted;
+    }
+
+    /// Breaks the ties between the nodes with the same score for the merge heuristic with a
+    /// random order drawn from the seed, instead of the order of their indexes, during the next
+    /// refinements. The random number generator of `Mdd::sample` on the current thread is
+    /// reseeded as well. Two compilations of the same problem with the same parameters and seed
+    /// give the same MDD.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        RNG.with_borrow_mut(|rng| *rng = Xoshiro256Plus::seed_from_u64(seed));
+    }
+
+    /// Limits the estimated memory of the MDD (see `Mdd::estimated_memory`) during the next
+    /// refinements. When a refined layer brings the MDD over the budget, the action is taken, and
+    /// the refinement stops if the MDD is still over the budget (see `Mdd::is_budget_exceeded`).
+    /// The MDD is then a valid relaxation, only less refined. The memory is not limited by default.
+    pub fn set_memory_budget(&mut self, bytes: usize, action: BudgetAction) {
+        self.memory_budget = Some((bytes, action));
+        self.budget_exceeded = false;
+    }
+
+    /// Returns true if the last refinement was stopped by the memory budget
+    pub fn is_budget_exceeded(&self) -> bool {
+        self.budget_exceeded
+    }
+
+    /// Returns an estimate of the number of bytes allocated for the MDD: its nodes and edges, the
+    /// recorded edges removed by the propagation, and the states of the constraints on the nodes
+    pub fn estimated_memory(&self) -> usize {
+        let nodes = self.nodes.iter().map(|layer| layer.iter().map(|node| node.memory()).sum::<usize>() + (layer.capacity() - layer.len()) * std::mem::size_of::<Node>()).sum::<usize>();
+        let edges = self.edges.iter().map(|edges| edges.memory()).sum::<usize>();
+        let removed = self.removed_edges.capacity() * std::mem::size_of::<RemovedEdge>() + self.removed_by_node.values().map(|positions| positions.capacity() * std::mem::size_of::<usize>()).sum::<usize>();
+        let properties = self.constraint_slots.iter().sum::<usize>() * self.problem.number_constraints() * PROPERTY_BYTES;
+        nodes + edges + removed + properties
+    }
+
+    /// Returns true if the MDD is within its memory budget, after taking the action of the budget
+    /// if it was exceeded
+    fn check_memory_budget(&mut self) -> bool {
+        let Some((bytes, action)) = self.memory_budget else { return true };
+        if self.estimated_memory() > bytes && action == BudgetAction::Compact {
+            self.compact();
+        }
+        self.budget_exceeded = self.estimated_memory() > bytes;
+        #[cfg(feature = "tracing")]
+        if self.budget_exceeded {
+            tracing::debug!(bytes, estimated = self.estimated_memory(), "the refinement stopped at the memory budget");
+        }
+        !self.budget_exceeded
+    }
+
+    /// Releases the unused capacity of the storage of the nodes and edges
+    fn compact(&mut self) {
+        for layer in self.nodes.iter_mut() {
+            layer.shrink_to_fit();
+            for node in layer.iter_mut() {
+                node.shrink_to_fit();
+            }
+        }
+        for edges in self.edges.iter_mut() {
+            edges.shrink_to_fit();
+        }
+        for free_nodes in self.free_nodes.iter_mut() {
+            free_nodes.shrink_to_fit();
+        }
+        self.removed_edges.shrink_to_fit();
+        self.removed_by_node.shrink_to_fit();
+    }
+
+    /// Returns the keys breaking the ties between the nodes of the layer ranked by the merge
+    /// heuristic, the node with the smallest key being ranked first: the indexes of the nodes,
+    /// or random keys drawn from the seed and the layer if the seed is set
+    pub(crate) fn tie_keys(&self, layer: usize) -> Vec<u64> {
+        let n = self.number_nodes_in_layer(layer);
+        match self.seed {
+            Some(seed) => {
+                let mut rng = Xoshiro256Plus::seed_from_u64(seed ^ (layer as u64).wrapping_mul(0x9e3779b97f4a7c15));
+                (0..n).map(|_| rng.random::<u64>()).collect()
+            },
+            None => (0..n as u64).collect(),
+        }
+    }
+
+    pub fn clear_edge_weights(&mut self) {
+        self.weights = None;
+    }
+
+    /// Returns the weight of the edges assigning the value index to the variable, 1 if no weights
+    /// are set
+    pub fn assignment_weight(&self, variable: VariableIndex, assignment: ValueIndex) -> f64 {
+        self.weights.as_ref().map_or(1.0, |weights| weights[variable.0][assignment.0])
+    }
+
+    pub fn edge_weight(&self, index: EdgeIndex) -> f64 {
+        self.assignment_weight(self.order[index.0], self.edge(index).assignment())
     }
 
     pub fn decision_at_layer(&self, layer: usize) -> VariableIndex {
         self.order[layer]
     }
 
+    pub fn problem(&self) -> &Problem {
+        &self.problem
+    }
+
     // --- split and refine strategy ---- //
 
     pub fn refine(&mut self) {
-        if self.unsat {
+        self.refine_with(|_, _| {});
+    }
+
+    /// Refines the MDD as `Mdd::refine`, calling the function with the MDD after each refined
+    /// layer. The number of paths is maintained during the refinement, hence the function can read
+    /// the upper bound on the number of solutions (see `Mdd::count_solutions`) at each step.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "refine", skip_all))]
+    pub fn refine_with(&mut self, mut after_layer: impl FnMut(&Mdd, usize)) {
+        assert!(self.saved_states.is_empty(), "The MDD cannot be refined while a state is saved");
+        if self.repair_enabled.is_none() {
+            self.repair_enabled = Some(false);
+            self.removed_edges = vec![];
+            self.removed_by_node = FxHashMap::default();
+        }
+        if self.unsat || !self.check_memory_budget() {
             return;
         }
         for layer in 1..self.nodes.len() - 1 {
-            if self.number_nodes_in_layer(layer) == self.max_width {
+            // The node of a restricted layer is split even at the maximum width, the extra nodes
+            // being removed, to make it exact
+            if self.number_nodes_in_layer(layer) == self.max_width && !self.restricted {
                 continue;
             }
+            #[cfg(feature = "tracing")]
+            let _round = tracing::debug_span!("refine_layer", layer, width = self.number_nodes_in_layer(layer)).entered();
             let node = NodeIndex(layer, 0);
             self.split_node(node);
+            self.record_frame(&format!("split layer {}", layer));
             self.propagate_constraints();
+            self.record_frame("propagate");
             if !self[self.root].is_active() || !self[self.sink].is_active() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("the MDD is unsat");
                 self.unsat = true;
                 return;
             }
             self.collapse();
+            self.record_frame("collapse");
             self.merge_layer(layer);
+            self.record_frame(&format!("merge layer {}", layer));
             self.clean();
+            self.record_frame("clean");
+            after_layer(self, layer);
+            if !self.check_memory_budget() {
+                return;
+            }
+        }
+    }
+
+    /// Starts recording the trace of the compilation (see `Trace`), with a graphviz frame after
+    /// each phase of the refinement if asked. The trace is reset if it was already recording.
+    pub fn enable_trace(&mut self, record_frames: bool) {
+        self.trace = Some(Trace::new(record_frames));
+        self.record_frame("initial");
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Stops recording the trace and returns it
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    fn record_event(&mut self, event: TraceEvent) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.events.push(event);
+        }
+    }
+
+    fn record_frame(&mut self, phase: &str) {
+        if self.trace.as_ref().is_some_and(|trace| trace.record_frames()) {
+            let graphviz = self.as_graphviz();
+            let trace = self.trace.as_mut().unwrap();
+            trace.frames.push(TraceFrame { number_events: trace.events.len(), phase: phase.to_string(), graphviz });
         }
     }
 
@@ -157,114 +756,249 @@ impl Mdd {
         let n = self[node].number_parents();
         let outgoing_assignments = self[node]
             .iter_children()
-            .filter(|edge| self[*edge].is_active())
-            .map(|edge| (self[edge].to(), self[edge].assignment()))
+            .filter(|edge| self.edge(*edge).is_active())
+            .map(|edge| (self.edge(edge).to(), self.edge(edge).assignment()))
             .collect::<Vec<(NodeIndex, ValueIndex)>>();
         self[node].set_relaxed(false);
+        // The new nodes inherit the edges removed from the node, to be restored on them as well
+        let removed_children = self.removed_by_node.get(&node).into_iter().flatten().map(|position| &self.removed_edges[*position]).filter(|edge| edge.layer == layer && edge.from == Some(node)).cloned().collect::<Vec<RemovedEdge>>();
+        let mut new_nodes = vec![];
         for i in (1..n).rev() {
             let new_node = self.add_node(layer, false);
+            new_nodes.push(new_node);
+            for edge in removed_children.iter() {
+                self.push_removed_edge(RemovedEdge { from: Some(new_node), ..*edge });
+            }
             let edge = self[node].parent_edge_at(i);
-            let from = self[edge].from();
-            let assignment = self[edge].assignment();
+            let from = self.edge(edge).from();
+            let assignment = self.edge(edge).assignment();
             self.add_edge(layer - 1, from, new_node, assignment);
             for (child, outgoing_assignment) in outgoing_assignments.iter().copied() {
                 self.add_edge(layer, new_node, child, outgoing_assignment);
             }
-            self[edge].deactivate();
+            self[from].remove_child_edge(edge);
             self[node].swap_remove_parent_edge(i);
+            self.edges[edge.0].deactivate(edge.1);
         }
+        self.record_event(TraceEvent::Split { node, new_nodes });
     }
 
 
+    /// Propagates the constraints until no constraint removes any edge, or until the maximum number
+    /// of propagation rounds is reached (see `Mdd::set_max_propagation_rounds`)
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub fn propagate_constraints(&mut self) {
-        let number_layers = self.nodes.len();
+        #[cfg(feature = "tracing")]
+        let number_removed = self.propagation_statistics.iter().map(|statistics| statistics.removed_edges).collect::<Vec<usize>>();
 
-        // Top-down pass.
-        for layer in 1..number_layers {
-            let variable = self.order[layer - 1];
-            let nodes_in_layer = self.nodes[layer].len();
-            for i in 0..nodes_in_layer {
-                let target = NodeIndex(layer, i);
-                for constraint in (0..self.problem.number_constraints()).map(ConstraintIndex) {
-                    self.problem[constraint].reset_property_top_down(target);
-                    for j in 0..self[target].number_parents() {
-                        let edge = self[target].parent_edge_at(j);
-                        let source = self[edge].from();
-                        let assignment = self.problem[variable].value(self[edge].assignment());
-                        self.problem[constraint].update_property_top_down(source, target, assignment);
-                    }
+        let _fixpoint = self.propagate_to_fixpoint(self.max_propagation_rounds);
+        #[cfg(feature = "tracing")]
+        if !_fixpoint {
+            tracing::debug!(rounds = self.max_propagation_rounds, "the propagation stopped before its fixpoint");
+        }
+        #[cfg(feature = "tracing")]
+        for (constraint, statistics) in self.propagation_statistics.iter().enumerate() {
+            let removed = statistics.removed_edges - number_removed.get(constraint).copied().unwrap_or(0);
+            if removed > 0 {
+                tracing::debug!(constraint, removed, "edges removed by the propagation");
+            }
+        }
+    }
 
+    /// Propagates the constraints in rounds, and returns true if the fixpoint is reached within the
+    /// maximum number of rounds (or if the MDD becomes unsat). Every constraint filters the edges
+    /// in the first round, then the constraints watching a layer whose edges changed are pending
+    /// (see `propagation::Watches`). Each round propagates the pending constraints of the cheapest
+    /// priority class (see `Constraint::priority`), the ones with the fewest edges to check first,
+    /// hence the expensive constraints wait for the cheap ones to remove no more edges. Since the
+    /// removals change the states of every layer, every constraint is propagated again once none
+    /// is pending, and the fixpoint is reached when such a round removes nothing. The entailed
+    /// constraints (see `Mdd::entailed_constraints`), which can not remove any edge, are checked
+    /// before each of these rounds and are not scheduled until the next one.
+    fn propagate_to_fixpoint(&mut self, max_rounds: usize) -> bool {
+        let watches = propagation::Watches::new(&self.problem, self.edges.len());
+        let key = |mdd: &Mdd, constraint: ConstraintIndex| (mdd.problem[constraint].priority(), watches.watched(constraint).iter().map(|layer| mdd.edges[*layer].len()).sum::<usize>());
+        let every_layer = vec![true; self.edges.len()];
+        self.propagation_statistics.resize(self.problem.number_constraints(), PropagationStatistics::default());
+        let mut pending = vec![false; self.problem.number_constraints()];
+        let mut entailed = vec![false; self.problem.number_constraints()];
+        let mut changed = every_layer.clone();
+        let mut full_round = false;
+        for round in 0..max_rounds {
+            for constraint in watches.schedule(&changed, |_| 0) {
+                pending[constraint.0] = !entailed[constraint.0];
+            }
+            let scheduled = if round == 0 || !pending.contains(&true) {
+                if full_round {
+                    return true;
                 }
+                full_round = true;
+                entailed.fill(false);
+                for constraint in self.entailed_constraints() {
+                    entailed[constraint.0] = true;
+                }
+                watches.schedule(&every_layer, |constraint| key(self, constraint)).into_iter().filter(|constraint| !entailed[constraint.0]).collect()
+            } else {
+                full_round = false;
+                let mut scheduled = self.problem.iter_constraints().filter(|constraint| pending[constraint.0]).collect::<Vec<ConstraintIndex>>();
+                scheduled.sort_by_key(|constraint| key(self, *constraint));
+                let cheapest = self.problem[scheduled[0]].priority();
+                scheduled.retain(|constraint| self.problem[*constraint].priority() == cheapest);
+                scheduled
+            };
+            for constraint in scheduled.iter() {
+                pending[constraint.0] = false;
+            }
+            // The propagation only removes edges, hence a layer has changed if it has fewer edges
+            let number_edges = self.edges.iter().map(|edges| edges.number_active()).collect::<Vec<usize>>();
+            self.propagation_round(&scheduled, &watches);
+            if !self[self.root].is_active() || !self[self.sink].is_active() {
+                return true;
+            }
+            for (layer, changed) in changed.iter_mut().enumerate() {
+                *changed = self.edges[layer].number_active() != number_edges[layer];
             }
         }
+        full_round && !changed.contains(&true)
+    }
+
+    /// Updates the properties of the scheduled constraints, and of the ones watching no layer, then
+    /// filters the edges with the scheduled constraints, in their order. The properties of the other
+    /// constraints are left as computed on a superset of the edges, hence they stay relaxed.
+    fn propagation_round(&mut self, scheduled: &[ConstraintIndex], watches: &propagation::Watches) {
+        let number_layers = self.nodes.len();
+        let constraints = self.problem.iter_constraints().filter(|constraint| scheduled.contains(constraint) || watches.watched(*constraint).is_empty()).collect::<Vec<ConstraintIndex>>();
+        for constraint in scheduled.iter() {
+            self.propagation_statistics[constraint.0].invocations += 1;
+        }
+        for layer in 1..number_layers {
+            propagation::timed(&constraints, self.propagation_timing.then_some(self.propagation_statistics.as_mut_slice()), |constraints| {
+                propagation::top_down_pass(&mut self.problem, constraints, layer, self.nodes[layer].len(), &self.edges[layer - 1], self.order[layer - 1]);
+            });
+        }
 
-        // We start by the bottom-up pass. We filter edges in this pass
+        // We start by the bottom-up pass. We filter edges in this pass. The edges of a layer are
+        // walked once to update the properties of the constraints of the round, then once to filter them
+        // with the scheduled constraints watching the layer.
         for layer in (0..number_layers - 1).rev() {
             let decision = self.order[layer];
-            let nodes_in_layer = self.nodes[layer].len();
-            for node_index in 0..nodes_in_layer {
-                let target = NodeIndex(layer, node_index);
+            propagation::timed(&constraints, self.propagation_timing.then_some(self.propagation_statistics.as_mut_slice()), |constraints| {
+                propagation::bottom_up_pass(&mut self.problem, constraints, layer, &self.nodes[layer], &self.edges[layer], decision);
+            });
+            let watchers = scheduled.iter().copied().filter(|constraint| watches.watchers(layer).contains(constraint)).collect::<Vec<ConstraintIndex>>();
+            if watchers.is_empty() {
+                continue;
+            }
+            for index in 0..self.edges[layer].len() {
+                // Edges are also removed when their nodes are removed by a previous edge
+                if !self.edges[layer].is_active(index) {
+                    continue;
+                }
+                let edge = EdgeIndex(layer, index);
+                let target = self.edges[layer].from(index);
+                let source = self.edges[layer].to(index);
                 if !self[target].is_active() {
                     continue;
                 }
-                for constraint in (0..self.problem.number_constraints()).map(ConstraintIndex) {
-                    for edge_index in 0..self[target].number_children() {
-                        if edge_index == 0 {
-                            self.problem[constraint].reset_property_bottom_up(target);
-                        }
-                        let edge = self.nodes[layer][node_index].child_edge_at(edge_index);
-                        let source = self[edge].to();
-                        let assignment = self.problem[decision].value(self[edge].assignment());
-                        self.problem[constraint].update_property_bottom_up(source, target, assignment);
+                if let Some(constraint) = propagation::invalidating_constraint(&self.problem, &watchers, &self.edges[layer], index, decision, self.propagation_timing.then_some(self.propagation_statistics.as_mut_slice())) {
+                    self.record_removal(edge, Some(constraint));
+                    self[target].remove_child_edge(edge);
+                    if self[target].number_children() == 0 {
+                        self.remove_node(target, Some(constraint));
                     }
-                    for edge_index in (0..self[target].number_children()).rev() {
-                        let edge = self.nodes[layer][node_index].child_edge_at(edge_index);
-                        let source = self[edge].to();
-                        let assignment = self.problem[decision].value(self[edge].assignment());
-                        if self.problem[constraint].is_layer_in_scope(layer) && self.problem[constraint].is_assignment_invalid(target, source, decision, assignment) {
-                            self[target].swap_remove_child_edge(edge_index);
-                            if self[target].number_children() == 0 {
-                                self.remove_node(target);
-                            }
-                            self[source].remove_parent_edge(edge);
-                            if self[source].number_parents() == 0 {
-                                self.remove_node(source);
-                            }
-                            self[edge].deactivate();
-                        }
+                    self[source].remove_parent_edge(edge);
+                    if self[source].number_parents() == 0 {
+                        self.remove_node(source, Some(constraint));
                     }
+                    self.deactivate_edge(edge);
                 }
             }
         }
     }
 
-    fn remove_node(&mut self, node: NodeIndex) {
+    fn update_properties_top_down(&mut self) {
+        let constraints = self.problem.iter_constraints().collect::<Vec<ConstraintIndex>>();
+        for layer in 1..self.nodes.len() {
+            propagation::top_down_pass(&mut self.problem, &constraints, layer, self.nodes[layer].len(), &self.edges[layer - 1], self.order[layer - 1]);
+        }
+    }
+
+    /// Computes the properties of the nodes without filtering the edges. The properties of the
+    /// constraints are not compacted with the nodes (see `Mdd::clean`), hence this must be done
+    /// before reading them on a cleaned MDD.
+    fn update_properties(&mut self) {
+        self.update_properties_top_down();
+        let constraints = self.problem.iter_constraints().collect::<Vec<ConstraintIndex>>();
+        for layer in (0..self.nodes.len() - 1).rev() {
+            propagation::bottom_up_pass(&mut self.problem, &constraints, layer, &self.nodes[layer], &self.edges[layer], self.order[layer]);
+        }
+    }
+
+    /// Removes the node and the nodes left without parents or children. The removed edges are
+    /// recorded if the removal is caused by the propagation of a constraint.
+    fn remove_node(&mut self, node: NodeIndex, cause: Option<ConstraintIndex>) {
         if !self[node].is_active() {
             return;
         }
-        self[node].deactivate();
+        self.free_node(node);
         for i in 0..self[node].number_parents() {
             let edge = self[node].parent_edge_at(i);
-            self[edge].deactivate();
-            let parent = self[edge].from();
+            self.record_removal(edge, cause);
+            self.deactivate_edge(edge);
+            let parent = self.edge(edge).from();
             self[parent].remove_child_edge(edge);
             if self[parent].number_children() == 0 {
-                self.remove_node(parent);
+                self.remove_node(parent, cause);
             }
         }
         for i in 0..self[node].number_children() {
             let edge = self[node].child_edge_at(i);
-            self[edge].deactivate();
-            let child = self[edge].to();
+            self.record_removal(edge, cause);
+            self.deactivate_edge(edge);
+            let child = self.edge(edge).to();
             self[child].remove_parent_edge(edge);
             if self[child].number_parents() == 0 {
-                self.remove_node(child);
+                self.remove_node(child, cause);
+            }
+        }
+    }
+
+    fn record_removal(&mut self, edge: EdgeIndex, cause: Option<ConstraintIndex>) {
+        if self.edge(edge).is_active() {
+            let EdgeIndex(layer, _) = edge;
+            self.record_event(TraceEvent::RemoveEdge { layer, from: self.edge(edge).from(), to: self.edge(edge).to(), assignment: self.edge(edge).assignment(), constraint: cause });
+        }
+        if let Some(constraint) = cause && self.edge(edge).is_active() {
+            self.propagation_statistics[constraint.0].removed_edges += 1;
+            if self.repair_enabled != Some(false) {
+                let EdgeIndex(layer, _) = edge;
+                self.push_removed_edge(RemovedEdge { layer, from: Some(self.edge(edge).from()), to: Some(self.edge(edge).to()), assignment: self.edge(edge).assignment(), constraint });
+            }
+        }
+    }
+
+    /// Records the removed edge and indexes it by its endpoints
+    fn push_removed_edge(&mut self, edge: RemovedEdge) {
+        let position = self.removed_edges.len();
+        for node in [edge.from, edge.to].into_iter().flatten() {
+            self.removed_by_node.entry(node).or_default().push(position);
+        }
+        self.removed_edges.push(edge);
+    }
+
+    /// Rebuilds the index of the removed edges by their endpoints (see `Mdd::removed_by_node`)
+    fn index_removed_edges(&mut self) {
+        self.removed_by_node.clear();
+        for (position, edge) in self.removed_edges.iter().enumerate() {
+            for node in [edge.from, edge.to].into_iter().flatten() {
+                self.removed_by_node.entry(node).or_default().push(position);
             }
         }
     }
 
     fn collapse(&mut self) {
         for layer in 1..self.nodes.len() - 1 {
+            let mut merges = vec![];
             let mut map: FxHashMap<MergeKey, NodeIndex> = FxHashMap::default();
             for index in 0..self.nodes[layer].len() {
                 let node = NodeIndex(layer, index);
@@ -279,50 +1013,74 @@ impl Mdd {
 
                     let NodeIndex(primary_layer, primary_index) = primary_node;
 
+                    // The nodes are merged only if no assignment leads to different children, so that
+                    // the merged node has a single child per assignment and its paths are not duplicated
+                    let mut existing_children = FxHashMap::<ValueIndex, NodeIndex>::default();
+                    for i in 0..self[primary_node].number_children() {
+                        let edge = self[primary_node].child_edge_at(i);
+                        if self.edge(edge).is_active() {
+                            existing_children.insert(self.edge(edge).assignment(), self.edge(edge).to());
+                        }
+                    }
+                    let conflict = self[node].iter_children().filter(|edge| self.edge(*edge).is_active()).any(|edge| {
+                        existing_children.get(&self.edge(edge).assignment()).is_some_and(|child| *child != self.edge(edge).to())
+                    });
+                    if conflict {
+                        continue;
+                    }
+
                     for i in 0..self[node].number_parents() {
                         let EdgeIndex(edge_layer, edge_index) = self[node].parent_edge_at(i);
-                        self.edges[edge_layer][edge_index].set_to(primary_node);
+                        self.edges[edge_layer].set_to(edge_index, primary_node);
                         self.nodes[primary_layer][primary_index].add_parent_edge(EdgeIndex(edge_layer, edge_index));
                     }
 
-                    let mut existing_children = FxHashSet::<(NodeIndex, ValueIndex)>::default();
-                    for i in 0..self[primary_node].number_children() {
-                        let edge = self[primary_node].child_edge_at(i);
-                        let child = self[edge].to();
-                        let assignment = self[edge].assignment();
-                        existing_children.insert((child, assignment));
-                    }
-
                     for i in 0..self[node].number_children() {
                         let edge = self[node].child_edge_at(i);
                         let EdgeIndex(edge_layer, edge_index) = edge;
-                        let child = self[edge].to();
-                        let assignment = self[edge].assignment();
-                        if !existing_children.contains(&(child, assignment)) {
-                            self.edges[edge_layer][edge_index].set_to(primary_node);
+                        let child = self.edge(edge).to();
+                        if !existing_children.contains_key(&self.edge(edge).assignment()) {
+                            self.edges[edge_layer].set_from(edge_index, primary_node);
                             self.nodes[primary_layer][primary_index].add_child_edge(edge);
+                        } else {
+                            // The same edge leaves the primary node
+                            self.edges[edge_layer].deactivate(edge_index);
+                            self.nodes[child.0][child.1].remove_parent_edge(edge);
                         }
                     }
                     self.nodes[layer][index].deactivate();
+                    self.free_nodes[layer].push(index);
+                    merges.push(TraceEvent::Merge { from: node, into: primary_node, exact: true });
                 } else {
                     map.insert(key, node);
                 }
             }
+            for event in merges {
+                self.record_event(event);
+            }
         }
     }
 
     fn merge_layer(&mut self, layer :usize) {
-        let number_nodes = self.nodes[layer].len();
+        // The nodes merged by the collapse are still in the layer until it is cleaned
+        let number_nodes = self.nodes[layer].iter().filter(|node| node.is_active()).count();
         if number_nodes <= self.max_width {
             return;
         }
-        let node_ranks = self.merge_heuristic.rank_nodes(self, layer);
+        let node_ranks = self.merge_heuristic.rank_nodes(self, layer).into_iter().filter(|(_, index)| self.nodes[layer][*index].is_active()).collect::<Vec<(f64, usize)>>();
+        if self.restricted {
+            for (_, index) in node_ranks.iter().copied().skip(self.max_width) {
+                self.remove_node(NodeIndex(layer, index), None);
+            }
+            return;
+        }
         let into = NodeIndex(layer, node_ranks[self.max_width - 1].1);
         self[into].set_relaxed(true);
-        for i in self.max_width..number_nodes {
-            let from = NodeIndex(layer, node_ranks[i].1);
+        for (_, index) in node_ranks.iter().copied().skip(self.max_width) {
+            let from = NodeIndex(layer, index);
             self.merge_nodes(from, into);
-            self[from].deactivate();
+            self.free_node(from);
+            self.record_event(TraceEvent::Merge { from, into, exact: false });
         }
     }
 
@@ -330,30 +1088,38 @@ impl Mdd {
         self[into].set_relaxed(true);
         for i in 0..self[from].number_parents() {
             let edge = self[from].parent_edge_at(i);
-            self[edge].set_to(into);
+            self.edges[edge.0].set_to(edge.1, into);
             self[into].add_parent_edge(edge);
         }
 
         let mut existing_children = FxHashSet::<(NodeIndex, ValueIndex)>::default();
         for i in 0..self[into].number_children() {
             let edge = self[into].child_edge_at(i);
-            let child = self[edge].to();
-            let assignment = self[edge].assignment();
+            let child = self.edge(edge).to();
+            let assignment = self.edge(edge).assignment();
             existing_children.insert((child, assignment));
         }
 
         for i in 0..self[from].number_children() {
             let edge = self[from].child_edge_at(i);
-            let child = self[edge].to();
-            let assignment = self[edge].assignment();
+            let child = self.edge(edge).to();
+            let assignment = self.edge(edge).assignment();
             if !existing_children.contains(&(child, assignment)) {
-                self[edge].set_from(into);
+                self.edges[edge.0].set_from(edge.1, into);
                 self[into].add_child_edge(edge);
             }
         }
     }
 
     fn clean(&mut self) {
+        // The nodes and edges keep their slots while a state is saved, to be restored in place
+        if !self.saved_states.is_empty() {
+            self.update_path_counts();
+            return;
+        }
+        for free_nodes in self.free_nodes.iter_mut() {
+            free_nodes.clear();
+        }
         let mut map_node_index = FxHashMap::<NodeIndex, NodeIndex>::default();
         map_node_index.insert(self.root, self.root);
         map_node_index.insert(self.sink, self.sink);
@@ -368,19 +1134,19 @@ impl Mdd {
             }
             self.nodes[layer].truncate(new_index);
         }
+        for edge in self.removed_edges.iter_mut() {
+            edge.from = edge.from.and_then(|node| map_node_index.get(&node).copied());
+            edge.to = edge.to.and_then(|node| map_node_index.get(&node).copied());
+        }
+        self.index_removed_edges();
         let mut map_edge_index = FxHashMap::<EdgeIndex, EdgeIndex>::default();
         for layer in 0..self.edges.len() {
-            let mut new_index = 0;
-            for index in 0..self.edges[layer].len() {
-                let from = self.edges[layer][index].from();
-                let to = self.edges[layer][index].to();
-                if self.edges[layer][index].is_active() && !(map_node_index.get(&from).is_none() || map_node_index.get(&to).is_none()) {
+            let new_indices = self.edges[layer].retain(|from, to| map_node_index.contains_key(&from) && map_node_index.contains_key(&to));
+            for (index, new_index) in new_indices.into_iter().enumerate() {
+                if let Some(new_index) = new_index {
                     map_edge_index.insert(EdgeIndex(layer, index), EdgeIndex(layer, new_index));
-                    self.edges[layer].swap(new_index, index);
-                    new_index += 1;
                 }
             }
-            self.edges[layer].truncate(new_index);
         }
 
         for layer in 0..self.nodes.len() {
@@ -388,10 +1154,75 @@ impl Mdd {
                 self.nodes[layer][index].update_edge_indices(&map_edge_index);
             }
             if layer > 0 {
-                for index in 0..self.edges[layer - 1].len() {
-                    self.edges[layer - 1][index].update_node_indices(&map_node_index);
+                self.edges[layer - 1].update_node_indices(&map_node_index);
+            }
+        }
+        self.update_path_counts();
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+    }
+
+    /// Updates the number of paths from the root to each node. The counts of a layer only depend on
+    /// the edges above it, hence they are only recomputed below the first layer whose edges changed
+    /// since the last update.
+    fn update_path_counts(&mut self) {
+        let root = self.root;
+        self[root].set_path_count(1);
+        let Some(first_changed) = self.edges.iter().position(|edges| edges.is_changed()) else { return };
+        for layer in first_changed + 1..self.nodes.len() {
+            let counts = self.nodes[layer].iter().map(|node| {
+                node.iter_parents().map(|edge| self.nodes[layer - 1][self.edge(edge).from().1].path_count()).fold(0u128, u128::saturating_add)
+            }).collect::<Vec<u128>>();
+            for (node, count) in self.nodes[layer].iter_mut().zip(counts) {
+                node.set_path_count(count);
+            }
+            self.edges[layer - 1].clear_changed();
+        }
+    }
+
+    /// Checks the structure of the MDD and panics if it is broken: the edges connect consecutive
+    /// layers, the endpoints of the active edges are active, the edges of the nodes are the active
+    /// edges from (or to) them, the active nodes have parents (except the root) and children
+    /// (except the sink), and the constraints have a state for every node. It only holds between
+    /// the refinement steps (once the MDD is cleaned), and is checked after each clean in debug
+    /// builds. Nothing is checked if the MDD is unsat.
+    pub fn check_invariants(&self) {
+        if self.unsat || !self[self.root].is_active() || !self[self.sink].is_active() {
+            return;
+        }
+        for layer in 0..self.nodes.len() {
+            assert!(self.constraint_slots[layer] >= self.nodes[layer].len(), "The constraints have no state for some nodes of layer {}", layer);
+            for index in (0..self.nodes[layer].len()).filter(|index| self.nodes[layer][*index].is_active()) {
+                let node = NodeIndex(layer, index);
+                assert!(layer == 0 || self[node].number_parents() > 0, "The node {:?} has no parent", node);
+                assert!(layer == self.nodes.len() - 1 || self[node].number_children() > 0, "The node {:?} has no child", node);
+                for edge in self[node].iter_parents() {
+                    assert!(edge.0 + 1 == layer && self.edges[edge.0].is_active(edge.1) && self.edge(edge).to() == node, "The parent edge {:?} of the node {:?} does not end at the node", edge, node);
                 }
+                for edge in self[node].iter_children() {
+                    assert!(edge.0 == layer && self.edges[edge.0].is_active(edge.1) && self.edge(edge).from() == node, "The child edge {:?} of the node {:?} does not start at the node", edge, node);
+                }
+            }
+        }
+        for layer in 0..self.edges.len() {
+            let mut number_edges = 0;
+            let mut supports = FxHashMap::<ValueIndex, usize>::default();
+            for edge in self.edges[layer].iter() {
+                let (from, to) = (edge.from(), edge.to());
+                assert!(from.0 == layer && to.0 == layer + 1, "The edge from {:?} to {:?} does not connect the layers {} and {}", from, to, layer, layer + 1);
+                assert!(self[from].is_active() && self[to].is_active(), "The edge from {:?} to {:?} has an inactive node", from, to);
+                number_edges += 1;
+                *supports.entry(edge.assignment()).or_default() += 1;
+            }
+            assert_eq!(self.edges[layer].supported_values().size(), supports.len(), "The supported values of layer {} do not match its edges", layer);
+            for (assignment, number) in supports {
+                assert_eq!(self.edges[layer].number_supports(assignment), number, "The supports of {:?} in layer {} do not match its edges", assignment, layer);
             }
+            // Each edge of the nodes is an active edge of the layer, hence every active edge is in
+            // the edges of its nodes if the numbers match
+            let number_children = (0..self.nodes[layer].len()).filter(|index| self.nodes[layer][*index].is_active()).map(|index| self.nodes[layer][index].number_children()).sum::<usize>();
+            let number_parents = (0..self.nodes[layer + 1].len()).filter(|index| self.nodes[layer + 1][*index].is_active()).map(|index| self.nodes[layer + 1][index].number_parents()).sum::<usize>();
+            assert!(number_children == number_edges && number_parents == number_edges, "The edges of the nodes do not match the {} active edges of layer {}", number_edges, layer);
         }
     }
 
@@ -411,31 +1242,219 @@ impl Mdd {
         self.nodes.len()
     }
 
-    pub fn get_solution(&self) -> Option<Vec<isize>> {
-        let mut assignment = vec![0; self.nodes.len() - 1];
-        let root = NodeIndex(0, 0);
-        if self.extract_solution(root, &mut assignment) {
-            Some(assignment)
-        } else {
-            None
-        }
+    /// Returns the number of paths from the root to the sink, i.e., the number of solutions if the
+    /// MDD is exact and an upper bound otherwise. The count saturates at `u128::MAX`. The counts
+    /// are maintained on the nodes as the MDD is refined, hence they are read in constant time
+    /// between the refinement steps, and recomputed if the MDD has changed since.
+    pub fn count_solutions(&self) -> u128 {
+        self.cached_count().unwrap_or_else(|| self.evaluate::<Counting>(|_, _| 1))
     }
 
-    fn extract_solution(&self, node: NodeIndex, assignment: &mut Vec<isize>) -> bool {
-        let layer = node.0;
-        if layer == self.nodes.len() - 1 {
-            return true;
+    /// Returns the number of paths maintained on the nodes, or None if the MDD has changed since
+    /// they were computed
+    pub(crate) fn cached_count(&self) -> Option<u128> {
+        if self.unsat {
+            return Some(0);
         }
-        if self[node].is_relaxed() {
+        if self.edges.iter().any(|edges| edges.is_changed()) {
+            return None;
+        }
+        Some(self[self.sink].path_count())
+    }
+
+    /// Returns the sum, over the paths from the root to the sink, of the product of the
+    /// probabilities of their assignments
+    pub fn probability_mass(&self) -> f64 {
+        self.evaluate::<SumProduct>(|variable, assignment| self.problem[variable].probability(assignment))
+    }
+
+    /// Returns the sum, over the paths from the root to the sink, of the product of the weights of
+    /// their edges
+    pub fn weighted_count(&self) -> f64 {
+        self.weighted_count_in::<SumProduct>()
+    }
+
+    /// Returns the weighted count (see `Mdd::weighted_count`) computed with the arithmetic of the
+    /// semiring
+    pub fn weighted_count_in<A: Arithmetic>(&self) -> A::Value {
+        self.evaluate::<A>(|variable, assignment| A::from_f64(self.assignment_weight(variable, assignment)))
+    }
+
+    /// Returns the minimum total weight of a path from the root to the sink, with the assignment
+    /// of the path, or None if the MDD is unsat. If the MDD is relaxed, the weight is a lower bound
+    /// and the assignment might not be a solution.
+    pub fn shortest_path(&self) -> Option<(f64, Vec<isize>)> {
+        self.best_path::<MinPlus>(|variable, assignment| self.assignment_weight(variable, assignment))
+    }
+
+    /// Returns the maximum total weight of a path from the root to the sink, with the assignment
+    /// of the path, or None if the MDD is unsat
+    pub fn longest_path(&self) -> Option<(f64, Vec<isize>)> {
+        self.best_path::<MaxPlus>(|variable, assignment| self.assignment_weight(variable, assignment))
+    }
+
+    /// Returns the best path for the sum of the semiring, whose product is the sum of the given
+    /// weights of the assignments
+    pub(crate) fn best_path<S: Semiring<Value = f64>>(&self, weight: impl Fn(VariableIndex, ValueIndex) -> f64 + Sync + Send) -> Option<(f64, Vec<isize>)> {
+        if self.unsat {
+            return None;
+        }
+        let extend = |value: f64, variable: VariableIndex, assignment: ValueIndex| S::mul(value, weight(variable, assignment));
+        // Best weight of a path from each node to the sink
+        let values = self.backward_values(S::one(), S::zero(), extend, S::add);
+        let weight = values[self.root.0][self.root.1];
+        if weight == S::zero() {
+            return None;
+        }
+        let mut assignment = vec![0; self.nodes.len() - 1];
+        let mut node = self.root;
+        while node != self.sink {
+            let variable = self.order[node.0];
+            let edge = self[node].iter_children().map(|edge| self.edge(edge))
+                .filter(|edge| edge.is_active())
+                .find(|edge| extend(values[edge.to().0][edge.to().1], variable, edge.assignment()) == values[node.0][node.1])
+                .unwrap();
+            assignment[variable.0] = self.problem[variable].value(edge.assignment());
+            node = edge.to();
+        }
+        Some((weight, assignment))
+    }
+
+    /// Evaluates the MDD in the semiring: the weights of the edges of a path are multiplied, and
+    /// the values of the paths from the root to the sink are summed. Returns the zero of the
+    /// semiring if the MDD is unsat.
+    pub fn evaluate<S: Semiring>(&self, weight: impl Fn(VariableIndex, ValueIndex) -> S::Value + Sync + Send) -> S::Value {
+        self.forward_pass(S::one(), S::zero(), |value, variable, assignment| S::mul(value, weight(variable, assignment)), S::add)
+    }
+
+    /// Returns, for each variable and each value of its domain, the probability that the variable
+    /// takes the value in a solution drawn with the probabilities of the assignments, i.e., the
+    /// probability mass of the paths assigning the value divided by the total probability mass.
+    /// The marginals are 0 if the MDD is unsat.
+    pub fn marginals(&self) -> Vec<Vec<f64>> {
+        self.marginals_in::<SumProduct>()
+    }
+
+    /// Returns the marginals (see `Mdd::marginals`) computed with the arithmetic of the semiring,
+    /// e.g., in log-space with `LogSumProduct` for problems whose paths have too small
+    /// probabilities for f64
+    pub fn marginals_in<A: Arithmetic>(&self) -> Vec<Vec<A::Value>> {
+        self.marginals_with::<A>(|variable, assignment| self.problem[variable].probability(assignment))
+    }
+
+    /// Returns, for each variable and each value of its domain, the weighted count of the paths
+    /// assigning the value divided by the weighted count of the MDD (see `Mdd::set_edge_weights`)
+    pub fn weighted_marginals(&self) -> Vec<Vec<f64>> {
+        self.marginals_with::<SumProduct>(|variable, assignment| self.assignment_weight(variable, assignment))
+    }
+
+    pub(crate) fn marginals_with<A: Arithmetic>(&self, weight: impl Fn(VariableIndex, ValueIndex) -> f64 + Sync + Send) -> Vec<Vec<A::Value>> {
+        let mut marginals = self.problem.iter_variables().map(|variable| vec![A::zero(); self.problem[variable].domain_size()]).collect::<Vec<Vec<A::Value>>>();
+        if self.unsat {
+            return marginals;
+        }
+        let weight = |mass: A::Value, variable: VariableIndex, assignment: ValueIndex| A::mul(mass, A::from_f64(weight(variable, assignment)));
+        let forward = self.forward_values(A::one(), A::zero(), weight, A::add);
+        let backward = self.backward_values(A::one(), A::zero(), weight, A::add);
+        let total = forward[self.sink.0][self.sink.1].clone();
+        if total == A::zero() {
+            return marginals;
+        }
+        // The layers are independent once the forward and backward values are known
+        let layer_marginals = map_items(&self.edges, |layer, edges| {
+            let variable = self.order[layer];
+            let mut masses = vec![A::zero(); self.problem[variable].domain_size()];
+            for edge in edges.iter() {
+                let assignment = edge.assignment();
+                let mass = A::mul(weight(forward[layer][edge.from().1].clone(), variable, assignment), backward[layer + 1][edge.to().1].clone());
+                masses[assignment.0] = A::add(std::mem::replace(&mut masses[assignment.0], A::zero()), mass);
+            }
+            masses
+        });
+        for (layer, masses) in layer_marginals.into_iter().enumerate() {
+            marginals[self.order[layer].0] = masses.into_iter().map(|mass| A::div(mass, total.clone())).collect();
+        }
+        marginals
+    }
+
+    /// Aggregates a value over the paths from the root to the sink, layer by layer: the value of a
+    /// path is extended along each edge, and the values of the paths reaching a node are summed,
+    /// starting from the given zero.
+    fn forward_pass<T: Clone + Send + Sync>(&self, root: T, zero: T, extend: impl Fn(T, VariableIndex, ValueIndex) -> T + Sync + Send, sum: impl Fn(T, T) -> T + Sync + Send) -> T {
+        if self.unsat {
+            return zero;
+        }
+        self.forward_values(root, zero, extend, sum)[self.sink.0][self.sink.1].clone()
+    }
+
+    /// Returns the value aggregated at each node over the paths from the root (see
+    /// `Mdd::forward_pass`). The nodes of a layer only depend on the previous layer, and are
+    /// computed in parallel with the `parallel` feature.
+    pub(crate) fn forward_values<T: Clone + Send + Sync>(&self, root: T, zero: T, extend: impl Fn(T, VariableIndex, ValueIndex) -> T + Sync + Send, sum: impl Fn(T, T) -> T + Sync + Send) -> Vec<Vec<T>> {
+        let mut values = vec![vec![zero.clone(); self.nodes[0].len()]];
+        values[0][self.root.1] = root;
+        for layer in 1..self.nodes.len() {
+            let variable = self.order[layer - 1];
+            let previous = &values[layer - 1];
+            let next = map_items(&self.nodes[layer], |_, node| {
+                if !node.is_active() {
+                    return zero.clone();
+                }
+                node.iter_parents().map(|edge| self.edge(edge)).filter(|edge| edge.is_active()).fold(zero.clone(), |value, edge| {
+                    sum(value, extend(previous[edge.from().1].clone(), variable, edge.assignment()))
+                })
+            });
+            values.push(next);
+        }
+        values
+    }
+
+    /// Returns the value aggregated at each node over the paths to the sink, the value of a path
+    /// being extended along its edges from the sink
+    pub(crate) fn backward_values<T: Clone + Send + Sync>(&self, sink: T, zero: T, extend: impl Fn(T, VariableIndex, ValueIndex) -> T + Sync + Send, sum: impl Fn(T, T) -> T + Sync + Send) -> Vec<Vec<T>> {
+        let mut values = vec![vec![]; self.nodes.len()];
+        values[self.sink.0] = vec![zero.clone(); self.nodes[self.sink.0].len()];
+        values[self.sink.0][self.sink.1] = sink;
+        for layer in (0..self.nodes.len() - 1).rev() {
+            let variable = self.order[layer];
+            let next = &values[layer + 1];
+            values[layer] = map_items(&self.nodes[layer], |_, node| {
+                if !node.is_active() {
+                    return zero.clone();
+                }
+                node.iter_children().map(|edge| self.edge(edge)).filter(|edge| edge.is_active()).fold(zero.clone(), |value, edge| {
+                    sum(value, extend(next[edge.to().1].clone(), variable, edge.assignment()))
+                })
+            });
+        }
+        values
+    }
+
+    pub fn get_solution(&self) -> Option<Vec<isize>> {
+        let mut assignment = vec![0; self.nodes.len() - 1];
+        let root = NodeIndex(0, 0);
+        if self.extract_solution(root, &mut assignment) {
+            Some(assignment)
+        } else {
+            None
+        }
+    }
+
+    fn extract_solution(&self, node: NodeIndex, assignment: &mut Vec<isize>) -> bool {
+        let layer = node.0;
+        if layer == self.nodes.len() - 1 {
+            return true;
+        }
+        if self[node].is_relaxed() {
             return false;
         }
         let variable = self.order[layer];
         for edge in self[node].iter_children() {
-            if !self[edge].is_active() {
+            if !self.edge(edge).is_active() {
                 continue;
             }
-            let to = self[edge].to();
-            let value = self.problem[variable].value(self[edge].assignment());
+            let to = self.edge(edge).to();
+            let value = self.problem[variable].value(self.edge(edge).assignment());
             assignment[*variable] = value;
             if self.extract_solution(to, assignment) {
                 return true;
@@ -444,10 +1463,201 @@ impl Mdd {
         false
     }
 
+    /// Returns the minimum total cost of the values (see `Variable::cost`) of a path from the root
+    /// to the sink, and the assignment of the path. If the MDD is relaxed, the cost is a lower
+    /// bound and the assignment might not be a solution.
+    pub fn minimum_cost_solution(&self) -> Option<(isize, Vec<isize>)> {
+        if self.unsat {
+            return None;
+        }
+        // Minimum cost of a path from each node to the sink
+        let mut costs = self.nodes.iter().map(|layer| vec![None; layer.len()]).collect::<Vec<Vec<Option<isize>>>>();
+        let NodeIndex(sink_layer, sink_index) = self.sink;
+        costs[sink_layer][sink_index] = Some(0);
+        for layer in (0..self.nodes.len() - 1).rev() {
+            let variable = self.order[layer];
+            for index in 0..self.nodes[layer].len() {
+                if !self.nodes[layer][index].is_active() {
+                    continue;
+                }
+                costs[layer][index] = self.nodes[layer][index].iter_children()
+                    .filter(|edge| self.edge(*edge).is_active())
+                    .filter_map(|edge| {
+                        let NodeIndex(to_layer, to_index) = self.edge(edge).to();
+                        costs[to_layer][to_index].map(|cost| cost + self.problem[variable].cost(self.edge(edge).assignment()))
+                    }).min();
+            }
+        }
+        let NodeIndex(root_layer, root_index) = self.root;
+        let cost = costs[root_layer][root_index]?;
+        let mut assignment = vec![0; self.nodes.len() - 1];
+        let mut node = self.root;
+        while node != self.sink {
+            let NodeIndex(layer, _) = node;
+            let variable = self.order[layer];
+            let remaining = costs[layer][node.1].unwrap();
+            let edge = self[node].iter_children()
+                .filter(|edge| self.edge(*edge).is_active())
+                .find(|edge| {
+                    let NodeIndex(to_layer, to_index) = self.edge(*edge).to();
+                    costs[to_layer][to_index].is_some_and(|to_cost| to_cost + self.problem[variable].cost(self.edge(*edge).assignment()) == remaining)
+                }).unwrap();
+            assignment[variable.0] = self.problem[variable].value(self.edge(edge).assignment());
+            node = self.edge(edge).to();
+        }
+        Some((cost, assignment))
+    }
+
     pub fn is_unsat(&self) -> bool {
         self.unsat
     }
 
+    /// Returns true if no active node of the MDD is relaxed, i.e., if its paths are exactly the
+    /// solutions of the problem. A restricted MDD (see `Mdd::set_restricted`) is never exact, as the
+    /// nodes exceeding the width might have been removed.
+    pub fn is_exact(&self) -> bool {
+        // The sink is the only node of its layer, whatever its parents
+        !self.restricted && self.nodes[..self.nodes.len() - 1].iter().flatten().all(|node| !node.is_active() || !node.is_relaxed())
+    }
+
+    /// Returns true if the assignment satisfies every constraint of the problem
+    pub fn is_solution(&self, solution: &[isize]) -> bool {
+        self.problem.iter_constraints().all(|constraint| self.problem[constraint].is_satisfied(solution))
+    }
+
+    /// Returns the pairs (variable, value) such that the variable takes the value on every path from
+    /// the root to the sink, i.e., in every solution if the MDD is exact. Returns no pair if the MDD
+    /// is unsat.
+    pub fn backbone(&self) -> Vec<(VariableIndex, isize)> {
+        if self.unsat {
+            return vec![];
+        }
+        let reachable = self.reachability();
+        self.problem.iter_variables().filter_map(|variable| self.implied_value(variable, &reachable).map(|value| (variable, value))).collect()
+    }
+
+    /// Returns the value taken by the variable on every path from the root to the sink, if any
+    pub fn implied(&self, variable: VariableIndex) -> Option<isize> {
+        if self.unsat {
+            return None;
+        }
+        self.implied_value(variable, &self.reachability())
+    }
+
+    /// Returns, for each node, if it is reached from the root and if it reaches the sink
+    pub(crate) fn reachability(&self) -> (Vec<Vec<bool>>, Vec<Vec<bool>>) {
+        let forward = self.forward_values(true, false, |reached, _, _| reached, |a, b| a || b);
+        let backward = self.backward_values(true, false, |reached, _, _| reached, |a, b| a || b);
+        (forward, backward)
+    }
+
+    fn implied_value(&self, variable: VariableIndex, (forward, backward): &(Vec<Vec<bool>>, Vec<Vec<bool>>)) -> Option<isize> {
+        let layer = self.order.iter().position(|v| *v == variable).unwrap();
+        // Only the edges on a path from the root to the sink are considered
+        let mut assignments = self.edges[layer].iter().filter(|edge| forward[layer][edge.from().1] && backward[layer + 1][edge.to().1]).map(|edge| edge.assignment());
+        let first = assignments.next()?;
+        if assignments.all(|assignment| assignment == first) {
+            Some(self.problem[variable].value(first))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the values of the variable assigned by at least one edge of the MDD, in the order of
+    /// its domain. With an exact MDD, these are the values taken by the variable in the solutions.
+    pub fn feasible_values(&self, variable: VariableIndex) -> Vec<isize> {
+        let mut assignments = self.current_assignments(variable).iter().collect::<Vec<usize>>();
+        assignments.sort_unstable();
+        assignments.into_iter().map(|index| self.problem[variable].value(ValueIndex(index))).collect()
+    }
+
+    /// Returns the values of the variable assigned by at least one edge of the MDD, in no
+    /// particular order. They are maintained with the edges, hence they are read without scanning
+    /// the layer of the variable (see `Mdd::feasible_values` for the values in order).
+    pub fn current_domain(&self, variable: VariableIndex) -> impl Iterator<Item = isize> + '_ {
+        self.current_assignments(variable).iter().map(move |index| self.problem[variable].value(ValueIndex(index)))
+    }
+
+    /// Returns the number of values of the variable assigned by at least one edge of the MDD
+    pub fn current_domain_size(&self, variable: VariableIndex) -> usize {
+        self.current_assignments(variable).size()
+    }
+
+    /// Returns true if at least one edge of the MDD assigns the value to the variable
+    pub fn is_in_current_domain(&self, variable: VariableIndex, value: isize) -> bool {
+        self.problem[variable].domain().index_of(value).is_some_and(|index| self.current_assignments(variable).contains(index))
+    }
+
+    /// Restricts the domain of each variable of the problem to its values assigned by at least one
+    /// edge of the MDD (see `Mdd::current_domain`), and returns the number of removed values. The
+    /// problem must have the variables of the problem of the MDD, e.g., be the compiled problem,
+    /// and keeps its solutions since the MDD is a relaxation of them. The domains become empty if
+    /// the MDD is unsat.
+    pub fn strengthen_problem(&self, problem: &mut Problem) -> usize {
+        assert_eq!(problem.number_variables(), self.problem.number_variables(), "The problem must have the variables of the MDD");
+        let mut removed = 0;
+        for variable in problem.iter_variables().collect::<Vec<VariableIndex>>() {
+            let before = problem[variable].domain_size();
+            if self.unsat {
+                problem.restrict_domain(variable, []);
+            } else {
+                problem.restrict_domain(variable, self.current_domain(variable));
+            }
+            removed += before - problem[variable].domain_size();
+        }
+        removed
+    }
+
+    /// Returns the value of the variable if it is the only one assigned by the edges of the MDD (see
+    /// `Mdd::current_domain`)
+    pub fn single_value(&self, variable: VariableIndex) -> Option<isize> {
+        let assignments = self.current_assignments(variable);
+        if assignments.size() == 1 {
+            assignments.iter().next().map(|index| self.problem[variable].value(ValueIndex(index)))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the indexes of the values of the variable assigned by at least one active edge
+    fn current_assignments(&self, variable: VariableIndex) -> &SparseSet {
+        let layer = self.order.iter().position(|v| *v == variable).unwrap();
+        self.edges[layer].supported_values()
+    }
+
+    /// Returns the constraints satisfied by every path of the MDD (see `Constraint::is_entailed`)
+    pub fn entailed_constraints(&self) -> Vec<ConstraintIndex> {
+        if self.unsat {
+            return vec![];
+        }
+        self.problem.iter_constraints().filter(|constraint| self.problem[*constraint].is_entailed(self)).collect()
+    }
+
+    /// Explains why the value is not in the feasible set of the variable, i.e., why no edge of the
+    /// MDD assigns it. Returns the constraints whose propagation removed the edges assigning the
+    /// value, in the order in which they removed their first edge: the first constraint started the
+    /// chain of removals and the last one removed the value. The explanation is empty if the value
+    /// is still feasible, if it is not in the domain of the variable, or if the removed edges are not
+    /// recorded (see `Mdd::enable_repair`).
+    pub fn explain(&self, variable: VariableIndex, value: isize) -> Vec<ConstraintIndex> {
+        let Some(layer) = self.order.iter().position(|v| *v == variable) else {
+            return vec![];
+        };
+        let Some(assignment) = self.problem[variable].domain().index_of(value).map(ValueIndex) else {
+            return vec![];
+        };
+        if !self.unsat && self.edges[layer].iter().any(|edge| edge.is_active() && edge.assignment() == assignment) {
+            return vec![];
+        }
+        let mut explanation = vec![];
+        for edge in self.removed_edges.iter().filter(|edge| edge.layer == layer && edge.assignment == assignment) {
+            if !explanation.contains(&edge.constraint) {
+                explanation.push(edge.constraint);
+            }
+        }
+        explanation
+    }
+
     pub fn set_probabilities(&mut self, probabilities: &[Vec<f64>]) {
         for variable in (0..self.number_layers() - 1).map(VariableIndex) {
             self.problem[variable].set_probabilities(&probabilities[variable.0]);
@@ -463,17 +1673,18 @@ impl Mdd {
                 let variable = self.order[layer];
                 let mut total_probability_mass = 0.0;
                 for edge in self[cur_node].iter_children() {
-                    let assignment = self[edge].assignment();
+                    let assignment = self.edge(edge).assignment();
                     total_probability_mass += self.problem[variable].probability(assignment);
                 }
 
                 let mut target = rng.random_range(0.0..total_probability_mass);
                 for edge in self[cur_node].iter_children() {
-                    let assignment = self[edge].assignment();
+                    let assignment = self.edge(edge).assignment();
                     target -= self.problem[variable].probability(assignment);
                     if target <= 0.0 {
                         assignments[variable.0] = self.problem[variable].value(assignment);
-                        cur_node = self[edge].to();
+                        cur_node = self.edge(edge).to();
+                        break;
                     }
                 }
                 if cur_node.0 == layer {
@@ -493,7 +1704,7 @@ impl Mdd {
         }
         for layer in 0..self.edges.len() {
             for index in 0..self.edges[layer].len() {
-                let edge = &self.edges[layer][index];
+                let edge = self.edges[layer].get(index);
                 let variable = self.order[layer];
                 let NodeIndex(from_layer, from_index) = edge.from();
                 let NodeIndex(to_layer, to_index) = edge.to();
@@ -507,11 +1718,57 @@ impl Mdd {
     }
 }
 
+/// Options of the graphviz export of the MDD (see `Mdd::as_graphviz_with`)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GraphvizOptions {
+    /// Labels the nodes with the state of each constraint (see `Constraint::node_state_label`)
+    pub state_labels: bool,
+    /// Draws the edges removed by the propagation, in red with the constraint that removed them,
+    /// instead of omitting them, if they are recorded (see `Mdd::enable_repair`)
+    pub removed_edges: bool,
+    /// Draws each layer in its own cluster
+    pub cluster_layers: bool,
+}
+
+/// Options of the TikZ export of the MDD (see `Mdd::as_tikz`), the distances being in centimeters
+#[derive(Clone, Copy, Debug)]
+pub struct TikzOptions {
+    /// Vertical distance between two layers
+    pub layer_spacing: f64,
+    /// Horizontal distance between two nodes of a layer
+    pub node_spacing: f64,
+    /// Labels the edges with their value
+    pub value_labels: bool,
+}
+
+impl Default for TikzOptions {
+    fn default() -> Self {
+        Self {
+            layer_spacing: 1.5,
+            node_spacing: 1.0,
+            value_labels: true,
+        }
+    }
+}
+
 /* ---- Various helper implementation to make life easier ---- */
 
 impl Mdd {
 
-    pub fn as_graphviz(&self) ->  String {
+    pub fn as_graphviz(&self) -> String {
+        self.as_graphviz_with(&GraphvizOptions::default())
+    }
+
+    /// Returns the MDD in the graphviz format, drawn with the given options
+    pub fn as_graphviz_with(&self, options: &GraphvizOptions) -> String {
+        // The properties must match the current nodes to label them
+        let labelled = options.state_labels.then(|| {
+            let mut mdd = self.clone();
+            mdd.update_properties();
+            mdd
+        });
+        let is_drawn = |node: NodeIndex| node.1 < self.nodes[node.0].len() && self[node].is_active();
+
         let mut out = String::new();
         out.push_str("digraph {\nrankdir=TD;\ntranksep = 3;\n\n");
 
@@ -525,15 +1782,30 @@ impl Mdd {
         }
 
         for layer in 0..self.nodes.len() {
-            for index in (0..self.nodes[layer].len()).filter(|i| self[NodeIndex(layer, *i)].is_active()) {
-                let id = format!("{{rank=same; N{}_{} [shape=point,width=0.05] L{}}}", layer, index, layer);
+            if options.cluster_layers {
+                subgraph.push_str(&format!("subgraph cluster_{} {{\nstyle=dotted;\n", layer));
+            }
+            for index in (0..self.nodes[layer].len()).filter(|i| is_drawn(NodeIndex(layer, *i))) {
+                let mut attributes = String::from("shape=point,width=0.05");
+                if let Some(mdd) = labelled.as_ref() {
+                    let labels = mdd.problem.iter_constraints().filter_map(|constraint| {
+                        mdd.problem[constraint].node_state_label(NodeIndex(layer, index)).map(|label| format!("c{}: {}", constraint.0, label))
+                    }).collect::<Vec<String>>();
+                    if !labels.is_empty() {
+                        attributes.push_str(&format!(",xlabel=\"{}\"", labels.join("\\n")));
+                    }
+                }
+                let id = format!("{{rank=same; N{}_{} [{}] L{}}}", layer, index, attributes, layer);
                 subgraph.push_str(&format!("\t{id};\n"));
             }
+            if options.cluster_layers {
+                subgraph.push_str("}\n");
+            }
         }
 
         for layer in 0..self.edges.len() {
             let variable = self.order[layer];
-            for edge in self.edges[layer].iter().filter(|e| e.is_active()) {
+            for edge in self.edges[layer].iter().filter(|e| is_drawn(e.from()) && is_drawn(e.to())) {
                 let NodeIndex(layer_from, index_from) = edge.from();
                 let NodeIndex(layer_to, index_to) = edge.to();
                 let assignment = self.problem[variable].value(edge.assignment());
@@ -541,6 +1813,20 @@ impl Mdd {
             }
         }
 
+        if options.removed_edges {
+            let mut drawn = FxHashSet::<(NodeIndex, NodeIndex, ValueIndex)>::default();
+            for edge in self.removed_edges.iter() {
+                let (Some(from), Some(to)) = (edge.from, edge.to) else {
+                    continue;
+                };
+                if !is_drawn(from) || !is_drawn(to) || !drawn.insert((from, to, edge.assignment)) {
+                    continue;
+                }
+                let assignment = self.problem[self.order[edge.layer]].value(edge.assignment);
+                subgraph.push_str(&format!("\tN{}_{} -> N{}_{} [penwidth=1, color=red, fontcolor=red, style=dashed, label=\"{} (c{})\"];\n", from.0, from.1, to.0, to.1, assignment, edge.constraint.0));
+            }
+        }
+
         layer_labels.push_str("}\n");
         subgraph.push_str("}\n");
 
@@ -550,22 +1836,271 @@ impl Mdd {
         out
     }
 
+    /// Returns a standalone LaTeX document drawing the MDD with TikZ, laid out with the given
+    /// options. The layers are drawn from top to bottom, labelled by their variable, and the
+    /// parallel edges are bent apart.
+    pub fn as_tikz(&self, options: &TikzOptions) -> String {
+        let mut out = String::new();
+        out.push_str("\\documentclass[tikz]{standalone}\n\\begin{document}\n\\begin{tikzpicture}[\n");
+        out.push_str("\tstate/.style={circle, fill, inner sep=1.5pt},\n");
+        out.push_str("\tvalue/.style={midway, fill=white, inner sep=1pt, font=\\scriptsize}]\n");
+
+        // Position of each active node in its layer, the layers being centered
+        let mut positions = FxHashMap::<NodeIndex, f64>::default();
+        let widest = (0..self.nodes.len()).map(|layer| self.nodes[layer].iter().filter(|node| node.is_active()).count()).max().unwrap_or(0);
+        for layer in 0..self.nodes.len() {
+            let active = (0..self.nodes[layer].len()).map(|index| NodeIndex(layer, index)).filter(|node| self[*node].is_active()).collect::<Vec<NodeIndex>>();
+            let y = (self.nodes.len() - 1 - layer) as f64 * options.layer_spacing;
+            for (position, node) in active.iter().copied().enumerate() {
+                let x = (position as f64 - (active.len() as f64 - 1.0) / 2.0) * options.node_spacing;
+                positions.insert(node, x);
+                out.push_str(&format!("\t\\node[state] (N{}_{}) at ({:.2}, {:.2}) {{}};\n", node.0, node.1, x, y));
+            }
+            if layer < self.order.len() {
+                let x = -(widest as f64 / 2.0 + 0.5) * options.node_spacing;
+                out.push_str(&format!("\t\\node at ({:.2}, {:.2}) {{$x_{{{}}}$}};\n", x, y - options.layer_spacing / 2.0, self.order[layer].0));
+            }
+        }
+
+        for layer in 0..self.edges.len() {
+            let variable = self.order[layer];
+            let mut parallel = FxHashMap::<(NodeIndex, NodeIndex), Vec<ValueIndex>>::default();
+            for edge in self.edges[layer].iter().filter(|e| positions.contains_key(&e.from()) && positions.contains_key(&e.to())) {
+                parallel.entry((edge.from(), edge.to())).or_default().push(edge.assignment());
+            }
+            let mut endpoints = parallel.keys().copied().collect::<Vec<(NodeIndex, NodeIndex)>>();
+            endpoints.sort_unstable();
+            for (from, to) in endpoints {
+                let assignments = &parallel[&(from, to)];
+                for (k, assignment) in assignments.iter().copied().enumerate() {
+                    let bend = (k as f64 - (assignments.len() as f64 - 1.0) / 2.0) * 20.0;
+                    let label = if options.value_labels {
+                        format!(" node[value] {{{}}}", self.problem[variable].value(assignment))
+                    } else {
+                        String::new()
+                    };
+                    let path = if bend == 0.0 { String::from("--") } else { format!("to[bend left={:.0}]", bend) };
+                    out.push_str(&format!("\t\\draw[->] (N{}_{}) {}{} (N{}_{});\n", from.0, from.1, path, label, to.0, to.1));
+                }
+            }
+        }
+
+        out.push_str("\\end{tikzpicture}\n\\end{document}\n");
+        out
+    }
+
+    #[cfg(feature = "fs")]
     pub fn to_file(&self, filename: &str) {
         fs::write(filename, self.as_graphviz()).unwrap();
     }
+
+    /// Saves the compiled MDD in a compact binary format (see `Mdd::to_bytes`), to be loaded with
+    /// `Mdd::load`
+    #[cfg(feature = "fs")]
+    pub fn save(&self, filename: &str) {
+        fs::write(filename, self.to_bytes()).expect("Failed to write the MDD file");
+    }
+
+    /// Loads an MDD saved with `Mdd::save` (see `Mdd::from_bytes`)
+    #[cfg(feature = "fs")]
+    pub fn load(filename: &str, problem: Problem, max_width: usize, merge_heuristic: MergeHeuristic) -> Self {
+        let bytes = fs::read(filename).expect("Failed to read the MDD file");
+        Self::from_bytes(&bytes, problem, max_width, merge_heuristic)
+    }
+
+    /// Serializes the compiled MDD in a compact binary format, to be read with `Mdd::from_bytes`.
+    /// It contains the variable ordering, the domain sizes, then for each layer its nodes (whether
+    /// they are relaxed) and its edges (endpoints and value indexes), with variable-length integers.
+    /// The problem itself is not serialized.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MDD_FILE_MAGIC.to_vec();
+        write_varint(&mut bytes, self.order.len() as u64);
+        for variable in self.order.iter().copied() {
+            write_varint(&mut bytes, variable.0 as u64);
+            write_varint(&mut bytes, self.problem[variable].domain_size() as u64);
+        }
+        bytes.push(self.unsat as u8);
+        if !self.unsat {
+            // Index of each active node in its layer, once the inactive nodes are skipped
+            let mut map_node_index = FxHashMap::<NodeIndex, usize>::default();
+            for layer in 0..self.nodes.len() {
+                let active = (0..self.nodes[layer].len()).map(|index| NodeIndex(layer, index)).filter(|node| self[*node].is_active()).collect::<Vec<NodeIndex>>();
+                write_varint(&mut bytes, active.len() as u64);
+                for (index, node) in active.into_iter().enumerate() {
+                    map_node_index.insert(node, index);
+                    bytes.push(self[node].is_relaxed() as u8);
+                }
+            }
+            for layer in 0..self.edges.len() {
+                let active = self.edges[layer].iter().collect::<Vec<Edge>>();
+                write_varint(&mut bytes, active.len() as u64);
+                for edge in active {
+                    write_varint(&mut bytes, map_node_index[&edge.from()] as u64);
+                    write_varint(&mut bytes, map_node_index[&edge.to()] as u64);
+                    write_varint(&mut bytes, edge.assignment().0 as u64);
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Reads an MDD serialized with `Mdd::to_bytes`. The problem must be the one of the serialized
+    /// MDD, with the same variables and domains, and its constraints are propagated once on the
+    /// read MDD to compute their properties. Panics if the bytes are not a valid MDD for the problem.
+    pub fn from_bytes(bytes: &[u8], problem: Problem, max_width: usize, merge_heuristic: MergeHeuristic) -> Self {
+        assert!(bytes.starts_with(MDD_FILE_MAGIC), "The file is not an MDD file");
+        let mut reader = ByteReader { bytes, cursor: MDD_FILE_MAGIC.len() };
+        let number_variables = reader.read_varint();
+        assert!(number_variables == problem.number_variables(), "The MDD was saved for a problem with {} variables", number_variables);
+        let mut order = vec![];
+        for _ in 0..number_variables {
+            let variable = VariableIndex(reader.read_varint());
+            assert!(reader.read_varint() == problem[variable].domain_size(), "The domain of variable {} differs from the saved MDD", variable.0);
+            order.push(variable);
+        }
+        if reader.read_byte() != 0 {
+            return Self::from_layers(problem, order, max_width, merge_heuristic, None, vec![]);
+        }
+        let mut nodes = vec![];
+        for _ in 0..number_variables + 1 {
+            nodes.push((0..reader.read_varint()).map(|_| reader.read_byte() != 0).collect::<Vec<bool>>());
+        }
+        let mut edges = vec![];
+        for layer in 0..number_variables {
+            let layer_edges = (0..reader.read_varint()).map(|_| (reader.read_varint(), reader.read_varint(), ValueIndex(reader.read_varint()))).collect::<Vec<(usize, usize, ValueIndex)>>();
+            assert!(layer_edges.iter().all(|(from, to, _)| *from < nodes[layer].len() && *to < nodes[layer + 1].len()), "Invalid edge in the MDD file");
+            edges.push(layer_edges);
+        }
+        Self::from_layers(problem, order, max_width, merge_heuristic, Some(nodes), edges)
+    }
+
+    /// Creates the MDD deciding the variables in the given order, with the given nodes (whether
+    /// they are relaxed) and edges (from, to, assignment) in each layer, the nodes being indexed in
+    /// their layer. The MDD is unsat if there are no nodes. The constraints of the problem are then
+    /// propagated on the MDD.
+    pub(crate) fn from_layers(problem: Problem, order: Vec<VariableIndex>, max_width: usize, merge_heuristic: MergeHeuristic, nodes: Option<Vec<Vec<bool>>>, edges: Vec<Vec<(usize, usize, ValueIndex)>>) -> Self {
+        let number_variables = order.len();
+        let mut mdd = Self {
+            nodes: vec![vec![]; number_variables + 1],
+            edges: vec![EdgeLayer::default(); number_variables],
+            free_nodes: vec![vec![]; number_variables + 1],
+            constraint_slots: vec![0; number_variables + 1],
+            order,
+            max_width,
+            merge_heuristic,
+            problem,
+            unsat: false,
+            root: NodeIndex(0, 0),
+            sink: NodeIndex(number_variables, 0),
+            removed_edges: vec![],
+            removed_by_node: FxHashMap::default(),
+            repair_enabled: Some(false),
+            trace: None,
+            max_propagation_rounds: usize::MAX,
+            weights: None,
+            restricted: false,
+            seed: None,
+            memory_budget: None,
+            budget_exceeded: false,
+            trail: vec![],
+            saved_states: vec![],
+            propagation_statistics: vec![],
+            propagation_timing: false,
+        };
+        mdd.problem.init_constraints();
+        let mut var_order_inv = vec![0; number_variables];
+        for (layer, variable) in mdd.order.iter().copied().enumerate() {
+            var_order_inv[variable.0] = layer;
+        }
+        for constraint in mdd.problem.iter_constraints().collect::<Vec<ConstraintIndex>>() {
+            mdd.problem[constraint].update_variable_ordering(&var_order_inv);
+        }
+
+        let Some(nodes) = nodes else {
+            for layer in 0..mdd.number_layers() {
+                mdd.add_node(layer, false);
+            }
+            let (root, sink) = (mdd.root, mdd.sink);
+            mdd[root].deactivate();
+            mdd[sink].deactivate();
+            mdd.unsat = true;
+            return mdd;
+        };
+        for (layer, relaxed) in nodes.into_iter().enumerate() {
+            for relaxed in relaxed {
+                mdd.add_node(layer, relaxed);
+            }
+        }
+        for (layer, edges) in edges.into_iter().enumerate() {
+            for (from, to, assignment) in edges {
+                mdd.add_edge(layer, NodeIndex(layer, from), NodeIndex(layer + 1, to), assignment);
+            }
+        }
+        mdd.propagate_constraints();
+        if !mdd[mdd.root].is_active() || !mdd[mdd.sink].is_active() {
+            mdd.unsat = true;
+            return mdd;
+        }
+        mdd.clean();
+        mdd
+    }
+
+    /// Returns the MDD of the problem of this MDD with the given order and layers (see
+    /// `Mdd::from_layers`), with the same parameters and edge weights
+    pub(crate) fn with_layers(&self, order: Vec<VariableIndex>, layers: operations::DiagramLayers) -> Self {
+        let (nodes, edges) = match layers {
+            Some((nodes, edges)) => (Some(nodes), edges),
+            None => (None, vec![]),
+        };
+        let mut mdd = Self::from_layers(self.problem.clone(), order, self.max_width, self.merge_heuristic.clone(), nodes, edges);
+        mdd.weights = self.weights.clone();
+        mdd.max_propagation_rounds = self.max_propagation_rounds;
+        mdd.restricted = self.restricted;
+        mdd.seed = self.seed;
+        mdd.memory_budget = self.memory_budget;
+        mdd.propagation_timing = self.propagation_timing;
+        mdd
+    }
 }
 
-impl std::ops::Index<EdgeIndex> for Mdd {
-    type Output = Edge;
+/// First bytes of the MDDs serialized by `Mdd::to_bytes`
+const MDD_FILE_MAGIC: &[u8] = b"AICADMDD\x01";
 
-    fn index(&self, index: EdgeIndex) -> &Self::Output {
-        &self.edges[index.0][index.1]
+/// Writes the integer with the LEB128 variable-length encoding (7 bits per byte)
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
     }
+    bytes.push(value as u8);
 }
 
-impl std::ops::IndexMut<EdgeIndex> for Mdd {
-    fn index_mut(&mut self, index: EdgeIndex) -> &mut Self::Output {
-        &mut self.edges[index.0][index.1]
+/// Reads the bytes written by `Mdd::to_bytes`
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    cursor: usize,
+}
+
+impl ByteReader<'_> {
+
+    fn read_byte(&mut self) -> u8 {
+        let byte = *self.bytes.get(self.cursor).expect("Truncated MDD file");
+        self.cursor += 1;
+        byte
+    }
+
+    /// Reads an integer written by `write_varint`
+    fn read_varint(&mut self) -> usize {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_byte();
+            value |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+            shift += 7;
+        }
     }
 }
 
@@ -614,9 +2149,9 @@ impl std::fmt::Debug for Mdd {
             for layer in 0..self.edges.len() {
                 let variable = self.order[layer];
                 for i in 0..self.edges[layer].len() {
-                    let source = map_node_id[&self.edges[layer][i].from()];
-                    let to = map_node_id[&self.edges[layer][i].to()];
-                    let assignment = self.problem[variable].value(self.edges[layer][i].assignment());
+                    let source = map_node_id[&self.edges[layer].from(i)];
+                    let to = map_node_id[&self.edges[layer].to(i)];
+                    let assignment = self.problem[variable].value(self.edges[layer].assignment(i));
                     if layer < self.edges.len() - 1 || i < self.edges[layer].len() - 1 {
                         writeln!(f, "{} {} {}", source, to, assignment)?;
                     } else {
@@ -632,49 +2167,27 @@ impl std::fmt::Debug for Mdd {
 #[cfg(test)]
 pub mod test_mdd {
 
+    use crate::constraints::*;
     use crate::modelling::*;
     use crate::mdd::*;
     use crate::mdd::heuristics::*;
+    use std::time::Duration;
+    use crate::fuzz::*;
+    use rustc_hash::FxHashSet;
 
-    pub fn get_all_solutions(mdd: &Mdd) -> Vec<Vec<isize>> {
-        let mut solutions: Vec<Vec<isize>> = vec![];
-        let mut current_solution: Vec<isize> = vec![0; mdd.number_layers() - 1];
-        let root = NodeIndex(0, 0);
-        _get_all_solutions(mdd, root, &mut solutions, &mut current_solution);
-        solutions
-    }
+    pub use crate::mdd::analysis::{get_all_solutions, is_solution};
 
-    fn _get_all_solutions(mdd: &Mdd, node: NodeIndex, solutions: &mut Vec<Vec<isize>>, current_solution: &mut Vec<isize>) {
-        let NodeIndex(layer, _) = node;
-        if layer == mdd.number_layers() - 1 {
-            solutions.push(current_solution.clone());
+    /// Asserts that the propagation of the MDD is at its fixpoint, i.e., that a new round of
+    /// propagation with every constraint removes no edge
+    pub fn assert_propagation_fixpoint(mdd: &Mdd) {
+        if mdd.is_unsat() {
             return;
         }
-        let variable = mdd.decision_at_layer(layer);
-        for edge in mdd[node].iter_children() {
-            if mdd[edge].is_active() {
-                let child = mdd[edge].to();
-                let assignment = mdd.problem[variable].value(mdd[edge].assignment());
-                current_solution[*variable] = assignment;
-                _get_all_solutions(mdd, child, solutions, current_solution);
-            }
-        }
-    }
-
-    pub fn is_solution(solution: Vec<isize>, all_solutions: &[Vec<isize>]) -> bool {
-        for sol in all_solutions.iter() {
-            let mut eq = true;
-            for i in 0..sol.len() {
-                if sol[i] != solution[i] {
-                    eq = false;
-                    break
-                }
-            }
-            if eq {
-                return true;
-            }
-        }
-        false
+        let mut propagated = mdd.clone();
+        let removed = |mdd: &Mdd| mdd.propagation_statistics().iter().map(|statistics| statistics.removed_edges).sum::<usize>();
+        let number_removed = removed(&propagated);
+        assert!(propagated.propagate_to_fixpoint(1), "The propagation is not at its fixpoint");
+        assert_eq!(removed(&propagated), number_removed);
     }
 
     #[test]
@@ -716,4 +2229,794 @@ pub mod test_mdd {
         mdd.refine();
         // TODO assert?
     }
+
+    #[test]
+    pub fn mdd_add_constraint_after_compilation() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(get_all_solutions(&mdd).len(), 6);
+
+        mdd.add_constraint(LessEqual::new(xs[0], xs[1], 0));
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 3);
+        assert!(solutions.iter().all(|solution| solution[0] <= solution[1]));
+        mdd.add_constraint(LessEqual::new(xs[2], xs[0], 0));
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions, vec![vec![1, 2, 0]]);
+        mdd.add_constraint(LessEqual::new(xs[1], xs[2], 0));
+        assert!(mdd.is_unsat());
+    }
+
+    #[test]
+    pub fn mdd_add_constraint_after_merges() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, 2, OrderingHeuristic::Custom(vec![0, 1, 2, 3]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        // The new constraint has a state for the nodes added in the slots freed by the merges
+        mdd.add_constraint(LessEqual::new(xs[0], xs[1], 0));
+        assert!(mdd.constraint_slots[1] > mdd.number_nodes_in_layer(1));
+        let mut added = vec![];
+        while mdd.number_nodes_in_layer(1) < mdd.constraint_slots[1] {
+            added.push(mdd.add_node(1, true));
+        }
+        mdd.propagate_constraints();
+        for node in added {
+            mdd.remove_node(node, None);
+        }
+        mdd.clean();
+        // The relaxed MDD keeps the 12 permutations with x0 < x1
+        assert!(!mdd.is_unsat() && mdd.count_solutions() >= 12);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not start at the node")]
+    pub fn mdd_check_invariants() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        mdd.check_invariants();
+        // The edge is deactivated but remains in the edges of its nodes
+        let edge = mdd[NodeIndex(1, 0)].parent_edge_at(0);
+        mdd.edges[edge.0].deactivate(edge.1);
+        mdd.check_invariants();
+    }
+
+    #[test]
+    pub fn mdd_propagation_fixpoint() {
+        let problems: Vec<fn() -> Problem> = vec![
+            || {
+                let mut problem = Problem::default();
+                let xs = problem.add_variables(4, vec![0, 1, 2, 3], None);
+                all_different(&mut problem, xs.clone());
+                less_than(&mut problem, xs[0], xs[3]);
+                problem
+            },
+            || {
+                let mut problem = Problem::default();
+                let xs = problem.add_variables(4, vec![0, 1, 2], None);
+                not_equals(&mut problem, xs[0], xs[1]);
+                not_equals(&mut problem, xs[1], xs[2]);
+                table(&mut problem, vec![xs[2], xs[3]], vec![vec![0, 1], vec![1, 2], vec![2, 0]]);
+                problem
+            },
+        ];
+        for problem in problems {
+            for width in [usize::MAX, 3, 2] {
+                let mut mdd = Mdd::new(problem(), width, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+                assert_propagation_fixpoint(&mdd);
+                mdd.refine();
+                assert_propagation_fixpoint(&mdd);
+            }
+        }
+    }
+
+    #[test]
+    pub fn mdd_propagation_statistics() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[1]);
+        less_than(&mut problem, xs[2], xs[3]);
+        let mut untimed = Mdd::new(problem.clone(), 2, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        untimed.refine();
+        let mut mdd = Mdd::new(problem, 2, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.set_propagation_timing(true);
+        mdd.refine();
+        // The timed propagation removes the same edges
+        assert_eq!(get_all_solutions(&mdd), get_all_solutions(&untimed));
+        let statistics = mdd.propagation_statistics();
+        assert_eq!(statistics.len(), 3);
+        assert!(statistics.iter().all(|statistics| statistics.invocations > 0 && statistics.time > Duration::ZERO));
+        assert!(untimed.propagation_statistics().iter().all(|statistics| statistics.time == Duration::ZERO));
+        assert_eq!(statistics.iter().map(|statistics| statistics.removed_edges).sum::<usize>(), untimed.propagation_statistics().iter().map(|statistics| statistics.removed_edges).sum::<usize>());
+        mdd.reset_propagation_statistics();
+        assert!(mdd.propagation_statistics().iter().all(|statistics| *statistics == PropagationStatistics::default()));
+    }
+
+    #[test]
+    pub fn mdd_propagation_timing_passes() {
+        // The timed propagation runs a pass over the layers for each constraint instead of a pass
+        // shared by all of them, which must compute the same states and remove the same edges
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(6, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs[0..4].to_vec());
+        less_than(&mut problem, xs[1], xs[4]);
+        not_equals(&mut problem, xs[4], xs[5]);
+        table(&mut problem, vec![xs[0], xs[5]], vec![vec![0, 1], vec![1, 2], vec![2, 3], vec![3, 3]]);
+        increasing(&mut problem, vec![xs[2], xs[4], xs[5]]);
+        let options = GraphvizOptions { state_labels: true, ..GraphvizOptions::default() };
+        for width in [2, 3, usize::MAX] {
+            let mut shared = Mdd::new(problem.clone(), width, OrderingHeuristic::Custom(vec![0, 1, 2, 3, 4, 5]), MergeHeuristic::LessRelaxed);
+            shared.refine();
+            let mut timed = Mdd::new(problem.clone(), width, OrderingHeuristic::Custom(vec![0, 1, 2, 3, 4, 5]), MergeHeuristic::LessRelaxed);
+            timed.set_propagation_timing(true);
+            timed.refine();
+            assert_eq!(timed.as_graphviz_with(&options), shared.as_graphviz_with(&options));
+            for (timed, shared) in timed.propagation_statistics().iter().zip(shared.propagation_statistics().iter()) {
+                assert_eq!((timed.invocations, timed.removed_edges), (shared.invocations, shared.removed_edges));
+            }
+            assert_eq!(timed.count_solutions(), shared.count_solutions());
+        }
+    }
+
+    #[test]
+    pub fn mdd_propagation_round_limit() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.set_max_propagation_rounds(1);
+        mdd.refine();
+        // A limited propagation keeps every solution
+        assert!(mdd.count_solutions() >= 6);
+        mdd.set_max_propagation_rounds(usize::MAX);
+        mdd.propagate_constraints();
+        mdd.clean();
+        assert_propagation_fixpoint(&mdd);
+        assert_eq!(get_all_solutions(&mdd).len(), 6);
+    }
+
+    #[test]
+    pub fn mdd_retract_constraint() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        let less = problem.add_constraint(LessEqual::new(xs[0], xs[1], 1));
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.enable_repair();
+        mdd.refine();
+        assert_eq!(get_all_solutions(&mdd).len(), 3);
+
+        mdd.retract_constraint(less);
+        let solutions = get_all_solutions(&mdd);
+        assert!(is_solution(vec![1, 0, 2], &solutions));
+        assert_eq!(solutions.iter().filter(|solution| mdd.is_solution(solution)).count(), 6);
+    }
+
+    #[test]
+    pub fn mdd_removed_edges_index() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs.clone());
+        let less = problem.add_constraint(LessEqual::new(xs[0], xs[4], 1));
+        let mut mdd = Mdd::new(problem.clone(), 3, OrderingHeuristic::Custom(vec![0, 1, 2, 3, 4]), MergeHeuristic::LessRelaxed);
+        mdd.enable_repair();
+        // The endpoints of the removed edges are indexed while the slots of the nodes are reused
+        mdd.refine_with(|mdd, _| {
+            for (position, edge) in mdd.removed_edges.iter().enumerate() {
+                for node in [edge.from, edge.to].into_iter().flatten() {
+                    assert!(mdd.removed_by_node.get(&node).is_some_and(|positions| positions.contains(&position)));
+                }
+            }
+        });
+        mdd.retract_constraint(less);
+        mdd.max_width = usize::MAX;
+        mdd.refine();
+        problem.retract_constraint(less);
+        assert_eq!(get_all_solutions(&mdd).iter().filter(|solution| mdd.is_solution(solution)).count() as u128, crate::reference::count_solutions(&problem));
+    }
+
+    #[test]
+    pub fn mdd_relax_unsat_constraint() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1, 2], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        less_than(&mut problem, x, y);
+        let greater = problem.add_constraint(LessEqual::new(y, x, 1));
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1]), MergeHeuristic::LessRelaxed);
+        mdd.enable_repair();
+        mdd.refine();
+        assert!(mdd.is_unsat());
+
+        // y + 1 <= x is relaxed into y - 1 <= x
+        mdd.relax_constraint(greater, LessEqual::new(y, x, -1));
+        assert!(!mdd.is_unsat());
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 2);
+        assert!(is_solution(vec![0, 1], &solutions));
+        assert!(is_solution(vec![1, 2], &solutions));
+    }
+
+    #[test]
+    pub fn mdd_explain() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let first = problem.add_constraint(LessEqual::new(xs[0], xs[1], 1));
+        let second = problem.add_constraint(LessEqual::new(xs[1], xs[2], 1));
+        not_equals(&mut problem, xs[0], xs[2]);
+        let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.enable_repair();
+        mdd.refine();
+        assert_eq!(mdd.explain(xs[0], 2), vec![first]);
+        assert_eq!(mdd.explain(xs[2], 0), vec![second]);
+        // x1 = 0 is removed by x0 < x1 and x1 = 2 by x1 < x2
+        assert_eq!(mdd.explain(xs[1], 0), vec![first]);
+        assert_eq!(mdd.explain(xs[1], 1), vec![]);
+        assert_eq!(mdd.explain(xs[0], 5), vec![]);
+
+        // Without the repair, the removed edges are not recorded
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert!(mdd.removed_edges.is_empty());
+        assert_eq!(mdd.explain(xs[0], 2), vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mdd::enable_repair")]
+    pub fn mdd_retract_constraint_without_repair() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(2, vec![0, 1, 2], None);
+        let less = problem.add_constraint(LessEqual::new(xs[0], xs[1], 1));
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        mdd.retract_constraint(less);
+    }
+
+    #[test]
+    pub fn mdd_unsat_core() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        let cycle = vec![
+            problem.add_constraint(LessEqual::new(xs[0], xs[1], 1)),
+            problem.add_constraint(LessEqual::new(xs[1], xs[2], 1)),
+            problem.add_constraint(LessEqual::new(xs[2], xs[0], 1)),
+        ];
+        problem.add_constraint(LessEqual::new(xs[3], xs[0], 0));
+        for max_width in [2, usize::MAX] {
+            let core = Mdd::unsat_core(&problem, max_width, &OrderingHeuristic::MinDomMaxLinked, &MergeHeuristic::LessRelaxed);
+            assert_eq!(core, Some(cycle.clone()));
+        }
+
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(2, vec![0, 1], None);
+        not_equals(&mut problem, xs[0], xs[1]);
+        assert_eq!(Mdd::unsat_core(&problem, usize::MAX, &OrderingHeuristic::MinDomMaxLinked, &MergeHeuristic::LessRelaxed), None);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    pub fn mdd_save_load() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[3]);
+        let mut mdd = Mdd::new(problem.clone(), 3, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let path = std::env::temp_dir().join("aicad_mdd_save_load.mdd");
+        let filename = path.to_str().unwrap();
+        mdd.save(filename);
+        let loaded = Mdd::load(filename, problem, 3, MergeHeuristic::LessRelaxed);
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(loaded.as_graphviz(), mdd.as_graphviz());
+        let (mut solutions, mut loaded_solutions) = (get_all_solutions(&mdd), get_all_solutions(&loaded));
+        solutions.sort();
+        loaded_solutions.sort();
+        assert_eq!(loaded_solutions, solutions);
+
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1], None);
+        let y = problem.add_variable(vec![0, 1], None);
+        less_than(&mut problem, x, y);
+        less_than(&mut problem, y, x);
+        let mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        let path = std::env::temp_dir().join("aicad_mdd_save_load_unsat.mdd");
+        let filename = path.to_str().unwrap();
+        mdd.save(filename);
+        assert!(mdd.is_unsat());
+        let loaded = Mdd::load(filename, problem, usize::MAX, MergeHeuristic::LessRelaxed);
+        std::fs::remove_file(filename).unwrap();
+        assert!(loaded.is_unsat());
+    }
+
+    #[test]
+    pub fn mdd_from_bytes_unsat() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1], None);
+        let mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        // The constraints added to the problem read with the MDD may remove all its paths
+        all_different(&mut problem, xs);
+        let loaded = Mdd::from_bytes(&mdd.to_bytes(), problem, usize::MAX, MergeHeuristic::LessRelaxed);
+        assert!(loaded.is_unsat());
+        assert_eq!(loaded.count_solutions(), 0);
+    }
+
+    #[test]
+    pub fn mdd_graphviz_options() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1, 2], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        not_equals(&mut problem, x, y);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1]), MergeHeuristic::LessRelaxed);
+        mdd.enable_repair();
+        mdd.refine();
+        assert!(!mdd.as_graphviz().contains("style=dashed"));
+        let options = GraphvizOptions { state_labels: true, removed_edges: true, cluster_layers: true };
+        let graphviz = mdd.as_graphviz_with(&options);
+        // The edges y = x are removed from the three nodes of the second layer
+        assert_eq!(graphviz.matches("style=dashed").count(), 3);
+        assert!(graphviz.contains("subgraph cluster_1"));
+        assert!(graphviz.contains("c0: td {0} / bu {"));
+    }
+
+    #[test]
+    pub fn mdd_tikz() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1, 2], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        less_than(&mut problem, x, y);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let tikz = mdd.as_tikz(&TikzOptions::default());
+        assert!(tikz.starts_with("\\documentclass[tikz]{standalone}"));
+        assert!(tikz.ends_with("\\end{document}\n"));
+        // One edge per solution and one per value of x
+        assert_eq!(tikz.matches("\\draw").count(), 5);
+        assert!(tikz.contains("node[value] {2}"));
+        let tikz = mdd.as_tikz(&TikzOptions { value_labels: false, ..TikzOptions::default() });
+        assert!(!tikz.contains("node[value] {"));
+    }
+
+    #[test]
+    pub fn mdd_trace() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, 2, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.enable_trace(true);
+        mdd.refine();
+        let trace = mdd.take_trace().unwrap();
+        assert!(trace.events.iter().any(|event| matches!(event, TraceEvent::Split { .. })));
+        assert!(trace.events.iter().any(|event| matches!(event, TraceEvent::Merge { exact: false, .. })));
+        assert!(trace.events.iter().any(|event| matches!(event, TraceEvent::RemoveEdge { constraint: Some(ConstraintIndex(0)), .. })));
+        // The initial frame, then five phases for each of the two refined layers
+        assert_eq!(trace.frames.len(), 11);
+        assert_eq!(trace.frames.last().unwrap().graphviz, mdd.as_graphviz());
+        assert_eq!(trace.replay().map(|(events, _)| events.len()).sum::<usize>(), trace.events.len());
+        assert_eq!(trace.log().lines().count(), trace.events.len() + trace.frames.len());
+    }
+
+    #[test]
+    pub fn mdd_backbone() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[1]);
+        less_than(&mut problem, xs[1], xs[2]);
+        less_than(&mut problem, xs[2], xs[3]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.backbone(), vec![(xs[0], 0), (xs[1], 1), (xs[2], 2), (xs[3], 3)]);
+
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        less_than(&mut problem, xs[0], xs[1]);
+        less_than(&mut problem, xs[1], xs[2]);
+        less_than(&mut problem, xs[0], xs[2]);
+        let ys = problem.add_variables(2, vec![0, 1], None);
+        not_equals(&mut problem, ys[0], ys[1]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.implied(xs[1]), Some(1));
+        assert_eq!(mdd.implied(ys[0]), None);
+        assert_eq!(mdd.backbone(), vec![(xs[0], 0), (xs[1], 1), (xs[2], 2)]);
+    }
+
+    #[test]
+    pub fn mdd_feasible_values() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1, 2], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        less_than(&mut problem, x, y);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.feasible_values(x), vec![0, 1]);
+        assert_eq!(mdd.feasible_values(y), vec![1, 2]);
+        mdd.add_constraint(Table::new(vec![y], vec![vec![Some(1)]]));
+        assert_eq!(mdd.feasible_values(x), vec![0]);
+    }
+
+    #[test]
+    pub fn mdd_free_slots() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        not_equals(&mut problem, xs[0], xs[2]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        // The second parent edge moved to a new node is stored in the slot of the first one
+        mdd.split_node(NodeIndex(1, 0));
+        assert_eq!(mdd.number_nodes_in_layer(1), 3);
+        assert_eq!(mdd.edges[0].len(), 4);
+        mdd.remove_node(NodeIndex(1, 1), None);
+        assert_eq!(mdd.add_node(1, false), NodeIndex(1, 1));
+        assert_eq!(mdd.number_nodes_in_layer(1), 3);
+        mdd.remove_node(NodeIndex(1, 1), None);
+        // The paths through the removed node are lost, i.e., one value of the first variable
+        mdd.clean();
+        mdd.refine();
+        assert_eq!(get_all_solutions(&mdd).len(), 12);
+    }
+
+    #[test]
+    pub fn mdd_count_solutions() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        problem.set_probabilities(xs[0], vec![0.5, 0.25, 0.25]);
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        assert!(mdd.count_solutions() >= 6);
+        mdd.refine();
+        assert_eq!(mdd.count_solutions(), 6);
+        assert!((mdd.probability_mass() - 2.0 / 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    pub fn mdd_anytime_count() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs[0..4].to_vec());
+        less_than(&mut problem, xs[4], xs[1]);
+        let mut mdd = Mdd::new(problem, 3, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        let mut bounds = vec![mdd.count_solutions()];
+        mdd.refine_with(|mdd, _| {
+            // The maintained counts match a count from scratch
+            assert!(!mdd.edges.iter().any(|edges| edges.is_changed()));
+            assert_eq!(mdd.count_solutions(), mdd.evaluate::<Counting>(|_, _| 1));
+            bounds.push(mdd.count_solutions());
+        });
+        assert!(bounds.len() > 1);
+        assert!(bounds.windows(2).all(|pair| pair[1] <= pair[0]));
+        mdd.add_constraint(Table::new(vec![xs[0]], vec![vec![Some(0)]]));
+        let count = mdd.count_solutions();
+        assert_eq!(count, mdd.evaluate::<Counting>(|_, _| 1));
+        // The counts are recomputed while the MDD is not cleaned
+        mdd.remove_node(NodeIndex(1, 0), None);
+        assert!(mdd.count_solutions() < count);
+        assert_eq!(mdd.count_solutions(), mdd.evaluate::<Counting>(|_, _| 1));
+    }
+
+    #[test]
+    pub fn mdd_seeded_compilation() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(6, vec![0, 1, 2, 3, 4, 5], None);
+        all_different(&mut problem, xs[0..5].to_vec());
+        less_than(&mut problem, xs[5], xs[1]);
+        let compile = |seed: Option<u64>| {
+            let mut mdd = Mdd::new(problem.clone(), 3, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            if let Some(seed) = seed {
+                mdd.set_seed(seed);
+            }
+            mdd.refine();
+            mdd
+        };
+        assert_eq!(compile(None).as_graphviz(), compile(None).as_graphviz());
+        let mdd = compile(Some(42));
+        assert_eq!(mdd.as_graphviz(), compile(Some(42)).as_graphviz());
+        // The samples are reproducible once the seed is set
+        let mut mdd = mdd;
+        mdd.set_seed(7);
+        let samples = (0..10).map(|_| mdd.sample()).collect::<Vec<Vec<isize>>>();
+        mdd.set_seed(7);
+        assert_eq!(samples, (0..10).map(|_| mdd.sample()).collect::<Vec<Vec<isize>>>());
+    }
+
+    #[test]
+    pub fn mdd_memory_budget() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(6, vec![0, 1, 2, 3, 4, 5], None);
+        all_different(&mut problem, xs[0..5].to_vec());
+        less_than(&mut problem, xs[5], xs[1]);
+        let mut exact = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        exact.refine();
+        assert!(!exact.is_budget_exceeded());
+        for action in [BudgetAction::Stop, BudgetAction::Compact] {
+            let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            let budget = 2 * mdd.estimated_memory();
+            mdd.set_memory_budget(budget, action);
+            mdd.refine();
+            // The refinement stops at the first layer exceeding the budget, with a relaxation
+            assert!(mdd.is_budget_exceeded());
+            assert!(mdd.estimated_memory() > budget && mdd.estimated_memory() < exact.estimated_memory());
+            assert!(mdd.count_solutions() > exact.count_solutions());
+            // A budget large enough lets the refinement finish
+            mdd.set_memory_budget(usize::MAX, action);
+            mdd.refine();
+            assert!(!mdd.is_budget_exceeded());
+            assert_eq!(mdd.count_solutions(), exact.count_solutions());
+        }
+    }
+
+    #[test]
+    pub fn mdd_current_domain() {
+        let config = FuzzConfig { min_variables: 4, max_constraints: 5, ..FuzzConfig::default() };
+        for seed in 0..20 {
+            let problem = random_problem(seed, &config);
+            let solutions = crate::reference::solutions(&problem);
+            let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            mdd.check_invariants();
+            let Some(solution) = solutions.first() else {
+                continue;
+            };
+            let first = VariableIndex(0);
+            mdd.push_state();
+            mdd.condition(first, solution[0]);
+            mdd.check_invariants();
+            for variable in problem.iter_variables() {
+                let mut domain = mdd.current_domain(variable).collect::<Vec<isize>>();
+                domain.sort();
+                let mut expected = solutions.iter().filter(|other| other[0] == solution[0]).map(|other| other[variable.0]).collect::<Vec<isize>>();
+                expected.sort();
+                expected.dedup();
+                assert_eq!(domain, expected, "Seed {}", seed);
+                assert_eq!(mdd.current_domain_size(variable), expected.len());
+                assert!(mdd.is_in_current_domain(variable, solution[variable.0]));
+            }
+            mdd.pop_state();
+            mdd.check_invariants();
+            assert_eq!(mdd.current_domain_size(first), solutions.iter().map(|other| other[0]).collect::<FxHashSet<isize>>().len());
+        }
+    }
+
+    #[test]
+    pub fn mdd_strengthen_problem() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2, 3, 4], None);
+        less_than(&mut problem, xs[0], xs[1]);
+        less_than(&mut problem, xs[1], xs[2]);
+        not_equals(&mut problem, xs[2], xs[3]);
+        let mut solutions = crate::reference::solutions(&problem);
+        solutions.sort();
+        for width in [1, usize::MAX] {
+            let mut mdd = Mdd::new(problem.clone(), width, OrderingHeuristic::Custom(vec![0, 1, 2, 3]), MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            let mut strengthened = problem.clone();
+            let removed = mdd.strengthen_problem(&mut strengthened);
+            assert!(removed >= 6);
+            assert_eq!(strengthened[xs[0]].iter_domain().collect::<Vec<isize>>(), vec![0, 1, 2]);
+            assert_eq!(strengthened[xs[2]].iter_domain().collect::<Vec<isize>>(), vec![2, 3, 4]);
+            // The recompilation of the strengthened problem keeps the solutions
+            let mut recompiled = Mdd::new(strengthened, usize::MAX, OrderingHeuristic::Custom(vec![3, 2, 1, 0]), MergeHeuristic::LessRelaxed);
+            recompiled.refine();
+            let mut recompiled_solutions = get_all_solutions(&recompiled);
+            recompiled_solutions.sort();
+            assert_eq!(recompiled_solutions, solutions);
+        }
+        less_than(&mut problem, xs[2], xs[0]);
+        let mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.strengthen_problem(&mut problem);
+        assert!(problem.iter_variables().all(|variable| problem[variable].domain_size() == 0));
+    }
+
+    #[test]
+    pub fn mdd_entailed_constraints() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let not_equals = problem.add_constraint(NotEquals::new(xs[0], xs[1]));
+        let all_different = problem.add_constraint(AllDifferent::new(xs.clone()));
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert!(mdd.entailed_constraints().is_empty());
+
+        mdd.push_state();
+        mdd.condition(xs[0], 0);
+        mdd.condition(xs[1], 1);
+        assert_eq!(mdd.single_value(xs[2]), Some(2));
+        let entailed = mdd.entailed_constraints();
+        assert!(entailed.contains(&not_equals) && entailed.contains(&all_different));
+        assert_propagation_fixpoint(&mdd);
+        mdd.pop_state();
+        assert!(mdd.entailed_constraints().is_empty());
+        assert_eq!(mdd.count_solutions(), 6);
+    }
+
+    #[test]
+    pub fn mdd_push_pop_state() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs[0..4].to_vec());
+        less_than(&mut problem, xs[4], xs[1]);
+        let solutions = crate::reference::solutions(&problem);
+        let count = |filter: &dyn Fn(&Vec<isize>) -> bool| solutions.iter().filter(|solution| filter(solution)).count() as u128;
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let mut expected = get_all_solutions(&mdd);
+        expected.sort();
+        mdd.push_state();
+        mdd.condition(xs[1], 2);
+        assert_eq!(mdd.count_solutions(), count(&|solution| solution[1] == 2));
+        mdd.push_state();
+        mdd.condition(xs[4], 1);
+        assert_eq!(mdd.count_solutions(), count(&|solution| solution[1] == 2 && solution[4] == 1));
+        mdd.condition(xs[0], 2);
+        assert!(mdd.is_unsat());
+        mdd.pop_state();
+        assert!(!mdd.is_unsat());
+        assert_eq!(mdd.count_solutions(), count(&|solution| solution[1] == 2));
+        mdd.check_invariants();
+        mdd.pop_state();
+        assert_eq!(mdd.number_saved_states(), 0);
+        assert_eq!(mdd.problem().number_constraints(), 2);
+        mdd.check_invariants();
+        let mut restored = get_all_solutions(&mdd);
+        restored.sort();
+        assert_eq!(restored, expected);
+        assert_eq!(mdd.count_solutions(), solutions.len() as u128);
+        // The MDD is propagated again once restored
+        mdd.add_constraint(Table::new(vec![xs[0]], vec![vec![Some(0)]]));
+        assert_eq!(mdd.count_solutions(), count(&|solution| solution[0] == 0));
+
+        // A relaxed MDD can be refined again once its state is restored
+        let mut mdd = Mdd::new(mdd.problem().clone(), 2, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let relaxed = mdd.count_solutions();
+        mdd.push_state();
+        mdd.condition(xs[4], 0);
+        assert!(mdd.count_solutions() < relaxed);
+        mdd.pop_state();
+        assert_eq!(mdd.count_solutions(), relaxed);
+        mdd.refine();
+        mdd.check_invariants();
+        assert!(mdd.count_solutions() <= relaxed && mdd.count_solutions() >= count(&|solution| solution[0] == 0));
+    }
+
+    #[test]
+    pub fn mdd_edge_weights() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], Some(vec![0.2, 0.3, 0.5]));
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.weighted_count(), 6.0);
+        mdd.set_edge_weights(|variable, value| (value * (variable.0 as isize + 1)) as f64);
+        let (shortest, solution) = mdd.shortest_path().unwrap();
+        assert_eq!(shortest, 4.0);
+        assert_eq!(solution, vec![2, 1, 0]);
+        let (longest, solution) = mdd.longest_path().unwrap();
+        assert_eq!(longest, 8.0);
+        assert_eq!(solution, vec![0, 1, 2]);
+        let root_edge = mdd[NodeIndex(0, 0)].child_edge_at(0);
+        let variable = mdd.decision_at_layer(0);
+        assert_eq!(mdd.edge_weight(root_edge), (mdd.problem()[variable].value(mdd.edge(root_edge).assignment()) * (variable.0 as isize + 1)) as f64);
+        mdd.set_edge_weights_from_probabilities();
+        assert!((mdd.weighted_count() - mdd.probability_mass()).abs() < 1e-9);
+        mdd.clear_edge_weights();
+        assert_eq!(mdd.weighted_count(), 6.0);
+    }
+
+    #[test]
+    pub fn mdd_marginals() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        problem.set_probabilities(xs[0], vec![0.5, 0.25, 0.25]);
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let marginals = mdd.marginals();
+        let expected = [vec![0.5, 0.25, 0.25], vec![0.25, 0.375, 0.375], vec![0.25, 0.375, 0.375]];
+        for (variable, expected) in xs.iter().zip(expected.iter()) {
+            assert!(marginals[variable.0].iter().zip(expected.iter()).all(|(marginal, expected)| (marginal - expected).abs() < 1e-9));
+        }
+    }
+
+    #[test]
+    pub fn mdd_is_exact() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs);
+        let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert!(mdd.is_exact());
+        let mut mdd = Mdd::new(problem.clone(), 1, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert!(!mdd.is_exact());
+        let mut mdd = Mdd::new(problem, 1, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.set_restricted(true);
+        mdd.refine();
+        assert!(!mdd.is_exact());
+    }
+
+    #[test]
+    pub fn mdd_is_solution() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[2]);
+        let mut mdd = Mdd::new(problem, 1, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert!(mdd.is_solution(&[0, 2, 1]));
+        assert!(!mdd.is_solution(&[1, 1, 2]));
+        assert!(!mdd.is_solution(&[2, 0, 1]));
+        // The paths of a relaxed MDD over-approximate its solutions
+        let paths = get_all_solutions(&mdd);
+        assert!(paths.len() > 3);
+        assert_eq!(paths.iter().filter(|path| mdd.is_solution(path)).count(), 3);
+    }
+
+    #[test]
+    pub fn mdd_collapse_moves_children() {
+        // Without constraints, the two nodes of the first layer are equivalent, and the child of the
+        // value 1 of the second one is moved to the first one, which lacks it
+        let mut problem = Problem::default();
+        problem.add_variables(3, vec![0, 1], None);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.split_node(NodeIndex(1, 0));
+        let edge = mdd[NodeIndex(1, 0)].iter_children().find(|edge| mdd.edge(*edge).assignment() == ValueIndex(1)).unwrap();
+        mdd.deactivate_edge(edge);
+        mdd[NodeIndex(1, 0)].remove_child_edge(edge);
+        mdd[NodeIndex(2, 0)].remove_parent_edge(edge);
+        mdd.collapse();
+        mdd.check_invariants();
+        mdd.clean();
+        assert_eq!(mdd.number_nodes_in_layer(1), 1);
+        assert_eq!(get_all_solutions(&mdd).len(), 8);
+    }
+
+    #[test]
+    pub fn mdd_merge_after_collapse() {
+        // The three nodes of each layer are collapsed into one, hence the width is never exceeded although
+        // the layers still hold the collapsed nodes when they are merged
+        let mut problem = Problem::default();
+        problem.add_variables(3, vec![0, 1, 2], None);
+        let mut mdd = Mdd::new(problem, 2, OrderingHeuristic::Custom(vec![0, 1, 2]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        mdd.check_invariants();
+        assert!(mdd.is_exact());
+        assert_eq!(mdd.count_solutions(), 27);
+    }
+
+    #[test]
+    pub fn mdd_collapse_deterministic() {
+        // Some equivalent nodes have different children for the same value, and collapsing them would
+        // duplicate the paths below
+        let mut problem = Problem::default();
+        let x0 = problem.add_variable(vec![4], None);
+        let x1 = problem.add_variable(vec![0, 1, 4], None);
+        let x2 = problem.add_variable(vec![-1, 0, 2], None);
+        let x3 = problem.add_variable(vec![-1, 1, 2, 3, 4], None);
+        let x4 = problem.add_variable(vec![-1, 2, 4], None);
+        let x5 = problem.add_variable(vec![-1, 0, 1, 2, 3], None);
+        all_different(&mut problem, vec![x5, x0, x3, x1]);
+        not_equals(&mut problem, x2, x3);
+        not_equals(&mut problem, x1, x2);
+        table(&mut problem, vec![x4, x2], vec![vec![-1, -1], vec![-1, 0], vec![4, -1], vec![4, 2]]);
+        all_different(&mut problem, vec![x0, x3, x5, x4]);
+        all_different(&mut problem, vec![x4, x5, x3]);
+        let mut solutions = crate::reference::solutions(&problem);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        mdd.check_invariants();
+        let mut paths = get_all_solutions(&mdd);
+        paths.sort();
+        solutions.sort();
+        assert_eq!(paths, solutions);
+        assert_eq!(mdd.count_solutions(), solutions.len() as u128);
+    }
 }
@@ -1,14 +1,51 @@
+#[allow(clippy::module_inception)]
 pub mod mdd;
 pub mod node;
 pub mod layer;
 pub mod edge;
 pub mod heuristics;
+pub mod trace;
+pub mod propagation;
+pub mod analysis;
+pub mod semiring;
+pub mod lagrangian;
+pub mod search;
+pub mod ranking;
+pub mod operations;
+pub mod export;
+pub mod bdd;
+pub mod reordering;
+pub mod portfolio;
+pub mod anytime;
+pub mod approximate;
+pub mod information;
+pub mod sensitivity;
+pub mod learning;
+pub mod queries;
+pub mod puzzle;
+pub mod dfs;
+pub mod components;
 
 // re-export modules
-pub use mdd::Mdd;
+pub use mdd::{Mdd, BudgetAction, GraphvizOptions, TikzOptions};
 pub use node::Node;
 pub use layer::Layer;
-pub use edge::Edge;
+pub use edge::{Edge, EdgeLayer, RemovedEdge};
+pub use trace::{Trace, TraceEvent, TraceFrame};
+pub use semiring::{Semiring, Arithmetic, Counting, SumProduct, LogSumProduct, Expectation, MaxPlus, MinPlus, Boolean};
+#[cfg(feature = "rational")]
+pub use semiring::Rational;
+pub use lagrangian::{Penalty, LagrangianBound};
+pub use search::{SearchStrategy, minimize};
+pub use export::Cnf;
+pub use bdd::{Bdd, BddNode};
+pub use portfolio::{Probe, Portfolio, compile_portfolio};
+pub use anytime::{CountBounds, count_anytime};
+pub use approximate::ApproximateCount;
+pub use puzzle::{Clue, generate_puzzle};
+pub use propagation::PropagationStatistics;
+pub use dfs::{SearchStatistics, SearchOptions, RestartStrategy, VariableSelection, ValueSelection};
+pub use components::{Component, ComposedMdd};
 
 use crate::constraints::Constraint;
 use std::hash::{Hash, Hasher};
@@ -1,5 +1,9 @@
 use super::*;
 use rustc_hash::FxHashMap;
+use smallvec::SmallVec;
+
+/// Edges of a node, stored inline up to four edges to avoid an allocation for most nodes
+pub type NodeEdges = SmallVec<[EdgeIndex; 4]>;
 
 /// A decision node of the MDD
 #[derive(Default, Clone)]
@@ -9,15 +13,18 @@ pub struct Node {
     /// What is the index of the node in the layer
     index_in_layer: usize,
     /// Edges to from the parent of the nodes
-    parents_edges: Vec<EdgeIndex>,
+    parents_edges: NodeEdges,
     /// Edges to the children of the nodes
-    children_edges: Vec<EdgeIndex>,
+    children_edges: NodeEdges,
     /// Is the node active
     active: bool,
     /// Is the node relaxed
     relaxed: bool,
     /// Is the node flaged for property update
     property_flag: bool,
+    /// Number of paths from the root to the node, saturating at `u128::MAX`, maintained by the MDD
+    /// once it is cleaned
+    path_count: u128,
 }
 
 impl Node {
@@ -26,11 +33,12 @@ impl Node {
         Self {
             layer,
             index_in_layer,
-            parents_edges: vec![],
-            children_edges: vec![],
+            parents_edges: NodeEdges::new(),
+            children_edges: NodeEdges::new(),
             active: true,
             relaxed,
             property_flag: false,
+            path_count: 0,
         }
     }
 
@@ -104,6 +112,19 @@ impl Node {
         self.active = false;
     }
 
+    /// Activates the node again with the edges it had when it was deactivated (see
+    /// `Mdd::pop_state`)
+    pub fn restore(&mut self) {
+        self.active = true;
+    }
+
+    /// Activates the node again, without any edge
+    pub fn reactivate(&mut self) {
+        self.active = true;
+        self.parents_edges.clear();
+        self.children_edges.clear();
+    }
+
     pub fn is_active(&self) -> bool {
         self.active
     }
@@ -136,6 +157,26 @@ impl Node {
         self.property_flag = false;
     }
 
+    pub fn path_count(&self) -> u128 {
+        self.path_count
+    }
+
+    pub fn set_path_count(&mut self, count: u128) {
+        self.path_count = count;
+    }
+
+    /// Returns the number of bytes of the node, including its edges stored on the heap
+    pub fn memory(&self) -> usize {
+        let spilled = |edges: &NodeEdges| if edges.spilled() { edges.capacity() * std::mem::size_of::<EdgeIndex>() } else { 0 };
+        std::mem::size_of::<Node>() + spilled(&self.parents_edges) + spilled(&self.children_edges)
+    }
+
+    /// Releases the unused capacity of the edges of the node
+    pub fn shrink_to_fit(&mut self) {
+        self.parents_edges.shrink_to_fit();
+        self.children_edges.shrink_to_fit();
+    }
+
     pub fn update_edge_indices(&mut self, map: &FxHashMap::<EdgeIndex, EdgeIndex>) {
         for i in (0..self.parents_edges.len()).rev() {
             match map.get(&self.parents_edges[i]) {
@@ -151,3 +192,40 @@ impl Node {
         }
     }
 }
+
+#[cfg(test)]
+mod test_node {
+
+    use crate::mdd::*;
+    use rustc_hash::FxHashMap;
+
+    #[test]
+    pub fn test_node_edges() {
+        let mut node = Node::new(1, 0, false);
+        for i in 0..4 {
+            node.add_child_edge(EdgeIndex(1, i));
+            node.add_parent_edge(EdgeIndex(0, i));
+        }
+        // Up to four edges are stored inline
+        assert_eq!(node.memory(), std::mem::size_of::<Node>());
+        for i in 4..10 {
+            node.add_child_edge(EdgeIndex(1, i));
+        }
+        assert_eq!(node.number_children(), 10);
+        assert!(node.memory() >= std::mem::size_of::<Node>() + 10 * std::mem::size_of::<EdgeIndex>());
+        node.remove_child_edge(EdgeIndex(1, 2));
+        node.swap_remove_parent_edge(0);
+        assert!(!node.iter_children().any(|edge| edge == EdgeIndex(1, 2)));
+        assert_eq!(node.number_parents(), 3);
+
+        // The edges missing from the map are dropped, and the edges are stored inline again once shrunk
+        let map = (0..3).map(|i| (EdgeIndex(1, i), EdgeIndex(1, 10 + i))).chain((0..4).map(|i| (EdgeIndex(0, i), EdgeIndex(0, i)))).collect::<FxHashMap<EdgeIndex, EdgeIndex>>();
+        node.update_edge_indices(&map);
+        let mut children = node.child_edges().to_vec();
+        children.sort();
+        assert_eq!(children, vec![EdgeIndex(1, 10), EdgeIndex(1, 11)]);
+        assert_eq!(node.number_parents(), 3);
+        node.shrink_to_fit();
+        assert_eq!(node.memory(), std::mem::size_of::<Node>());
+    }
+}
@@ -0,0 +1,418 @@
+use super::*;
+use super::heuristics::*;
+use crate::modelling::*;
+use rustc_hash::FxHashMap;
+
+// Operations building new diagrams from compiled MDDs.
+//
+// The projection of an MDD on a subset of its variables existentially quantifies the other ones.
+// Removing the layers of the quantified variables makes the diagram non-deterministic (a node can
+// have several edges with the same value), hence it is determinized layer by layer: a node of the
+// projection is the set of the nodes of the MDD reached by the same assignments of the projected
+// variables. The projection is then reduced by merging, from the sink, the nodes with the same
+// edges. Counting the paths of the MDD counts an assignment of the projected variables once per
+// extension to the other variables, whereas it has a single path in the determinized projection.
+//
+// The union and intersection of two MDDs with the same variable ordering are built by the product
+// of the diagrams: a node of the product is a pair of nodes, one of each MDD, reached by the same
+// assignments, the node of an MDD being None once the assignments leave its paths. The product is
+// then reduced as the projection, the nodes without path to the sink being removed.
+
+/// Layers of a diagram, as the relaxed flag of their nodes and their edges (from, to, assignment),
+/// or None if the diagram has no path
+pub(crate) type DiagramLayers = Option<(Vec<Vec<bool>>, Vec<Vec<(usize, usize, ValueIndex)>>)>;
+
+/// Edges (to, assignment) of the nodes of each layer, sorted by assignment
+pub(crate) type PathChildren = Vec<Vec<Vec<(usize, ValueIndex)>>>;
+
+/// Node of the product of two MDDs, as the index of the node in its layer of each MDD
+type ProductState = (Option<usize>, Option<usize>);
+
+impl Mdd {
+
+    /// Returns the MDD of the assignments of the variables that extend to a path of the MDD, i.e.,
+    /// to a solution if the MDD is exact. The variables of the problem of the projection are the
+    /// given ones, in the same order, with the same domains, probabilities and costs, and it has no
+    /// constraint. The layers keep the order of the MDD.
+    pub fn project(&self, variables: &[VariableIndex]) -> Mdd {
+        let (order, layers) = self.projected_layers(variables);
+        self.diagram(variables, order, layers)
+    }
+
+    /// Returns the number of distinct assignments of the variables that extend to a path of the
+    /// MDD, i.e., to a solution if the MDD is exact. The count saturates at `u128::MAX`.
+    pub fn count_projected_solutions(&self, variables: &[VariableIndex]) -> u128 {
+        let (_, layers) = self.projected_layers(variables);
+        let Some((nodes, edges)) = layers else { return 0 };
+        let mut counts = vec![1u128];
+        for (layer, layer_edges) in edges.iter().enumerate() {
+            let mut next = vec![0u128; nodes[layer + 1].len()];
+            for (from, to, _) in layer_edges.iter().copied() {
+                next[to] = next[to].saturating_add(counts[from]);
+            }
+            counts = next;
+        }
+        counts[0]
+    }
+
+    /// Returns the MDD of the paths of this MDD or of the other one, which must have the same
+    /// variable ordering and domains. The problem of the union has the variables of this MDD and
+    /// no constraint.
+    pub fn union(&self, other: &Mdd) -> Mdd {
+        let layers = self.product(other, |a, b| a || b);
+        self.diagram(&self.problem().iter_variables().collect::<Vec<VariableIndex>>(), self.order(), layers)
+    }
+
+    /// Returns the MDD of the paths of both this MDD and the other one, which must have the same
+    /// variable ordering and domains. The problem of the intersection has the variables of this
+    /// MDD and no constraint.
+    pub fn intersection(&self, other: &Mdd) -> Mdd {
+        let layers = self.product(other, |a, b| a && b);
+        self.diagram(&self.problem().iter_variables().collect::<Vec<VariableIndex>>(), self.order(), layers)
+    }
+
+    /// Returns true if every path of this MDD is a path of the other one, which must have the same
+    /// variable ordering and domains
+    pub fn is_subset(&self, other: &Mdd) -> bool {
+        self.check_compatible(other);
+        if self.is_unsat() {
+            return true;
+        }
+        if other.is_unsat() {
+            return false;
+        }
+        let (children, other_children) = (self.path_children(), other.path_children());
+        let mut states = vec![(0, 0)];
+        for layer in 0..self.number_layers() - 1 {
+            let mut next_states = vec![];
+            for (node, other_node) in states {
+                for (to, assignment) in children[layer][node].iter().copied() {
+                    let Some(other_to) = child(&other_children[layer][other_node], assignment) else { return false };
+                    next_states.push((to, other_to));
+                }
+            }
+            next_states.sort();
+            next_states.dedup();
+            states = next_states;
+        }
+        true
+    }
+
+    /// Returns true if both MDDs, which must have the same variable ordering and domains, have the
+    /// same paths
+    pub fn equivalent(&self, other: &Mdd) -> bool {
+        self.is_subset(other) && other.is_subset(self)
+    }
+
+    /// Returns the order of the projected variables, indexed by their position in the given slice,
+    /// and the layers of the reduced projection
+    pub(crate) fn projected_layers(&self, variables: &[VariableIndex]) -> (Vec<VariableIndex>, DiagramLayers) {
+        assert!(!variables.is_empty(), "The MDD must be projected on at least one variable");
+        let number_decisions = self.number_layers() - 1;
+        let kept = (0..number_decisions).filter(|layer| variables.contains(&self.decision_at_layer(*layer))).collect::<Vec<usize>>();
+        assert!(kept.len() == variables.len(), "The projected variables must be distinct variables of the MDD");
+        let order = kept.iter().map(|layer| VariableIndex(variables.iter().position(|variable| *variable == self.decision_at_layer(*layer)).unwrap())).collect::<Vec<VariableIndex>>();
+        if self.is_unsat() {
+            return (order, None);
+        }
+        let children = self.path_children();
+        // Nodes reached from the set of nodes of the layer through the edges of the layers up to
+        // the target one, whatever their assignment
+        let closure = |mut nodes: Vec<usize>, from: usize, to: usize| {
+            nodes.sort();
+            nodes.dedup();
+            for layer_children in children[from..to].iter() {
+                nodes = nodes.iter().flat_map(|node| layer_children[*node].iter().map(|(child, _)| *child)).collect();
+                nodes.sort();
+                nodes.dedup();
+            }
+            nodes
+        };
+
+        // Determinization, each node of the projection being a set of nodes of the MDD
+        let mut states = vec![vec![closure(vec![0], 0, kept[0])]];
+        let mut edges = vec![];
+        for (k, layer) in kept.iter().copied().enumerate() {
+            let next_layer = if k + 1 < kept.len() { kept[k + 1] } else { number_decisions };
+            let mut ids = FxHashMap::<Vec<usize>, usize>::default();
+            let mut next_states = vec![];
+            let mut layer_edges = vec![];
+            for (id, state) in states[k].iter().enumerate() {
+                let mut reached = state.iter().flat_map(|node| children[layer][*node].iter().copied()).collect::<Vec<(usize, ValueIndex)>>();
+                reached.sort_by_key(|(to, assignment)| (*assignment, *to));
+                for group in reached.chunk_by(|a, b| a.1 == b.1) {
+                    let target = closure(group.iter().map(|(to, _)| *to).collect(), layer + 1, next_layer);
+                    let target_id = *ids.entry(target.clone()).or_insert_with(|| {
+                        next_states.push(target);
+                        next_states.len() - 1
+                    });
+                    layer_edges.push((id, target_id, group[0].1));
+                }
+            }
+            states.push(next_states);
+            edges.push(layer_edges);
+        }
+        let relaxed = states.iter().zip(kept.iter()).map(|(layer_states, layer)| {
+            layer_states.iter().map(|state| state.iter().any(|node| self[NodeIndex(*layer, *node)].is_relaxed())).collect::<Vec<bool>>()
+        }).chain(std::iter::once(vec![false])).collect::<Vec<Vec<bool>>>();
+        (order, reduce(relaxed, edges))
+    }
+
+    /// Returns the layers of the reduced product of the MDDs, keeping the edges to the pairs of
+    /// nodes for which `keep` is true given which of the nodes are on a path of their MDD
+    fn product(&self, other: &Mdd, keep: impl Fn(bool, bool) -> bool) -> DiagramLayers {
+        self.check_compatible(other);
+        let root = |mdd: &Mdd| (!mdd.is_unsat()).then_some(0);
+        let state: ProductState = (root(self), root(other));
+        if !keep(state.0.is_some(), state.1.is_some()) {
+            return None;
+        }
+        let (children, other_children) = (self.path_children(), other.path_children());
+        let relaxed_state = |layer: usize, state: ProductState| {
+            state.0.is_some_and(|node| self[NodeIndex(layer, node)].is_relaxed()) || state.1.is_some_and(|node| other[NodeIndex(layer, node)].is_relaxed())
+        };
+        let number_decisions = self.number_layers() - 1;
+        let mut states = vec![state];
+        let mut relaxed = vec![vec![relaxed_state(0, state)]];
+        let mut edges = vec![];
+        for layer in 0..number_decisions {
+            let mut ids = FxHashMap::<ProductState, usize>::default();
+            let mut next_states = vec![];
+            let mut layer_edges = vec![];
+            for (id, (node, other_node)) in states.iter().copied().enumerate() {
+                let node_children = node.map_or(&[][..], |node| &children[layer][node]);
+                let other_node_children = other_node.map_or(&[][..], |node| &other_children[layer][node]);
+                let mut assignments = node_children.iter().chain(other_node_children.iter()).map(|(_, assignment)| *assignment).collect::<Vec<ValueIndex>>();
+                assignments.sort();
+                assignments.dedup();
+                for assignment in assignments {
+                    let target = (child(node_children, assignment), child(other_node_children, assignment));
+                    if !keep(target.0.is_some(), target.1.is_some()) {
+                        continue;
+                    }
+                    let target_id = *ids.entry(target).or_insert_with(|| {
+                        next_states.push(target);
+                        next_states.len() - 1
+                    });
+                    layer_edges.push((id, target_id, assignment));
+                }
+            }
+            relaxed.push(next_states.iter().map(|state| layer + 1 < number_decisions && relaxed_state(layer + 1, *state)).collect());
+            states = next_states;
+            edges.push(layer_edges);
+        }
+        reduce(relaxed, edges)
+    }
+
+    /// Returns the edges of the nodes on a path from the root to the sink, by layer
+    pub(crate) fn path_children(&self) -> PathChildren {
+        if self.is_unsat() {
+            return vec![];
+        }
+        let (forward, backward) = self.reachability();
+        (0..self.number_layers() - 1).map(|layer| {
+            (0..self.number_nodes_in_layer(layer)).map(|index| {
+                if !self[NodeIndex(layer, index)].is_active() || !forward[layer][index] {
+                    return vec![];
+                }
+                let mut children = self[NodeIndex(layer, index)].iter_children().map(|edge| self.edge(edge))
+                    .filter(|edge| edge.is_active() && backward[layer + 1][edge.to().1])
+                    .map(|edge| (edge.to().1, edge.assignment()))
+                    .collect::<Vec<(usize, ValueIndex)>>();
+                children.sort_by_key(|(to, assignment)| (*assignment, *to));
+                children
+            }).collect::<Vec<Vec<(usize, ValueIndex)>>>()
+        }).collect()
+    }
+
+    fn order(&self) -> Vec<VariableIndex> {
+        (0..self.number_layers() - 1).map(|layer| self.decision_at_layer(layer)).collect()
+    }
+
+    fn check_compatible(&self, other: &Mdd) {
+        assert!(self.order() == other.order(), "The MDDs must have the same variable ordering");
+        assert!(self.problem().iter_variables().all(|variable| self.problem()[variable].domain() == other.problem()[variable].domain()), "The variables of the MDDs must have the same domains");
+    }
+
+    /// Returns the MDD of the layers, whose problem has the given variables with the same domains,
+    /// probabilities and costs, and no constraint
+    fn diagram(&self, variables: &[VariableIndex], order: Vec<VariableIndex>, layers: DiagramLayers) -> Mdd {
+        let mut problem = Problem::default();
+        for variable in variables.iter().map(|variable| &self.problem()[*variable]) {
+            let probabilities = (!variable.probabilities().is_empty()).then(|| variable.probabilities().to_vec());
+            let index = problem.add_domain_variable(variable.domain().clone(), probabilities);
+            if !variable.costs().is_empty() {
+                problem[index].set_costs(variable.costs());
+            }
+        }
+        match layers {
+            Some((nodes, edges)) => Mdd::from_layers(problem, order, usize::MAX, MergeHeuristic::LessRelaxed, Some(nodes), edges),
+            None => Mdd::from_layers(problem, order, usize::MAX, MergeHeuristic::LessRelaxed, None, vec![]),
+        }
+    }
+}
+
+/// Returns the child of the node, given its sorted edges, reached by the assignment
+fn child(children: &[(usize, ValueIndex)], assignment: ValueIndex) -> Option<usize> {
+    children.binary_search_by_key(&assignment, |(_, a)| *a).ok().map(|index| children[index].0)
+}
+
+/// Reduces the layers of a deterministic diagram by merging, from the sink, the nodes with the same
+/// edges. The nodes of the last layer are merged into the sink and the other nodes without edge are
+/// removed. Returns None if the root is removed.
+pub(crate) fn reduce(relaxed: Vec<Vec<bool>>, edges: Vec<Vec<(usize, usize, ValueIndex)>>) -> DiagramLayers {
+    let number_layers = relaxed.len();
+    let sink = number_layers - 1;
+    let mut canonical: Vec<Vec<Option<usize>>> = vec![vec![]; number_layers];
+    let mut nodes = vec![vec![]; number_layers];
+    canonical[sink] = vec![Some(0); relaxed[sink].len()];
+    nodes[sink] = vec![false];
+    for k in (0..sink).rev() {
+        let mut signatures = vec![vec![]; relaxed[k].len()];
+        for (from, to, assignment) in edges[k].iter().copied() {
+            if let Some(to) = canonical[k + 1][to] {
+                signatures[from].push((assignment, to));
+            }
+        }
+        let mut ids = FxHashMap::<Vec<(ValueIndex, usize)>, usize>::default();
+        for (state, signature) in signatures.into_iter().enumerate() {
+            if signature.is_empty() {
+                canonical[k].push(None);
+                continue;
+            }
+            let id = *ids.entry(signature).or_insert_with(|| {
+                nodes[k].push(false);
+                nodes[k].len() - 1
+            });
+            nodes[k][id] |= relaxed[k][state];
+            canonical[k].push(Some(id));
+        }
+    }
+    canonical[0][0]?;
+    let reduced_edges = edges.iter().enumerate().map(|(k, layer_edges)| {
+        let mut reduced = layer_edges.iter().filter_map(|(from, to, assignment)| Some((canonical[k][*from]?, canonical[k + 1][*to]?, *assignment))).collect::<Vec<(usize, usize, ValueIndex)>>();
+        reduced.sort_by_key(|(from, to, assignment)| (*from, *assignment, *to));
+        reduced.dedup();
+        reduced
+    }).collect();
+    Some((nodes, reduced_edges))
+}
+
+#[cfg(test)]
+mod test_operations {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+    use crate::reference;
+
+    fn problem() -> (Problem, Vec<VariableIndex>) {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs[0..4].to_vec());
+        less_than(&mut problem, xs[0], xs[4]);
+        less_than(&mut problem, xs[1], xs[0]);
+        (problem, xs)
+    }
+
+    #[test]
+    pub fn test_project() {
+        let (problem, xs) = problem();
+        let solutions = reference::solutions(&problem);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        for variables in [vec![xs[4], xs[0]], vec![xs[2]], xs.clone()] {
+            let mut expected = solutions.iter().map(|solution| variables.iter().map(|variable| solution[variable.0]).collect::<Vec<isize>>()).collect::<Vec<Vec<isize>>>();
+            expected.sort();
+            expected.dedup();
+            let projection = mdd.project(&variables);
+            let mut projected = get_all_solutions(&projection);
+            projected.sort();
+            assert_eq!(projected, expected);
+            assert_eq!(projection.count_solutions(), expected.len() as u128);
+            projection.check_invariants();
+        }
+        // The reduction merges the nodes with the same edges
+        let projection = mdd.project(&[xs[2]]);
+        assert_eq!(projection.number_nodes_in_layer(0), 1);
+        assert_eq!(projection.number_nodes_in_layer(1), 1);
+    }
+
+    #[test]
+    pub fn test_count_projected_solutions() {
+        let (problem, xs) = problem();
+        let solutions = reference::solutions(&problem);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        for variables in [vec![xs[0]], vec![xs[3], xs[4]], vec![xs[1], xs[2], xs[4]], xs.clone()] {
+            let mut expected = solutions.iter().map(|solution| variables.iter().map(|variable| solution[variable.0]).collect::<Vec<isize>>()).collect::<Vec<Vec<isize>>>();
+            expected.sort();
+            expected.dedup();
+            assert_eq!(mdd.count_projected_solutions(&variables), expected.len() as u128);
+        }
+        assert_eq!(mdd.count_projected_solutions(&xs), mdd.count_solutions());
+    }
+
+    /// Compiles an exact MDD of the problem with the variables decided in their index order
+    fn compile(problem: Problem) -> Mdd {
+        let order = (0..problem.number_variables()).collect::<Vec<usize>>();
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(order), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        mdd
+    }
+
+    #[test]
+    pub fn test_union_intersection() {
+        let mut a = Problem::default();
+        let xs = a.add_variables(4, vec![0, 1, 2, 3], None);
+        all_different(&mut a, xs[0..3].to_vec());
+        let mut b = Problem::default();
+        b.add_variables(4, vec![0, 1, 2, 3], None);
+        less_than(&mut b, xs[0], xs[1]);
+        less_than(&mut b, xs[2], xs[3]);
+        let (solutions_a, solutions_b) = (reference::solutions(&a), reference::solutions(&b));
+        let (a, b) = (compile(a), compile(b));
+
+        let union = a.union(&b);
+        let mut expected = solutions_a.iter().chain(solutions_b.iter()).cloned().collect::<Vec<Vec<isize>>>();
+        expected.sort();
+        expected.dedup();
+        let mut solutions = get_all_solutions(&union);
+        solutions.sort();
+        assert_eq!(solutions, expected);
+        union.check_invariants();
+
+        let intersection = a.intersection(&b);
+        let expected = solutions_a.iter().filter(|solution| solutions_b.contains(solution)).cloned().collect::<Vec<Vec<isize>>>();
+        let mut solutions = get_all_solutions(&intersection);
+        solutions.sort();
+        assert_eq!(solutions, expected);
+        intersection.check_invariants();
+
+        // x0 = x1 contradicts the all different constraint
+        let mut c = Problem::default();
+        c.add_variables(4, vec![0, 1, 2, 3], None);
+        table(&mut c, xs[0..2].to_vec(), vec![vec![0, 0]]);
+        let c = compile(c);
+        assert!(a.intersection(&c).is_unsat());
+        assert_eq!(a.union(&c).count_solutions(), a.count_solutions() + c.count_solutions());
+    }
+
+    #[test]
+    pub fn test_is_subset() {
+        let mut a = Problem::default();
+        let xs = a.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut a, xs.clone());
+        let mut b = Problem::default();
+        b.add_variables(3, vec![0, 1, 2], None);
+        not_equals(&mut b, xs[0], xs[1]);
+        let (a, b) = (compile(a), compile(b));
+        assert!(a.is_subset(&b));
+        assert!(!b.is_subset(&a));
+        assert!(a.is_subset(&a));
+        assert!(a.equivalent(&a.intersection(&b)));
+        assert!(b.equivalent(&a.union(&b)));
+        assert!(!a.equivalent(&b));
+    }
+}
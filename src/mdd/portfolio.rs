@@ -0,0 +1,118 @@
+use super::*;
+use super::heuristics::*;
+use crate::modelling::*;
+use crate::utils::map_items;
+
+// Compilation with a portfolio of variable orderings. The ordering is the main factor of the size
+// of an exact MDD and of the tightness of a relaxed one, and it is hard to guess. Each ordering is
+// probed by compiling a restricted and a relaxed MDD of small width, in parallel with the
+// `parallel` feature. The paths of the restricted MDD are solutions and the paths of the relaxed
+// one contain the solutions, hence their numbers bound the number of solutions from below and from
+// above. The problem is then compiled with the ordering of the tightest probe: an unsat relaxed
+// probe proves the problem unsat (an empty restricted one does not), otherwise the probes with the
+// smallest gap between their bounds are preferred, then those with the fewest nodes.
+
+/// Measures of the restricted and relaxed MDDs compiled with an ordering of the portfolio
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Probe {
+    /// Is the relaxed MDD unsat, i.e., the problem
+    pub unsat: bool,
+    /// Number of paths of the restricted MDD, a lower bound on the number of solutions
+    pub lower_bound: u128,
+    /// Number of paths of the relaxed MDD, an upper bound on the number of solutions, saturating at
+    /// `u128::MAX`
+    pub upper_bound: u128,
+    /// Number of nodes of the restricted MDD
+    pub number_nodes: usize,
+    /// Largest number of nodes in a layer of the restricted MDD
+    pub width: usize,
+}
+
+/// MDD compiled with the best ordering of a portfolio (see `compile_portfolio`)
+pub struct Portfolio {
+    pub mdd: Mdd,
+    /// Index of the selected ordering
+    pub selected: usize,
+    /// Probe of each ordering
+    pub probes: Vec<Probe>,
+}
+
+/// Compiles the problem with the given maximum width and the ordering, among the given ones, whose
+/// restricted and relaxed MDDs of width `probe_width` bound the number of solutions the tightest
+pub fn compile_portfolio(problem: &Problem, orderings: &[OrderingHeuristic], probe_width: usize, max_width: usize, merge_heuristic: &MergeHeuristic) -> Portfolio {
+    assert!(!orderings.is_empty(), "The portfolio must have at least one ordering");
+    let probes = map_items(orderings, |_, ordering| {
+        let compile = |restricted: bool| {
+            let mut mdd = Mdd::new(problem.clone(), probe_width, ordering.clone(), merge_heuristic.clone());
+            mdd.set_restricted(restricted);
+            mdd.refine();
+            mdd
+        };
+        probe(&compile(true), &compile(false))
+    });
+    let selected = (0..probes.len()).min_by_key(|index| {
+        let probe = probes[*index];
+        (!probe.unsat, probe.upper_bound.saturating_sub(probe.lower_bound), probe.number_nodes)
+    }).unwrap();
+    let mut mdd = Mdd::new(problem.clone(), max_width, orderings[selected].clone(), merge_heuristic.clone());
+    mdd.refine();
+    Portfolio { mdd, selected, probes }
+}
+
+fn probe(restricted: &Mdd, relaxed: &Mdd) -> Probe {
+    let widths = (0..restricted.number_layers()).map(|layer| (0..restricted.number_nodes_in_layer(layer)).filter(|index| restricted[NodeIndex(layer, *index)].is_active()).count()).collect::<Vec<usize>>();
+    Probe {
+        unsat: relaxed.is_unsat(),
+        lower_bound: restricted.count_solutions(),
+        upper_bound: relaxed.count_solutions(),
+        number_nodes: widths.iter().sum(),
+        width: widths.iter().copied().max().unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod test_portfolio {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::heuristics::ordering::Strategy;
+    use crate::reference;
+
+    #[test]
+    pub fn test_compile_portfolio() {
+        // The pairs (x_i, y_i) are equal, hence the relaxation is tighter when they are adjacent
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let ys = problem.add_variables(3, vec![0, 1, 2], None);
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            table(&mut problem, vec![*x, *y], (0..3).map(|value| vec![value, value]).collect());
+        }
+        let orderings = vec![OrderingHeuristic::Custom((0..6).collect()), OrderingHeuristic::ConstraintGraph(Strategy::MaximumCardinality)];
+        let portfolio = compile_portfolio(&problem, &orderings, 3, usize::MAX, &MergeHeuristic::LessRelaxed);
+        assert_eq!(portfolio.selected, 1);
+        assert_eq!(portfolio.probes.len(), 2);
+        assert_eq!(portfolio.probes[0].upper_bound, 729);
+        assert!(portfolio.probes[1].upper_bound < 729);
+        assert!(portfolio.probes.iter().all(|probe| probe.lower_bound > 0 && probe.lower_bound <= 27));
+        assert!(portfolio.probes.iter().all(|probe| !probe.unsat && probe.width <= 3));
+        assert_eq!(portfolio.mdd.count_solutions(), reference::count_solutions(&problem));
+    }
+
+    #[test]
+    pub fn test_compile_portfolio_restricted_probes() {
+        // x0 < x1 < x2 < x3 has 5 solutions among {0, ..., 4}. Both relaxed MDDs have 6 paths and
+        // both restricted MDDs 9 nodes, but the restricted MDD of the first ordering finds fewer
+        // solutions, hence the second ordering is selected
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2, 3, 4], None);
+        strictly_increasing(&mut problem, xs);
+        let orderings = vec![OrderingHeuristic::Custom(vec![0, 2, 1, 3]), OrderingHeuristic::Custom(vec![3, 2, 1, 0])];
+        let portfolio = compile_portfolio(&problem, &orderings, 3, usize::MAX, &MergeHeuristic::LessRelaxed);
+        assert!(portfolio.probes.iter().all(|probe| probe.upper_bound == 6 && probe.number_nodes == 9));
+        assert_eq!(portfolio.probes[0].lower_bound, 3);
+        assert_eq!(portfolio.probes[1].lower_bound, 4);
+        assert_eq!(portfolio.selected, 1);
+        assert_eq!(portfolio.mdd.count_solutions(), 5);
+    }
+}
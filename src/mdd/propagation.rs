@@ -0,0 +1,192 @@
+use super::*;
+use crate::modelling::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+// Shared traversal of the MDD for the propagation of the constraints. Instead of one sweep of the
+// MDD per constraint, each pass walks the edges of a layer once and dispatches every edge to the
+// state updates of all the scheduled constraints. The constraints filter the edges of the layers
+// they watch (see `Watches`), and are propagated again only if the edges of one of these layers
+// changed. The time spent on each constraint can not be measured in a shared pass, hence the passes
+// are run separately for each constraint when the propagation is timed (see `timed`).
+
+/// Statistics of the propagation of a constraint (see `Mdd::propagation_statistics`)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PropagationStatistics {
+    /// Number of propagation rounds in which the constraint filtered the edges
+    pub invocations: usize,
+    /// Number of edges removed by the constraint, including the edges of the nodes left without
+    /// parents or children by its removals
+    pub removed_edges: usize,
+    /// Time spent updating the properties of the constraint and checking the edges, if the
+    /// propagation is timed (see `Mdd::set_propagation_timing`)
+    pub time: Duration,
+}
+
+/// Runs the pass on the constraints, once for all of them, or separately for each one if the
+/// statistics are given, adding the duration of its pass to its time
+pub fn timed(constraints: &[ConstraintIndex], statistics: Option<&mut [PropagationStatistics]>, mut pass: impl FnMut(&[ConstraintIndex])) {
+    match statistics {
+        None => pass(constraints),
+        Some(statistics) => {
+            for constraint in constraints.iter() {
+                let start = Instant::now();
+                pass(std::slice::from_ref(constraint));
+                statistics[constraint.0].time += start.elapsed();
+            }
+        },
+    }
+}
+
+/// Resets the top-down properties of the nodes of the layer, then updates them along the active
+/// edges from the previous layer, whose decision is the given variable
+pub fn top_down_pass(problem: &mut Problem, constraints: &[ConstraintIndex], layer: usize, number_nodes: usize, edges: &EdgeLayer, variable: VariableIndex) {
+    for constraint in constraints.iter().copied() {
+        for index in 0..number_nodes {
+            problem[constraint].reset_property_top_down(NodeIndex(layer, index));
+        }
+    }
+    for index in edges.iter_active() {
+        let (from, to) = (edges.from(index), edges.to(index));
+        let assignment = edges.assignment(index);
+        let value = problem[variable].value(assignment);
+        for constraint in constraints.iter().copied() {
+            problem[constraint].update_property_top_down_at(from, to, assignment, value);
+        }
+    }
+}
+
+/// Resets the bottom-up properties of the active nodes of the layer, then updates them along their
+/// active edges to the next layer, whose decision is the given variable
+pub fn bottom_up_pass(problem: &mut Problem, constraints: &[ConstraintIndex], layer: usize, nodes: &[Node], edges: &EdgeLayer, variable: VariableIndex) {
+    for constraint in constraints.iter().copied() {
+        for index in (0..nodes.len()).filter(|index| nodes[*index].is_active()) {
+            problem[constraint].reset_property_bottom_up(NodeIndex(layer, index));
+        }
+    }
+    for index in edges.iter_active() {
+        let (from, to) = (edges.from(index), edges.to(index));
+        let assignment = edges.assignment(index);
+        let value = problem[variable].value(assignment);
+        for constraint in constraints.iter().copied() {
+            problem[constraint].update_property_bottom_up_at(to, from, assignment, value);
+        }
+    }
+}
+
+/// Returns the first of the constraints invalidating the edge of the layer, if any. The time of each
+/// check is added to the statistics of the constraint, if given.
+pub fn invalidating_constraint(problem: &Problem, constraints: &[ConstraintIndex], edges: &EdgeLayer, index: usize, variable: VariableIndex, statistics: Option<&mut [PropagationStatistics]>) -> Option<ConstraintIndex> {
+    let (from, to) = (edges.from(index), edges.to(index));
+    let assignment = edges.assignment(index);
+    let value = problem[variable].value(assignment);
+    let Some(statistics) = statistics else {
+        return constraints.iter().copied().find(|constraint| problem[*constraint].is_assignment_invalid_at(from, to, variable, assignment, value));
+    };
+    constraints.iter().copied().find(|constraint| {
+        let start = Instant::now();
+        let invalid = problem[*constraint].is_assignment_invalid_at(from, to, variable, assignment, value);
+        statistics[constraint.0].time += start.elapsed();
+        invalid
+    })
+}
+
+/// Layers watched by the constraints, i.e., the layers of their scope at which they filter the
+/// edges
+pub struct Watches {
+    /// Constraints watching each layer
+    watchers: Vec<Vec<ConstraintIndex>>,
+    /// Layers watched by each constraint
+    watched: Vec<Vec<usize>>,
+}
+
+impl Watches {
+
+    pub fn new(problem: &Problem, number_layers: usize) -> Self {
+        let mut watchers = vec![vec![]; number_layers];
+        let mut watched = vec![vec![]; problem.number_constraints()];
+        for constraint in problem.iter_constraints() {
+            for layer in (0..number_layers).filter(|layer| problem[constraint].is_layer_in_scope(*layer)) {
+                watchers[layer].push(constraint);
+                watched[constraint.0].push(layer);
+            }
+        }
+        Self { watchers, watched }
+    }
+
+    pub fn watchers(&self, layer: usize) -> &[ConstraintIndex] {
+        &self.watchers[layer]
+    }
+
+    pub fn watched(&self, constraint: ConstraintIndex) -> &[usize] {
+        &self.watched[constraint.0]
+    }
+
+    /// Returns the constraints watching one of the changed layers, by increasing key (e.g., the
+    /// estimated cost of propagation)
+    pub fn schedule<K: Ord>(&self, changed: &[bool], cost: impl Fn(ConstraintIndex) -> K) -> Vec<ConstraintIndex> {
+        let mut queue = BinaryHeap::new();
+        for constraint in (0..self.watched.len()).map(ConstraintIndex) {
+            if self.watched[constraint.0].iter().any(|layer| changed[*layer]) {
+                queue.push(Reverse((cost(constraint), constraint)));
+            }
+        }
+        std::iter::from_fn(|| queue.pop().map(|Reverse((_, constraint))| constraint)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test_propagation {
+
+    use crate::constraints::*;
+    use crate::mdd::propagation::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_watches() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2], None);
+        let all_different = problem.add_constraint(AllDifferent::new(xs.clone()));
+        let not_equals = problem.add_constraint(NotEquals::new(xs[2], xs[3]));
+        problem.init_constraints();
+        for constraint in problem.iter_constraints().collect::<Vec<ConstraintIndex>>() {
+            problem[constraint].update_variable_ordering(&[0, 1, 2, 3]);
+        }
+        let watches = Watches::new(&problem, 4);
+        assert_eq!(watches.watchers(1), &[all_different]);
+        assert_eq!(watches.watchers(3), &[all_different, not_equals]);
+        assert_eq!(watches.watched(not_equals), &[2, 3]);
+
+        let cost = |constraint: ConstraintIndex| watches.watched(constraint).len();
+        assert_eq!(watches.schedule(&[false, true, false, false], cost), vec![all_different]);
+        assert_eq!(watches.schedule(&[false, false, false, true], cost), vec![not_equals, all_different]);
+        assert!(watches.schedule(&[false; 4], cost).is_empty());
+    }
+
+    #[test]
+    pub fn test_priorities() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3, 4], None);
+        let all_different = problem.add_constraint(AllDifferent::new(xs[0..4].to_vec()));
+        let not_equals = (0..4).map(|i| problem.add_constraint(NotEquals::new(xs[i], xs[i + 1]))).collect::<Vec<ConstraintIndex>>();
+        let fixed = problem.add_constraint(Table::new(vec![xs[4]], vec![vec![Some(0)], vec![Some(1)]]));
+        assert_eq!(problem[all_different].priority(), Priority::Global);
+        assert_eq!(problem[not_equals[0]].priority(), Priority::Binary);
+        assert_eq!(problem[fixed].priority(), Priority::Unary);
+
+        // The cheap constraints are propagated first, without changing the fixpoint
+        let expected = reference::count_solutions(&problem);
+        for width in [1, 3, usize::MAX] {
+            let mut mdd = Mdd::new(problem.clone(), width, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            assert_propagation_fixpoint(&mdd);
+            mdd.refine();
+            assert_propagation_fixpoint(&mdd);
+            if width == usize::MAX {
+                assert_eq!(mdd.count_solutions(), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use super::*;
+use super::heuristics::*;
+use crate::modelling::*;
+use rand::prelude::*;
+
+// Generation of puzzles, i.e., of sets of clues (variables fixed to their value in a solution) such
+// that the problem with the clues has a unique solution. The generation starts from every value of
+// the solution as a clue, and tries to remove the clues in a random order: a clue is removed if the
+// problem with the remaining clues still has a unique solution, checked on the MDD of the problem
+// with `Mdd::is_unique_solution`. Without a stopping criterion, the clues returned are irreducible,
+// i.e., none of them can be removed, which is not the same as having the fewest clues. The stopping
+// criterion is given as a function of the remaining clues, e.g., to stop at a target number of
+// clues.
+
+/// Value of a variable given by a puzzle
+pub type Clue = (VariableIndex, isize);
+
+/// Returns clues of the solution of the problem such that the problem with the clues has a unique
+/// solution, the clues being removed in a random order until the given function returns true for
+/// the remaining clues, or none of them can be removed. The uniqueness is checked on the MDDs of
+/// the problem with the given parameters.
+pub fn generate_puzzle(problem: &Problem, solution: &[isize], order: &OrderingHeuristic, merge_heuristic: &MergeHeuristic, max_width: usize, rng: &mut impl Rng, mut stop: impl FnMut(&[Clue]) -> bool) -> Vec<Clue> {
+    let mut clues = problem.iter_variables().map(|variable| (variable, solution[variable.0])).collect::<Vec<Clue>>();
+    assert!(is_unique(problem, &clues, order, merge_heuristic, max_width), "The assignment must be a solution of the problem");
+    let mut candidates = clues.clone();
+    candidates.shuffle(rng);
+    for candidate in candidates {
+        if stop(&clues) {
+            break;
+        }
+        let remaining = clues.iter().copied().filter(|clue| *clue != candidate).collect::<Vec<Clue>>();
+        if is_unique(problem, &remaining, order, merge_heuristic, max_width) {
+            clues = remaining;
+        }
+    }
+    clues
+}
+
+/// Returns true if the problem with the clues has a unique solution
+fn is_unique(problem: &Problem, clues: &[Clue], order: &OrderingHeuristic, merge_heuristic: &MergeHeuristic, max_width: usize) -> bool {
+    let mut puzzle = problem.clone();
+    for (variable, value) in clues.iter().copied() {
+        puzzle.fix(variable, value);
+    }
+    let mut mdd = Mdd::new(puzzle, max_width, order.clone(), merge_heuristic.clone());
+    mdd.refine();
+    mdd.is_unique_solution()
+}
+
+#[cfg(test)]
+mod test_puzzle {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    /// Returns the problem of the latin squares of size n, the variable of the cell (i, j) being
+    /// at index i * n + j
+    fn latin_square(n: usize) -> Problem {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(n * n, (0..n as isize).collect(), None);
+        for i in 0..n {
+            all_different(&mut problem, xs[i * n..(i + 1) * n].to_vec());
+            all_different(&mut problem, (0..n).map(|j| xs[j * n + i]).collect());
+        }
+        problem
+    }
+
+    #[test]
+    pub fn test_generate_puzzle() {
+        let problem = latin_square(4);
+        let solution = vec![0, 1, 2, 3, 1, 0, 3, 2, 2, 3, 0, 1, 3, 2, 1, 0];
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let clues = generate_puzzle(&problem, &solution, &OrderingHeuristic::MinDomMaxLinked, &MergeHeuristic::LessRelaxed, usize::MAX, &mut rng, |_| false);
+        let fixed = |clues: &[Clue]| {
+            let mut puzzle = problem.clone();
+            for (variable, value) in clues.iter().copied() {
+                puzzle.fix(variable, value);
+            }
+            reference::solutions(&puzzle)
+        };
+        assert_eq!(fixed(&clues), vec![solution.clone()]);
+        // The clues are irreducible
+        for clue in clues.iter() {
+            let remaining = clues.iter().copied().filter(|other| other != clue).collect::<Vec<Clue>>();
+            assert!(fixed(&remaining).len() > 1);
+        }
+
+        let clues = generate_puzzle(&problem, &solution, &OrderingHeuristic::MinDomMaxLinked, &MergeHeuristic::LessRelaxed, 2, &mut rng, |clues| clues.len() <= 12);
+        assert_eq!(clues.len(), 12);
+        assert_eq!(fixed(&clues), vec![solution]);
+    }
+}
@@ -0,0 +1,98 @@
+use super::*;
+
+// Existence and uniqueness queries on the solutions of an MDD, which stop as soon as their answer is
+// known instead of counting every path. The paths are enumerated depth-first, and each complete path
+// is checked against the constraints, hence the queries are correct on relaxed MDDs as well. On an
+// exact MDD, every path is a solution and at most k paths are enumerated to decide whether there
+// are at least k solutions. The number of paths maintained on the nodes, when it is up to date, is
+// an upper bound on the number of solutions that answers the negative queries in constant time.
+
+impl Mdd {
+
+    /// Returns true if the MDD has a path that is a solution
+    pub fn has_solution(&self) -> bool {
+        self.count_at_least(1)
+    }
+
+    /// Returns true if exactly one path of the MDD is a solution
+    pub fn is_unique_solution(&self) -> bool {
+        self.count_up_to(2) == 1
+    }
+
+    /// Returns true if at least k paths of the MDD are solutions
+    pub fn count_at_least(&self, k: u128) -> bool {
+        if self.cached_count().is_some_and(|count| count < k) {
+            return false;
+        }
+        self.count_up_to(k) >= k
+    }
+
+    /// Returns the number of paths that are solutions, counted up to k
+    fn count_up_to(&self, k: u128) -> u128 {
+        if k == 0 || self.is_unsat() {
+            return 0;
+        }
+        let mut assignment = vec![0; self.number_layers() - 1];
+        let mut found = 0;
+        self.enumerate_solutions(NodeIndex(0, 0), &mut assignment, &mut found, k);
+        found
+    }
+
+    /// Enumerates the paths from the node to the sink, completing the assignment, until k of the
+    /// paths are solutions. Returns true once they are found.
+    fn enumerate_solutions(&self, node: NodeIndex, assignment: &mut Vec<isize>, found: &mut u128, k: u128) -> bool {
+        let layer = node.0;
+        if layer == self.number_layers() - 1 {
+            if self.is_solution(assignment) {
+                *found += 1;
+            }
+            return *found >= k;
+        }
+        let variable = self.decision_at_layer(layer);
+        for edge in self[node].iter_children().map(|edge| self.edge(edge)).filter(|edge| edge.is_active()) {
+            assignment[variable.0] = self.problem()[variable].value(edge.assignment());
+            if self.enumerate_solutions(edge.to(), assignment, found, k) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test_queries {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_solution_queries() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[1]);
+        less_than(&mut problem, xs[1], xs[2]);
+        let count = reference::count_solutions(&problem);
+        for width in [1, 2, usize::MAX] {
+            let mut mdd = Mdd::new(problem.clone(), width, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            assert!(mdd.has_solution());
+            assert!(!mdd.is_unique_solution());
+            assert!(mdd.count_at_least(count));
+            assert!(!mdd.count_at_least(count + 1));
+        }
+
+        // The only solution is the increasing permutation
+        less_than(&mut problem, xs[2], xs[3]);
+        let mut mdd = Mdd::new(problem.clone(), 2, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert!(mdd.is_unique_solution());
+        less_than(&mut problem, xs[3], xs[0]);
+        let mut mdd = Mdd::new(problem, 2, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert!(!mdd.has_solution());
+        assert!(mdd.count_at_least(0));
+    }
+}
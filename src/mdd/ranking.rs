@@ -0,0 +1,198 @@
+use super::*;
+use crate::modelling::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+// Enumeration of the paths of an MDD by increasing total weight of their edges (see
+// `Mdd::set_edge_weights`). The weight of the shortest path from each node to the sink is computed
+// first, and the partial paths from the root are then extended best-first, their priority being
+// their weight plus the weight of the shortest path completing them. Since this estimate is exact,
+// the complete paths are popped in order, and only the partial paths on the way to the first k
+// ones are extended.
+//
+// The diverse solutions are selected greedily: the weight of an assignment is the number of
+// selected paths sharing it, such that the shortest path maximizes the sum of its Hamming distances
+// to the selected paths.
+
+/// Partial path from the root, ordered by decreasing priority for the max-heap
+struct PartialPath {
+    priority: f64,
+    weight: f64,
+    node: NodeIndex,
+    assignment: Vec<isize>,
+}
+
+impl PartialEq for PartialPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PartialPath {}
+
+impl PartialOrd for PartialPath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PartialPath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+impl Mdd {
+
+    /// Returns the k paths from the root to the sink with the least total weight of their edges,
+    /// with their weight, by increasing weight. If the MDD is relaxed, the paths might not be
+    /// solutions.
+    pub fn k_best(&self, k: usize) -> Vec<(f64, Vec<isize>)> {
+        self.k_best_with(k, |variable, assignment| self.assignment_weight(variable, assignment))
+    }
+
+    /// Returns the k most likely paths from the root to the sink, i.e., those whose product of the
+    /// probabilities of their assignments is the largest, with their probability
+    pub fn k_most_likely(&self, k: usize) -> Vec<(f64, Vec<isize>)> {
+        self.k_best_with(k, |variable, assignment| -self.problem()[variable].probability(assignment).ln())
+            .into_iter().map(|(weight, assignment)| ((-weight).exp(), assignment)).collect()
+    }
+
+    /// Returns up to m distinct paths from the root to the sink, each path maximizing the sum of
+    /// its Hamming distances to the previous ones. If the MDD is relaxed, the paths might not be
+    /// solutions.
+    pub fn diverse_solutions(&self, m: usize) -> Vec<Vec<isize>> {
+        let mut selected: Vec<Vec<isize>> = vec![];
+        // Number of selected paths assigning each value index to each variable
+        let mut shared = self.problem().iter_variables().map(|variable| vec![0usize; self.problem()[variable].domain_size()]).collect::<Vec<Vec<usize>>>();
+        while selected.len() < m {
+            // The shortest paths can be some of the selected ones, hence one more is enumerated
+            let candidates = self.k_best_with(selected.len() + 1, |variable, assignment| shared[variable.0][assignment.0] as f64);
+            let Some((_, path)) = candidates.into_iter().find(|(_, path)| !selected.contains(path)) else { break };
+            for (variable, value) in path.iter().copied().enumerate() {
+                let index = self.problem()[VariableIndex(variable)].iter_domain().position(|v| v == value).unwrap();
+                shared[variable][index] += 1;
+            }
+            selected.push(path);
+        }
+        selected
+    }
+
+    /// Returns the path from the root to the sink with the least Hamming distance to the target
+    /// assignment, which may not be a solution, with the distance. Returns None if the MDD is
+    /// unsat. If the MDD is relaxed, the path might not be a solution.
+    pub fn closest_solution(&self, target: &[isize]) -> Option<(usize, Vec<isize>)> {
+        assert!(target.len() == self.problem().number_variables(), "The target must assign every variable");
+        self.best_path::<MinPlus>(|variable, assignment| if self.problem()[variable].value(assignment) == target[variable.0] { 0.0 } else { 1.0 })
+            .map(|(distance, path)| (distance as usize, path))
+    }
+
+    fn k_best_with(&self, k: usize, weight: impl Fn(VariableIndex, ValueIndex) -> f64 + Sync + Send) -> Vec<(f64, Vec<isize>)> {
+        let mut best = vec![];
+        if self.is_unsat() || k == 0 {
+            return best;
+        }
+        let extend = |value: f64, variable: VariableIndex, assignment: ValueIndex| value + weight(variable, assignment);
+        let remaining = self.backward_values(0.0, f64::INFINITY, extend, f64::min);
+        let sink = self.number_layers() - 1;
+        let mut queue = BinaryHeap::new();
+        queue.push(PartialPath { priority: remaining[0][0], weight: 0.0, node: NodeIndex(0, 0), assignment: vec![0; sink] });
+        while let Some(path) = queue.pop() {
+            if path.priority == f64::INFINITY {
+                break;
+            }
+            if path.node.0 == sink {
+                best.push((path.weight, path.assignment));
+                if best.len() == k {
+                    break;
+                }
+                continue;
+            }
+            let variable = self.decision_at_layer(path.node.0);
+            for edge in self[path.node].iter_children().map(|edge| self.edge(edge)).filter(|edge| edge.is_active()) {
+                let to = edge.to();
+                let weight = path.weight + weight(variable, edge.assignment());
+                let mut assignment = path.assignment.clone();
+                assignment[variable.0] = self.problem()[variable].value(edge.assignment());
+                queue.push(PartialPath { priority: weight + remaining[to.0][to.1], weight, node: to, assignment });
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod test_ranking {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_k_best() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2, 3], Some(vec![0.1, 0.2, 0.3, 0.4]));
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let weight = |solution: &[isize]| solution.iter().enumerate().map(|(variable, value)| (value * (variable as isize + 1)) as f64).sum::<f64>();
+        mdd.set_edge_weights(|variable, value| (value * (variable.0 as isize + 1)) as f64);
+        let mut expected = get_all_solutions(&mdd).into_iter().map(|solution| weight(&solution)).collect::<Vec<f64>>();
+        expected.sort_by(f64::total_cmp);
+
+        let best = mdd.k_best(5);
+        assert_eq!(best.len(), 5);
+        assert_eq!(best[0], (4.0, vec![2, 1, 0]));
+        assert_eq!(best.iter().map(|(weight, _)| *weight).collect::<Vec<f64>>(), expected[..5].to_vec());
+        assert!(best.iter().all(|(cost, solution)| *cost == weight(solution)));
+        assert_eq!(mdd.k_best(100).len(), 24);
+
+        // The permutations of the values 1, 2 and 3 are the most likely
+        let likely = mdd.k_most_likely(7);
+        for (probability, mut solution) in likely[..6].iter().cloned() {
+            assert!((probability - 0.4 * 0.3 * 0.2).abs() < 1e-9);
+            solution.sort();
+            assert_eq!(solution, vec![1, 2, 3]);
+        }
+        assert!((likely[6].0 - 0.4 * 0.3 * 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    pub fn test_diverse_solutions() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let hamming = |a: &[isize], b: &[isize]| a.iter().zip(b.iter()).filter(|(x, y)| x != y).count();
+        // Three permutations differing at every position form a latin square
+        let diverse = mdd.diverse_solutions(3);
+        assert_eq!(diverse.len(), 3);
+        for i in 0..3 {
+            for j in (i + 1)..3 {
+                assert_eq!(hamming(&diverse[i], &diverse[j]), 3);
+            }
+        }
+        let all = mdd.diverse_solutions(10);
+        assert_eq!(all.len(), 6);
+        assert!(all.iter().all(|solution| is_solution(solution.clone(), &get_all_solutions(&mdd))));
+    }
+
+    #[test]
+    pub fn test_closest_solution() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[3]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.closest_solution(&[0, 1, 2, 3]), Some((0, vec![0, 1, 2, 3])));
+        let (distance, solution) = mdd.closest_solution(&[3, 3, 1, 0]).unwrap();
+        assert_eq!(distance, 2);
+        assert!(mdd.is_solution(&solution));
+        let hamming = |a: &[isize]| a.iter().zip([3, 3, 1, 0].iter()).filter(|(x, y)| x != y).count();
+        assert_eq!(hamming(&solution), 2);
+        assert!(get_all_solutions(&mdd).iter().all(|solution| hamming(solution) >= 2));
+    }
+}
@@ -0,0 +1,174 @@
+use super::*;
+use super::operations::{DiagramLayers, reduce};
+use crate::modelling::*;
+use rustc_hash::FxHashMap;
+
+// Dynamic reordering of the variables of a compiled MDD. Swapping two adjacent layers i and i + 1,
+// deciding x then y, rebuilds the nodes of layer i + 1: each node u of layer i has a new child for
+// each value b of y, whose edges go, for each value a of x, to the node reached from u by the edges
+// x = a then y = b. The diagram is then reduced, and the MDD is rebuilt from its layers with the new
+// ordering such that the properties of the constraints match it.
+//
+// Sifting moves each variable, from the widest layers, through every position of the ordering by
+// adjacent swaps, and places it at the position giving the fewest nodes. The swaps are applied on
+// the layers, the MDD being rebuilt once at the end.
+
+impl Mdd {
+
+    /// Swaps the variables of the layers i and i + 1, keeping the same paths
+    pub fn swap_layers(&mut self, i: usize) {
+        assert!(i + 2 < self.number_layers(), "The layers {} and {} must decide variables", i, i + 1);
+        let (mut order, layers) = self.current_layers();
+        order.swap(i, i + 1);
+        *self = self.with_layers(order, swap(layers, i));
+    }
+
+    /// Reorders the variables of the MDD by sifting to reduce its number of nodes, keeping the same
+    /// paths. Returns the number of nodes of the reduced diagram with the new ordering.
+    pub fn sift(&mut self) -> usize {
+        let (mut order, mut layers) = self.current_layers();
+        let Some((nodes, _)) = layers.as_ref() else { return 0 };
+        let mut variables = (0..order.len()).collect::<Vec<usize>>();
+        variables.sort_by_key(|layer| std::cmp::Reverse(nodes[*layer].len()));
+        let variables = variables.into_iter().map(|layer| order[layer]).collect::<Vec<VariableIndex>>();
+        let mut best_size = size(&layers);
+        for variable in variables {
+            let mut position = order.iter().position(|v| *v == variable).unwrap();
+            let mut best_position = position;
+            // Down to the last layer, then up to the first one
+            let targets = ((position + 1)..order.len()).chain((0..order.len() - 1).rev());
+            for target in targets {
+                let swapped = if target > position { position } else { target };
+                layers = swap(layers, swapped);
+                order.swap(swapped, swapped + 1);
+                position = target;
+                let size = size(&layers);
+                if size < best_size {
+                    best_size = size;
+                    best_position = position;
+                }
+            }
+            while position < best_position {
+                layers = swap(layers, position);
+                order.swap(position, position + 1);
+                position += 1;
+            }
+        }
+        *self = self.with_layers(order, layers);
+        best_size
+    }
+
+    /// Returns the order and the reduced layers of the paths of the MDD
+    fn current_layers(&self) -> (Vec<VariableIndex>, DiagramLayers) {
+        let order = (0..self.number_layers() - 1).map(|layer| self.decision_at_layer(layer)).collect::<Vec<VariableIndex>>();
+        if self.is_unsat() {
+            return (order, None);
+        }
+        let relaxed = (0..self.number_layers()).map(|layer| {
+            (0..self.number_nodes_in_layer(layer)).map(|index| self[NodeIndex(layer, index)].is_relaxed()).collect::<Vec<bool>>()
+        }).collect::<Vec<Vec<bool>>>();
+        let edges = self.path_children().into_iter().map(|layer_children| {
+            layer_children.into_iter().enumerate().flat_map(|(from, children)| children.into_iter().map(move |(to, assignment)| (from, to, assignment))).collect()
+        }).collect();
+        (order, reduce(relaxed, edges))
+    }
+}
+
+/// Returns the reduced layers with the layers i and i + 1 swapped
+fn swap(layers: DiagramLayers, i: usize) -> DiagramLayers {
+    let (mut nodes, mut edges) = layers?;
+    // Children (assignment, to) of the nodes of layer i + 1
+    let mut children = vec![vec![]; nodes[i + 1].len()];
+    for (from, to, assignment) in edges[i + 1].iter().copied() {
+        children[from].push((assignment, to));
+    }
+    let mut relaxed = vec![];
+    let mut first = vec![];
+    let mut second = vec![];
+    let mut ids = FxHashMap::<(usize, ValueIndex), usize>::default();
+    for (from, middle, a) in edges[i].iter().copied() {
+        for (b, to) in children[middle].iter().copied() {
+            let id = *ids.entry((from, b)).or_insert_with(|| {
+                relaxed.push(nodes[i][from]);
+                first.push((from, relaxed.len() - 1, b));
+                relaxed.len() - 1
+            });
+            relaxed[id] |= nodes[i + 1][middle];
+            second.push((id, to, a));
+        }
+    }
+    nodes[i + 1] = relaxed;
+    edges[i] = first;
+    edges[i + 1] = second;
+    reduce(nodes, edges)
+}
+
+/// Returns the number of nodes of the layers
+fn size(layers: &DiagramLayers) -> usize {
+    layers.as_ref().map_or(0, |(nodes, _)| nodes.iter().map(|layer| layer.len()).sum())
+}
+
+#[cfg(test)]
+mod test_reordering {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    fn number_active_nodes(mdd: &Mdd) -> usize {
+        (0..mdd.number_layers()).map(|layer| (0..mdd.number_nodes_in_layer(layer)).filter(|index| mdd[NodeIndex(layer, *index)].is_active()).count()).sum()
+    }
+
+    #[test]
+    pub fn test_swap_layers() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs[0..3].to_vec());
+        less_than(&mut problem, xs[3], xs[1]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let mut solutions = get_all_solutions(&mdd);
+        solutions.sort();
+        let order = (0..4).map(|layer| mdd.decision_at_layer(layer)).collect::<Vec<VariableIndex>>();
+        for i in 0..3 {
+            mdd.swap_layers(i);
+            assert_eq!(mdd.decision_at_layer(i), order[i + 1]);
+            assert_eq!(mdd.decision_at_layer(i + 1), order[i]);
+            let mut swapped = get_all_solutions(&mdd);
+            swapped.sort();
+            assert_eq!(swapped, solutions);
+            mdd.check_invariants();
+            assert_propagation_fixpoint(&mdd);
+            mdd.swap_layers(i);
+        }
+        // The properties of the constraints follow the new ordering
+        mdd.swap_layers(1);
+        mdd.refine();
+        assert_eq!(mdd.count_solutions(), solutions.len() as u128);
+    }
+
+    #[test]
+    pub fn test_sift() {
+        // The pairs (x_i, y_i) are equal, hence the diagram is small when they are adjacent
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        let ys = problem.add_variables(3, vec![0, 1, 2], None);
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            table(&mut problem, vec![*x, *y], (0..3).map(|value| vec![value, value]).collect());
+        }
+        let order = (0..6).collect::<Vec<usize>>();
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(order), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let mut solutions = get_all_solutions(&mdd);
+        solutions.sort();
+        let before = number_active_nodes(&mdd);
+        let size = mdd.sift();
+        assert_eq!(size, number_active_nodes(&mdd));
+        assert!(size < before);
+        let mut sifted = get_all_solutions(&mdd);
+        sifted.sort();
+        assert_eq!(sifted, solutions);
+        mdd.check_invariants();
+    }
+}
@@ -0,0 +1,146 @@
+use super::*;
+use super::heuristics::*;
+use crate::constraints::MddConstraint;
+use crate::modelling::*;
+use rustc_hash::FxHashMap;
+
+// Search for a minimum cost solution of a problem with relaxed MDDs, the cost of a solution being
+// the sum of the costs of its values (see `Variable::cost`). The minimum cost of a path of a
+// relaxed MDD bounds the cost of the solutions of its problem, and the problems whose bound is not
+// better than the best solution found are pruned. Otherwise, the problem is divided into
+// sub-problems, which are compiled again:
+// - with branch and bound, the first variable of the MDD with several feasible values is fixed to
+//   each of them;
+// - with peel and bound, each node of the deepest exact layer of the MDD is peeled: its
+//   sub-problem keeps only the paths through the node, by constraining the variables of the
+//   previous layers to the exact diagram from the root to the node (see `MddConstraint`). The
+//   compilation of the previous layers is hence reused instead of being split again. If the layer
+//   has a single node, the sub-problems branch as with branch and bound.
+// The sub-problems are compiled with the variable ordering of the first MDD, such that the peeled
+// diagrams decide their variables in the same order as the MDDs.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchStrategy {
+    BranchAndBound,
+    PeelAndBound,
+}
+
+/// Returns a minimum cost solution of the problem with its cost, or None if it is unsat. The
+/// problems are compiled with the given maximum width.
+pub fn minimize(problem: &Problem, max_width: usize, strategy: SearchStrategy) -> Option<(isize, Vec<isize>)> {
+    let order = OrderingHeuristic::MinDomMaxLinked.get_order(problem).into_iter().map(|variable| variable.0).collect::<Vec<usize>>();
+    let mut best: Option<(isize, Vec<isize>)> = None;
+    let mut pending = vec![problem.clone()];
+    while let Some(sub_problem) = pending.pop() {
+        let mut mdd = Mdd::new(sub_problem.clone(), max_width, OrderingHeuristic::Custom(order.clone()), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let Some((bound, path)) = mdd.minimum_cost_solution() else { continue };
+        if best.as_ref().is_some_and(|(cost, _)| bound >= *cost) {
+            continue;
+        }
+        if mdd.is_solution(&path) {
+            // The bound is reached by a solution, which is optimal for the sub-problem
+            best = Some((bound, path));
+            continue;
+        }
+        let layer = exact_layer(&mdd);
+        let peeled = (0..mdd.number_nodes_in_layer(layer)).map(|index| NodeIndex(layer, index)).filter(|node| mdd[*node].is_active()).collect::<Vec<NodeIndex>>();
+        if strategy == SearchStrategy::PeelAndBound && peeled.len() > 1 {
+            let variables = (0..layer).map(|layer| mdd.decision_at_layer(layer)).collect::<Vec<VariableIndex>>();
+            for node in peeled {
+                let mut peeled_problem = sub_problem.clone();
+                let (arcs, terminal) = prefix_diagram(&mdd, node);
+                peeled_problem.add_constraint(MddConstraint::new(variables.clone(), arcs, 0, terminal));
+                pending.push(peeled_problem);
+            }
+        } else {
+            // The relaxed MDD contains every solution, hence if each variable has a single
+            // feasible value, the only candidate is the path, which is not a solution
+            let Some((variable, values)) = (0..mdd.number_layers() - 1).map(|layer| mdd.decision_at_layer(layer))
+                .map(|variable| (variable, mdd.feasible_values(variable)))
+                .find(|(_, values)| values.len() > 1) else { continue };
+            for value in values.into_iter().rev() {
+                let mut fixed_problem = sub_problem.clone();
+                fixed_problem.fix(variable, value);
+                pending.push(fixed_problem);
+            }
+        }
+    }
+    best
+}
+
+/// Returns the deepest layer of the MDD whose active nodes, and the active nodes of the previous
+/// layers, are all exact
+fn exact_layer(mdd: &Mdd) -> usize {
+    let mut layer = 0;
+    while layer + 1 < mdd.number_layers() && (0..mdd.number_nodes_in_layer(layer + 1)).all(|index| !mdd[NodeIndex(layer + 1, index)].is_active() || !mdd[NodeIndex(layer + 1, index)].is_relaxed()) {
+        layer += 1;
+    }
+    layer
+}
+
+/// Returns the arcs (from, value, to) of the diagram of the paths from the root of the MDD to the
+/// node, with the root numbered 0, and the number of the node
+fn prefix_diagram(mdd: &Mdd, node: NodeIndex) -> (Vec<(usize, isize, usize)>, usize) {
+    let mut ids = FxHashMap::<NodeIndex, usize>::default();
+    ids.insert(NodeIndex(0, 0), 0);
+    ids.insert(node, ids.len());
+    let mut arcs = vec![];
+    let mut stack = vec![node];
+    while let Some(to) = stack.pop() {
+        let variable = mdd.decision_at_layer(to.0 - 1);
+        for edge in mdd[to].iter_parents().map(|edge| mdd.edge(edge)).filter(|edge| edge.is_active()) {
+            let from = edge.from();
+            if !ids.contains_key(&from) {
+                ids.insert(from, ids.len());
+                stack.push(from);
+            }
+            arcs.push((ids[&from], mdd.problem()[variable].value(edge.assignment()), ids[&to]));
+        }
+    }
+    (arcs, ids[&node])
+}
+
+#[cfg(test)]
+mod test_search {
+
+    use crate::fuzz::*;
+    use crate::mdd::search::*;
+    use crate::reference;
+
+    /// Returns the minimum cost of a solution of the problem, enumerated by the reference solver
+    fn minimum_cost(problem: &Problem) -> Option<isize> {
+        let mut best = None;
+        reference::for_each_solution(problem, |solution| {
+            let cost = problem.iter_variables().map(|variable| {
+                problem[variable].cost(ValueIndex(problem[variable].iter_domain().position(|value| value == solution[variable.0]).unwrap()))
+            }).sum::<isize>();
+            if best.is_none_or(|best| cost < best) {
+                best = Some(cost);
+            }
+        });
+        best
+    }
+
+    #[test]
+    pub fn test_minimize() {
+        let config = FuzzConfig { min_variables: 4, max_constraints: 5, ..FuzzConfig::default() };
+        for seed in 0..40 {
+            let mut problem = random_problem(seed, &config);
+            for variable in problem.iter_variables().collect::<Vec<VariableIndex>>() {
+                let costs = (0..problem[variable].domain_size()).map(|index| ((index * 7 + variable.0 * 3 + seed as usize) % 5) as isize).collect::<Vec<isize>>();
+                problem[variable].set_costs(&costs);
+            }
+            let expected = minimum_cost(&problem);
+            for strategy in [SearchStrategy::BranchAndBound, SearchStrategy::PeelAndBound] {
+                for max_width in [1, 2, usize::MAX] {
+                    let result = minimize(&problem, max_width, strategy);
+                    assert_eq!(result.as_ref().map(|(cost, _)| *cost), expected, "Seed {} with {:?} and width {}", seed, strategy, max_width);
+                    if let Some((_, solution)) = result {
+                        assert_eq!(problem.check(&solution), Ok(()));
+                    }
+                }
+            }
+        }
+    }
+}
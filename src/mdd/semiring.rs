@@ -0,0 +1,235 @@
+// Semirings in which an MDD can be evaluated (see `Mdd::evaluate`). The value of a path is the
+// product of the weights of its edges, and the value of the MDD is the sum of the values of its
+// paths from the root to the sink. For example, the number of solutions is computed in the
+// counting semiring with a weight of 1 on each edge, and the minimum cost of a path in the
+// min-plus semiring with the costs of the assignments as weights.
+//
+// The weighted counts and the marginals are computed in the semirings representing real numbers
+// (see `Arithmetic`). The products of many probabilities underflow in f64, hence they can be
+// computed in log-space, whose values are the logarithms of the real numbers, or exactly with the
+// rational numbers (with the `rational` feature), whose size grows with the number of paths.
+
+#[cfg(feature = "rational")]
+use num_rational::BigRational;
+#[cfg(feature = "rational")]
+use num_traits::{One, ToPrimitive, Zero};
+
+pub trait Semiring {
+    type Value: Clone + Send + Sync;
+    /// Neutral element of the sum, the value of an MDD without path
+    fn zero() -> Self::Value;
+    /// Neutral element of the product, the value of the empty path
+    fn one() -> Self::Value;
+    fn add(a: Self::Value, b: Self::Value) -> Self::Value;
+    fn mul(a: Self::Value, b: Self::Value) -> Self::Value;
+}
+
+/// Counting semiring (N, +, *), saturating at `u128::MAX`
+pub struct Counting;
+
+impl Semiring for Counting {
+    type Value = u128;
+
+    fn zero() -> u128 { 0 }
+    fn one() -> u128 { 1 }
+    fn add(a: u128, b: u128) -> u128 { a.saturating_add(b) }
+    fn mul(a: u128, b: u128) -> u128 { a.saturating_mul(b) }
+}
+
+/// Sum-product semiring (R, +, *), e.g., for the probability mass of the paths
+pub struct SumProduct;
+
+impl Semiring for SumProduct {
+    type Value = f64;
+
+    fn zero() -> f64 { 0.0 }
+    fn one() -> f64 { 1.0 }
+    fn add(a: f64, b: f64) -> f64 { a + b }
+    fn mul(a: f64, b: f64) -> f64 { a * b }
+}
+
+/// Log-space sum-product semiring (R ∪ {-inf}, log-sum-exp, +), whose values are the logarithms
+/// of the values of the sum-product semiring
+pub struct LogSumProduct;
+
+impl Semiring for LogSumProduct {
+    type Value = f64;
+
+    fn zero() -> f64 { f64::NEG_INFINITY }
+    fn one() -> f64 { 0.0 }
+    fn add(a: f64, b: f64) -> f64 {
+        let max = a.max(b);
+        if max == f64::NEG_INFINITY {
+            return max;
+        }
+        max + ((a - max).exp() + (b - max).exp()).ln()
+    }
+    fn mul(a: f64, b: f64) -> f64 { a + b }
+}
+
+/// Semiring representing the non-negative real numbers, with their sum and product
+pub trait Arithmetic: Semiring<Value: PartialEq> {
+    /// Returns the value representing the number
+    fn from_f64(value: f64) -> Self::Value;
+    /// Returns the number represented by the value, possibly rounded to 0 or infinity
+    fn to_f64(value: Self::Value) -> f64;
+    fn div(a: Self::Value, b: Self::Value) -> Self::Value;
+}
+
+impl Arithmetic for SumProduct {
+    fn from_f64(value: f64) -> f64 { value }
+    fn to_f64(value: f64) -> f64 { value }
+    fn div(a: f64, b: f64) -> f64 { a / b }
+}
+
+impl Arithmetic for LogSumProduct {
+    fn from_f64(value: f64) -> f64 { value.ln() }
+    fn to_f64(value: f64) -> f64 { value.exp() }
+    fn div(a: f64, b: f64) -> f64 { a - b }
+}
+
+/// Sum-product semiring over the rational numbers, computing the weighted counts and the marginals
+/// exactly. The weights are converted exactly from f64.
+#[cfg(feature = "rational")]
+pub struct Rational;
+
+#[cfg(feature = "rational")]
+impl Semiring for Rational {
+    type Value = BigRational;
+
+    fn zero() -> BigRational { BigRational::zero() }
+    fn one() -> BigRational { BigRational::one() }
+    fn add(a: BigRational, b: BigRational) -> BigRational { a + b }
+    fn mul(a: BigRational, b: BigRational) -> BigRational { a * b }
+}
+
+#[cfg(feature = "rational")]
+impl Arithmetic for Rational {
+    fn from_f64(value: f64) -> BigRational { BigRational::from_float(value).expect("The weight is not a finite number") }
+    fn to_f64(value: BigRational) -> f64 { value.to_f64().unwrap_or(f64::NAN) }
+    fn div(a: BigRational, b: BigRational) -> BigRational { a / b }
+}
+
+/// Expectation semiring over the pairs (w, s), whose product is (w1 w2, w1 s2 + w2 s1). With the
+/// pairs (p, p f) on the edges, the value of an MDD is the sum of the weights of its paths and the
+/// sum of the weights times the sum of the f of their edges, e.g., the entropy with f = ln p.
+pub struct Expectation;
+
+impl Semiring for Expectation {
+    type Value = (f64, f64);
+
+    fn zero() -> (f64, f64) { (0.0, 0.0) }
+    fn one() -> (f64, f64) { (1.0, 0.0) }
+    fn add(a: (f64, f64), b: (f64, f64)) -> (f64, f64) { (a.0 + b.0, a.1 + b.1) }
+    fn mul(a: (f64, f64), b: (f64, f64)) -> (f64, f64) { (a.0 * b.0, a.0 * b.1 + b.0 * a.1) }
+}
+
+/// Max-plus semiring (R ∪ {-inf}, max, +), for the longest path
+pub struct MaxPlus;
+
+impl Semiring for MaxPlus {
+    type Value = f64;
+
+    fn zero() -> f64 { f64::NEG_INFINITY }
+    fn one() -> f64 { 0.0 }
+    fn add(a: f64, b: f64) -> f64 { a.max(b) }
+    fn mul(a: f64, b: f64) -> f64 { a + b }
+}
+
+/// Min-plus semiring (R ∪ {+inf}, min, +), for the shortest path
+pub struct MinPlus;
+
+impl Semiring for MinPlus {
+    type Value = f64;
+
+    fn zero() -> f64 { f64::INFINITY }
+    fn one() -> f64 { 0.0 }
+    fn add(a: f64, b: f64) -> f64 { a.min(b) }
+    fn mul(a: f64, b: f64) -> f64 { a + b }
+}
+
+/// Boolean semiring ({false, true}, or, and), for the existence of a path
+pub struct Boolean;
+
+impl Semiring for Boolean {
+    type Value = bool;
+
+    fn zero() -> bool { false }
+    fn one() -> bool { true }
+    fn add(a: bool, b: bool) -> bool { a || b }
+    fn mul(a: bool, b: bool) -> bool { a && b }
+}
+
+#[cfg(test)]
+mod test_semiring {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_evaluate() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[1]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        let value = |variable: VariableIndex, assignment: ValueIndex| mdd.problem()[variable].value(assignment);
+        assert_eq!(mdd.evaluate::<Counting>(|_, _| 1), solutions.len() as u128);
+        assert_eq!(mdd.evaluate::<Counting>(|_, _| 2), 8 * solutions.len() as u128);
+        assert!(mdd.evaluate::<Boolean>(|_, _| true));
+        assert!(!mdd.evaluate::<Boolean>(|variable, assignment| variable != xs[0] || value(variable, assignment) == 2));
+        // Weighted sum 4 * x0 + 2 * x1 + x2 over the solutions
+        let weighted = |variable: VariableIndex, assignment: ValueIndex| (value(variable, assignment) << (2 - variable.0)) as f64;
+        let sums = solutions.iter().map(|solution| (4 * solution[0] + 2 * solution[1] + solution[2]) as f64).collect::<Vec<f64>>();
+        assert_eq!(mdd.evaluate::<MaxPlus>(weighted), sums.iter().copied().fold(f64::NEG_INFINITY, f64::max));
+        assert_eq!(mdd.evaluate::<MinPlus>(weighted), sums.iter().copied().fold(f64::INFINITY, f64::min));
+        assert_eq!(mdd.evaluate::<SumProduct>(|_, _| 0.5), solutions.len() as f64 * 0.125);
+    }
+
+    #[test]
+    pub fn test_log_space() {
+        // The weight of each path, 10^-600, underflows in f64
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(300, vec![0, 1], None);
+        problem.set_probabilities(xs[0], vec![0.25, 0.75]);
+        clause(&mut problem, vec![xs[0], xs[1]], vec![]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        mdd.set_edge_weights(|_, _| 1e-2);
+        assert_eq!(mdd.weighted_count(), 0.0);
+        let log_count = mdd.weighted_count_in::<LogSumProduct>();
+        let expected = 3.0f64.ln() + 298.0 * 2.0f64.ln() - 600.0 * 10.0f64.ln();
+        assert!((log_count - expected).abs() < 1e-9);
+        // P(x0 = 0 | x0 or x1) = 0.25 * 0.5 / (1 - 0.25 * 0.5)
+        let marginals = mdd.marginals_in::<LogSumProduct>();
+        assert!((LogSumProduct::to_f64(marginals[0][0]) - 1.0 / 7.0).abs() < 1e-9);
+        assert!((LogSumProduct::to_f64(marginals[5][1]) - 0.5).abs() < 1e-9);
+        let marginals = mdd.marginals();
+        assert!((marginals[0][0] - 1.0 / 7.0).abs() < 1e-9);
+    }
+    #[test]
+    #[cfg(feature = "rational")]
+    pub fn test_rational() {
+        use num_rational::BigRational;
+
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1], None);
+        problem.set_probabilities(xs[0], vec![0.25, 0.75]);
+        clause(&mut problem, vec![xs[0], xs[1]], vec![]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        mdd.set_edge_weights(|variable, value| if value == 0 { 0.5 } else { 0.25 + variable.0 as f64 });
+        assert_eq!(Rational::to_f64(mdd.weighted_count_in::<Rational>()), mdd.weighted_count());
+        // P(x0 = 0 | x0 or x1) = 0.25 * 0.5 / (1 - 0.25 * 0.5) is exactly 1/7
+        let marginals = mdd.marginals_in::<Rational>();
+        assert_eq!(marginals[0][0], BigRational::new(1.into(), 7.into()));
+        assert_eq!(marginals[2][1], BigRational::new(1.into(), 2.into()));
+        for (exact, approximate) in marginals.into_iter().flatten().zip(mdd.marginals().into_iter().flatten()) {
+            assert!((Rational::to_f64(exact) - approximate).abs() < 1e-12);
+        }
+    }
+}
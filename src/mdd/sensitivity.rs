@@ -0,0 +1,116 @@
+use super::*;
+use crate::modelling::*;
+
+// Sensitivity of the probability mass of an MDD to the probabilities of the assignments. The mass Z
+// is a sum of products in which each probability p(x = v) appears at most once per path (at the
+// layer deciding x), hence it is linear in each of them and its derivative with respect to p(x = v)
+// is the sum, over the edges assigning v to x, of the mass of the paths reaching the source of the
+// edge times the mass of the paths from its target to the sink. Both masses are computed by a
+// forward and a backward pass, hence the whole gradient costs two passes over the MDD.
+//
+// The probabilities are seen as independent parameters, i.e., the derivatives do not account for
+// the probabilities of a variable summing to 1. The derivative of a marginal P(x = v) = M / Z, where
+// M is the mass of the paths assigning v to x, is (M' Z - M Z') / Z^2, the gradient of M being the
+// gradient of the mass of the MDD whose edges assigning another value to x are weighted by 0.
+
+impl Mdd {
+
+    /// Returns, for each variable and each value of its domain, the derivative of the probability
+    /// mass of the MDD (see `Mdd::probability_mass`) with respect to the probability of the value
+    pub fn probability_gradient(&self) -> Vec<Vec<f64>> {
+        self.gradient_with(|variable, assignment| self.problem()[variable].probability(assignment)).1
+    }
+
+    /// Returns, for each variable and each value of its domain, the derivative of the marginal of
+    /// the value of the variable (see `Mdd::marginals`) with respect to the probability of the
+    /// value. The derivatives are 0 if the MDD has no probability mass.
+    pub fn marginal_gradient(&self, variable: VariableIndex, value: ValueIndex) -> Vec<Vec<f64>> {
+        let (total, total_gradient) = self.gradient_with(|other, assignment| self.problem()[other].probability(assignment));
+        let (mass, mut gradient) = self.gradient_with(|other, assignment| {
+            if other == variable && assignment != value { 0.0 } else { self.problem()[other].probability(assignment) }
+        });
+        // The derivatives with respect to the variable itself do not see its zeroed values
+        for (index, derivative) in gradient[variable.0].iter_mut().enumerate() {
+            if index != value.0 {
+                *derivative = 0.0;
+            }
+        }
+        if total == 0.0 {
+            return gradient.into_iter().map(|derivatives| vec![0.0; derivatives.len()]).collect();
+        }
+        gradient.iter().zip(total_gradient.iter()).map(|(derivatives, total_derivatives)| {
+            derivatives.iter().zip(total_derivatives.iter()).map(|(derivative, total_derivative)| (derivative * total - mass * total_derivative) / (total * total)).collect()
+        }).collect()
+    }
+
+    /// Returns the sum, over the paths, of the product of the weights of their assignments, with
+    /// its derivative with respect to the weight of each value of each variable
+    pub(crate) fn gradient_with(&self, weight: impl Fn(VariableIndex, ValueIndex) -> f64 + Sync + Send) -> (f64, Vec<Vec<f64>>) {
+        let mut gradient = self.problem().iter_variables().map(|variable| vec![0.0; self.problem()[variable].domain_size()]).collect::<Vec<Vec<f64>>>();
+        if self.is_unsat() {
+            return (0.0, gradient);
+        }
+        let extend = |mass: f64, variable: VariableIndex, assignment: ValueIndex| mass * weight(variable, assignment);
+        let forward = self.forward_values(1.0, 0.0, extend, |a, b| a + b);
+        let backward = self.backward_values(1.0, 0.0, extend, |a, b| a + b);
+        for layer in 0..self.number_layers() - 1 {
+            let variable = self.decision_at_layer(layer);
+            for node in 0..self.number_nodes_in_layer(layer) {
+                for edge in self[NodeIndex(layer, node)].iter_children().map(|edge| self.edge(edge)).filter(|edge| edge.is_active()) {
+                    gradient[variable.0][edge.assignment().0] += forward[layer][node] * backward[layer + 1][edge.to().1];
+                }
+            }
+        }
+        (backward[0][0], gradient)
+    }
+}
+
+#[cfg(test)]
+mod test_sensitivity {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+
+    /// Returns the sum, over the solutions accepted by the filter, of the product of the
+    /// probabilities of their values, the probability of the given variable being left out
+    fn partial_mass(problem: &Problem, solutions: &[Vec<isize>], filter: impl Fn(&[isize]) -> bool, skipped: usize) -> f64 {
+        solutions.iter().filter(|solution| filter(solution)).map(|solution| {
+            solution.iter().enumerate().filter(|(variable, _)| *variable != skipped).map(|(variable, value)| problem[VariableIndex(variable)].probability(ValueIndex(*value as usize))).product::<f64>()
+        }).sum()
+    }
+
+    #[test]
+    pub fn test_sensitivity() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        problem.set_probabilities(xs[0], vec![0.5, 0.2, 0.3]);
+        problem.set_probabilities(xs[1], vec![0.1, 0.6, 0.3]);
+        less_than(&mut problem, xs[0], xs[1]);
+        not_equals(&mut problem, xs[1], xs[2]);
+        let solutions = reference::solutions(&problem);
+        let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let total = partial_mass(&problem, &solutions, |_| true, usize::MAX);
+        let gradient = mdd.probability_gradient();
+        for (variable, derivatives) in gradient.iter().enumerate() {
+            for (value, derivative) in derivatives.iter().enumerate() {
+                let expected = partial_mass(&problem, &solutions, |solution| solution[variable] == value as isize, variable);
+                assert!((derivative - expected).abs() < 1e-9);
+            }
+        }
+
+        // The derivatives of the marginal P(x1 = 2) = M / Z
+        let mass = partial_mass(&problem, &solutions, |solution| solution[1] == 2, usize::MAX);
+        let gradient = mdd.marginal_gradient(xs[1], ValueIndex(2));
+        for (variable, derivatives) in gradient.iter().enumerate() {
+            for (value, derivative) in derivatives.iter().enumerate() {
+                let mass_derivative = partial_mass(&problem, &solutions, |solution| solution[1] == 2 && solution[variable] == value as isize, variable);
+                let total_derivative = partial_mass(&problem, &solutions, |solution| solution[variable] == value as isize, variable);
+                let expected = (mass_derivative * total - mass * total_derivative) / (total * total);
+                assert!((derivative - expected).abs() < 1e-9);
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use super::*;
+use crate::modelling::*;
+
+// Trace of the compilation of an MDD, for teaching and debugging the refinement. When the trace is
+// enabled (see `Mdd::enable_trace`), the MDD records every split, merge and edge removal, and
+// optionally a graphviz frame after each phase of the refinement, such that the compilation can be
+// replayed step by step.
+
+/// Event of the compilation of the MDD
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// The node is split, each of its parent edges but the first being moved to a new node
+    Split { node: NodeIndex, new_nodes: Vec<NodeIndex> },
+    /// The node is merged into another one. The merge is exact if both nodes have the same state
+    /// for every constraint, otherwise the node receiving the merge becomes relaxed.
+    Merge { from: NodeIndex, into: NodeIndex, exact: bool },
+    /// The edge is removed, by the propagation of the constraint or because one of its nodes lost
+    /// all its parents or children. The constraint is None if the removal is not caused by the
+    /// propagation.
+    RemoveEdge { layer: usize, from: NodeIndex, to: NodeIndex, assignment: ValueIndex, constraint: Option<ConstraintIndex> },
+}
+
+impl std::fmt::Display for TraceEvent {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceEvent::Split { node, new_nodes } => {
+                let new_nodes = new_nodes.iter().map(|new_node| format!("N{}_{}", new_node.0, new_node.1)).collect::<Vec<String>>();
+                write!(f, "split N{}_{} into [{}]", node.0, node.1, new_nodes.join(", "))
+            },
+            TraceEvent::Merge { from, into, exact } => {
+                write!(f, "merge N{}_{} into N{}_{}{}", from.0, from.1, into.0, into.1, if *exact { " (exact)" } else { "" })
+            },
+            TraceEvent::RemoveEdge { layer, from, to, assignment, constraint } => {
+                write!(f, "remove edge N{}_{} -> N{}_{} with value index {} at layer {}", from.0, from.1, to.0, to.1, assignment.0, layer)?;
+                match constraint {
+                    Some(constraint) => write!(f, " by constraint {}", constraint.0),
+                    None => Ok(()),
+                }
+            },
+        }
+    }
+}
+
+/// Graphviz drawing of the MDD after a phase of the refinement
+#[derive(Clone, Debug)]
+pub struct TraceFrame {
+    /// Number of events recorded before the frame
+    pub number_events: usize,
+    /// Phase after which the frame is drawn (e.g., "propagate")
+    pub phase: String,
+    pub graphviz: String,
+}
+
+/// Events and frames recorded during the compilation of an MDD
+#[derive(Clone, Debug, Default)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+    pub frames: Vec<TraceFrame>,
+    /// Are the frames recorded
+    record_frames: bool,
+}
+
+impl Trace {
+
+    pub fn new(record_frames: bool) -> Self {
+        Self {
+            events: vec![],
+            frames: vec![],
+            record_frames,
+        }
+    }
+
+    pub fn record_frames(&self) -> bool {
+        self.record_frames
+    }
+
+    /// Replays the trace: returns, for each frame, the events recorded since the previous frame
+    /// and the frame itself
+    pub fn replay(&self) -> impl Iterator<Item = (&[TraceEvent], &TraceFrame)> + '_ {
+        self.frames.iter().enumerate().map(|(i, frame)| {
+            let start = if i == 0 { 0 } else { self.frames[i - 1].number_events };
+            (&self.events[start..frame.number_events], frame)
+        })
+    }
+
+    /// Writes the frames in the directory, as the graphviz files frame_000.dot, frame_001.dot, ...
+    #[cfg(feature = "fs")]
+    pub fn write_frames(&self, directory: &str) {
+        for (i, frame) in self.frames.iter().enumerate() {
+            let path = std::path::Path::new(directory).join(format!("frame_{:03}.dot", i));
+            std::fs::write(path, &frame.graphviz).expect("Failed to write the trace frame");
+        }
+    }
+
+    /// Returns the log of the trace, with one line per event and per frame
+    pub fn log(&self) -> String {
+        let mut out = String::new();
+        let mut next_frame = self.frames.iter().peekable();
+        for (i, event) in self.events.iter().enumerate() {
+            while let Some(frame) = next_frame.next_if(|frame| frame.number_events == i) {
+                out.push_str(&format!("-- {}\n", frame.phase));
+            }
+            out.push_str(&format!("{}\n", event));
+        }
+        for frame in next_frame {
+            out.push_str(&format!("-- {}\n", frame.phase));
+        }
+        out
+    }
+}
@@ -0,0 +1,213 @@
+use super::*;
+use super::distribution::check_probabilities;
+use crate::mdd::{Mdd, MaxPlus, SumProduct};
+use rustc_hash::FxHashMap;
+
+// Encoding of Bayesian networks into problems whose weighted solutions are the assignments of the
+// network with their joint probability. Each variable of the network is a variable of the problem.
+// The probabilities of a variable without parents weight its values. A variable X with parents U
+// has a parameter variable theta_u for each assignment u of its parents (each row of its table),
+// whose domain is the domain of X and a sentinel value: theta_u equals X if U = u, and takes the
+// sentinel otherwise (a table constraint over U, X and the parameters). The value x of theta_u is
+// weighted by P(X = x | U = u) and the sentinel by 1, hence each assignment of the network has a
+// single solution, whose weight is its joint probability.
+//
+// The weights depend on the rows of the tables, hence they are given to the MDD as edge weights
+// rather than as the probabilities of the variables, which must be distributions. The weighted
+// count of the MDD is then the probability of the evidence (the fixed variables), the weighted
+// marginals are the posterior marginals, and the longest path with the logarithms of the weights is
+// the most probable explanation (MAP) of the evidence.
+
+#[derive(Clone, Debug)]
+struct BayesNode {
+    domain: Vec<isize>,
+    parents: Vec<usize>,
+    /// Distribution of the variable for each assignment of the parents
+    table: Vec<Vec<f64>>,
+}
+
+/// Bayesian network over discrete variables, given with their conditional probability tables
+#[derive(Clone, Debug, Default)]
+pub struct BayesianNetwork {
+    nodes: Vec<BayesNode>,
+}
+
+impl BayesianNetwork {
+
+    /// Adds a variable with the given parents, which must already be in the network, and returns
+    /// its index. The table has a row per assignment of the parents, ordered with the last parent
+    /// varying fastest, giving the probability of each value of the domain given the assignment.
+    pub fn add_variable(&mut self, domain: Vec<isize>, parents: Vec<usize>, table: Vec<Vec<f64>>) -> usize {
+        assert!(parents.iter().all(|parent| *parent < self.nodes.len()), "The parents must be added before their children");
+        let number_rows = parents.iter().map(|parent| self.nodes[*parent].domain.len()).product::<usize>();
+        assert!(table.len() == number_rows, "The table must have one row per assignment of the parents");
+        for row in table.iter() {
+            check_probabilities(row, domain.len());
+        }
+        self.nodes.push(BayesNode { domain, parents, table });
+        self.nodes.len() - 1
+    }
+
+    pub fn number_variables(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the joint probability of the values of the variables
+    pub fn joint_probability(&self, assignment: &[isize]) -> f64 {
+        self.nodes.iter().enumerate().map(|(variable, node)| {
+            let row = node.parents.iter().fold(0, |row, parent| {
+                let domain = &self.nodes[*parent].domain;
+                row * domain.len() + domain.iter().position(|value| *value == assignment[*parent]).unwrap()
+            });
+            let index = node.domain.iter().position(|value| *value == assignment[variable]).unwrap();
+            node.table[row][index]
+        }).product()
+    }
+
+    /// Returns the problem encoding the network
+    pub fn encode(&self) -> BayesEncoding {
+        let mut problem = Problem::default();
+        let mut weights: Vec<FxHashMap<isize, f64>> = vec![];
+        let mut variables = vec![];
+        for node in self.nodes.iter() {
+            let variable = problem.add_variable(node.domain.clone(), None);
+            variables.push(variable);
+            weights.push(FxHashMap::default());
+            if node.parents.is_empty() {
+                weights[variable.0] = node.domain.iter().copied().zip(node.table[0].iter().copied()).collect();
+                continue;
+            }
+            let sentinel = node.domain.iter().copied().max().unwrap() + 1;
+            let mut parameter_domain = node.domain.clone();
+            parameter_domain.push(sentinel);
+            let parameters = node.table.iter().map(|row| {
+                let parameter = problem.add_variable(parameter_domain.clone(), None);
+                weights.push(node.domain.iter().copied().zip(row.iter().copied()).collect());
+                parameter
+            }).collect::<Vec<VariableIndex>>();
+            let mut tuples = vec![];
+            for row in 0..node.table.len() {
+                // Values of the parents of the row, the last one varying fastest
+                let mut parent_values = vec![];
+                let mut rest = row;
+                for parent in node.parents.iter().rev() {
+                    let domain = &self.nodes[*parent].domain;
+                    parent_values.push(domain[rest % domain.len()]);
+                    rest /= domain.len();
+                }
+                parent_values.reverse();
+                for value in node.domain.iter().copied() {
+                    let mut tuple = parent_values.clone();
+                    tuple.push(value);
+                    tuple.extend((0..parameters.len()).map(|other| if other == row { value } else { sentinel }));
+                    tuples.push(tuple);
+                }
+            }
+            let scope = node.parents.iter().map(|parent| variables[*parent]).chain(std::iter::once(variable)).chain(parameters).collect::<Vec<VariableIndex>>();
+            table(&mut problem, scope, tuples);
+        }
+        BayesEncoding { problem, variables, weights }
+    }
+}
+
+/// Problem encoding a Bayesian network (see `BayesianNetwork::encode`)
+pub struct BayesEncoding {
+    pub problem: Problem,
+    /// Variables of the problem, the variable i of the network being at index i
+    pub variables: Vec<VariableIndex>,
+    /// Weight of the values of each variable of the problem, the missing values weighting 1
+    weights: Vec<FxHashMap<isize, f64>>,
+}
+
+impl BayesEncoding {
+
+    /// Fixes the variable of the network to the observed value
+    pub fn observe(&mut self, variable: usize, value: isize) {
+        self.problem.fix(self.variables[variable], value);
+    }
+
+    /// Returns the weight of the value of the variable of the problem
+    pub fn weight(&self, variable: VariableIndex, value: isize) -> f64 {
+        self.weights[variable.0].get(&value).copied().unwrap_or(1.0)
+    }
+
+    /// Sets the weights of the edges of the MDD of the problem, such that its weighted count is the
+    /// probability of the evidence
+    pub fn set_weights(&self, mdd: &mut Mdd) {
+        mdd.set_edge_weights(|variable, value| self.weight(variable, value));
+    }
+
+    /// Returns the probability of the evidence, from the MDD of the problem. If the MDD is
+    /// relaxed, it is an upper bound.
+    pub fn evidence_probability(&self, mdd: &Mdd) -> f64 {
+        mdd.evaluate::<SumProduct>(|variable, assignment| self.weight(variable, mdd.problem()[variable].value(assignment)))
+    }
+
+    /// Returns, for each variable of the network and each value of its domain, its probability
+    /// given the evidence, from the exact MDD of the problem
+    pub fn posterior_marginals(&self, mdd: &Mdd) -> Vec<Vec<f64>> {
+        let marginals = mdd.marginals_with::<SumProduct>(|variable, assignment| self.weight(variable, mdd.problem()[variable].value(assignment)));
+        self.variables.iter().map(|variable| marginals[variable.0].clone()).collect()
+    }
+
+    /// Returns the most probable assignment of the variables of the network given the evidence,
+    /// with its joint probability, from the exact MDD of the problem. Returns None if the evidence
+    /// is impossible.
+    pub fn most_probable_explanation(&self, mdd: &Mdd) -> Option<(f64, Vec<isize>)> {
+        let (log_probability, assignment) = mdd.best_path::<MaxPlus>(|variable, assignment| self.weight(variable, mdd.problem()[variable].value(assignment)).ln())?;
+        if log_probability == f64::NEG_INFINITY {
+            return None;
+        }
+        Some((log_probability.exp(), self.variables.iter().map(|variable| assignment[variable.0]).collect()))
+    }
+}
+
+#[cfg(test)]
+mod test_bayes {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+
+    /// Returns the network Rain -> Sprinkler, (Rain, Sprinkler) -> Wet grass
+    fn network() -> BayesianNetwork {
+        let mut network = BayesianNetwork::default();
+        let rain = network.add_variable(vec![0, 1], vec![], vec![vec![0.8, 0.2]]);
+        let sprinkler = network.add_variable(vec![0, 1], vec![rain], vec![vec![0.6, 0.4], vec![0.99, 0.01]]);
+        network.add_variable(vec![0, 1], vec![rain, sprinkler], vec![vec![1.0, 0.0], vec![0.1, 0.9], vec![0.2, 0.8], vec![0.01, 0.99]]);
+        network
+    }
+
+    #[test]
+    pub fn test_bayes_inference() {
+        let network = network();
+        let mut encoding = network.encode();
+        encoding.observe(2, 1);
+        let mut mdd = Mdd::new(encoding.problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        // The joint probabilities of the assignments with wet grass
+        let assignments = (0..4).map(|bits| vec![bits >> 1, bits & 1, 1]).collect::<Vec<Vec<isize>>>();
+        let probabilities = assignments.iter().map(|assignment| network.joint_probability(assignment)).collect::<Vec<f64>>();
+        let evidence = probabilities.iter().sum::<f64>();
+        assert!((encoding.evidence_probability(&mdd) - evidence).abs() < 1e-9);
+        encoding.set_weights(&mut mdd);
+        assert!((mdd.weighted_count() - evidence).abs() < 1e-9);
+        let marginals = encoding.posterior_marginals(&mdd);
+        assert!((marginals[0][1] - (probabilities[2] + probabilities[3]) / evidence).abs() < 1e-9);
+        // The domain of the observed variable is reduced to its value
+        assert_eq!(marginals[2].len(), 1);
+        assert!((marginals[2][0] - 1.0).abs() < 1e-9);
+        let (probability, assignment) = encoding.most_probable_explanation(&mdd).unwrap();
+        let best = (0..4).max_by(|a, b| probabilities[*a].total_cmp(&probabilities[*b])).unwrap();
+        assert_eq!(assignment, assignments[best]);
+        assert!((probability - probabilities[best]).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "The table must have one row per assignment of the parents")]
+    pub fn test_bayes_invalid_table() {
+        let mut network = BayesianNetwork::default();
+        let rain = network.add_variable(vec![0, 1], vec![], vec![vec![0.8, 0.2]]);
+        network.add_variable(vec![0, 1], vec![rain], vec![vec![0.6, 0.4]]);
+    }
+}
@@ -0,0 +1,172 @@
+use super::*;
+use crate::constraints::{Constraint, Table};
+use rustc_hash::FxHashMap;
+
+// Decomposition of constraints without a dedicated propagator into table constraints. The
+// satisfying assignments of the scope are enumerated with `Constraint::is_satisfied`. If the scope
+// has at most three variables, they are posted as a single table. Otherwise, they are compiled into
+// a layered automaton (the variables are read in the order of the scope), whose states are added
+// as auxiliary variables: the constraint is then the chain of ternary tables (state, variable, next
+// state), with binary tables at both ends.
+
+/// Maximum number of assignments of the scope enumerated to decompose a constraint
+const MAX_ENUMERATION: usize = 1 << 20;
+
+/// Decomposes the constraint into binary/ternary table constraints and adds them to the problem,
+/// instead of the constraint itself. This is a fallback for constraints whose propagator is missing
+/// or too weak, as the decomposition is exponential in the size of the scope. With the `tracing`
+/// feature, a warning is emitted for each decomposed constraint.
+pub fn decompose(problem: &mut Problem, mut constraint: impl Constraint) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!("a constraint is decomposed into table constraints");
+    constraint.init(problem.variables());
+    let scope = constraint.iter_scope().collect::<Vec<VariableIndex>>();
+    let mut assignment = vec![0; problem.number_variables()];
+    let tuples = satisfying_tuples(problem, &scope, |values| {
+        for (variable, value) in scope.iter().zip(values.iter()) {
+            assignment[variable.0] = *value;
+        }
+        constraint.is_satisfied(&assignment)
+    });
+    post_tuples(problem, scope, tuples);
+}
+
+/// Enforces that the values of the variables, in the order of the scope, satisfy the predicate. The
+/// predicate is decomposed into table constraints (see `decompose`).
+pub fn predicate(problem: &mut Problem, scope: Vec<VariableIndex>, predicate: impl Fn(&[isize]) -> bool) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!("a predicate is decomposed into table constraints");
+    let tuples = satisfying_tuples(problem, &scope, predicate);
+    post_tuples(problem, scope, tuples);
+}
+
+/// Returns the assignments of the scope, in lexicographic order, satisfying the predicate
+fn satisfying_tuples(problem: &Problem, scope: &[VariableIndex], mut predicate: impl FnMut(&[isize]) -> bool) -> Vec<Vec<isize>> {
+    let domains = scope.iter().map(|variable| problem[*variable].iter_domain().collect::<Vec<isize>>()).collect::<Vec<Vec<isize>>>();
+    let size = domains.iter().try_fold(1usize, |size, domain| size.checked_mul(domain.len()));
+    assert!(size.is_some_and(|size| size <= MAX_ENUMERATION), "The scope of the decomposed constraint is too large");
+    let mut tuples = vec![];
+    let mut indexes = vec![0; scope.len()];
+    if domains.iter().any(|domain| domain.is_empty()) {
+        return tuples;
+    }
+    loop {
+        let tuple = indexes.iter().zip(domains.iter()).map(|(index, domain)| domain[*index]).collect::<Vec<isize>>();
+        if predicate(&tuple) {
+            tuples.push(tuple);
+        }
+        // Next assignment, in lexicographic order
+        let mut position = scope.len();
+        loop {
+            if position == 0 {
+                return tuples;
+            }
+            position -= 1;
+            indexes[position] += 1;
+            if indexes[position] < domains[position].len() {
+                break;
+            }
+            indexes[position] = 0;
+        }
+    }
+}
+
+/// Posts the tuples on the scope as binary/ternary table constraints
+fn post_tuples(problem: &mut Problem, scope: Vec<VariableIndex>, tuples: Vec<Vec<isize>>) {
+    let n = scope.len();
+    if n <= 3 {
+        table(problem, scope, tuples);
+        return;
+    }
+    // states[i] maps the prefixes of length i to their state. Two prefixes have the same state if
+    // they have the same completions.
+    let mut states = vec![FxHashMap::<Vec<isize>, usize>::default(); n + 1];
+    for tuple in tuples.iter() {
+        states[n].insert(tuple.clone(), 0);
+    }
+    for length in (0..n).rev() {
+        let mut completions = FxHashMap::<Vec<isize>, Vec<(isize, usize)>>::default();
+        for tuple in tuples.iter() {
+            let next = states[length + 1][&tuple[..length + 1]];
+            completions.entry(tuple[..length].to_vec()).or_default().push((tuple[length], next));
+        }
+        let mut ids = FxHashMap::<Vec<(isize, usize)>, usize>::default();
+        for (prefix, mut completion) in completions {
+            completion.sort_unstable();
+            completion.dedup();
+            let number_ids = ids.len();
+            let id = *ids.entry(completion).or_insert(number_ids);
+            states[length].insert(prefix, id);
+        }
+    }
+    // Auxiliary variables for the states after the variables 0..n-1 (the first and last states are
+    // unique and omitted)
+    let auxiliaries = (1..n).map(|length| {
+        let number_states = states[length].values().copied().max().map(|id| id + 1).unwrap_or(0);
+        problem.add_variable((0..number_states as isize).collect(), None)
+    }).collect::<Vec<VariableIndex>>();
+    for position in 0..n {
+        let mut transitions = tuples.iter().map(|tuple| {
+            let mut transition = vec![];
+            if position > 0 {
+                transition.push(Some(states[position][&tuple[..position]] as isize));
+            }
+            transition.push(Some(tuple[position]));
+            if position < n - 1 {
+                transition.push(Some(states[position + 1][&tuple[..position + 1]] as isize));
+            }
+            transition
+        }).collect::<Vec<Vec<Option<isize>>>>();
+        transitions.sort_unstable();
+        transitions.dedup();
+        let mut variables = vec![];
+        if position > 0 {
+            variables.push(auxiliaries[position - 1]);
+        }
+        variables.push(scope[position]);
+        if position < n - 1 {
+            variables.push(auxiliaries[position]);
+        }
+        problem.add_constraint(Table::new(variables, transitions));
+    }
+}
+
+#[cfg(test)]
+mod test_decomposition {
+
+    use crate::constraints::*;
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_decompose_constraint() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1, 2], None);
+        let y = problem.add_variable(vec![0, 1, 2], None);
+        decompose(&mut problem, LessEqual::new(x, y, 1));
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 3);
+        assert!(solutions.iter().all(|solution| solution[0] < solution[1]));
+    }
+
+    #[test]
+    pub fn test_chain_decomposition() {
+        // The number of 1s is even, over five variables
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1], None);
+        predicate(&mut problem, xs, |values| values.iter().sum::<isize>() % 2 == 0);
+        // A state variable is added between two consecutive variables, with two states (parity)
+        assert_eq!(problem.number_variables(), 9);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 16);
+        assert!(solutions.iter().all(|solution| solution[0..5].iter().sum::<isize>() % 2 == 0));
+    }
+}
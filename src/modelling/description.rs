@@ -0,0 +1,263 @@
+use crate::constraints::*;
+use super::*;
+use rustc_hash::FxHashMap;
+
+// Declarative description of a problem: its variables with their domain, probabilities and costs,
+// and its constraints with the arguments of their constructor. A problem can be built from its
+// description and described again (see `Problem::from_description` and `Problem::description`),
+// which allows to generate models outside of Rust. With the `serde` feature, the descriptions can
+// be serialized, and the problems read from or written to JSON (see `Problem::from_json`).
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProblemDescription {
+    pub variables: Vec<VariableDescription>,
+    pub constraints: Vec<ConstraintDescription>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariableDescription {
+    pub domain: DomainDescription,
+    /// Probability of each value of the domain, uniform if None
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub probabilities: Option<Vec<f64>>,
+    /// Cost of each value of the domain, 0 if empty
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub costs: Vec<isize>,
+}
+
+/// Description of a domain (see `Domain`), the intervals being given without their offsets
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DomainDescription {
+    Values(Vec<isize>),
+    Boolean,
+    Intervals(Vec<(isize, isize)>),
+}
+
+/// Description of a constraint, with the arguments of its constructor
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+pub enum ConstraintDescription {
+    AbsDiff { x: VariableIndex, y: VariableIndex, relation: Relation, constant: isize },
+    AllDifferent { variables: Vec<VariableIndex>, offsets: Vec<isize> },
+    AllDifferentExcept { variables: Vec<VariableIndex>, sentinel: isize },
+    Arithmetic { x: VariableIndex, y: VariableIndex, z: VariableIndex, operation: Operation },
+    Cardinality { literals: Vec<(VariableIndex, bool)>, lower: usize, upper: usize },
+    CostBound { variables: Vec<VariableIndex>, costs: Vec<Vec<(isize, isize)>>, bound: isize },
+    CostGcc { variables: Vec<VariableIndex>, values: Vec<isize>, lower: Vec<usize>, upper: Vec<usize>, costs: Vec<isize>, z: VariableIndex },
+    CostRegular { variables: Vec<VariableIndex>, transitions: Vec<(usize, isize, usize, isize)>, initial: usize, finals: Vec<usize>, z: VariableIndex },
+    GlobalContiguity { variables: Vec<VariableIndex> },
+    Increasing { variables: Vec<VariableIndex>, strict: bool },
+    Inverse { xs: Vec<VariableIndex>, ys: Vec<VariableIndex> },
+    LessEqual { x: VariableIndex, y: VariableIndex, offset: isize },
+    Maximum { xs: Vec<VariableIndex>, y: VariableIndex },
+    MddConstraint { variables: Vec<VariableIndex>, arcs: Vec<(usize, isize, usize)>, root: usize, terminal: usize },
+    Minimum { xs: Vec<VariableIndex>, y: VariableIndex },
+    NotEquals { x: VariableIndex, y: VariableIndex },
+    PseudoBoolean { terms: Vec<(VariableIndex, isize)>, bound: isize },
+    Reified { constraint: Box<ConstraintDescription>, b: VariableIndex },
+    Stretch { variables: Vec<VariableIndex>, values: Vec<isize>, min_lengths: Vec<usize>, max_lengths: Vec<usize> },
+    Table { variables: Vec<VariableIndex>, tuples: Vec<Vec<Option<isize>>> },
+    Tautology,
+}
+
+impl From<&Domain> for DomainDescription {
+    fn from(domain: &Domain) -> Self {
+        match domain {
+            Domain::Values(values) => DomainDescription::Values(values.clone()),
+            Domain::Boolean => DomainDescription::Boolean,
+            Domain::Intervals { intervals, .. } => DomainDescription::Intervals(intervals.clone()),
+        }
+    }
+}
+
+impl From<&DomainDescription> for Domain {
+    fn from(description: &DomainDescription) -> Self {
+        match description {
+            DomainDescription::Values(values) => Domain::Values(values.clone()),
+            DomainDescription::Boolean => Domain::Boolean,
+            DomainDescription::Intervals(intervals) => Domain::intervals(intervals.clone()),
+        }
+    }
+}
+
+impl ConstraintDescription {
+
+    /// Builds the described constraint
+    pub fn build(&self) -> Box<dyn Constraint + Send + Sync> {
+        match self.clone() {
+            ConstraintDescription::AbsDiff { x, y, relation, constant } => Box::new(AbsDiff::new(x, y, relation, constant)),
+            ConstraintDescription::AllDifferent { variables, offsets } => Box::new(AllDifferent::with_offsets(variables, offsets)),
+            ConstraintDescription::AllDifferentExcept { variables, sentinel } => Box::new(AllDifferentExcept::new(variables, sentinel)),
+            ConstraintDescription::Arithmetic { x, y, z, operation } => Box::new(Arithmetic::new(x, y, z, operation)),
+            ConstraintDescription::Cardinality { literals, lower, upper } => Box::new(Cardinality::new(literals, lower, upper)),
+            ConstraintDescription::CostBound { variables, costs, bound } => Box::new(CostBound::new(variables, costs, bound)),
+            ConstraintDescription::CostGcc { variables, values, lower, upper, costs, z } => Box::new(CostGcc::new(variables, values, lower, upper, costs, z)),
+            ConstraintDescription::CostRegular { variables, transitions, initial, finals, z } => Box::new(CostRegular::new(variables, transitions, initial, finals, z)),
+            ConstraintDescription::GlobalContiguity { variables } => Box::new(GlobalContiguity::new(variables)),
+            ConstraintDescription::Increasing { variables, strict } => Box::new(Increasing::new(variables, strict)),
+            ConstraintDescription::Inverse { xs, ys } => Box::new(Inverse::new(xs, ys)),
+            ConstraintDescription::LessEqual { x, y, offset } => Box::new(LessEqual::new(x, y, offset)),
+            ConstraintDescription::Maximum { xs, y } => Box::new(MinMax::max(xs, y)),
+            ConstraintDescription::MddConstraint { variables, arcs, root, terminal } => Box::new(MddConstraint::new(variables, arcs, root, terminal)),
+            ConstraintDescription::Minimum { xs, y } => Box::new(MinMax::min(xs, y)),
+            ConstraintDescription::NotEquals { x, y } => Box::new(NotEquals::new(x, y)),
+            ConstraintDescription::PseudoBoolean { terms, bound } => Box::new(PseudoBoolean::new(terms, bound)),
+            ConstraintDescription::Reified { constraint, b } => Box::new(Reified::new(constraint.build(), b)),
+            ConstraintDescription::Stretch { variables, values, min_lengths, max_lengths } => Box::new(Stretch::new(variables, values, min_lengths, max_lengths)),
+            ConstraintDescription::Table { variables, tuples } => Box::new(Table::new(variables, tuples)),
+            ConstraintDescription::Tautology => Box::new(Tautology),
+        }
+    }
+
+    /// Returns the description of the same constraint on the variables given by the function, e.g.,
+    /// to post it in another problem
+    pub fn map_variables(&self, f: &dyn Fn(VariableIndex) -> VariableIndex) -> Self {
+        let map = |variables: Vec<VariableIndex>| variables.into_iter().map(f).collect::<Vec<VariableIndex>>();
+        match self.clone() {
+            ConstraintDescription::AbsDiff { x, y, relation, constant } => ConstraintDescription::AbsDiff { x: f(x), y: f(y), relation, constant },
+            ConstraintDescription::AllDifferent { variables, offsets } => ConstraintDescription::AllDifferent { variables: map(variables), offsets },
+            ConstraintDescription::AllDifferentExcept { variables, sentinel } => ConstraintDescription::AllDifferentExcept { variables: map(variables), sentinel },
+            ConstraintDescription::Arithmetic { x, y, z, operation } => ConstraintDescription::Arithmetic { x: f(x), y: f(y), z: f(z), operation },
+            ConstraintDescription::Cardinality { literals, lower, upper } => ConstraintDescription::Cardinality { literals: literals.into_iter().map(|(variable, polarity)| (f(variable), polarity)).collect(), lower, upper },
+            ConstraintDescription::CostBound { variables, costs, bound } => ConstraintDescription::CostBound { variables: map(variables), costs, bound },
+            ConstraintDescription::CostGcc { variables, values, lower, upper, costs, z } => ConstraintDescription::CostGcc { variables: map(variables), values, lower, upper, costs, z: f(z) },
+            ConstraintDescription::CostRegular { variables, transitions, initial, finals, z } => ConstraintDescription::CostRegular { variables: map(variables), transitions, initial, finals, z: f(z) },
+            ConstraintDescription::GlobalContiguity { variables } => ConstraintDescription::GlobalContiguity { variables: map(variables) },
+            ConstraintDescription::Increasing { variables, strict } => ConstraintDescription::Increasing { variables: map(variables), strict },
+            ConstraintDescription::Inverse { xs, ys } => ConstraintDescription::Inverse { xs: map(xs), ys: map(ys) },
+            ConstraintDescription::LessEqual { x, y, offset } => ConstraintDescription::LessEqual { x: f(x), y: f(y), offset },
+            ConstraintDescription::Maximum { xs, y } => ConstraintDescription::Maximum { xs: map(xs), y: f(y) },
+            ConstraintDescription::MddConstraint { variables, arcs, root, terminal } => ConstraintDescription::MddConstraint { variables: map(variables), arcs, root, terminal },
+            ConstraintDescription::Minimum { xs, y } => ConstraintDescription::Minimum { xs: map(xs), y: f(y) },
+            ConstraintDescription::NotEquals { x, y } => ConstraintDescription::NotEquals { x: f(x), y: f(y) },
+            ConstraintDescription::PseudoBoolean { terms, bound } => ConstraintDescription::PseudoBoolean { terms: terms.into_iter().map(|(variable, weight)| (f(variable), weight)).collect(), bound },
+            ConstraintDescription::Reified { constraint, b } => ConstraintDescription::Reified { constraint: Box::new(constraint.map_variables(f)), b: f(b) },
+            ConstraintDescription::Stretch { variables, values, min_lengths, max_lengths } => ConstraintDescription::Stretch { variables: map(variables), values, min_lengths, max_lengths },
+            ConstraintDescription::Table { variables, tuples } => ConstraintDescription::Table { variables: map(variables), tuples },
+            ConstraintDescription::Tautology => ConstraintDescription::Tautology,
+        }
+    }
+}
+
+impl Problem {
+
+    /// Builds the problem from its description
+    pub fn from_description(description: &ProblemDescription) -> Self {
+        let mut problem = Problem::default();
+        for variable in description.variables.iter() {
+            let index = problem.add_domain_variable(Domain::from(&variable.domain), variable.probabilities.clone());
+            if !variable.costs.is_empty() {
+                problem[index].set_costs(&variable.costs);
+            }
+        }
+        for constraint in description.constraints.iter() {
+            problem.add_boxed_constraint(constraint.build());
+        }
+        problem
+    }
+
+    /// Returns the description of the problem. Panics if one of its constraints can not be
+    /// described (see `Constraint::description`).
+    pub fn description(&self) -> ProblemDescription {
+        let variables = self.variables().iter().map(|variable| VariableDescription {
+            domain: DomainDescription::from(variable.domain()),
+            probabilities: (!variable.probabilities().is_empty()).then(|| variable.probabilities().to_vec()),
+            costs: variable.costs().to_vec(),
+        }).collect();
+        let constraints = self.iter_constraints().map(|constraint| {
+            self[constraint].description().unwrap_or_else(|| panic!("The constraint {} can not be described", constraint.0))
+        }).collect();
+        ProblemDescription { variables, constraints }
+    }
+
+    /// Returns the problem on the given variables with the given constraints, whose scopes must be
+    /// in these variables. The variable i of the sub-problem is variables[i]. Returns None if one of
+    /// the constraints can not be described (see `Constraint::description`).
+    pub fn sub_problem(&self, variables: &[VariableIndex], constraints: &[ConstraintIndex]) -> Option<Problem> {
+        let indexes = variables.iter().enumerate().map(|(index, variable)| (*variable, VariableIndex(index))).collect::<FxHashMap<VariableIndex, VariableIndex>>();
+        let mut problem = Problem::default();
+        for variable in variables.iter().map(|variable| &self[*variable]) {
+            let index = problem.add_domain_variable(variable.domain().clone(), (!variable.probabilities().is_empty()).then(|| variable.probabilities().to_vec()));
+            if !variable.costs().is_empty() {
+                problem[index].set_costs(variable.costs());
+            }
+        }
+        for constraint in constraints.iter().copied() {
+            let description = self[constraint].description()?;
+            let description = description.map_variables(&|variable| *indexes.get(&variable).expect("The scopes of the constraints must be in the variables of the sub-problem"));
+            problem.add_boxed_constraint(description.build());
+        }
+        Some(problem)
+    }
+
+    /// Reads the problem from the JSON serialization of its description. Panics if the JSON is not
+    /// a valid description.
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Self {
+        let description: ProblemDescription = serde_json::from_str(json).expect("Invalid JSON description of a problem");
+        Self::from_description(&description)
+    }
+
+    /// Returns the JSON serialization of the description of the problem
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.description()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test_description {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    fn problem() -> Problem {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], Some(vec![0.2, 0.3, 0.5]));
+        let b = problem.add_bool();
+        let y = problem.add_interval_variable(vec![(0, 1), (4, 4)], None);
+        problem[y].set_costs(&[1, 2, 3]);
+        all_different(&mut problem, xs.clone());
+        reify(&mut problem, LessEqual::new(xs[0], xs[1], 0), *b);
+        stretch(&mut problem, xs.clone(), vec![2, 0], vec![1, 1], vec![1, 2]);
+        table(&mut problem, vec![xs[2], y], vec![vec![0, 4], vec![1, 1], vec![2, 0]]);
+        problem
+    }
+
+    #[test]
+    pub fn test_round_trip() {
+        let problem = problem();
+        let description = problem.description();
+        assert_eq!(description.variables[4].domain, DomainDescription::Intervals(vec![(0, 1), (4, 4)]));
+        let rebuilt = Problem::from_description(&description);
+        assert_eq!(rebuilt.description(), description);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        let mut rebuilt_mdd = Mdd::new(rebuilt, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        rebuilt_mdd.refine();
+        assert_eq!(get_all_solutions(&mdd).len(), get_all_solutions(&rebuilt_mdd).len());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    pub fn test_json() {
+        let problem = problem();
+        let json = problem.to_json();
+        assert_eq!(Problem::from_json(&json).description(), problem.description());
+
+        let problem = Problem::from_json(r#"{
+            "variables": [{"domain": {"Values": [0, 1, 2]}}, {"domain": {"Intervals": [[0, 2]]}}],
+            "constraints": [{"type": "LessEqual", "x": 0, "y": 1, "offset": 1}]
+        }"#);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(get_all_solutions(&mdd).len(), 3);
+    }
+}
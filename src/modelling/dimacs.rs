@@ -0,0 +1,127 @@
+use std::io::BufRead;
+use super::*;
+
+/// Boolean problem read from a DIMACS CNF file (see `parse_dimacs`)
+pub struct Dimacs {
+    /// Problem with one 0/1 variable per DIMACS variable and one clause constraint per clause
+    pub problem: Problem,
+    /// Variables of the problem, the DIMACS variable v being at index v - 1
+    pub variables: Vec<VariableIndex>,
+    /// Product, over the variables, of the sum of the weights of their two literals. The weights
+    /// are normalized into the probabilities of the variables, hence the weighted model count is
+    /// the probability mass of the solutions multiplied by this factor. Without weights, it is
+    /// 2^n and the probability mass of the solutions times the factor is the number of models.
+    pub normalization: f64,
+}
+
+/// Parses a CNF formula in DIMACS format. The literal weights of weighted model counting can be
+/// given with lines `w <literal> <weight> [0]` or `c p weight <literal> <weight> [0]`, and the
+/// literals without weight have weight 1. Panics if the input is not a valid DIMACS file.
+pub fn parse_dimacs(reader: impl BufRead) -> Dimacs {
+    let mut problem = Problem::default();
+    let mut variables: Vec<VariableIndex> = vec![];
+    // Weights of the negative and positive literals of each variable
+    let mut weights: Vec<[f64; 2]> = vec![];
+    let mut clause_literals: Vec<isize> = vec![];
+    let mut clauses: Vec<Vec<isize>> = vec![];
+    for line in reader.lines() {
+        let line = line.expect("Failed to read the DIMACS input");
+        let tokens = line.split_whitespace().collect::<Vec<&str>>();
+        match tokens.as_slice() {
+            [] | ["%", ..] => (),
+            ["p", "cnf", number_variables, _number_clauses] => {
+                let number_variables = number_variables.parse::<usize>().expect("Invalid number of variables in the DIMACS header");
+                variables = (0..number_variables).map(|_| problem.add_bool().into()).collect();
+                weights = vec![[1.0, 1.0]; number_variables];
+            },
+            ["w", literal, weight, ..] | ["c", "p", "weight", literal, weight, ..] => {
+                let literal = literal.parse::<isize>().expect("Invalid literal in a DIMACS weight");
+                let weight = weight.parse::<f64>().expect("Invalid DIMACS weight");
+                assert!(literal != 0 && literal.unsigned_abs() <= weights.len(), "Weight of an unknown literal {}", literal);
+                weights[literal.unsigned_abs() - 1][(literal > 0) as usize] = weight;
+            },
+            ["c", ..] => (),
+            _ => {
+                assert!(!variables.is_empty(), "The DIMACS header must precede the clauses");
+                for token in tokens {
+                    let literal = token.parse::<isize>().expect("Invalid literal in a DIMACS clause");
+                    assert!(literal.unsigned_abs() <= variables.len(), "Unknown variable in literal {}", literal);
+                    if literal == 0 {
+                        clauses.push(std::mem::take(&mut clause_literals));
+                    } else {
+                        clause_literals.push(literal);
+                    }
+                }
+            },
+        }
+    }
+    if !clause_literals.is_empty() {
+        clauses.push(clause_literals);
+    }
+
+    for literals in clauses {
+        let mut positives = literals.iter().filter(|literal| **literal > 0).map(|literal| variables[literal.unsigned_abs() - 1]).collect::<Vec<VariableIndex>>();
+        let mut negatives = literals.iter().filter(|literal| **literal < 0).map(|literal| variables[literal.unsigned_abs() - 1]).collect::<Vec<VariableIndex>>();
+        positives.sort_unstable();
+        positives.dedup();
+        negatives.sort_unstable();
+        negatives.dedup();
+        if positives.iter().any(|variable| negatives.contains(variable)) {
+            // The clause contains x and not x
+            continue;
+        }
+        if positives.is_empty() && negatives.is_empty() {
+            // The empty clause is unsatisfiable, as are the unit clauses x and not x
+            clause(&mut problem, vec![variables[0]], vec![]);
+            clause(&mut problem, vec![], vec![variables[0]]);
+            continue;
+        }
+        clause(&mut problem, positives, negatives);
+    }
+
+    let mut normalization = 1.0;
+    for (variable, [negative, positive]) in variables.iter().copied().zip(weights) {
+        assert!(negative >= 0.0 && positive >= 0.0 && negative + positive > 0.0, "Invalid weights for the literals of variable {}", variable.0 + 1);
+        normalization *= negative + positive;
+        problem.set_probabilities(variable, vec![negative / (negative + positive), positive / (negative + positive)]);
+    }
+    Dimacs { problem, variables, normalization }
+}
+
+#[cfg(test)]
+mod test_dimacs {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    /// Returns the weighted model count of the formula, from the solutions of its MDD
+    fn weighted_count(input: &str) -> f64 {
+        let dimacs = parse_dimacs(input.as_bytes());
+        let problem = dimacs.problem.clone();
+        let mut mdd = Mdd::new(dimacs.problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let mass = get_all_solutions(&mdd).iter().map(|solution| {
+            dimacs.variables.iter().map(|variable| problem[*variable].probability(ValueIndex(solution[variable.0] as usize))).product::<f64>()
+        }).sum::<f64>();
+        mass * dimacs.normalization
+    }
+
+    #[test]
+    pub fn test_model_count() {
+        // (x1 or not x2) and (x2 or x3), with a clause split on two lines and a tautology
+        let input = "c example\np cnf 3 3\n1 -2 0\n2\n3 0\n1 -1 0\n";
+        let dimacs = parse_dimacs(input.as_bytes());
+        assert_eq!(dimacs.variables.len(), 3);
+        assert_eq!(dimacs.problem.number_constraints(), 2);
+        assert!((weighted_count(input) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    pub fn test_weighted_model_count() {
+        let input = "p cnf 3 2\nc p weight 1 0.3 0\nc p weight -1 0.7 0\nw 2 2 0\n1 -2 0\n2 3 0\n";
+        // Models 001, 101, 110 and 111, with weights 0.7, 0.3, 0.6 and 0.6
+        assert!((weighted_count(input) - 2.2).abs() < 1e-9);
+    }
+}
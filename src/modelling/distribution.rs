@@ -0,0 +1,89 @@
+use super::Domain;
+
+/// Probability distribution over the values of a domain, used to set the probabilities of a
+/// variable (see `Problem::set_distribution`). The discrete distributions (geometric, binomial) are
+/// defined over the indexes of the values, and are truncated to the domain then renormalized.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Distribution {
+    Uniform,
+    /// Probabilities proportional to the weights, given for each value of the domain
+    Weights(Vec<f64>),
+    /// P(i) proportional to (1 - p)^i p for the i-th value
+    Geometric(f64),
+    /// P(i) proportional to C(n, i) p^i (1 - p)^(n - i) for the i-th value, with n + 1 values
+    Binomial(f64),
+    /// Normal distribution over the values, discretized by its density at each value
+    Normal { mean: f64, std_dev: f64 },
+}
+
+impl Distribution {
+
+    /// Returns the probability of each value of the domain
+    pub fn probabilities(&self, domain: &Domain) -> Vec<f64> {
+        let size = domain.size();
+        let weights = match self {
+            Distribution::Uniform => vec![1.0; size],
+            Distribution::Weights(weights) => {
+                assert!(weights.len() == size, "There must be one weight per value of the domain");
+                weights.clone()
+            },
+            Distribution::Geometric(p) => (0..size).map(|i| (1.0 - p).powi(i as i32) * p).collect(),
+            Distribution::Binomial(p) => {
+                let n = size.saturating_sub(1);
+                let mut coefficient = 1.0;
+                (0..size).map(|i| {
+                    let weight = coefficient * p.powi(i as i32) * (1.0 - p).powi((n - i) as i32);
+                    coefficient = coefficient * (n - i) as f64 / (i + 1) as f64;
+                    weight
+                }).collect()
+            },
+            Distribution::Normal { mean, std_dev } => domain.iter().map(|value| {
+                let z = (value as f64 - mean) / std_dev;
+                (-0.5 * z * z).exp()
+            }).collect(),
+        };
+        assert!(weights.iter().all(|weight| *weight >= 0.0), "The weights of a distribution must be non-negative");
+        let total = weights.iter().sum::<f64>();
+        assert!(total > 0.0, "The weights of a distribution can not all be zero");
+        weights.into_iter().map(|weight| weight / total).collect()
+    }
+}
+
+/// Panics if the probabilities are not a distribution over a domain of the given size
+pub fn check_probabilities(probabilities: &[f64], size: usize) {
+    assert!(probabilities.len() == size, "There must be one probability per value of the domain");
+    assert!(probabilities.iter().all(|probability| (0.0..=1.0).contains(probability)), "The probabilities must be in [0, 1]");
+    assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-3, "The probabilities must sum to 1");
+}
+
+#[cfg(test)]
+mod test_distribution {
+
+    use crate::modelling::*;
+
+    #[test]
+    pub fn test_distributions() {
+        let domain = Domain::range(0, 3);
+        assert_eq!(Distribution::Uniform.probabilities(&domain), vec![0.25; 4]);
+        assert_eq!(Distribution::Weights(vec![1.0, 3.0, 0.0, 4.0]).probabilities(&domain), vec![0.125, 0.375, 0.0, 0.5]);
+        let binomial = Distribution::Binomial(0.5).probabilities(&domain);
+        assert!(binomial.iter().zip([1.0, 3.0, 3.0, 1.0]).all(|(p, c)| (p - c / 8.0).abs() < 1e-9));
+        let normal = Distribution::Normal { mean: 1.5, std_dev: 1.0 }.probabilities(&domain);
+        assert!((normal[0] - normal[3]).abs() < 1e-9 && normal[1] > normal[0]);
+
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![3, 1, 2], None);
+        problem.set_distribution(x, &Distribution::Geometric(0.5));
+        assert!((problem[x].probability(ValueIndex(0)) - 4.0 / 7.0).abs() < 1e-9);
+        problem.set_probabilities(x, vec![0.2, 0.3, 0.5]);
+        assert_eq!(problem[x].probability(ValueIndex(2)), 0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "The probabilities must sum to 1")]
+    pub fn test_invalid_probabilities() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1], None);
+        problem.set_probabilities(x, vec![0.2, 0.3]);
+    }
+}
@@ -0,0 +1,279 @@
+use rustc_hash::FxHashSet;
+
+/// Domain of a variable. It is either an explicit list of values, or a union of intervals for
+/// large domains (e.g., 0..100000) that should not be materialized. The values of a domain are
+/// identified by their index (see `ValueIndex`), in the order of the list or in increasing order
+/// for the intervals.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Domain {
+    /// Explicit list of values
+    Values(Vec<isize>),
+    /// The values 0 and 1, without storing them
+    Boolean,
+    /// Disjoint intervals [min, max], in increasing order. offsets[i] is the number of values in
+    /// the intervals before the i-th one, and the last offset is the size of the domain.
+    Intervals { intervals: Vec<(isize, isize)>, offsets: Vec<usize> },
+}
+
+impl Domain {
+
+    /// Creates a domain from the union of the intervals [min, max]
+    pub fn intervals(mut intervals: Vec<(isize, isize)>) -> Self {
+        intervals.retain(|(min, max)| min <= max);
+        intervals.sort_unstable();
+        let mut merged: Vec<(isize, isize)> = Vec::with_capacity(intervals.len());
+        for (min, max) in intervals {
+            match merged.last_mut() {
+                Some(last) if min <= last.1.saturating_add(1) => last.1 = last.1.max(max),
+                _ => merged.push((min, max)),
+            }
+        }
+        let mut offsets = vec![0];
+        for (min, max) in merged.iter().copied() {
+            offsets.push(offsets.last().unwrap() + (max - min) as usize + 1);
+        }
+        Domain::Intervals { intervals: merged, offsets }
+    }
+
+    /// Creates the domain [min, max]
+    pub fn range(min: isize, max: isize) -> Self {
+        Self::intervals(vec![(min, max)])
+    }
+
+    /// Returns the union of the domains, as a union of intervals
+    pub fn union<'a>(domains: impl Iterator<Item = &'a Domain>) -> Self {
+        let intervals = domains.flat_map(|domain| match domain {
+            Domain::Values(values) => values.iter().map(|value| (*value, *value)).collect::<Vec<(isize, isize)>>(),
+            Domain::Boolean => vec![(0, 1)],
+            Domain::Intervals { intervals, .. } => intervals.clone(),
+        }).collect::<Vec<(isize, isize)>>();
+        Self::intervals(intervals)
+    }
+
+    /// Returns the domain with each value shifted by the offset
+    pub fn shift(&self, offset: isize) -> Self {
+        match self {
+            Domain::Values(values) => Domain::Values(values.iter().map(|value| value + offset).collect()),
+            Domain::Boolean => Domain::range(offset, 1 + offset),
+            Domain::Intervals { intervals, offsets } => Domain::Intervals {
+                intervals: intervals.iter().map(|(min, max)| (min + offset, max + offset)).collect(),
+                offsets: offsets.clone(),
+            },
+        }
+    }
+
+    /// Returns the values of the domain satisfying the predicate, in the same order. Intervals are
+    /// kept as intervals.
+    pub fn retain(&self, predicate: impl Fn(isize) -> bool) -> Self {
+        match self {
+            Domain::Intervals { intervals, .. } => {
+                let mut kept = vec![];
+                for (min, max) in intervals.iter().copied() {
+                    let mut start = None;
+                    for value in min..=max {
+                        match (predicate(value), start) {
+                            (true, None) => start = Some(value),
+                            (false, Some(first)) => {
+                                kept.push((first, value - 1));
+                                start = None;
+                            },
+                            _ => (),
+                        }
+                    }
+                    if let Some(first) = start {
+                        kept.push((first, max));
+                    }
+                }
+                Self::intervals(kept)
+            },
+            _ => Domain::Values(self.iter().filter(|value| predicate(*value)).collect()),
+        }
+    }
+
+    /// Returns the values of the domain that are in the given set. Intervals are kept as intervals,
+    /// without enumerating their values.
+    pub fn intersect(&self, values: &FxHashSet<isize>) -> Self {
+        match self {
+            Domain::Intervals { .. } => Self::intervals(values.iter().copied().filter(|value| self.contains(*value)).map(|value| (value, value)).collect()),
+            _ => self.retain(|value| values.contains(&value)),
+        }
+    }
+
+    /// Returns the domain without the value
+    pub fn without(&self, value: isize) -> Self {
+        match self {
+            Domain::Intervals { intervals, .. } => Self::intervals(intervals.iter().copied().flat_map(|(min, max)| {
+                if min <= value && value <= max { vec![(min, value - 1), (value + 1, max)] } else { vec![(min, max)] }
+            }).collect()),
+            _ => self.retain(|v| v != value),
+        }
+    }
+
+    /// Returns the number of values in the domain
+    pub fn size(&self) -> usize {
+        match self {
+            Domain::Values(values) => values.len(),
+            Domain::Boolean => 2,
+            Domain::Intervals { offsets, .. } => *offsets.last().unwrap(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// Returns the value at the given index
+    pub fn value(&self, index: usize) -> isize {
+        match self {
+            Domain::Values(values) => values[index],
+            Domain::Boolean => {
+                debug_assert!(index < 2);
+                index as isize
+            },
+            Domain::Intervals { intervals, offsets } => {
+                let interval = offsets.partition_point(|offset| *offset <= index) - 1;
+                intervals[interval].0 + (index - offsets[interval]) as isize
+            },
+        }
+    }
+
+    /// Returns the index of the value, if it is in the domain
+    pub fn index_of(&self, value: isize) -> Option<usize> {
+        match self {
+            Domain::Values(values) => values.iter().position(|v| *v == value),
+            Domain::Boolean => (value == 0 || value == 1).then_some(value as usize),
+            Domain::Intervals { intervals, offsets } => {
+                let interval = intervals.partition_point(|(min, _)| *min <= value).checked_sub(1)?;
+                let (min, max) = intervals[interval];
+                (value <= max).then(|| offsets[interval] + (value - min) as usize)
+            },
+        }
+    }
+
+    /// Returns true if the value is in the domain
+    pub fn contains(&self, value: isize) -> bool {
+        match self {
+            Domain::Values(values) => values.contains(&value),
+            Domain::Boolean | Domain::Intervals { .. } => self.index_of(value).is_some(),
+        }
+    }
+
+    /// Returns the smallest value of the domain
+    pub fn min(&self) -> Option<isize> {
+        match self {
+            Domain::Values(values) => values.iter().copied().min(),
+            Domain::Boolean => Some(0),
+            Domain::Intervals { intervals, .. } => intervals.first().map(|(min, _)| *min),
+        }
+    }
+
+    /// Returns the largest value of the domain
+    pub fn max(&self) -> Option<isize> {
+        match self {
+            Domain::Values(values) => values.iter().copied().max(),
+            Domain::Boolean => Some(1),
+            Domain::Intervals { intervals, .. } => intervals.last().map(|(_, max)| *max),
+        }
+    }
+
+    /// Iterates over the values of the domain, in the order of their index
+    pub fn iter(&self) -> impl Iterator<Item = isize> + '_ {
+        let (values, intervals) = match self {
+            Domain::Values(values) => (values.as_slice(), &[][..]),
+            Domain::Boolean => (&[0, 1][..], &[][..]),
+            Domain::Intervals { intervals, .. } => (&[][..], intervals.as_slice()),
+        };
+        values.iter().copied().chain(intervals.iter().flat_map(|(min, max)| *min..=*max))
+    }
+}
+
+#[cfg(test)]
+mod test_domain {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_intervals() {
+        let domain = Domain::intervals(vec![(10, 12), (0, 3), (4, 5), (20, 19)]);
+        assert_eq!(domain, Domain::Intervals { intervals: vec![(0, 5), (10, 12)], offsets: vec![0, 6, 9] });
+        assert_eq!(domain.size(), 9);
+        assert_eq!(domain.value(7), 11);
+        assert_eq!(domain.index_of(11), Some(7));
+        assert_eq!(domain.index_of(8), None);
+        assert!(!domain.contains(-1));
+        assert_eq!(domain.iter().collect::<Vec<isize>>(), vec![0, 1, 2, 3, 4, 5, 10, 11, 12]);
+        assert!((0..domain.size()).all(|index| domain.index_of(domain.value(index)) == Some(index)));
+    }
+
+    #[test]
+    pub fn test_large_domain() {
+        let mut problem = Problem::default();
+        let x = problem.add_interval_variable(vec![(0, 100_000)], None);
+        let y = problem.add_variable(vec![5, 1], None);
+        assert_eq!(problem[x].domain_size(), 100_001);
+        assert_eq!(problem[x].value(ValueIndex(42)), 42);
+        let union = Domain::union([problem[x].domain(), problem[y].domain()].into_iter());
+        assert_eq!(union.size(), 100_001);
+        assert_eq!(Domain::union([problem[x].domain(), problem[x].domain()].into_iter()), *problem[x].domain());
+    }
+
+    #[test]
+    pub fn test_domain_mutation() {
+        let mut problem = Problem::default();
+        let x = problem.add_variable(vec![0, 1, 2, 3], Some(vec![0.1, 0.2, 0.3, 0.4]));
+        let y = problem.add_interval_variable(vec![(0, 1_000_000)], None);
+        problem[x].set_costs(&[0, 1, 2, 3]);
+        problem.remove_value(x, 1);
+        problem.remove_value(x, 7);
+        assert_eq!(problem[x].iter_domain().collect::<Vec<isize>>(), vec![0, 2, 3]);
+        assert!((problem[x].probability(ValueIndex(2)) - 0.5).abs() < 1e-9);
+        assert_eq!(problem[x].cost(ValueIndex(1)), 2);
+        problem.remove_value(y, 10);
+        assert_eq!(*problem[y].domain(), Domain::intervals(vec![(0, 9), (11, 1_000_000)]));
+        problem.restrict_domain(y, [5, 10, 11, -1]);
+        assert_eq!(problem[y].iter_domain().collect::<Vec<isize>>(), vec![5, 11]);
+        problem.fix(x, 3);
+        assert_eq!(problem[x].probability(ValueIndex(0)), 1.0);
+        problem.fix(y, 10);
+        assert!(problem[y].domain().is_empty());
+    }
+
+    #[test]
+    pub fn test_bool_variable() {
+        let mut problem = Problem::default();
+        let bs = problem.add_bools(2);
+        let x = problem.add_variable(vec![1, 0], None);
+        let y = problem.add_variable(vec![0, 2], None);
+        assert_eq!(*problem[*bs[0]].domain(), Domain::Boolean);
+        assert_eq!(problem[*bs[1]].iter_domain().collect::<Vec<isize>>(), vec![0, 1]);
+        assert_eq!(problem.as_bool(x), Some(BoolVariable(x)));
+        assert_eq!(problem.as_bool(y), None);
+
+        clause(&mut problem, vec![bs[0].into(), x], vec![bs[1].into()]);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        // Only b0 = x = 0 with b1 = 1 violates the clause
+        assert_eq!(get_all_solutions(&mdd).len(), 2 * 7);
+    }
+
+    #[test]
+    pub fn test_interval_propagation() {
+        let mut problem = Problem::default();
+        let x = problem.add_interval_variable(vec![(0, 9), (50, 59)], None);
+        let y = problem.add_interval_variable(vec![(8, 59)], None);
+        let z = problem.add_variable(vec![9, 50], None);
+        all_different(&mut problem, vec![x, y, z]);
+        not_equals(&mut problem, x, y);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom(vec![2, 1, 0]), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert!(solutions.iter().all(|solution| solution[0] != solution[1] && solution[0] != solution[2] && solution[1] != solution[2]));
+        // z takes 9 or 50, y takes one of the 51 other values, and x one of its values left: 18 if
+        // y is in the domain of x (11 values), and 19 otherwise
+        assert_eq!(solutions.len(), 2 * (11 * 18 + 40 * 19));
+    }
+}
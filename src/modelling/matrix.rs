@@ -0,0 +1,129 @@
+/// Two-dimensional array, stored row by row, with slicing helpers to post constraints on the rows,
+/// columns or blocks of grid models (e.g., Sudoku). It is usually a matrix of variables created by
+/// `Problem::add_matrix`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Matrix<T> {
+    rows: usize,
+    cols: usize,
+    data: Vec<T>,
+}
+
+impl<T: Copy> Matrix<T> {
+
+    /// Creates a matrix with the given number of rows and columns from its elements, given row by
+    /// row
+    pub fn new(rows: usize, cols: usize, data: Vec<T>) -> Self {
+        assert!(data.len() == rows * cols, "The matrix must have rows * cols elements");
+        Self { rows, cols, data }
+    }
+
+    pub fn number_rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn number_cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Returns the elements of the i-th row
+    pub fn row(&self, i: usize) -> Vec<T> {
+        self.data[i * self.cols..(i + 1) * self.cols].to_vec()
+    }
+
+    /// Returns the elements of the j-th column
+    pub fn col(&self, j: usize) -> Vec<T> {
+        (0..self.rows).map(|i| self[(i, j)]).collect()
+    }
+
+    /// Returns the elements of the block of height x width elements whose top-left corner is at row
+    /// r and column c, row by row
+    pub fn block(&self, r: usize, c: usize, height: usize, width: usize) -> Vec<T> {
+        (r..r + height).flat_map(|i| (c..c + width).map(move |j| (i, j))).map(|position| self[position]).collect()
+    }
+
+    /// Returns the blocks of height x width elements tiling the matrix, row by row
+    pub fn blocks(&self, height: usize, width: usize) -> Vec<Vec<T>> {
+        (0..self.rows / height).flat_map(|r| (0..self.cols / width).map(move |c| (r, c)))
+            .map(|(r, c)| self.block(r * height, c * width, height, width)).collect()
+    }
+
+    /// Returns the elements of the main diagonal
+    pub fn diagonal(&self) -> Vec<T> {
+        (0..self.rows.min(self.cols)).map(|i| self[(i, i)]).collect()
+    }
+
+    /// Returns the elements of the anti-diagonal, from the top-right corner
+    pub fn anti_diagonal(&self) -> Vec<T> {
+        (0..self.rows.min(self.cols)).map(|i| self[(i, self.cols - 1 - i)]).collect()
+    }
+
+    /// Iterates over the rows
+    pub fn iter_rows(&self) -> impl Iterator<Item = Vec<T>> + '_ {
+        (0..self.rows).map(|i| self.row(i))
+    }
+
+    /// Iterates over the columns
+    pub fn iter_cols(&self) -> impl Iterator<Item = Vec<T>> + '_ {
+        (0..self.cols).map(|j| self.col(j))
+    }
+
+    /// Returns all the elements, row by row
+    pub fn flatten(&self) -> Vec<T> {
+        self.data.clone()
+    }
+}
+
+impl<T> std::ops::Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+
+    fn index(&self, (i, j): (usize, usize)) -> &Self::Output {
+        debug_assert!(i < self.rows && j < self.cols);
+        &self.data[i * self.cols + j]
+    }
+}
+
+#[cfg(test)]
+mod test_matrix {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_slicing() {
+        let matrix = Matrix::new(4, 4, (0..16).collect());
+        assert_eq!(matrix.row(1), vec![4, 5, 6, 7]);
+        assert_eq!(matrix.col(2), vec![2, 6, 10, 14]);
+        assert_eq!(matrix.block(2, 2, 2, 2), vec![10, 11, 14, 15]);
+        assert_eq!(matrix.blocks(2, 2)[1], vec![2, 3, 6, 7]);
+        assert_eq!(matrix.diagonal(), vec![0, 5, 10, 15]);
+        assert_eq!(matrix.anti_diagonal(), vec![3, 6, 9, 12]);
+        assert_eq!(matrix[(3, 1)], 13);
+    }
+
+    #[test]
+    pub fn test_latin_square() {
+        // 4x4 Sudoku with a fixed first row
+        let mut problem = Problem::default();
+        let grid = problem.add_matrix(4, 4, vec![1, 2, 3, 4], None);
+        for row in grid.iter_rows() {
+            all_different(&mut problem, row);
+        }
+        for col in grid.iter_cols() {
+            all_different(&mut problem, col);
+        }
+        for block in grid.blocks(2, 2) {
+            all_different(&mut problem, block);
+        }
+        for (j, value) in [1, 2, 3, 4].into_iter().enumerate() {
+            equal(&mut problem, grid[(0, j)], value);
+        }
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom((0..16).collect()), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 12);
+        assert!(is_solution(vec![1, 2, 3, 4, 3, 4, 1, 2, 2, 1, 4, 3, 4, 3, 2, 1], &solutions));
+    }
+}
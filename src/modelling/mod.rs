@@ -1,26 +1,272 @@
+pub mod bayes;
+pub mod decomposition;
+pub mod description;
+pub mod dimacs;
+pub mod distribution;
+pub mod domain;
+pub mod matrix;
+pub mod presolve;
 pub mod problem;
 pub mod variable;
 
-pub use problem::Problem;
+pub use bayes::{BayesianNetwork, BayesEncoding};
+pub use decomposition::{decompose, predicate};
+pub use description::{ConstraintDescription, DomainDescription, ProblemDescription, VariableDescription};
+pub use dimacs::{Dimacs, parse_dimacs};
+pub use distribution::Distribution;
+pub use domain::Domain;
+pub use matrix::Matrix;
+pub use presolve::{PresolveStatistics, presolve};
+pub use problem::{Problem, Violation};
+pub use crate::constraints::{Operation, Relation};
 use crate::constraints::*;
+use crate::mdd::Mdd;
 
 pub fn all_different(problem: &mut Problem, variables: Vec<VariableIndex>) {
-    let constraint_index = ConstraintIndex(problem.number_constraints());
-    for variable in variables.iter().copied() {
-        problem[variable].add_constraint(constraint_index);
-    }
     problem.add_constraint(AllDifferent::new(variables));
 }
 
+/// Enforces that the values $x_i + c_i$ are pairwise distinct, with $c_i$ the offset of the i-th
+/// variable. For example, the diagonals of the N-Queens problem can be modelled with the offsets
+/// $c_i = i$ and $c_i = -i$.
+pub fn all_different_offset(problem: &mut Problem, variables: Vec<VariableIndex>, offsets: Vec<isize>) {
+    problem.add_constraint(AllDifferent::with_offsets(variables, offsets));
+}
+
+/// Enforces that the variables take pairwise distinct values, except for the sentinel value
+/// which can be taken by any number of variables.
+pub fn all_different_except(problem: &mut Problem, variables: Vec<VariableIndex>, sentinel: isize) {
+    problem.add_constraint(AllDifferentExcept::new(variables, sentinel));
+}
+
+/// Channels the two arrays of variables: $xs_i = j \Leftrightarrow ys_j = i$
+pub fn inverse(problem: &mut Problem, xs: Vec<VariableIndex>, ys: Vec<VariableIndex>) {
+    problem.add_constraint(Inverse::new(xs, ys));
+}
+
 pub fn not_equals(problem: &mut Problem, x: VariableIndex, y: VariableIndex) {
     problem.add_constraint(NotEquals::new(x, y));
 }
 
+/// Enforces that the variables are increasing, in the given order
+pub fn increasing(problem: &mut Problem, variables: Vec<VariableIndex>) {
+    problem.add_constraint(Increasing::new(variables, false));
+}
+
+/// Enforces that the variables are strictly increasing, in the given order
+pub fn strictly_increasing(problem: &mut Problem, variables: Vec<VariableIndex>) {
+    problem.add_constraint(Increasing::new(variables, true));
+}
+
+/// Enforces that the variables are decreasing, in the given order
+pub fn decreasing(problem: &mut Problem, mut variables: Vec<VariableIndex>) {
+    variables.reverse();
+    problem.add_constraint(Increasing::new(variables, false));
+}
+
+/// Enforces that the variables are strictly decreasing, in the given order
+pub fn strictly_decreasing(problem: &mut Problem, mut variables: Vec<VariableIndex>) {
+    variables.reverse();
+    problem.add_constraint(Increasing::new(variables, true));
+}
+
+/// Enforces that the stretches (maximal runs of consecutive variables taking the same value) of
+/// values[i] have a length between min_lengths[i] and max_lengths[i]. The other values can form
+/// stretches of any length.
+pub fn stretch(problem: &mut Problem, variables: Vec<VariableIndex>, values: Vec<isize>, min_lengths: Vec<usize>, max_lengths: Vec<usize>) {
+    problem.add_constraint(Stretch::new(variables, values, min_lengths, max_lengths));
+}
+
+/// Enforces that the variables taking the value 1 are consecutive in the sequence
+pub fn global_contiguity(problem: &mut Problem, variables: Vec<VariableIndex>) {
+    problem.add_constraint(GlobalContiguity::new(variables));
+}
+
+/// Enforces that x + offset <= y
+pub fn less_equal(problem: &mut Problem, x: VariableIndex, y: VariableIndex, offset: isize) {
+    problem.add_constraint(LessEqual::new(x, y, offset));
+}
+
+/// Enforces that x < y
+pub fn less_than(problem: &mut Problem, x: VariableIndex, y: VariableIndex) {
+    less_equal(problem, x, y, 1);
+}
+
+/// Enforces that |x - y| is in the given relation with the constant
+pub fn abs_diff(problem: &mut Problem, x: VariableIndex, y: VariableIndex, relation: Relation, constant: isize) {
+    problem.add_constraint(AbsDiff::new(x, y, relation, constant));
+}
+
+/// Enforces that z = x operation y
+pub fn arithmetic(problem: &mut Problem, x: VariableIndex, y: VariableIndex, z: VariableIndex, operation: Operation) {
+    problem.add_constraint(Arithmetic::new(x, y, z, operation));
+}
+
+/// Enforces that z = x + y
+pub fn plus(problem: &mut Problem, x: VariableIndex, y: VariableIndex, z: VariableIndex) {
+    arithmetic(problem, x, y, z, Operation::Add);
+}
+
+/// Enforces that z = x * y
+pub fn times(problem: &mut Problem, x: VariableIndex, y: VariableIndex, z: VariableIndex) {
+    arithmetic(problem, x, y, z, Operation::Mul);
+}
+
+/// Enforces that z = x / y, truncated towards zero
+pub fn division(problem: &mut Problem, x: VariableIndex, y: VariableIndex, z: VariableIndex) {
+    arithmetic(problem, x, y, z, Operation::Div);
+}
+
+/// Enforces that z = x % y, with the sign of x
+pub fn modulo(problem: &mut Problem, x: VariableIndex, y: VariableIndex, z: VariableIndex) {
+    arithmetic(problem, x, y, z, Operation::Mod);
+}
+
+/// Enforces that y = max(xs)
+pub fn maximum(problem: &mut Problem, xs: Vec<VariableIndex>, y: VariableIndex) {
+    problem.add_constraint(MinMax::max(xs, y));
+}
+
+/// Enforces that y = min(xs)
+pub fn minimum(problem: &mut Problem, xs: Vec<VariableIndex>, y: VariableIndex) {
+    problem.add_constraint(MinMax::min(xs, y));
+}
+
+/// Enforces that between lower and upper literals are true. A literal is a 0/1 variable with its
+/// polarity (true if positive).
+pub fn cardinality(problem: &mut Problem, literals: Vec<(VariableIndex, bool)>, lower: usize, upper: usize) {
+    problem.add_constraint(Cardinality::new(literals, lower, upper));
+}
+
+/// Adds the clause $\bigvee_{p} p \vee \bigvee_{n} \neg n$ over 0/1 variables
+pub fn clause(problem: &mut Problem, positives: Vec<VariableIndex>, negatives: Vec<VariableIndex>) {
+    let literals = positives.into_iter().map(|variable| (variable, true)).chain(negatives.into_iter().map(|variable| (variable, false))).collect::<Vec<(VariableIndex, bool)>>();
+    let upper = literals.len();
+    cardinality(problem, literals, 1, upper);
+}
+
+/// Enforces that at least one of the 0/1 variables is 1
+pub fn at_least_one(problem: &mut Problem, variables: Vec<VariableIndex>) {
+    clause(problem, variables, vec![]);
+}
+
+/// Enforces that at least k of the 0/1 variables are 1
+pub fn at_least_k(problem: &mut Problem, variables: Vec<VariableIndex>, k: usize) {
+    let upper = variables.len();
+    cardinality(problem, variables.into_iter().map(|variable| (variable, true)).collect(), k, upper);
+}
+
+/// Enforces that at most k of the 0/1 variables are 1
+pub fn at_most_k(problem: &mut Problem, variables: Vec<VariableIndex>, k: usize) {
+    cardinality(problem, variables.into_iter().map(|variable| (variable, true)).collect(), 0, k);
+}
+
+/// Enforces that exactly k of the 0/1 variables are 1
+pub fn exactly_k(problem: &mut Problem, variables: Vec<VariableIndex>, k: usize) {
+    cardinality(problem, variables.into_iter().map(|variable| (variable, true)).collect(), k, k);
+}
+
+/// Enforces that sum(weights[i] * variables[i]) >= bound over 0/1 variables
+pub fn pseudo_boolean_greater_equal(problem: &mut Problem, variables: Vec<VariableIndex>, weights: Vec<isize>, bound: isize) {
+    problem.add_constraint(PseudoBoolean::new(variables.into_iter().zip(weights).collect(), bound));
+}
+
+/// Enforces that sum(weights[i] * variables[i]) <= bound over 0/1 variables
+pub fn pseudo_boolean_less_equal(problem: &mut Problem, variables: Vec<VariableIndex>, weights: Vec<isize>, bound: isize) {
+    problem.add_constraint(PseudoBoolean::new(variables.into_iter().zip(weights.into_iter().map(|weight| -weight)).collect(), -bound));
+}
+
+/// Enforces that the 0/1 variable b is 1 if and only if the constraint is satisfied. The constraint
+/// must provide its negation (see `Constraint::negation`).
+pub fn reify(problem: &mut Problem, constraint: impl Constraint + 'static + Send + Sync, b: VariableIndex) {
+    problem.add_constraint(Reified::new(Box::new(constraint), b));
+}
+
+/// Enforces that the constraint premise implies the constraint conclusion. Both are reified by
+/// new 0/1 variables added to the problem.
+pub fn implies(problem: &mut Problem, premise: impl Constraint + 'static + Send + Sync, conclusion: impl Constraint + 'static + Send + Sync) {
+    let b_premise = problem.add_bool().into();
+    let b_conclusion = problem.add_bool().into();
+    reify(problem, premise, b_premise);
+    reify(problem, conclusion, b_conclusion);
+    clause(problem, vec![b_conclusion], vec![b_premise]);
+}
+
+/// Enforces that the constraint then_constraint holds if condition holds, and that else_constraint
+/// holds otherwise. The three constraints are reified by new 0/1 variables added to the problem.
+pub fn if_then_else(problem: &mut Problem, condition: impl Constraint + 'static + Send + Sync, then_constraint: impl Constraint + 'static + Send + Sync, else_constraint: impl Constraint + 'static + Send + Sync) {
+    let b_condition = problem.add_bool().into();
+    let b_then = problem.add_bool().into();
+    let b_else = problem.add_bool().into();
+    reify(problem, condition, b_condition);
+    reify(problem, then_constraint, b_then);
+    reify(problem, else_constraint, b_else);
+    clause(problem, vec![b_then], vec![b_condition]);
+    clause(problem, vec![b_condition, b_else], vec![]);
+}
+
+/// Makes the constraint soft: instead of being enforced, its violation costs the given amount.
+/// Returns the new 0/1 variable that is 1 if and only if the constraint is satisfied. The total
+/// violation can then be minimized with `Mdd::minimum_cost_solution`. The constraint is reified
+/// (see `reify`) and the cost is the one of the value 0 of the new variable, hence the edges are
+/// not charged by the constraint itself, and the constraint must have a negation (see
+/// `Constraint::negation`). Panics otherwise.
+pub fn soft(problem: &mut Problem, constraint: impl Constraint + 'static + Send + Sync, cost: isize) -> VariableIndex {
+    let satisfied: VariableIndex = problem.add_bool().into();
+    problem[satisfied].set_costs(&[cost, 0]);
+    reify(problem, constraint, satisfied);
+    satisfied
+}
+
+/// Enforces that the total cost of the values taken by the variables (see `Variable::cost`) is at
+/// most the bound. The costs are read when the constraint is posted.
+pub fn cost_at_most(problem: &mut Problem, variables: Vec<VariableIndex>, bound: isize) {
+    let costs = variables.iter().map(|variable| {
+        let variable = &problem[*variable];
+        (0..variable.domain_size()).map(ValueIndex).map(|index| (variable.value(index), variable.cost(index))).collect::<Vec<(isize, isize)>>()
+    }).collect::<Vec<Vec<(isize, isize)>>>();
+    problem.add_constraint(CostBound::new(variables, costs, bound));
+}
+
+/// Enforces that the sequence of values of the variables is accepted by the automaton, and that
+/// the total cost of its transitions is at most z. The transitions are given as (from, value, to,
+/// cost) and must be deterministic.
+pub fn cost_regular(problem: &mut Problem, variables: Vec<VariableIndex>, transitions: Vec<(usize, isize, usize, isize)>, initial: usize, finals: Vec<usize>, z: VariableIndex) {
+    problem.add_constraint(CostRegular::new(variables, transitions, initial, finals, z));
+}
+
+/// Enforces that values[i] is taken by between lower[i] and upper[i] variables, and that the total
+/// cost of the occurrences (costs[i] for each occurrence of values[i]) is at most z
+pub fn cost_global_cardinality(problem: &mut Problem, variables: Vec<VariableIndex>, values: Vec<isize>, lower: Vec<usize>, upper: Vec<usize>, costs: Vec<isize>, z: VariableIndex) {
+    problem.add_constraint(CostGcc::new(variables, values, lower, upper, costs, z));
+}
+
+/// Enforces that the values of the variables match one of the tuples. The tuples are compressed
+/// into short tuples (see `compress_tuples`) before being posted.
+pub fn table(problem: &mut Problem, variables: Vec<VariableIndex>, tuples: Vec<Vec<isize>>) {
+    let domains = variables.iter().map(|variable| problem[*variable].iter_domain().collect::<Vec<isize>>()).collect::<Vec<Vec<isize>>>();
+    let tuples = tuples.into_iter().map(|tuple| tuple.into_iter().map(Some).collect::<Vec<Option<isize>>>()).collect::<Vec<Vec<Option<isize>>>>();
+    problem.add_constraint(Table::new(variables, compress_tuples(tuples, &domains)));
+}
+
+/// Enforces that the values of the variables match one of the short tuples. An entry None is a
+/// wildcard matching any value.
+pub fn short_table(problem: &mut Problem, variables: Vec<VariableIndex>, tuples: Vec<Vec<Option<isize>>>) {
+    problem.add_constraint(Table::new(variables, tuples));
+}
+
+/// Enforces that the values of the variables form a solution of the compiled MDD. The variable i
+/// of the problem of the MDD is mapped to variables[i].
+pub fn mdd_constraint(problem: &mut Problem, mdd: &Mdd, variables: &[VariableIndex]) {
+    problem.add_constraint(MddConstraint::from_mdd(mdd, variables));
+}
+
 pub fn equal(problem: &mut Problem, variable: VariableIndex, value: isize) {
-    problem[variable].set_domain(vec![value]);
+    problem.fix(variable, value);
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariableIndex(pub usize);
 
 impl std::ops::Deref for VariableIndex {
@@ -31,6 +277,25 @@ impl std::ops::Deref for VariableIndex {
     }
 }
 
+/// Index of a variable whose domain is {0, 1}. It can be used wherever a `VariableIndex` is expected
+/// (see `Problem::add_bool` and `Problem::as_bool`).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct BoolVariable(pub VariableIndex);
+
+impl std::ops::Deref for BoolVariable {
+    type Target = VariableIndex;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<BoolVariable> for VariableIndex {
+    fn from(variable: BoolVariable) -> Self {
+        variable.0
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct ValueIndex(pub usize);
 
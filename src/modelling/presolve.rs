@@ -0,0 +1,219 @@
+use super::*;
+use std::collections::VecDeque;
+use rustc_hash::FxHashSet;
+
+// Presolve of a problem, i.e., cheap domain filtering at the level of the problem before the MDD is
+// built, so that the values it removes are never expanded in the diagram. The constraints are
+// filtered until a fixpoint with a queue: a constraint is enqueued again when the domain of a
+// variable of its scope is narrowed, including by its own filtering (e.g., an AllDifferent fixing a
+// variable after the fixed variables have been handled). The filtering of a constraint depends on its kind:
+//  - AllDifferent(Except): the values of the fixed variables are removed from the other domains,
+//    and the constraint fails if the variables have fewer values (with their offsets) than there
+//    are variables (pigeonhole).
+//  - Binary constraints (e.g., equalities, NotEquals, LessEqual) are made arc consistent with
+//    `Constraint::is_satisfied`, if their domains are small enough. Chains of equalities and
+//    disequalities from fixed variables are thus propagated to a fixpoint.
+//  - Other constraints are forward checked: once all the variables of the scope but one are fixed,
+//    the values of the last one that do not satisfy the constraint are removed.
+// The domains are narrowed with `Problem::restrict_domain`, hence the constraints are notified. If
+// a domain becomes empty, the problem is unsatisfiable and the presolve stops.
+
+/// Maximum number of pairs of values checked to make a binary constraint arc consistent
+const MAX_PAIRS: usize = 1 << 16;
+
+/// Statistics of the presolve of a problem
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct PresolveStatistics {
+    /// Number of values removed from the domains
+    pub removed_values: usize,
+    /// Number of variables fixed by the presolve
+    pub fixed_variables: usize,
+    /// True if the presolve proved that the problem has no solution, a domain being empty
+    pub unsat: bool,
+}
+
+/// Filters the domains of the variables of the problem with the constraints, to a fixpoint, and
+/// returns the statistics of the presolve. The solutions of the problem are unchanged.
+pub fn presolve(problem: &mut Problem) -> PresolveStatistics {
+    problem.init_constraints();
+    let sizes = problem.iter_variables().map(|variable| problem[variable].domain_size()).collect::<Vec<usize>>();
+    let mut queue = problem.iter_constraints().collect::<VecDeque<ConstraintIndex>>();
+    let mut queued = vec![true; problem.number_constraints()];
+    let mut assignment = vec![0; problem.number_variables()];
+    let mut unsat = sizes.contains(&0);
+    while let Some(constraint) = queue.pop_front() {
+        if unsat {
+            break;
+        }
+        queued[constraint.0] = false;
+        for variable in filter(problem, constraint, &mut assignment) {
+            if problem[variable].domain_size() == 0 {
+                unsat = true;
+                break;
+            }
+            for other in problem[variable].iter_constraints() {
+                if !queued[other.0] {
+                    queued[other.0] = true;
+                    queue.push_back(other);
+                }
+            }
+        }
+    }
+    let mut statistics = PresolveStatistics { unsat, ..Default::default() };
+    for variable in problem.iter_variables() {
+        statistics.removed_values += sizes[variable.0] - problem[variable].domain_size();
+        if sizes[variable.0] > 1 && problem[variable].domain_size() == 1 {
+            statistics.fixed_variables += 1;
+        }
+    }
+    statistics
+}
+
+/// Filters the domains of the scope of the constraint, and returns the variables whose domain has
+/// been narrowed
+fn filter(problem: &mut Problem, constraint: ConstraintIndex, assignment: &mut [isize]) -> Vec<VariableIndex> {
+    let mut narrowed = vec![];
+    match problem[constraint].description() {
+        Some(ConstraintDescription::AllDifferent { variables, offsets }) => {
+            let values = variables.iter().zip(offsets.iter()).flat_map(|(variable, offset)| problem[*variable].iter_domain().map(move |value| value + offset)).collect::<FxHashSet<isize>>();
+            if values.len() < variables.len() {
+                restrict(problem, variables[0], [], &mut narrowed);
+                return narrowed;
+            }
+            for i in 0..variables.len() {
+                if let Some(value) = fixed_value(problem, variables[i]) {
+                    for j in (0..variables.len()).filter(|j| *j != i) {
+                        let removed = value + offsets[i] - offsets[j];
+                        let domain = problem[variables[j]].iter_domain().filter(|other| *other != removed).collect::<Vec<isize>>();
+                        restrict(problem, variables[j], domain, &mut narrowed);
+                    }
+                }
+            }
+        },
+        Some(ConstraintDescription::AllDifferentExcept { variables, sentinel }) => {
+            for i in 0..variables.len() {
+                if let Some(value) = fixed_value(problem, variables[i]).filter(|value| *value != sentinel) {
+                    for j in (0..variables.len()).filter(|j| *j != i) {
+                        let domain = problem[variables[j]].iter_domain().filter(|other| *other != value).collect::<Vec<isize>>();
+                        restrict(problem, variables[j], domain, &mut narrowed);
+                    }
+                }
+            }
+        },
+        _ => {
+            let scope = problem[constraint].iter_scope().collect::<Vec<VariableIndex>>();
+            if scope.len() == 2 && scope[0] != scope[1] && problem[scope[0]].domain_size() * problem[scope[1]].domain_size() <= MAX_PAIRS {
+                for (x, y) in [(scope[0], scope[1]), (scope[1], scope[0])] {
+                    let domain = problem[x].iter_domain().filter(|value| {
+                        assignment[x.0] = *value;
+                        problem[y].iter_domain().any(|other| {
+                            assignment[y.0] = other;
+                            problem[constraint].is_satisfied(assignment)
+                        })
+                    }).collect::<Vec<isize>>();
+                    restrict(problem, x, domain, &mut narrowed);
+                }
+            } else {
+                let unfixed = scope.iter().copied().filter(|variable| problem[*variable].domain_size() != 1).collect::<FxHashSet<VariableIndex>>();
+                if unfixed.len() <= 1 {
+                    for variable in scope.iter().copied().filter(|variable| !unfixed.contains(variable)) {
+                        assignment[variable.0] = fixed_value(problem, variable).unwrap();
+                    }
+                    match unfixed.iter().next().copied() {
+                        Some(last) => {
+                            let domain = problem[last].iter_domain().filter(|value| {
+                                assignment[last.0] = *value;
+                                problem[constraint].is_satisfied(assignment)
+                            }).collect::<Vec<isize>>();
+                            restrict(problem, last, domain, &mut narrowed);
+                        },
+                        None => if !scope.is_empty() && !problem[constraint].is_satisfied(assignment) {
+                            restrict(problem, scope[0], [], &mut narrowed);
+                        },
+                    }
+                }
+            }
+        },
+    }
+    narrowed
+}
+
+/// Returns the value of the variable if its domain is a singleton
+fn fixed_value(problem: &Problem, variable: VariableIndex) -> Option<isize> {
+    if problem[variable].domain_size() == 1 {
+        problem[variable].iter_domain().next()
+    } else {
+        None
+    }
+}
+
+/// Restricts the domain of the variable to the values, and records the variable if its domain has
+/// been narrowed
+fn restrict(problem: &mut Problem, variable: VariableIndex, values: impl IntoIterator<Item = isize>, narrowed: &mut Vec<VariableIndex>) {
+    let size = problem[variable].domain_size();
+    problem.restrict_domain(variable, values);
+    if problem[variable].domain_size() != size {
+        narrowed.push(variable);
+    }
+}
+
+#[cfg(test)]
+mod test_presolve {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_presolve() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3], None);
+        problem.fix(xs[0], 1);
+        not_equals(&mut problem, xs[0], xs[1]);
+        abs_diff(&mut problem, xs[1], xs[2], Relation::Equal, 0);
+        all_different(&mut problem, xs[2..].to_vec());
+        less_than(&mut problem, xs[3], xs[4]);
+        problem.fix(xs[4], 1);
+        let solutions = reference::solutions(&problem);
+        let statistics = presolve(&mut problem);
+        assert!(!statistics.unsat);
+        // x3 = 0, hence x1 = x2 is 2 or 3
+        assert_eq!(problem[xs[3]].iter_domain().collect::<Vec<isize>>(), vec![0]);
+        assert_eq!(problem[xs[1]].iter_domain().collect::<Vec<isize>>(), vec![2, 3]);
+        assert_eq!(statistics.fixed_variables, 1);
+        assert_eq!(statistics.removed_values, 7);
+        assert_eq!(reference::solutions(&problem), solutions);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.evaluate::<Counting>(|_, _| 1), solutions.len() as u128);
+    }
+
+    #[test]
+    pub fn test_presolve_all_different() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs.clone());
+        problem.restrict_domain(xs[1], [0, 1]);
+        problem.fix(xs[0], 0);
+        let statistics = presolve(&mut problem);
+        assert!(!statistics.unsat);
+        assert_eq!(statistics.fixed_variables, 1);
+        assert_eq!(problem[xs[2]].iter_domain().collect::<Vec<isize>>(), vec![2, 3]);
+
+        // x2 = 1 fixes x0 = 0 after x0 has been handled, hence the constraint filters x1 again
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        problem.restrict_domain(xs[0], [0, 1]);
+        problem.fix(xs[2], 1);
+        assert_eq!(presolve(&mut problem).fixed_variables, 2);
+        assert_eq!(problem[xs[1]].iter_domain().collect::<Vec<isize>>(), vec![2]);
+
+        // Four variables can not take distinct values among three
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(4, vec![0, 1, 2], None);
+        all_different(&mut problem, xs);
+        assert!(presolve(&mut problem).unsat);
+    }
+}
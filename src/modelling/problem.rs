@@ -1,9 +1,11 @@
-use crate::constraints::Constraint;
+use crate::constraints::{Constraint, Tautology};
 use super::*;
 use super::variable::Variable;
+use rustc_hash::FxHashSet;
 
-///This structure represent a constrained optimisation problem.
-#[derive(Default)]
+///This structure represent a constrained optimisation problem. Cloning a problem copies its
+/// variables and constraints, e.g., to derive variants of a model and solve them independently.
+#[derive(Default, Clone)]
 pub struct Problem {
     /// Variables of the problem
     variables: Vec<Variable>,
@@ -20,6 +22,41 @@ impl Problem {
         ret
     }
 
+    /// Adds a variable whose domain is the union of the intervals [min, max] to the problem and
+    /// returns its index. The values of the domain are not materialized.
+    pub fn add_interval_variable(&mut self, intervals: Vec<(isize, isize)>, probabilities: Option<Vec<f64>>) -> VariableIndex {
+        self.add_domain_variable(Domain::intervals(intervals), probabilities)
+    }
+
+    /// Adds a variable with the given domain to the problem and returns its index.
+    pub fn add_domain_variable(&mut self, domain: Domain, probabilities: Option<Vec<f64>>) -> VariableIndex {
+        let ret = VariableIndex(self.variables.len());
+        self.variables.push(Variable::with_domain(domain, probabilities));
+        ret
+    }
+
+    /// Adds a matrix of rows x cols variables, with the same domain, to the problem and returns it
+    pub fn add_matrix(&mut self, rows: usize, cols: usize, domain: Vec<isize>, probabilities: Option<Vec<f64>>) -> Matrix<VariableIndex> {
+        Matrix::new(rows, cols, self.add_variables(rows * cols, domain, probabilities))
+    }
+
+    /// Adds a boolean variable (with domain {0, 1}) to the problem and returns its index.
+    pub fn add_bool(&mut self) -> BoolVariable {
+        let ret = BoolVariable(VariableIndex(self.variables.len()));
+        self.variables.push(Variable::with_domain(Domain::Boolean, None));
+        ret
+    }
+
+    /// Adds n boolean variables to the problem and returns their indexes.
+    pub fn add_bools(&mut self, n: usize) -> Vec<BoolVariable> {
+        (0..n).map(|_| self.add_bool()).collect()
+    }
+
+    /// Returns the variable as a boolean variable, if its domain is {0, 1}
+    pub fn as_bool(&self, variable: VariableIndex) -> Option<BoolVariable> {
+        self[variable].is_bool().then_some(BoolVariable(variable))
+    }
+
     /// Adds n variables, with the same domain, to the problem and return their indexes.
     pub fn add_variables(&mut self, n: usize, domain: Vec<isize>, probabilities: Option<Vec<f64>>) -> Vec<VariableIndex> {
         (0..n).map(|_| self.add_variable(domain.clone(), probabilities.clone())).collect()
@@ -27,20 +64,105 @@ impl Problem {
 
     /// Adds a constraint to the problem and returns its index.
     pub fn add_constraint(&mut self, constraint: impl Constraint + 'static + Send + Sync) -> ConstraintIndex {
+        self.add_boxed_constraint(Box::new(constraint))
+    }
+
+    /// Adds a boxed constraint to the problem and returns its index.
+    pub fn add_boxed_constraint(&mut self, constraint: Box<dyn Constraint + Send + Sync>) -> ConstraintIndex {
         let ret = ConstraintIndex(self.constraints.len());
         for variable in constraint.iter_scope() {
             self[variable].add_constraint(ret);
         }
-        self.constraints.push(Box::new(constraint));
+        self.constraints.push(constraint);
         ret
     }
 
+    /// Sets the probability of each value of the domain of the variable. Panics if there is not one
+    /// probability per value, or if they do not sum to 1.
+    pub fn set_probabilities(&mut self, variable: VariableIndex, probabilities: Vec<f64>) {
+        self[variable].set_probabilities(&probabilities);
+    }
+
+    /// Sets the probabilities of the values of the variable from the distribution
+    pub fn set_distribution(&mut self, variable: VariableIndex, distribution: &Distribution) {
+        let probabilities = distribution.probabilities(self[variable].domain());
+        self[variable].set_probabilities(&probabilities);
+    }
+
+    /// Removes the value from the domain of the variable, if present
+    pub fn remove_value(&mut self, variable: VariableIndex, value: isize) {
+        if self[variable].contains(value) {
+            let domain = self[variable].domain().without(value);
+            self.narrow_domain(variable, domain);
+        }
+    }
+
+    /// Restricts the domain of the variable to the given values. The values not in its domain are
+    /// ignored.
+    pub fn restrict_domain(&mut self, variable: VariableIndex, values: impl IntoIterator<Item = isize>) {
+        let values = values.into_iter().collect::<FxHashSet<isize>>();
+        let domain = self[variable].domain().intersect(&values);
+        if domain.size() != self[variable].domain_size() {
+            self.narrow_domain(variable, domain);
+        }
+    }
+
+    /// Fixes the variable to the value. If the value is not in its domain, the domain becomes empty
+    /// and the problem is unsatisfiable.
+    pub fn fix(&mut self, variable: VariableIndex, value: isize) {
+        self.restrict_domain(variable, [value]);
+    }
+
+    /// Narrows the domain of the variable and notifies the constraints on it
+    fn narrow_domain(&mut self, variable: VariableIndex, domain: Domain) {
+        self.variables[variable.0].narrow_domain(domain);
+        for constraint in self.variables[variable.0].iter_constraints() {
+            self.constraints[constraint.0].on_domain_change(variable, &self.variables);
+        }
+    }
+
+    /// Replaces the constraint at the given index by another one, and returns the replaced
+    /// constraint. The indexes of the other constraints are unchanged.
+    pub fn replace_constraint(&mut self, index: ConstraintIndex, constraint: impl Constraint + 'static + Send + Sync) -> Box<dyn Constraint + Send + Sync> {
+        let replaced = std::mem::replace(&mut self.constraints[index.0], Box::new(constraint));
+        for variable in replaced.iter_scope() {
+            self.variables[variable.0].remove_constraint(index);
+        }
+        for variable in self.constraints[index.0].iter_scope().collect::<Vec<VariableIndex>>() {
+            self.variables[variable.0].add_constraint(index);
+        }
+        replaced
+    }
+
+    /// Removes the constraint from the problem and returns it. It is replaced by a constraint
+    /// satisfied by every assignment (see `Tautology`), so that the indexes of the other
+    /// constraints are unchanged.
+    pub fn retract_constraint(&mut self, index: ConstraintIndex) -> Box<dyn Constraint + Send + Sync> {
+        self.replace_constraint(index, Tautology)
+    }
+
+    /// Removes the constraints added after the first ones, keeping the given number of constraints
+    pub fn truncate_constraints(&mut self, number_constraints: usize) {
+        while self.constraints.len() > number_constraints {
+            let index = ConstraintIndex(self.constraints.len() - 1);
+            let constraint = self.constraints.pop().unwrap();
+            for variable in constraint.iter_scope() {
+                self.variables[variable.0].remove_constraint(index);
+            }
+        }
+    }
+
     pub fn init_constraints(&mut self) {
-        for constraint in 0..self.constraints.len() {
-            self.constraints[constraint].init(&self.variables);
+        for constraint in self.iter_constraints().collect::<Vec<ConstraintIndex>>() {
+            self.init_constraint(constraint);
         }
     }
 
+    /// Initialises the data structures of the constraint (see `Constraint::init`)
+    pub fn init_constraint(&mut self, constraint: ConstraintIndex) {
+        self.constraints[constraint.0].init(&self.variables);
+    }
+
     /// Returns the number of variables in the problem
     pub fn number_variables(&self) -> usize {
         self.variables.len()
@@ -60,9 +182,51 @@ impl Problem {
         &self.constraints
     }
 
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
     pub fn iter_variables(&self) -> impl Iterator<Item = VariableIndex> {
         (0..self.variables.len()).map(VariableIndex)
     }
+
+    /// Checks that the full assignment is a solution of the problem, i.e., that it assigns each
+    /// variable to a value of its domain and satisfies every constraint. Returns the first
+    /// violation otherwise.
+    pub fn check(&self, solution: &[isize]) -> Result<(), Violation> {
+        if solution.len() != self.variables.len() {
+            return Err(Violation::Length { expected: self.variables.len(), actual: solution.len() });
+        }
+        if let Some(variable) = self.iter_variables().find(|variable| !self[*variable].contains(solution[variable.0])) {
+            return Err(Violation::Domain { variable, value: solution[variable.0] });
+        }
+        match self.iter_constraints().find(|constraint| !self[*constraint].is_satisfied(solution)) {
+            Some(constraint) => Err(Violation::Constraint(constraint)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reason why an assignment is not a solution of a problem (see `Problem::check`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The assignment does not have one value per variable
+    Length { expected: usize, actual: usize },
+    /// The value is not in the domain of the variable
+    Domain { variable: VariableIndex, value: isize },
+    /// The constraint is not satisfied
+    Constraint(ConstraintIndex),
+}
+
+impl std::fmt::Display for Violation {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::Length { expected, actual } => write!(f, "expected {} values, got {}", expected, actual),
+            Violation::Domain { variable, value } => write!(f, "the value {} is not in the domain of the variable {}", value, variable.0),
+            Violation::Constraint(constraint) => write!(f, "the constraint {} is violated", constraint.0),
+        }
+    }
 }
 
 impl std::ops::Index<VariableIndex> for Problem {
@@ -95,3 +259,61 @@ impl std::ops::IndexMut<ConstraintIndex> for Problem {
         &mut self.constraints[index.0]
     }
 }
+
+#[cfg(test)]
+mod test_problem {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_clone() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        let mut variant = problem.clone();
+        less_than(&mut variant, xs[0], xs[1]);
+        equal(&mut variant, xs[2], 2);
+
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(get_all_solutions(&mdd).len(), 6);
+        let mut mdd = Mdd::new(variant, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        assert_eq!(solutions.len(), 1);
+        assert!(is_solution(vec![0, 1, 2], &solutions));
+    }
+
+    #[test]
+    pub fn test_constraints_of_variables() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[1]);
+        assert_eq!(problem[xs[0]].number_constraints(), 2);
+        assert_eq!(problem[xs[1]].number_constraints(), 2);
+        assert_eq!(problem[xs[2]].number_constraints(), 1);
+    }
+
+    #[test]
+    pub fn test_check() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[1]);
+        assert_eq!(problem.check(&[0, 2, 1]), Ok(()));
+        assert_eq!(problem.check(&[0, 2]), Err(Violation::Length { expected: 3, actual: 2 }));
+        assert_eq!(problem.check(&[0, 3, 1]), Err(Violation::Domain { variable: xs[1], value: 3 }));
+        assert_eq!(problem.check(&[0, 0, 1]), Err(Violation::Constraint(ConstraintIndex(0))));
+        assert_eq!(problem.check(&[2, 1, 0]), Err(Violation::Constraint(ConstraintIndex(1))));
+
+        let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        for solution in get_all_solutions(&mdd) {
+            assert_eq!(problem.check(&solution), Ok(()));
+        }
+    }
+}
@@ -1,25 +1,31 @@
 use super::*;
+use super::distribution::check_probabilities;
 
+#[derive(Clone)]
 pub struct Variable {
-    domain: Vec<isize>,
+    domain: Domain,
+    /// Probability of each value of the domain (uniform if empty)
     probabilities: Vec<f64>,
+    /// Cost of each value of the domain (0 if empty)
+    costs: Vec<isize>,
     constraints: Vec<ConstraintIndex>,
 }
 
 impl Variable {
 
     pub fn new(domain: Vec<isize>, probs: Option<Vec<f64>>) -> Self {
-        let probabilities = match probs {
-            Some(probabilities) => probabilities,
-            None => {
-                let n = domain.len();
-                let p = 1.0 / (n as f64);
-                vec![p; n]
-            },
-        };
+        Self::with_domain(Domain::Values(domain), probs)
+    }
+
+    /// Creates a variable with the given domain. If no probabilities are given, they are uniform.
+    pub fn with_domain(domain: Domain, probs: Option<Vec<f64>>) -> Self {
+        if let Some(probabilities) = &probs {
+            check_probabilities(probabilities, domain.size());
+        }
         Self {
             domain,
-            probabilities,
+            probabilities: probs.unwrap_or_default(),
+            costs: vec![],
             constraints: vec![],
         }
     }
@@ -27,44 +33,102 @@ impl Variable {
 
     /// Returns the value of the domain at the given index
     pub fn value(&self, index: ValueIndex) -> isize {
-        self.domain[index.0]
+        self.domain.value(index.0)
     }
 
     /// Returns the probability that the variable takes the value from its domain at the given
     /// index.
     pub fn probability(&self, index: ValueIndex) -> f64 {
         if self.probabilities.is_empty() {
-            panic!("Trying to access probabilities on unweighted variable");
+            return 1.0 / (self.domain.size() as f64);
         }
         self.probabilities[index.0]
     }
 
+    /// Sets the probability of each value of the domain. Panics if they do not sum to 1.
     pub fn set_probabilities(&mut self, probabilities: &[f64]) {
+        check_probabilities(probabilities, self.domain.size());
         self.probabilities = probabilities.to_owned();
     }
 
+    /// Returns the probability of each value of the domain, or an empty slice if they are uniform
+    pub fn probabilities(&self) -> &[f64] {
+        &self.probabilities
+    }
+
+    /// Returns the cost of each value of the domain, or an empty slice if they are all 0
+    pub fn costs(&self) -> &[isize] {
+        &self.costs
+    }
+
+    /// Returns the cost of assigning the value from its domain at the given index
+    pub fn cost(&self, index: ValueIndex) -> isize {
+        self.costs.get(index.0).copied().unwrap_or(0)
+    }
+
+    /// Sets the cost of each value of the domain
+    pub fn set_costs(&mut self, costs: &[isize]) {
+        self.costs = costs.to_owned();
+    }
+
     /// Returns the number of elements in the domain
     pub fn domain_size(&self) -> usize {
-        self.domain.len()
+        self.domain.size()
+    }
+
+    pub fn domain(&self) -> &Domain {
+        &self.domain
+    }
+
+    /// Returns true if the domain of the variable is {0, 1}
+    pub fn is_bool(&self) -> bool {
+        match &self.domain {
+            Domain::Boolean => true,
+            Domain::Values(values) => values.len() == 2 && values.contains(&0) && values.contains(&1),
+            Domain::Intervals { intervals, .. } => intervals == &[(0, 1)],
+        }
+    }
+
+    /// Returns true if the value is in the domain
+    pub fn contains(&self, value: isize) -> bool {
+        self.domain.contains(value)
     }
 
     /// Iterates over the domain of the variable
-    pub fn iter_domain(&self) -> impl Iterator<Item = isize> {
-        self.domain.iter().copied()
+    pub fn iter_domain(&self) -> impl Iterator<Item = isize> + '_ {
+        self.domain.iter()
+    }
+
+    /// Narrows the domain of the variable to the given one, which must be a subset of the current
+    /// domain. The probabilities of the remaining values are renormalized to sum to 1, and their
+    /// costs are kept.
+    pub fn narrow_domain(&mut self, domain: Domain) {
+        let kept = domain.iter().map(|value| self.domain.index_of(value).unwrap()).collect::<Vec<usize>>();
+        if !self.probabilities.is_empty() {
+            let total = kept.iter().map(|index| self.probabilities[*index]).sum::<f64>();
+            self.probabilities = kept.iter().map(|index| if total > 0.0 { self.probabilities[*index] / total } else { 1.0 / kept.len() as f64 }).collect();
+        }
+        if !self.costs.is_empty() {
+            self.costs = kept.iter().map(|index| self.costs[*index]).collect();
+        }
+        self.domain = domain;
     }
 
     /// Sets the domain of the variable to the given values
     pub fn set_domain(&mut self, domain: Vec<isize>) {
-        let n = domain.len();
-        self.domain = domain;
-        let p = 1.0 / (n as f64);
-        self.probabilities = vec![p; n];
+        self.domain = Domain::Values(domain);
+        self.probabilities.clear();
+        self.costs.clear();
     }
 
     pub fn add_constraint(&mut self, constraint: ConstraintIndex) {
         self.constraints.push(constraint);
     }
 
+    pub fn remove_constraint(&mut self, constraint: ConstraintIndex) {
+        self.constraints.retain(|c| *c != constraint);
+    }
+
     pub fn iter_constraints(&self) -> impl Iterator<Item = ConstraintIndex> {
         self.constraints.iter().copied()
     }
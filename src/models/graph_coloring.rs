@@ -0,0 +1,98 @@
+use std::io::BufRead;
+use crate::modelling::*;
+
+/// Undirected graph, with vertices numbered from 0
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Graph {
+    pub number_vertices: usize,
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl Graph {
+
+    pub fn new(number_vertices: usize, edges: Vec<(usize, usize)>) -> Self {
+        assert!(edges.iter().all(|(u, v)| *u < number_vertices && *v < number_vertices), "The endpoints of the edges must be vertices of the graph");
+        Self { number_vertices, edges }
+    }
+
+    /// Parses a graph in DIMACS format: a header `p edge <vertices> <edges>` (or `p col`), then one
+    /// line `e <u> <v>` per edge, with vertices numbered from 1. Panics if the input is invalid.
+    pub fn from_dimacs(reader: impl BufRead) -> Self {
+        let mut number_vertices = None;
+        let mut edges = vec![];
+        for line in reader.lines() {
+            let line = line.expect("Failed to read the DIMACS input");
+            let tokens = line.split_whitespace().collect::<Vec<&str>>();
+            match tokens.as_slice() {
+                ["p", _, vertices, _] => number_vertices = Some(vertices.parse::<usize>().expect("Invalid number of vertices in the DIMACS header")),
+                ["e", u, v] => {
+                    let u = u.parse::<usize>().expect("Invalid vertex in a DIMACS edge");
+                    let v = v.parse::<usize>().expect("Invalid vertex in a DIMACS edge");
+                    assert!(u > 0 && v > 0, "The DIMACS vertices are numbered from 1");
+                    edges.push((u - 1, v - 1));
+                },
+                _ => (),
+            }
+        }
+        Self::new(number_vertices.expect("Missing DIMACS header"), edges)
+    }
+}
+
+/// Returns the problem of coloring the vertices of the graph with k colors, such that adjacent
+/// vertices have different colors. The variable of the i-th vertex is `VariableIndex(i)`, with
+/// domain 0..k. Every triangle of the graph is modelled by an AllDifferent constraint, and the
+/// other edges by NotEquals constraints.
+pub fn graph_coloring(graph: &Graph, k: usize) -> Problem {
+    let mut problem = Problem::default();
+    let vertices = problem.add_variables(graph.number_vertices, (0..k as isize).collect(), None);
+    let mut adjacency = vec![vec![]; graph.number_vertices];
+    for (u, v) in graph.edges.iter().copied().filter(|(u, v)| u != v) {
+        if !adjacency[u].contains(&v) {
+            adjacency[u].push(v);
+            adjacency[v].push(u);
+        }
+    }
+    let mut covered = vec![vec![]; graph.number_vertices];
+    for u in 0..graph.number_vertices {
+        for v in adjacency[u].iter().copied().filter(|v| u < *v) {
+            for w in adjacency[v].iter().copied().filter(|w| v < *w && adjacency[u].contains(w)) {
+                all_different(&mut problem, vec![vertices[u], vertices[v], vertices[w]]);
+                covered[u].extend([v, w]);
+                covered[v].push(w);
+            }
+        }
+    }
+    for u in 0..graph.number_vertices {
+        for v in adjacency[u].iter().copied().filter(|v| u < *v && !covered[u].contains(v)) {
+            not_equals(&mut problem, vertices[u], vertices[v]);
+        }
+    }
+    problem
+}
+
+#[cfg(test)]
+mod test_graph_coloring {
+
+    use crate::models::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+
+    #[test]
+    pub fn test_coloring() {
+        // Square 1-2-3-4 with the diagonal 1-3, and a pendant vertex 5 attached to 4
+        let input = "c example\np edge 5 6\ne 1 2\ne 2 3\ne 3 4\ne 4 1\ne 1 3\ne 4 5\n";
+        let graph = Graph::from_dimacs(input.as_bytes());
+        assert_eq!(graph, Graph::new(5, vec![(0, 1), (1, 2), (2, 3), (3, 0), (0, 2), (3, 4)]));
+
+        let problem = graph_coloring(&graph, 3);
+        // Two triangles, and the edge 4-5
+        assert_eq!(problem.number_constraints(), 3);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let solutions = get_all_solutions(&mdd);
+        // 6 colorings of the triangle 1-2-3, then 4 takes the color of 2, and 5 one of 2 colors
+        assert_eq!(solutions.len(), 12);
+        assert!(solutions.iter().all(|solution| mdd.is_solution(solution)));
+    }
+}
@@ -0,0 +1,36 @@
+use crate::modelling::*;
+
+/// Returns the problem of the 0-1 knapsack whose items have the given weights and profits, the
+/// total weight of the selected items being at most the capacity. The variable `VariableIndex(i)`
+/// is 1 if the i-th item is selected, and 0 otherwise. The value 1 of an item costs the opposite of
+/// its profit (see `Variable::cost`), hence the solutions of minimum cost have the maximum profit.
+pub fn knapsack(weights: &[isize], profits: &[isize], capacity: isize) -> Problem {
+    assert!(weights.len() == profits.len(), "The items must have a weight and a profit");
+    let mut problem = Problem::default();
+    let items = problem.add_variables(weights.len(), vec![0, 1], None);
+    for (item, profit) in items.iter().zip(profits.iter()) {
+        problem[*item].set_costs(&[0, -profit]);
+    }
+    pseudo_boolean_less_equal(&mut problem, items, weights.to_vec(), capacity);
+    problem
+}
+
+#[cfg(test)]
+mod test_knapsack {
+
+    use crate::models::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_knapsack() {
+        let problem = knapsack(&[5, 4, 6, 3], &[10, 40, 30, 50], 10);
+        let count = reference::count_solutions(&problem);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.count_solutions(), count);
+        // The items 1 and 3, of weight 7
+        assert_eq!(mdd.minimum_cost_solution(), Some((-90, vec![0, 1, 0, 1])));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod graph_coloring;
+pub mod n_queens;
+pub mod sudoku;
+pub mod knapsack;
+
+pub use graph_coloring::{Graph, graph_coloring};
+pub use n_queens::n_queens;
+pub use sudoku::sudoku;
+pub use knapsack::knapsack;
@@ -0,0 +1,31 @@
+use crate::modelling::*;
+
+/// Returns the problem of placing n queens on an n x n chessboard such that no two queens attack
+/// each other. The variable `VariableIndex(i)` is the column of the queen of the i-th row, with
+/// domain 0..n. The columns and both diagonals are modelled by AllDifferent constraints, the
+/// diagonals with offsets.
+pub fn n_queens(n: usize) -> Problem {
+    let mut problem = Problem::default();
+    let queens = problem.add_variables(n, (0..n as isize).collect(), None);
+    all_different(&mut problem, queens.clone());
+    all_different_offset(&mut problem, queens.clone(), (0..n as isize).collect());
+    all_different_offset(&mut problem, queens, (0..n as isize).map(|i| -i).collect());
+    problem
+}
+
+#[cfg(test)]
+mod test_n_queens {
+
+    use crate::models::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+
+    #[test]
+    pub fn test_n_queens() {
+        for (n, expected) in [(1, 1), (3, 0), (4, 2), (6, 4), (7, 40)] {
+            let mut mdd = Mdd::new(n_queens(n), usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+            mdd.refine();
+            assert_eq!(mdd.count_solutions(), expected);
+        }
+    }
+}
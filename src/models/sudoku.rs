@@ -0,0 +1,46 @@
+use crate::modelling::*;
+
+/// Returns the problem of the Sudoku grids with blocks of the given size, i.e., of the grids of
+/// n x n cells, with n the square of the block size, whose rows, columns and blocks contain the
+/// values 1..=n once. The variable of the cell (i, j) is `VariableIndex(i * n + j)`. The clues of a
+/// puzzle are given by fixing their cells (see `Problem::fix`).
+pub fn sudoku(block_size: usize) -> Problem {
+    let n = block_size * block_size;
+    let mut problem = Problem::default();
+    let grid = problem.add_matrix(n, n, (1..=n as isize).collect(), None);
+    for row in grid.iter_rows() {
+        all_different(&mut problem, row);
+    }
+    for col in grid.iter_cols() {
+        all_different(&mut problem, col);
+    }
+    for block in grid.blocks(block_size, block_size) {
+        all_different(&mut problem, block);
+    }
+    problem
+}
+
+#[cfg(test)]
+mod test_sudoku {
+
+    use crate::modelling::*;
+    use crate::models::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+
+    #[test]
+    pub fn test_sudoku() {
+        let mut problem = sudoku(2);
+        assert_eq!(problem.number_variables(), 16);
+        let mut mdd = Mdd::new(problem.clone(), usize::MAX, OrderingHeuristic::Custom((0..16).collect()), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.count_solutions(), 288);
+        // The first row fixed to 1 2 3 4
+        for (cell, value) in [1, 2, 3, 4].into_iter().enumerate() {
+            problem.fix(VariableIndex(cell), value);
+        }
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::Custom((0..16).collect()), MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        assert_eq!(mdd.count_solutions(), 12);
+    }
+}
@@ -3,27 +3,41 @@ use pyo3::prelude::*;
 use crate::mdd::*;
 use crate::mdd::heuristics::*;
 use crate::modelling::*;
+use crate::constraints::MddConstraint;
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256Plus;
 
-#[pyclass]
+#[pyclass(from_py_object)]
 #[derive(Clone)]
 pub enum PyOrderingHeuristic {
     MinDomMaxLinked(),
     Custom(Vec<usize>),
+    MinFill(),
+    MinDegree(),
+    MaximumCardinality(),
 }
 
-#[pyclass]
+#[pyclass(from_py_object)]
 #[derive(Clone)]
 pub enum PyMergeHeuristic {
     LessRelaxed,
     MostLikely,
 }
 
+#[pyclass(from_py_object)]
+#[derive(Clone)]
+pub enum PySearchStrategy {
+    BranchAndBound,
+    PeelAndBound,
+}
+
 #[pyclass]
 pub struct Solver {
     problem: Problem,
     mdd: Option<Mdd>,
     is_unsat: bool,
     is_solution_sat: bool,
+    profiling: bool,
 }
 
 #[pymethods]
@@ -36,6 +50,7 @@ impl Solver {
             mdd: None,
             is_unsat: false,
             is_solution_sat: false,
+            profiling: false,
         }
     }
 
@@ -44,20 +59,164 @@ impl Solver {
         var.0
     }
 
-    fn add_bool_var(&mut self) -> usize {
-        let var = self.problem.add_variable(vec![0, 1], None);
+    fn add_int_var_range(&mut self, intervals: Vec<(isize, isize)>) -> usize {
+        let var = self.problem.add_interval_variable(intervals, None);
         var.0
     }
 
+    fn add_bool_var(&mut self) -> usize {
+        let var = self.problem.add_bool();
+        var.0.0
+    }
+
     fn add_all_different(&mut self, scope: Vec<usize>) {
         let vars = scope.into_iter().map(VariableIndex).collect();
         all_different(&mut self.problem, vars);
     }
 
+    fn add_all_different_offset(&mut self, scope: Vec<usize>, offsets: Vec<isize>) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        all_different_offset(&mut self.problem, vars, offsets);
+    }
+
+    fn add_all_different_except(&mut self, scope: Vec<usize>, sentinel: isize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        all_different_except(&mut self.problem, vars, sentinel);
+    }
+
+    fn add_inverse(&mut self, xs: Vec<usize>, ys: Vec<usize>) {
+        let xs = xs.into_iter().map(VariableIndex).collect();
+        let ys = ys.into_iter().map(VariableIndex).collect();
+        inverse(&mut self.problem, xs, ys);
+    }
+
+    fn add_stretch(&mut self, scope: Vec<usize>, values: Vec<isize>, min_lengths: Vec<usize>, max_lengths: Vec<usize>) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        stretch(&mut self.problem, vars, values, min_lengths, max_lengths);
+    }
+
+    fn add_global_contiguity(&mut self, scope: Vec<usize>) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        global_contiguity(&mut self.problem, vars);
+    }
+
     fn add_not_equals(&mut self, x: usize, y: usize) {
         not_equals(&mut self.problem, VariableIndex(x), VariableIndex(y));
     }
 
+    #[pyo3(signature = (x, y, offset=0))]
+    fn add_less_equal(&mut self, x: usize, y: usize, offset: isize) {
+        less_equal(&mut self.problem, VariableIndex(x), VariableIndex(y), offset);
+    }
+
+    fn add_less_than(&mut self, x: usize, y: usize) {
+        less_than(&mut self.problem, VariableIndex(x), VariableIndex(y));
+    }
+
+    fn add_abs_diff_equal(&mut self, x: usize, y: usize, constant: isize) {
+        abs_diff(&mut self.problem, VariableIndex(x), VariableIndex(y), Relation::Equal, constant);
+    }
+
+    fn add_abs_diff_less_equal(&mut self, x: usize, y: usize, constant: isize) {
+        abs_diff(&mut self.problem, VariableIndex(x), VariableIndex(y), Relation::LessEqual, constant);
+    }
+
+    fn add_abs_diff_greater_equal(&mut self, x: usize, y: usize, constant: isize) {
+        abs_diff(&mut self.problem, VariableIndex(x), VariableIndex(y), Relation::GreaterEqual, constant);
+    }
+
+    fn add_plus(&mut self, x: usize, y: usize, z: usize) {
+        plus(&mut self.problem, VariableIndex(x), VariableIndex(y), VariableIndex(z));
+    }
+
+    fn add_times(&mut self, x: usize, y: usize, z: usize) {
+        times(&mut self.problem, VariableIndex(x), VariableIndex(y), VariableIndex(z));
+    }
+
+    fn add_division(&mut self, x: usize, y: usize, z: usize) {
+        division(&mut self.problem, VariableIndex(x), VariableIndex(y), VariableIndex(z));
+    }
+
+    fn add_modulo(&mut self, x: usize, y: usize, z: usize) {
+        modulo(&mut self.problem, VariableIndex(x), VariableIndex(y), VariableIndex(z));
+    }
+
+    fn add_maximum(&mut self, xs: Vec<usize>, y: usize) {
+        let xs = xs.into_iter().map(VariableIndex).collect();
+        maximum(&mut self.problem, xs, VariableIndex(y));
+    }
+
+    fn add_minimum(&mut self, xs: Vec<usize>, y: usize) {
+        let xs = xs.into_iter().map(VariableIndex).collect();
+        minimum(&mut self.problem, xs, VariableIndex(y));
+    }
+
+    fn add_clause(&mut self, positives: Vec<usize>, negatives: Vec<usize>) {
+        let positives = positives.into_iter().map(VariableIndex).collect();
+        let negatives = negatives.into_iter().map(VariableIndex).collect();
+        clause(&mut self.problem, positives, negatives);
+    }
+
+    fn add_at_least_one(&mut self, scope: Vec<usize>) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        at_least_one(&mut self.problem, vars);
+    }
+
+    fn add_at_least_k(&mut self, scope: Vec<usize>, k: usize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        at_least_k(&mut self.problem, vars, k);
+    }
+
+    fn add_at_most_k(&mut self, scope: Vec<usize>, k: usize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        at_most_k(&mut self.problem, vars, k);
+    }
+
+    fn add_exactly_k(&mut self, scope: Vec<usize>, k: usize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        exactly_k(&mut self.problem, vars, k);
+    }
+
+    fn add_pseudo_boolean_greater_equal(&mut self, scope: Vec<usize>, weights: Vec<isize>, bound: isize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        pseudo_boolean_greater_equal(&mut self.problem, vars, weights, bound);
+    }
+
+    fn add_pseudo_boolean_less_equal(&mut self, scope: Vec<usize>, weights: Vec<isize>, bound: isize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        pseudo_boolean_less_equal(&mut self.problem, vars, weights, bound);
+    }
+
+    fn add_cost_at_most(&mut self, scope: Vec<usize>, bound: isize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        cost_at_most(&mut self.problem, vars, bound);
+    }
+
+    fn add_cost_regular(&mut self, scope: Vec<usize>, transitions: Vec<(usize, isize, usize, isize)>, initial: usize, finals: Vec<usize>, z: usize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        cost_regular(&mut self.problem, vars, transitions, initial, finals, VariableIndex(z));
+    }
+
+    fn add_cost_global_cardinality(&mut self, scope: Vec<usize>, values: Vec<isize>, lower: Vec<usize>, upper: Vec<usize>, costs: Vec<isize>, z: usize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        cost_global_cardinality(&mut self.problem, vars, values, lower, upper, costs, VariableIndex(z));
+    }
+
+    fn add_table(&mut self, scope: Vec<usize>, tuples: Vec<Vec<isize>>) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        table(&mut self.problem, vars, tuples);
+    }
+
+    fn add_short_table(&mut self, scope: Vec<usize>, tuples: Vec<Vec<Option<isize>>>) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        short_table(&mut self.problem, vars, tuples);
+    }
+
+    fn add_mdd_constraint(&mut self, scope: Vec<usize>, arcs: Vec<(usize, isize, usize)>, root: usize, terminal: usize) {
+        let vars = scope.into_iter().map(VariableIndex).collect();
+        self.problem.add_constraint(MddConstraint::new(vars, arcs, root, terminal));
+    }
+
     fn add_equal(&mut self, x: usize, value: isize) {
         equal(&mut self.problem, VariableIndex(x), value);
     }
@@ -74,25 +233,74 @@ impl Solver {
         }
     }
 
+    fn set_costs(&mut self, x: usize, costs: Vec<isize>) {
+        self.problem[VariableIndex(x)].set_costs(&costs);
+    }
+
+    fn set_variable_probabilities(&mut self, x: usize, probabilities: Vec<f64>) {
+        self.problem.set_probabilities(VariableIndex(x), probabilities);
+    }
+
+    fn remove_value(&mut self, x: usize, value: isize) {
+        self.problem.remove_value(VariableIndex(x), value);
+    }
+
+    fn restrict_domain(&mut self, x: usize, values: Vec<isize>) {
+        self.problem.restrict_domain(VariableIndex(x), values);
+    }
+
+    fn fix(&mut self, x: usize, value: isize) {
+        self.problem.fix(VariableIndex(x), value);
+    }
+
     // --- SOLVE --- //
     fn compile(&mut self, max_width: Option<usize>, pyordering: PyOrderingHeuristic, pymerge: PyMergeHeuristic) {
         let width = max_width.unwrap_or(usize::MAX);
-        let ordering = match pyordering {
-            PyOrderingHeuristic::MinDomMaxLinked() => OrderingHeuristic::MinDomMaxLinked,
-            PyOrderingHeuristic::Custom(order) => OrderingHeuristic::Custom(order),
-        };
-
-        let merge = match pymerge {
-            PyMergeHeuristic::LessRelaxed => MergeHeuristic::LessRelaxed,
-            PyMergeHeuristic::MostLikely => MergeHeuristic::MostLikely,
-        };
-
-        let mut mdd = Mdd::new(std::mem::take(&mut self.problem), width, ordering, merge);
+        let (ordering, merge) = heuristics(pyordering, pymerge);
+        let mut mdd = Mdd::new(self.problem.clone(), width, ordering, merge);
+        mdd.set_propagation_timing(self.profiling);
+        mdd.enable_repair();
         mdd.refine();
         self.is_unsat = mdd.is_unsat();
         self.mdd = Some(mdd);
     }
 
+    /// Restricts the domains of the variables to their values in the compiled MDD, for the next
+    /// compilations, and returns the number of removed values
+    fn strengthen_domains(&mut self) -> usize {
+        self.mdd.as_ref().unwrap().strengthen_problem(&mut self.problem)
+    }
+
+    /// Filters the domains of the variables with the constraints before the next compilations (see
+    /// `presolve`), and returns the number of removed values
+    fn presolve(&mut self) -> usize {
+        presolve(&mut self.problem).removed_values
+    }
+
+    /// Measures the time spent propagating each constraint in the next compilations (see `stats`)
+    fn set_profiling(&mut self, profiling: bool) {
+        self.profiling = profiling;
+    }
+
+    /// Returns, for each constraint, the number of propagation rounds in which it filtered the
+    /// edges of the compiled MDD, the number of edges it removed, and the time spent propagating it
+    /// in seconds (0 unless the profiling is enabled)
+    fn stats(&self) -> Vec<(usize, usize, f64)> {
+        self.mdd.as_ref().unwrap().propagation_statistics().iter().map(|statistics| (statistics.invocations, statistics.removed_edges, statistics.time.as_secs_f64())).collect()
+    }
+
+    /// Compiles the problem with the ordering whose restricted and relaxed MDDs of width
+    /// `probe_width` bound the number of solutions the tightest, and returns its index
+    #[pyo3(signature = (orderings, probe_width, max_width=None, pymerge=PyMergeHeuristic::LessRelaxed))]
+    fn compile_portfolio(&mut self, orderings: Vec<PyOrderingHeuristic>, probe_width: usize, max_width: Option<usize>, pymerge: PyMergeHeuristic) -> usize {
+        let orderings = orderings.into_iter().map(|pyordering| heuristics(pyordering, pymerge.clone()).0).collect::<Vec<OrderingHeuristic>>();
+        let (_, merge) = heuristics(PyOrderingHeuristic::MinDomMaxLinked(), pymerge);
+        let portfolio = compile_portfolio(&self.problem, &orderings, probe_width, max_width.unwrap_or(usize::MAX), &merge);
+        self.is_unsat = portfolio.mdd.is_unsat();
+        self.mdd = Some(portfolio.mdd);
+        portfolio.selected
+    }
+
     #[pyo3(signature = (max_width=None,
             pyordering=PyOrderingHeuristic::MinDomMaxLinked(),
             pymerge=PyMergeHeuristic::LessRelaxed,
@@ -116,6 +324,28 @@ impl Solver {
         solution
     }
 
+    #[pyo3(signature = (max_width=None,
+            pyordering=PyOrderingHeuristic::MinDomMaxLinked(),
+            pymerge=PyMergeHeuristic::LessRelaxed))]
+    fn unsat_core(&self, max_width: Option<usize>, pyordering: PyOrderingHeuristic, pymerge: PyMergeHeuristic) -> Option<Vec<usize>> {
+        let (ordering, merge) = heuristics(pyordering, pymerge);
+        Mdd::unsat_core(&self.problem, max_width.unwrap_or(usize::MAX), &ordering, &merge).map(|core| core.into_iter().map(|constraint| constraint.0).collect())
+    }
+
+    fn minimum_cost_solution(&self) -> Option<(isize, Vec<isize>)> {
+        self.mdd.as_ref().and_then(|mdd| mdd.minimum_cost_solution())
+    }
+
+    /// Searches a minimum cost solution with relaxed MDDs of the given width, pruned by their bound
+    #[pyo3(signature = (max_width=None, strategy=PySearchStrategy::PeelAndBound))]
+    fn minimize(&self, max_width: Option<usize>, strategy: PySearchStrategy) -> Option<(isize, Vec<isize>)> {
+        let strategy = match strategy {
+            PySearchStrategy::BranchAndBound => SearchStrategy::BranchAndBound,
+            PySearchStrategy::PeelAndBound => SearchStrategy::PeelAndBound,
+        };
+        minimize(&self.problem, max_width.unwrap_or(usize::MAX), strategy)
+    }
+
     // --- SOLUTION INFO --- //
 
     fn is_unsat(&self) -> bool {
@@ -135,12 +365,34 @@ impl Solver {
         true
     }
 
+    fn explain(&self, x: usize, value: isize) -> Vec<usize> {
+        self.mdd.as_ref().map(|mdd| mdd.explain(VariableIndex(x), value).into_iter().map(|constraint| constraint.0).collect()).unwrap_or_default()
+    }
+
     fn proportion_satisfied_constraints(&self, solution: Vec<isize>) -> f64 {
         let number_constraints = self.problem.number_constraints() as f64;
         let satisfied = self.problem.iter_constraints().filter(|&constraint| self.problem[constraint].is_satisfied(&solution)).count() as f64;
         satisfied / number_constraints
     }
 
+    /// Returns an estimate of the weighted count of the solutions, within a factor 1 +- epsilon with
+    /// probability at least 1 - delta, from the paths of the compiled MDD drawn with the seed
+    #[pyo3(signature = (epsilon, delta, rng, max_samples=1_000_000))]
+    fn approx_count(&self, epsilon: f64, delta: f64, rng: u64, max_samples: usize) -> Option<f64> {
+        let mut rng = Xoshiro256Plus::seed_from_u64(rng);
+        self.mdd.as_ref().unwrap().approximate_count(epsilon, delta, max_samples, &mut rng).map(|count| count.estimate)
+    }
+
+    fn marginals(&self) -> Vec<Vec<f64>> {
+        self.mdd.as_ref().unwrap().marginals()
+    }
+
+    /// Returns the derivatives of the probability mass of the compiled MDD with respect to the
+    /// probability of each value of each variable
+    fn probability_gradient(&self) -> Vec<Vec<f64>> {
+        self.mdd.as_ref().unwrap().probability_gradient()
+    }
+
     fn topological_order(&self) -> Vec<(usize, usize, usize, isize)> {
         self.mdd.as_ref().unwrap().topological_order()
     }
@@ -176,10 +428,27 @@ impl Solver {
     }
 }
 
+fn heuristics(pyordering: PyOrderingHeuristic, pymerge: PyMergeHeuristic) -> (OrderingHeuristic, MergeHeuristic) {
+    let ordering = match pyordering {
+        PyOrderingHeuristic::MinDomMaxLinked() => OrderingHeuristic::MinDomMaxLinked,
+        PyOrderingHeuristic::Custom(order) => OrderingHeuristic::Custom(order),
+        PyOrderingHeuristic::MinFill() => OrderingHeuristic::ConstraintGraph(ordering::Strategy::MinFill),
+        PyOrderingHeuristic::MinDegree() => OrderingHeuristic::ConstraintGraph(ordering::Strategy::MinDegree),
+        PyOrderingHeuristic::MaximumCardinality() => OrderingHeuristic::ConstraintGraph(ordering::Strategy::MaximumCardinality),
+    };
+
+    let merge = match pymerge {
+        PyMergeHeuristic::LessRelaxed => MergeHeuristic::LessRelaxed,
+        PyMergeHeuristic::MostLikely => MergeHeuristic::MostLikely,
+    };
+    (ordering, merge)
+}
+
 #[pymodule]
 fn pyaicad(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Solver>()?;
     m.add_class::<PyOrderingHeuristic>()?;
     m.add_class::<PyMergeHeuristic>()?;
+    m.add_class::<PySearchStrategy>()?;
     Ok(())
 }
@@ -0,0 +1,98 @@
+use crate::modelling::*;
+
+// Naive backtracking solver over a problem, used as a reference to validate the MDDs in the tests.
+// The variables are assigned in the order of their indexes, and each constraint is checked as soon
+// as its whole scope is assigned. It does not propagate anything, hence it is only practical on
+// small problems.
+
+/// Calls the function on each solution of the problem, in the lexicographic order of the value
+/// indexes of the variables
+pub fn for_each_solution(problem: &Problem, mut f: impl FnMut(&[isize])) {
+    let number_variables = problem.number_variables();
+    // The constraints whose scope is fully assigned once each variable is assigned
+    let mut checked = vec![vec![]; number_variables];
+    for constraint in problem.iter_constraints() {
+        if let Some(last) = problem[constraint].iter_scope().map(|variable| variable.0).max() {
+            checked[last].push(constraint);
+        }
+    }
+    let mut assignment = vec![0; number_variables];
+    if number_variables == 0 {
+        f(&assignment);
+        return;
+    }
+    let mut value_index = vec![0; number_variables];
+    let mut depth = 0;
+    loop {
+        let variable = VariableIndex(depth);
+        if value_index[depth] == problem[variable].domain_size() {
+            // Every value is tried, we backtrack
+            value_index[depth] = 0;
+            if depth == 0 {
+                return;
+            }
+            depth -= 1;
+            value_index[depth] += 1;
+            continue;
+        }
+        assignment[depth] = problem[variable].value(ValueIndex(value_index[depth]));
+        if !checked[depth].iter().all(|constraint| problem[*constraint].is_satisfied(&assignment)) {
+            value_index[depth] += 1;
+        } else if depth == number_variables - 1 {
+            f(&assignment);
+            value_index[depth] += 1;
+        } else {
+            depth += 1;
+        }
+    }
+}
+
+/// Returns the solutions of the problem (see `for_each_solution`)
+pub fn solutions(problem: &Problem) -> Vec<Vec<isize>> {
+    let mut solutions = vec![];
+    for_each_solution(problem, |solution| solutions.push(solution.to_vec()));
+    solutions
+}
+
+pub fn count_solutions(problem: &Problem) -> u128 {
+    let mut count = 0;
+    for_each_solution(problem, |_| count += 1);
+    count
+}
+
+#[cfg(test)]
+mod test_reference {
+
+    use crate::modelling::*;
+    use crate::mdd::*;
+    use crate::mdd::heuristics::*;
+    use crate::mdd::mdd::test_mdd::*;
+    use crate::reference;
+
+    #[test]
+    pub fn test_reference_solutions() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(3, vec![0, 1, 2], None);
+        all_different(&mut problem, xs.clone());
+        less_than(&mut problem, xs[0], xs[2]);
+        assert_eq!(reference::solutions(&problem), vec![vec![0, 1, 2], vec![0, 2, 1], vec![1, 0, 2]]);
+    }
+
+    #[test]
+    pub fn test_reference_against_mdd() {
+        let mut problem = Problem::default();
+        let xs = problem.add_variables(5, vec![0, 1, 2, 3], None);
+        all_different(&mut problem, xs[0..3].to_vec());
+        not_equals(&mut problem, xs[3], xs[4]);
+        less_than(&mut problem, xs[1], xs[4]);
+        table(&mut problem, vec![xs[0], xs[3]], vec![vec![0, 1], vec![1, 2], vec![2, 3], vec![3, 0], vec![1, 1]]);
+        let mut expected = reference::solutions(&problem);
+        let mut mdd = Mdd::new(problem, usize::MAX, OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.refine();
+        let mut solutions = get_all_solutions(&mdd);
+        expected.sort();
+        solutions.sort();
+        assert_eq!(solutions, expected);
+        assert_eq!(mdd.count_solutions(), expected.len() as u128);
+    }
+}
@@ -1,7 +1,9 @@
+use crate::modelling::Domain;
 use rustc_hash::FxHashMap;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
 pub struct Bitset {
     words: Vec<u64>,
 }
@@ -10,13 +12,43 @@ impl Bitset {
 
     /// Creates a new bitset for storing n contiguous integers (starting from 0)
     pub fn new(n: usize) -> Self {
-        //debug_assert!(n > 0);
-        let number_words = (n / 64) + 1;
+        Self::with_capacity(n)
+    }
+
+    /// Creates a new empty bitset for the integers 0..n, with ceil(n / 64) words (at least one)
+    pub fn with_capacity(n: usize) -> Self {
         Self {
-            words: vec![0; number_words],
+            words: vec![0; n.div_ceil(64).max(1)],
+        }
+    }
+
+    /// Returns the number of integers that can be stored, i.e., 64 times the number of words
+    pub fn capacity(&self) -> usize {
+        self.words.len() * 64
+    }
+
+    /// Grows the bitset to store the integers 0..n, the new integers not being in the set
+    pub fn grow(&mut self, n: usize) {
+        if n > self.capacity() {
+            self.words.resize(n.div_ceil(64), 0);
         }
     }
 
+    /// Returns the integers in the set, in increasing order
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().copied().enumerate().flat_map(|(index, word)| {
+            let mut remaining = word;
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+                let shift = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1;
+                Some(index * 64 + shift)
+            })
+        })
+    }
+
     pub fn contains(&self, element: usize) -> bool {
         let word = element / 64;
         let shift = element % 64;
@@ -57,47 +89,155 @@ impl Bitset {
         }
     }
 
+    /// Removes the elements of the other bitset
+    pub fn difference(&mut self, other: &Bitset) {
+        debug_assert!(self.words.len() == other.words.len());
+        for word in 0..self.words.len() {
+            self.words[word] &= !other.words[word]
+        }
+    }
+
+    /// Keeps the elements in exactly one of the bitsets
+    pub fn symmetric_difference(&mut self, other: &Bitset) {
+        debug_assert!(self.words.len() == other.words.len());
+        for word in 0..self.words.len() {
+            self.words[word] ^= other.words[word]
+        }
+    }
+
+    pub fn is_subset(&self, other: &Bitset) -> bool {
+        debug_assert!(self.words.len() == other.words.len());
+        self.words.iter().zip(other.words.iter()).all(|(word, other)| word & !other == 0)
+    }
+
+    pub fn is_disjoint(&self, other: &Bitset) -> bool {
+        debug_assert!(self.words.len() == other.words.len());
+        self.words.iter().zip(other.words.iter()).all(|(word, other)| word & other == 0)
+    }
+
+    /// Replaces the set by its complement in 0..n
+    pub fn complement(&mut self, n: usize) {
+        for word in 0..self.words.len() {
+            self.words[word] = !self.words[word];
+        }
+        let last = n / 64;
+        if last < self.words.len() {
+            self.words[last] &= (1u64 << (n % 64)) - 1;
+            for word in last + 1..self.words.len() {
+                self.words[word] = 0;
+            }
+        }
+    }
+
     pub fn reset(&mut self, value: u64) {
         for word in 0..self.words.len() {
             self.words[word] = value;
         }
     }
 
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+}
+
+/// Maps the elements of a sparse bitset to their bit. It is shared by all the bitsets over the same
+/// elements.
+enum SparseIndex<T> {
+    /// The elements, in the order of their bits, with the bit of each element
+    Map(Vec<T>, FxHashMap<T, usize>),
+    /// The elements are the values of a domain, and their bit is their index
+    Domain(Domain),
 }
 
 #[derive(Clone)]
 pub struct SparseBitset<T: Eq + Hash + Copy> {
     plain: Bitset,
-    map: FxHashMap<T, usize>,
+    index: Arc<SparseIndex<T>>,
 }
 
-impl<T: Eq + Hash + Copy> SparseBitset<T> {
+impl SparseBitset<isize> {
+
+    /// Creates a new empty bitset over the values of the domain. The domain is not materialized.
+    pub fn from_domain(domain: &Domain) -> Self {
+        Self {
+            plain: Bitset::new(domain.size()),
+            index: Arc::new(SparseIndex::Domain(domain.clone())),
+        }
+    }
+
+    /// Returns an iterator over the elements of the set, in the order of their bits
+    pub fn iter(&self) -> impl Iterator<Item = isize> + '_ {
+        self.plain.iter_ones().take_while(|bit| *bit < self.universe_size()).map(|bit| match self.index.as_ref() {
+            SparseIndex::Map(elements, _) => elements[bit],
+            SparseIndex::Domain(domain) => domain.value(bit),
+        })
+    }
+
+    /// Returns the elements of the set, in the order of their bits
+    pub fn elements(&self) -> Vec<isize> {
+        self.iter().collect()
+    }
+}
+
+impl<T: Eq + Hash + Copy + Into<isize>> SparseBitset<T> {
 
     pub fn new(elements: impl Iterator<Item = T>) -> Self {
         let mut map = FxHashMap::<T, usize>::default();
-        for (bit, element) in elements.enumerate() {
-            map.insert(element, bit);
+        let mut ordered = vec![];
+        for element in elements {
+            if let std::collections::hash_map::Entry::Vacant(entry) = map.entry(element) {
+                entry.insert(ordered.len());
+                ordered.push(element);
+            }
         }
         Self {
             plain: Bitset::new(map.len()),
-            map,
+            index: Arc::new(SparseIndex::Map(ordered, map)),
         }
     }
 
+    /// Returns the bit of the element
+    pub fn bit(&self, element: T) -> usize {
+        match self.index.as_ref() {
+            SparseIndex::Map(_, map) => *map.get(&element).unwrap(),
+            SparseIndex::Domain(domain) => domain.index_of(element.into()).unwrap(),
+        }
+    }
+
+    /// Returns the number of elements over which the set is defined
+    fn universe_size(&self) -> usize {
+        match self.index.as_ref() {
+            SparseIndex::Map(elements, _) => elements.len(),
+            SparseIndex::Domain(domain) => domain.size(),
+        }
+    }
 
     pub fn contains(&self, element: T) -> bool {
-        let element = *self.map.get(&element).unwrap();
-        self.plain.contains(element)
+        self.plain.contains(self.bit(element))
+    }
+
+    /// Returns true if the element of the bit (see `SparseBitset::bit`) is in the set
+    pub fn contains_bit(&self, bit: usize) -> bool {
+        self.plain.contains(bit)
+    }
+
+    pub fn insert_bit(&mut self, bit: usize) {
+        self.plain.insert(bit);
+    }
+
+    pub fn remove_bit(&mut self, bit: usize) {
+        self.plain.remove(bit);
     }
 
     pub fn insert(&mut self, element: T) {
-        let element = *self.map.get(&element).unwrap();
-        self.plain.insert(element);
+        let bit = self.bit(element);
+        self.plain.insert(bit);
     }
 
     pub fn remove(&mut self, element: T) {
-        let element = *self.map.get(&element).unwrap();
-        self.plain.remove(element);
+        let bit = self.bit(element);
+        self.plain.remove(bit);
     }
 
     pub fn size(&self) -> usize {
@@ -116,6 +256,30 @@ impl<T: Eq + Hash + Copy> SparseBitset<T> {
         self.plain.intersect(&other.plain);
     }
 
+    /// Removes the elements of the other set, defined over the same elements
+    pub fn difference(&mut self, other: &SparseBitset<T>) {
+        self.plain.difference(&other.plain);
+    }
+
+    /// Keeps the elements in exactly one of the sets, defined over the same elements
+    pub fn symmetric_difference(&mut self, other: &SparseBitset<T>) {
+        self.plain.symmetric_difference(&other.plain);
+    }
+
+    pub fn is_subset(&self, other: &SparseBitset<T>) -> bool {
+        self.plain.is_subset(&other.plain)
+    }
+
+    pub fn is_disjoint(&self, other: &SparseBitset<T>) -> bool {
+        self.plain.is_disjoint(&other.plain)
+    }
+
+    /// Replaces the set by its complement in the elements over which it is defined
+    pub fn complement(&mut self) {
+        let size = self.universe_size();
+        self.plain.complement(size);
+    }
+
     pub fn reset(&mut self, value: u64) {
         self.plain.reset(value);
     }
@@ -154,3 +318,53 @@ impl<T: Eq + Hash + Copy> Hash for SparseBitset<T> {
         self.plain.hash(state);
     }
 }
+
+#[cfg(test)]
+mod test_bitset {
+
+    use crate::utils::{Bitset, SparseBitset};
+    use crate::modelling::Domain;
+
+    #[test]
+    pub fn test_capacity() {
+        assert_eq!(Bitset::with_capacity(0).capacity(), 64);
+        assert_eq!(Bitset::with_capacity(64).capacity(), 64);
+        let mut bitset = Bitset::with_capacity(65);
+        assert_eq!(bitset.capacity(), 128);
+        bitset.insert(64);
+        bitset.insert(3);
+        bitset.grow(200);
+        assert_eq!(bitset.capacity(), 256);
+        bitset.insert(199);
+        assert_eq!(bitset.iter_ones().collect::<Vec<usize>>(), vec![3, 64, 199]);
+        assert_eq!(bitset.size(), 3);
+    }
+
+    #[test]
+    pub fn test_sparse_set_algebra() {
+        let values = [10, -3, 7, 42, 5];
+        let mut a = SparseBitset::new(values.iter().copied());
+        let mut b = SparseBitset::new(values.iter().copied());
+        for value in [10, 7, 42] {
+            a.insert(value);
+        }
+        for value in [7, 5] {
+            b.insert(value);
+        }
+        assert!(!a.is_subset(&b) && !a.is_disjoint(&b));
+        let mut difference = a.clone();
+        difference.difference(&b);
+        assert_eq!(difference.iter().collect::<Vec<isize>>(), vec![10, 42]);
+        assert!(difference.is_subset(&a) && difference.is_disjoint(&b));
+        let mut symmetric_difference = a.clone();
+        symmetric_difference.symmetric_difference(&b);
+        assert_eq!(symmetric_difference.elements(), vec![10, 42, 5]);
+        a.complement();
+        assert_eq!(a.elements(), vec![-3, 5]);
+        let mut domain = SparseBitset::from_domain(&Domain::range(0, 69));
+        domain.insert(68);
+        domain.complement();
+        assert_eq!(domain.size(), 69);
+        assert!(!domain.contains(68));
+    }
+}
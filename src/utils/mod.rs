@@ -1,3 +1,7 @@
 pub mod bitset;
+pub mod parallel;
+pub mod sparse_set;
 
-pub use bitset::SparseBitset;
+pub use bitset::{Bitset, SparseBitset};
+pub use parallel::map_items;
+pub use sparse_set::SparseSet;
@@ -0,0 +1,15 @@
+// Data-parallel helpers. With the `parallel` feature, the items are processed by the rayon thread
+// pool, otherwise sequentially.
+
+/// Returns the image of each item by the function, in the order of the items
+#[cfg(feature = "parallel")]
+pub fn map_items<I: Sync, T: Send>(items: &[I], function: impl Fn(usize, &I) -> T + Sync + Send) -> Vec<T> {
+    use rayon::prelude::*;
+    items.par_iter().enumerate().map(|(index, item)| function(index, item)).collect()
+}
+
+/// Returns the image of each item by the function, in the order of the items
+#[cfg(not(feature = "parallel"))]
+pub fn map_items<I: Sync, T: Send>(items: &[I], function: impl Fn(usize, &I) -> T + Sync + Send) -> Vec<T> {
+    items.iter().enumerate().map(|(index, item)| function(index, item)).collect()
+}
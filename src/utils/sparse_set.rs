@@ -0,0 +1,101 @@
+// Sparse set over the integers 0..n, i.e., a dense array of the elements whose first `size` entries
+// are the members, and the position of each element in the array. Insertions and removals swap the
+// element with the first non-member or the last member, hence both take constant time, as do the
+// membership test and the size. The members are not kept in any particular order.
+
+/// Set of integers 0..n with constant-time insertion, removal and membership test
+#[derive(Clone, Default, Debug)]
+pub struct SparseSet {
+    dense: Vec<usize>,
+    position: Vec<usize>,
+    size: usize,
+}
+
+impl SparseSet {
+
+    /// Returns the empty set over 0..n
+    pub fn new(n: usize) -> Self {
+        Self {
+            dense: (0..n).collect(),
+            position: (0..n).collect(),
+            size: 0,
+        }
+    }
+
+    /// Extends the universe of the set to 0..n, if it is smaller
+    pub fn grow(&mut self, n: usize) {
+        for element in self.dense.len()..n {
+            self.dense.push(element);
+            self.position.push(element);
+        }
+    }
+
+    pub fn contains(&self, element: usize) -> bool {
+        element < self.position.len() && self.position[element] < self.size
+    }
+
+    pub fn insert(&mut self, element: usize) {
+        if !self.contains(element) {
+            self.swap(self.position[element], self.size);
+            self.size += 1;
+        }
+    }
+
+    pub fn remove(&mut self, element: usize) {
+        if self.contains(element) {
+            self.size -= 1;
+            self.swap(self.position[element], self.size);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.size = 0;
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the members of the set, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dense[..self.size].iter().copied()
+    }
+
+    /// Swaps the elements at the two positions of the dense array
+    fn swap(&mut self, i: usize, j: usize) {
+        self.dense.swap(i, j);
+        self.position[self.dense[i]] = i;
+        self.position[self.dense[j]] = j;
+    }
+}
+
+#[cfg(test)]
+mod test_sparse_set {
+
+    use crate::utils::SparseSet;
+
+    #[test]
+    pub fn test_sparse_set() {
+        let mut set = SparseSet::new(5);
+        assert!(set.is_empty());
+        for element in [3, 0, 4, 3] {
+            set.insert(element);
+        }
+        assert_eq!(set.size(), 3);
+        set.remove(0);
+        set.remove(1);
+        assert!(set.contains(3) && set.contains(4) && !set.contains(0));
+        set.grow(8);
+        set.insert(7);
+        let mut members = set.iter().collect::<Vec<usize>>();
+        members.sort();
+        assert_eq!(members, vec![3, 4, 7]);
+        assert!(!set.contains(100));
+        set.clear();
+        assert!(set.is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use wasm_bindgen::prelude::*;
+
+use crate::mdd::*;
+use crate::mdd::heuristics::*;
+use crate::modelling::*;
+use crate::constraints::Table;
+
+// WebAssembly bindings for in-browser configurators. The problem is given as the JSON
+// serialization of its description (see `ProblemDescription`), compiled once, then the user
+// choices are applied as conditions on the compiled MDD, which gives the values still valid for
+// every variable.
+
+#[wasm_bindgen]
+pub struct Configurator {
+    mdd: Mdd,
+}
+
+#[wasm_bindgen]
+impl Configurator {
+
+    /// Compiles the problem described by the JSON, with an unbounded width by default
+    #[wasm_bindgen(constructor)]
+    pub fn compile(json: &str, max_width: Option<usize>) -> Configurator {
+        let problem = Problem::from_json(json);
+        let mut mdd = Mdd::new(problem, max_width.unwrap_or(usize::MAX), OrderingHeuristic::MinDomMaxLinked, MergeHeuristic::LessRelaxed);
+        mdd.enable_repair();
+        mdd.refine();
+        Configurator { mdd }
+    }
+
+    /// Conditions the MDD on the variable taking the value. Returns the index of the added
+    /// constraint, to be given to `retract` to undo the condition.
+    pub fn condition(&mut self, variable: usize, value: isize) -> usize {
+        self.mdd.add_constraint(Table::new(vec![VariableIndex(variable)], vec![vec![Some(value)]])).0
+    }
+
+    /// Retracts a condition, or a constraint of the problem
+    pub fn retract(&mut self, constraint: usize) {
+        self.mdd.retract_constraint(ConstraintIndex(constraint));
+    }
+
+    /// Returns the values of the variable that are still valid
+    pub fn valid_domain(&self, variable: usize) -> Vec<isize> {
+        if self.mdd.is_unsat() {
+            return vec![];
+        }
+        self.mdd.feasible_values(VariableIndex(variable))
+    }
+
+    /// Returns the constraints that removed the value from the valid domain of the variable
+    pub fn explain(&self, variable: usize, value: isize) -> Vec<usize> {
+        self.mdd.explain(VariableIndex(variable), value).into_iter().map(|constraint| constraint.0).collect()
+    }
+
+    pub fn is_unsat(&self) -> bool {
+        self.mdd.is_unsat()
+    }
+
+    pub fn number_variables(&self) -> usize {
+        self.mdd.number_layers() - 1
+    }
+}
+
+#[cfg(test)]
+mod test_wasm {
+
+    use crate::wasm::*;
+
+    #[test]
+    pub fn test_configurator() {
+        let json = r#"{
+            "variables": [{"domain": {"Values": [0, 1, 2]}}, {"domain": {"Values": [0, 1, 2]}}],
+            "constraints": [{"type": "NotEquals", "x": 0, "y": 1}]
+        }"#;
+        let mut configurator = Configurator::compile(json, None);
+        assert_eq!(configurator.valid_domain(1), vec![0, 1, 2]);
+        let condition = configurator.condition(0, 1);
+        assert_eq!(configurator.valid_domain(0), vec![1]);
+        assert_eq!(configurator.valid_domain(1), vec![0, 2]);
+        // The value is removed by the condition and the NotEquals constraint
+        let mut explanation = configurator.explain(1, 1);
+        explanation.sort();
+        assert_eq!(explanation, vec![0, condition]);
+        configurator.retract(condition);
+        assert_eq!(configurator.valid_domain(1), vec![0, 1, 2]);
+    }
+}